@@ -0,0 +1,94 @@
+/// Pin the calling thread to the given cores, so the emulation doesn't compete with the other threads.
+///
+/// Nothing is done if `cores` is empty or the system has a single core;
+/// failures (e.g. missing privileges) are logged and the thread keeps running anywhere.
+pub fn pin_current_thread(name: &str, cores: &[usize]) {
+    let available = std::thread::available_parallelism().map_or(1, |n| n.get());
+    pin_with(name, cores, available, set_affinity);
+}
+
+/// Pin the thread with the `set` helper, given the number of `available` cores.
+///
+/// Returns the cores the thread has been pinned to, if any.
+fn pin_with(
+    name: &str,
+    cores: &[usize],
+    available: usize,
+    set: impl FnOnce(&[usize]) -> std::io::Result<()>,
+) -> Option<Vec<usize>> {
+    if cores.is_empty() {
+        return None;
+    }
+    if available < 2 {
+        debug!("Single core system; not pinning the {name} thread");
+        return None;
+    }
+
+    let cores: Vec<usize> = cores
+        .iter()
+        .copied()
+        .filter(|core| *core < available)
+        .collect();
+    if cores.is_empty() {
+        warn!("None of the cores of the {name} thread exists (available: {available})");
+        return None;
+    }
+
+    match set(&cores) {
+        Ok(()) => {
+            info!("Pinned the {name} thread to cores {cores:?}");
+            Some(cores)
+        }
+        Err(err) => {
+            warn!("Failed to pin the {name} thread to cores {cores:?}: {err}");
+            None
+        }
+    }
+}
+
+/// Set the affinity of the calling thread with `sched_setaffinity`
+fn set_affinity(cores: &[usize]) -> std::io::Result<()> {
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        libc::CPU_ZERO(&mut set);
+        for core in cores {
+            libc::CPU_SET(*core, &mut set);
+        }
+        if libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_should_pin_thread_to_configured_cores() {
+        let mut mask = vec![];
+        assert_eq!(
+            pin_with("cpu", &[3, 1, 7], 4, |cores| {
+                mask = cores.to_vec();
+                Ok(())
+            }),
+            Some(vec![3, 1])
+        );
+        // cores beyond the available ones are dropped
+        assert_eq!(mask, vec![3, 1]);
+
+        let fail = |_: &[usize]| -> std::io::Result<()> { panic!("must not be called") };
+        assert_eq!(pin_with("cpu", &[], 4, fail), None);
+        assert_eq!(pin_with("cpu", &[0], 1, fail), None);
+        assert_eq!(pin_with("cpu", &[5], 4, fail), None);
+        assert_eq!(
+            pin_with("cpu", &[0], 4, |_| Err(
+                std::io::ErrorKind::PermissionDenied.into()
+            )),
+            None
+        );
+    }
+}
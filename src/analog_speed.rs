@@ -0,0 +1,98 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use rboy::input::adc::{Adc, IioAdc};
+
+use crate::GBEvent;
+use crate::app_config::AnalogSpeedConfig;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// Smallest speed change sent to the CPU, to ignore the ADC noise
+const SPEED_THRESHOLD: f32 = 0.05;
+
+/// Map a raw ADC reading to a speed multiplier.
+///
+/// `raw_min` gives the minimum speed and `raw_max` the maximum one, with a linear ramp in between;
+/// readings out of the range are clamped. `raw_min` can be greater than `raw_max` for
+/// potentiometers wired the other way round.
+pub fn speed_multiplier(raw: u32, config: &AnalogSpeedConfig) -> f32 {
+    let span = config.raw_max as f32 - config.raw_min as f32;
+    let position = if span == 0.0 {
+        0.0
+    } else {
+        ((raw as f32 - config.raw_min as f32) / span).clamp(0.0, 1.0)
+    };
+
+    config.min_speed + position * (config.max_speed - config.min_speed)
+}
+
+/// Poll the potentiometer and send the speed multiplier to the CPU whenever it changes,
+/// until `exit` is set or the CPU is gone.
+pub fn run(
+    config: AnalogSpeedConfig,
+    exit: Arc<AtomicBool>,
+    sender: Sender<GBEvent>,
+) -> JoinHandle<()> {
+    thread::spawn(move || {
+        let mut adc = IioAdc::new(&config.adc);
+        let mut last_speed = 1.0;
+        while !exit.load(Ordering::Relaxed) {
+            match adc.read() {
+                Ok(raw) => {
+                    let speed = speed_multiplier(raw, &config);
+                    if (speed - last_speed).abs() >= SPEED_THRESHOLD {
+                        debug!("Speed multiplier changed to {speed:.2}");
+                        if sender.send(GBEvent::Speed(speed)).is_err() {
+                            break;
+                        }
+                        last_speed = speed;
+                    }
+                }
+                Err(err) => error!("Failed to read the speed potentiometer: {err}"),
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_should_map_adc_to_speed_multiplier() {
+        let config: AnalogSpeedConfig = toml::from_str(
+            r#"
+adc = "/sys/bus/iio/devices/iio:device0/in_voltage0_raw"
+raw_min = 100
+raw_max = 4000
+max_speed = 4.0
+"#,
+        )
+        .unwrap();
+
+        let speeds: Vec<f32> = (0..=4095)
+            .step_by(15)
+            .map(|raw| speed_multiplier(raw, &config))
+            .collect();
+        assert!(speeds.windows(2).all(|w| w[0] <= w[1]));
+        assert_eq!(speed_multiplier(0, &config), 1.0);
+        assert_eq!(speed_multiplier(100, &config), 1.0);
+        assert_eq!(speed_multiplier(2050, &config), 2.5);
+        assert_eq!(speed_multiplier(4000, &config), 4.0);
+        assert_eq!(speed_multiplier(4095, &config), 4.0);
+
+        // reversed wiring
+        let config = AnalogSpeedConfig {
+            raw_min: 4000,
+            raw_max: 100,
+            ..config
+        };
+        assert_eq!(speed_multiplier(4095, &config), 1.0);
+        assert_eq!(speed_multiplier(0, &config), 4.0);
+    }
+}
@@ -1,10 +1,19 @@
 mod keycode;
+mod keymap;
 
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::time::Duration;
 
+use rboy::PrintOptions;
+use rboy::framebuffer::{PixelFormat, ScaleContext, ScaleFilter};
+use rboy::input::{InitialState, KeyMode};
 use serde::Deserialize;
 
+use crate::menu::{DuplicateRoms, MenuBackground, Platform, SplashWait};
+use crate::pacing::{SPEED_PERCENTS, SpeedPercent, is_valid_speed};
+use crate::rotation::Rotation;
+
 pub use self::keycode::Keycode;
 
 /// Pinout configuration structure
@@ -16,14 +25,126 @@ pub struct AppConfig {
     pub default_active_low: bool,
     /// polling interval in milliseconds
     poll_interval_ms: u64,
-    /// path to ROMs directory
-    pub roms_directory: PathBuf,
-    /// Keys configuration
-    #[serde(rename = "key", default)]
+    /// maximum presses a single key can emit per second, excess presses are dropped; unlimited if not set
+    pub max_key_events_per_sec: Option<u32>,
+    /// paths to the ROMs directories, a single one or a list; the first one also keeps the settings
+    #[serde(
+        rename = "roms_directory",
+        deserialize_with = "deserialize_roms_directories"
+    )]
+    pub roms_directories: Vec<PathBuf>,
+    /// Keys configuration, from the `[[key]]` array or the compact `keymap` table
+    #[serde(
+        rename = "key",
+        alias = "keymap",
+        default,
+        deserialize_with = "keymap::deserialize"
+    )]
     pub keys: Vec<KeyConfig>,
+    /// Alternative key mappings, switchable at runtime
+    #[serde(rename = "profile", default)]
+    pub profiles: Vec<KeyProfile>,
     /// Power switches configuration
     #[serde(rename = "powerswitch", default)]
     pub power_switches: Vec<PowerSwitchConfig>,
+    /// Hotkeys configuration
+    #[serde(default)]
+    pub hotkeys: HotkeysConfig,
+    /// Don't use GPIO; input commands are read from stdin instead
+    #[serde(default)]
+    pub no_gpio: bool,
+    /// Running in safe mode, see [`AppConfig::safe_mode`]
+    #[serde(skip)]
+    pub safe_mode: bool,
+    /// Sampling of the scaled frames and glyphs during the games and in the menus
+    #[serde(default)]
+    pub scale_filter: ScaleFilterConfig,
+    /// Start the last game played, from its resume state, if the previous session shut down cleanly
+    #[serde(default)]
+    pub boot_last_game: bool,
+    /// Relaunch each game with the mode, palette and scaling it was last played with
+    #[serde(default)]
+    pub remember_launch_options: bool,
+    /// Move the save states which can't be loaded aside, to `<state>.bad`
+    #[serde(default)]
+    pub backup_corrupt_states: bool,
+    /// Emulation speed the games start at, in percent; one of [`SPEED_PERCENTS`]
+    #[serde(default = "AppConfig::default_speed_percent")]
+    pub speed_percent: u32,
+    /// PNG or BMP image shown at boot instead of the default splash screen
+    pub splash_image: Option<PathBuf>,
+    /// Keep the splash screen until a key is pressed, e.g. on demo units; it stays a few seconds if not set
+    pub splash_hold: Option<SplashHoldConfig>,
+    /// DMG boot ROM run before classic games; games start directly if not set
+    pub dmg_boot_rom: Option<PathBuf>,
+    /// CGB boot ROM run before games in color mode; games start directly if not set
+    pub cgb_boot_rom: Option<PathBuf>,
+    /// How the menu shows ROMs with the same content under different names
+    #[serde(default)]
+    pub duplicate_roms: DuplicateRoms,
+    /// Extra ROM file extensions listed in the menu, with the platform they run as; `gb` and `gbc` are always listed
+    #[serde(default)]
+    pub rom_extensions: HashMap<String, Platform>,
+    /// Display defaults of the games of each platform, overriding the global ones
+    #[serde(rename = "platform", default)]
+    pub platforms: HashMap<Platform, PlatformConfig>,
+    /// Settings of single games, by ROM file name
+    #[serde(rename = "game", default)]
+    pub games: HashMap<String, GameConfig>,
+    /// Key to hold while starting a game from the menu to run it in classic (DMG) mode
+    pub force_classic_key: Option<Keycode>,
+    /// Key to hold while starting a game from the menu to run it in color mode, overriding the remembered mode
+    pub force_color_key: Option<Keycode>,
+    /// Key to hold while starting a game from the menu to pick its save file, for several playthroughs
+    pub save_picker_key: Option<Keycode>,
+    /// Clockwise rotation of the display in degrees (0, 90, 180 or 270); the D-pad is remapped to match it
+    #[serde(default)]
+    pub input_rotation: Rotation,
+    /// Retries of the GPIO initialization
+    #[serde(default)]
+    pub gpio_init: GpioInitConfig,
+    /// Opacity of the in-game overlay backgrounds, from 0 (transparent) to 255 (opaque)
+    #[serde(default = "AppConfig::default_overlay_alpha")]
+    pub overlay_alpha: u8,
+    /// Picture adjustment bounds
+    #[serde(default)]
+    pub picture: PictureConfig,
+    /// Attract mode; disabled if not set
+    pub attract: Option<AttractConfig>,
+    /// Menu configuration
+    #[serde(default)]
+    pub menu: MenuConfig,
+    /// Fast-forward when a game starts, to skip long intros; disabled if not set
+    pub boot_turbo: Option<BootTurboConfig>,
+    /// Speed multiplier controlled by a potentiometer through an ADC; disabled if not set
+    pub analog_speed: Option<AnalogSpeedConfig>,
+    /// Emulate a Game Boy Printer on the link port, saving the prints as PGM images; disabled if not set
+    pub printer: Option<PrintOptions>,
+    /// Vibration motor driven by the games with an MBC5 rumble cartridge; disabled if not set
+    pub rumble: Option<RumbleConfig>,
+    /// Low battery warning from a fuel gauge; disabled if not set
+    pub battery: Option<BatteryConfig>,
+    /// Dim, then blank, the game when no input is received for a while; disabled if not set
+    pub idle_dim: Option<IdleDimConfig>,
+    /// Output gain and limiter of the audio
+    #[serde(default)]
+    pub audio: AudioConfig,
+    /// Cores the threads are pinned to; threads run on any core if not set
+    #[serde(default)]
+    pub affinity: AffinityConfig,
+    /// Invert the colors of the display, for a high contrast
+    #[serde(default)]
+    pub invert_colors: bool,
+    /// Colorization palette the classic games start with; shades of gray if not set
+    pub dmg_palette: Option<String>,
+    /// Memory in MiB which must stay free after starting a game; games which would need more are not started
+    #[serde(default = "AppConfig::default_min_free_memory_mb")]
+    min_free_memory_mb: u64,
+    /// Secondary display showing the game and the battery level; disabled if not set
+    pub status_display: Option<StatusDisplayConfig>,
+    /// Load the cheats of the games from their `.cht` file
+    #[serde(default = "AppConfig::default_cheats")]
+    pub cheats: bool,
 }
 
 impl AppConfig {
@@ -33,9 +154,188 @@ impl AppConfig {
             .map_err(|e| anyhow::anyhow!("Failed to read config file {:?}: {}", path, e))?;
         let config: AppConfig = toml::from_str(&config_str)
             .map_err(|e| anyhow::anyhow!("Failed to parse config file {:?}: {}", path, e))?;
+        config
+            .validate()
+            .map_err(|e| anyhow::anyhow!("Invalid config file {:?}: {}", path, e))?;
         Ok(config)
     }
 
+    /// Check the settings which can't be expressed by the config structure
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let profile_keys = self.profiles.iter().flat_map(|p| &p.keys);
+        for key in self.keys.iter().chain(profile_keys) {
+            if key.repeat && key.repeat_timing().is_none() {
+                anyhow::bail!(
+                    "key {} (GPIO {}) has `repeat = true` but misses `repeat_delay_ms` or `repeat_rate_ms`",
+                    key.keycode,
+                    key.gpio
+                );
+            }
+        }
+        if let Some((name, _)) = self
+            .games
+            .iter()
+            .find(|(_, game)| game.max_speed.is_some_and(|speed| !is_valid_speed(speed)))
+        {
+            anyhow::bail!("max_speed of game {name} must be greater than 0");
+        }
+        if let Some(turbo) = &self.boot_turbo
+            && !is_valid_speed(turbo.speed)
+        {
+            anyhow::bail!("boot_turbo speed must be greater than 0");
+        }
+        if let Some(analog) = &self.analog_speed
+            && !(is_valid_speed(analog.min_speed) && is_valid_speed(analog.max_speed))
+        {
+            anyhow::bail!("analog_speed min_speed and max_speed must be greater than 0");
+        }
+        if SpeedPercent::new(self.speed_percent).is_none() {
+            anyhow::bail!("speed_percent must be one of {SPEED_PERCENTS:?}");
+        }
+        if self.max_key_events_per_sec == Some(0) {
+            anyhow::bail!("max_key_events_per_sec must be greater than 0");
+        }
+        if self.audio.gain < 0.0 {
+            anyhow::bail!("audio gain must not be negative");
+        }
+        if let Some(threshold) = self.audio.limiter_threshold
+            && !(0.0..1.0).contains(&threshold)
+        {
+            anyhow::bail!("audio limiter_threshold must be between 0.0 and 1.0");
+        }
+        // the player queues up to one second of audio
+        if self.audio.prefill_ms.is_some_and(|ms| ms >= 1000) {
+            anyhow::bail!("audio prefill_ms must be less than 1000");
+        }
+        let picture = &self.picture;
+        for (name, min, max) in [
+            ("brightness", picture.brightness_min, picture.brightness_max),
+            ("gamma", picture.gamma_min, picture.gamma_max),
+        ] {
+            if !min.is_finite() || !max.is_finite() || min > max {
+                anyhow::bail!(
+                    "picture {name}_min and {name}_max must be numbers, with {name}_min not greater than {name}_max"
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Main ROMs directory, where the settings and the session files are kept
+    pub fn roms_directory(&self) -> &Path {
+        &self.roms_directories[0]
+    }
+
+    /// Get the [`Platform`] of the ROMs with the given extension, case insensitive
+    pub fn rom_platform(&self, extension: &str) -> Option<Platform> {
+        let extension = extension.to_lowercase();
+        self.rom_extensions
+            .iter()
+            .find(|(ext, _)| ext.to_lowercase() == extension)
+            .map(|(_, platform)| *platform)
+            .or(match extension.as_str() {
+                "gb" => Some(Platform::GameBoy),
+                "gbc" => Some(Platform::GameBoyColor),
+                _ => None,
+            })
+    }
+
+    /// Get the display settings of a game of `platform`, merging its platform block with the
+    /// globals; `cgb_color_correct` is the global color correction
+    pub fn display(&self, platform: Option<Platform>, cgb_color_correct: bool) -> DisplaySettings {
+        let overrides = platform
+            .and_then(|platform| self.platforms.get(&platform))
+            .cloned()
+            .unwrap_or_default();
+        DisplaySettings {
+            cgb_color_correct: overrides.cgb_color_correct.unwrap_or(cgb_color_correct),
+            invert_colors: overrides.invert_colors.unwrap_or(self.invert_colors),
+        }
+    }
+
+    /// Get the settings of the game in `rom_file`, or the defaults if it has none
+    pub fn game(&self, rom_file: &Path) -> GameConfig {
+        rom_file
+            .file_name()
+            .and_then(|name| self.games.get(name.to_string_lossy().as_ref()))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// How long the splash screen stays
+    pub fn splash_wait(&self) -> SplashWait {
+        match &self.splash_hold {
+            Some(hold) => SplashWait::Hold {
+                timeout: hold.timeout_secs.map(Duration::from_secs),
+            },
+            None => SplashWait::Timer(Duration::from_secs(4)),
+        }
+    }
+
+    /// Memory in bytes which must stay free after starting a game
+    pub fn min_free_memory(&self) -> u64 {
+        self.min_free_memory_mb * 1024 * 1024
+    }
+
+    fn default_min_free_memory_mb() -> u64 {
+        16
+    }
+
+    fn default_overlay_alpha() -> u8 {
+        u8::MAX
+    }
+
+    fn default_speed_percent() -> u32 {
+        100
+    }
+
+    /// Emulation speed the games start at
+    pub fn speed(&self) -> SpeedPercent {
+        SpeedPercent::new(self.speed_percent).unwrap_or_default()
+    }
+
+    fn default_cheats() -> bool {
+        true
+    }
+
+    /// Disable the optional subsystems, overriding the file, to run the bare emulator and
+    /// isolate a misbehaving feature.
+    ///
+    /// The keys and the hotkeys are kept, and so are the settings which don't run any code of
+    /// their own: `speed_percent`, `backup_corrupt_states` and the `power_entries` of the menu.
+    /// The settings of single games, e.g. their `max_speed` cap, are cleared with the platform defaults,
+    /// and the volume and the picture settings saved in the ROMs directory are neither read nor written.
+    pub fn safe_mode(&mut self) {
+        self.safe_mode = true;
+        self.audio.enabled = false;
+        self.cheats = false;
+        self.dmg_palette = None;
+        self.platforms.clear();
+        self.games.clear();
+        self.invert_colors = false;
+        self.boot_last_game = false;
+        self.remember_launch_options = false;
+        self.splash_image = None;
+        self.splash_hold = None;
+        self.dmg_boot_rom = None;
+        self.cgb_boot_rom = None;
+        self.attract = None;
+        self.boot_turbo = None;
+        self.analog_speed = None;
+        self.printer = None;
+        self.rumble = None;
+        self.battery = None;
+        self.idle_dim = None;
+        self.status_display = None;
+        self.affinity = AffinityConfig::default();
+    }
+
+    /// Directory the volume and the picture settings are saved in; none in safe mode
+    pub fn settings_directory(&self) -> Option<&Path> {
+        (!self.safe_mode).then(|| self.roms_directory())
+    }
+
     /// Default debounce time
     pub fn default_debounce(&self) -> Duration {
         Duration::from_millis(self.default_debounce_ms)
@@ -61,9 +361,23 @@ pub struct KeyConfig {
     pub repeat: bool,
     repeat_delay_ms: Option<u64>,
     repeat_rate_ms: Option<u64>,
+    /// Expected state of the key at startup
+    pub initial_state: Option<InitialState>,
+    /// Number of raw samples to median-filter before debounce, to reject glitches on noisy wiring
+    pub filter_samples: Option<usize>,
+    /// Whether the key is held while pressed (`momentary`) or latches on and off (`toggle`)
+    #[serde(default)]
+    pub mode: KeyMode,
+    /// Length in milliseconds of the pulse of the rumble motor when the key is pressed; no pulse if not set
+    haptic_ms: Option<u64>,
 }
 
 impl KeyConfig {
+    /// Length of the haptic pulse when the key is pressed
+    pub fn haptic(&self) -> Option<Duration> {
+        self.haptic_ms.map(Duration::from_millis)
+    }
+
     /// Debounce time
     pub fn debounce(&self) -> Option<Duration> {
         self.debounce_ms.map(Duration::from_millis)
@@ -78,6 +392,28 @@ impl KeyConfig {
     pub fn repeat_rate(&self) -> Option<Duration> {
         self.repeat_rate_ms.map(Duration::from_millis)
     }
+
+    /// Delay and rate of the auto-repeat, if enabled and both are set
+    pub fn repeat_timing(&self) -> Option<(Duration, Duration)> {
+        if !self.repeat {
+            return None;
+        }
+
+        Some((self.repeat_delay()?, self.repeat_rate()?))
+    }
+}
+
+/// Named key mapping replacing the default `[[key]]` mapping while active
+#[derive(Debug, Clone, Deserialize)]
+pub struct KeyProfile {
+    pub name: String,
+    #[serde(
+        rename = "key",
+        alias = "keymap",
+        default,
+        deserialize_with = "keymap::deserialize"
+    )]
+    pub keys: Vec<KeyConfig>,
 }
 
 /// Configuration for an individual power switch
@@ -87,6 +423,446 @@ pub struct PowerSwitchConfig {
     pub gpio: u8,
     /// Whether the switch is active low; if true, switch is active when GPIO is low
     pub active_low: Option<bool>,
+    debounce_ms: Option<u64>,
+}
+
+impl PowerSwitchConfig {
+    /// Debounce time
+    pub fn debounce(&self) -> Option<Duration> {
+        self.debounce_ms.map(Duration::from_millis)
+    }
+}
+
+/// Hotkeys configuration; each hotkey is a combination of keys to hold together during gameplay
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct HotkeysConfig {
+    /// Increase the brightness of the picture
+    pub brightness_up: Option<Vec<Keycode>>,
+    /// Decrease the brightness of the picture
+    pub brightness_down: Option<Vec<Keycode>>,
+    /// Increase the gamma of the picture
+    pub gamma_up: Option<Vec<Keycode>>,
+    /// Decrease the gamma of the picture
+    pub gamma_down: Option<Vec<Keycode>>,
+    /// Pause the game and open the in-game menu
+    pub menu: Option<Vec<Keycode>>,
+    /// Switch to the next key profile
+    pub next_profile: Option<Vec<Keycode>>,
+    /// Print a screenshot of the current frame to stdout
+    pub screenshot: Option<Vec<Keycode>>,
+    /// Save the state of the game to the quick slot
+    pub quick_save: Option<Vec<Keycode>>,
+    /// Load the state of the game from the quick slot
+    pub quick_load: Option<Vec<Keycode>>,
+    /// Show or hide the debug overlay (frame rate, dropped frames, speed)
+    pub debug_overlay: Option<Vec<Keycode>>,
+    /// Invert the colors of the display
+    pub invert_colors: Option<Vec<Keycode>>,
+    /// Switch to the next colorization palette of the classic games
+    pub next_palette: Option<Vec<Keycode>>,
+    /// Switch to the next scaling mode of the frames
+    pub next_scaling: Option<Vec<Keycode>>,
+    /// Switch to the next emulation speed
+    pub next_speed: Option<Vec<Keycode>>,
+}
+
+/// Sampling of the scaled frames and glyphs in each [`ScaleContext`]
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ScaleFilterConfig {
+    /// Filter while the game runs, sharp by default
+    pub game: ScaleFilter,
+    /// Filter of the menus, smooth by default
+    pub menu: ScaleFilter,
+}
+
+impl Default for ScaleFilterConfig {
+    fn default() -> Self {
+        Self {
+            game: ScaleFilter::Nearest,
+            menu: ScaleFilter::Smooth,
+        }
+    }
+}
+
+impl ScaleFilterConfig {
+    /// The filter of the given context
+    pub fn for_context(&self, context: ScaleContext) -> ScaleFilter {
+        match context {
+            ScaleContext::Game => self.game,
+            ScaleContext::Menu => self.menu,
+        }
+    }
+}
+
+/// Retries of the GPIO initialization, for lines still held by another process at boot
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct GpioInitConfig {
+    /// Number of retries after the first failed attempt
+    pub retries: u32,
+    /// Delay before the first retry; it doubles after each failure
+    backoff_ms: u64,
+}
+
+impl GpioInitConfig {
+    /// Delay before the first retry
+    pub fn backoff(&self) -> Duration {
+        Duration::from_millis(self.backoff_ms)
+    }
+}
+
+impl Default for GpioInitConfig {
+    fn default() -> Self {
+        Self {
+            retries: 5,
+            backoff_ms: 100,
+        }
+    }
+}
+
+/// Bounds of the picture settings adjustable during gameplay
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct PictureConfig {
+    pub brightness_min: f32,
+    pub brightness_max: f32,
+    pub gamma_min: f32,
+    pub gamma_max: f32,
+    /// Amount each hotkey press changes a setting by
+    pub step: f32,
+}
+
+impl Default for PictureConfig {
+    fn default() -> Self {
+        Self {
+            brightness_min: 0.5,
+            brightness_max: 1.5,
+            gamma_min: 0.5,
+            gamma_max: 2.5,
+            step: 0.1,
+        }
+    }
+}
+
+/// Menu configuration
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct MenuConfig {
+    repeat_delay_ms: Option<u64>,
+    repeat_rate_ms: Option<u64>,
+    /// Maximum redraws per second of the menu
+    max_fps: Option<u32>,
+    /// Background drawn behind the games list
+    #[serde(default)]
+    pub background: MenuBackground,
+    /// List "Shut down" and "Reboot" after the games, e.g. on handhelds without a power switch
+    #[serde(default)]
+    pub power_entries: bool,
+    /// List "Test Inputs" after the games, showing the buttons pressed to check the wiring
+    #[serde(default)]
+    pub test_inputs_entry: bool,
+}
+
+impl MenuConfig {
+    /// Delay and rate of the auto-repeat of Up/Down in the menu, if both are set
+    pub fn repeat(&self) -> Option<(Duration, Duration)> {
+        Some((
+            Duration::from_millis(self.repeat_delay_ms?),
+            Duration::from_millis(self.repeat_rate_ms?),
+        ))
+    }
+
+    /// Minimum time between two redraws of the menu, which is also the input poll interval
+    pub fn frame_interval(&self) -> Duration {
+        Duration::from_secs(1) / self.max_fps.unwrap_or(20).max(1)
+    }
+}
+
+/// Attract mode configuration: a demo played when the menu is idle
+#[derive(Debug, Clone, Deserialize)]
+pub struct AttractConfig {
+    /// Game to play; relative paths are resolved from the ROMs directory
+    pub game: PathBuf,
+    /// Input replay to play; relative paths are resolved from the ROMs directory
+    pub replay: PathBuf,
+    /// Seconds the menu must be idle before the demo starts
+    #[serde(default = "AttractConfig::default_idle_secs")]
+    idle_secs: u64,
+}
+
+impl AttractConfig {
+    fn default_idle_secs() -> u64 {
+        60
+    }
+
+    /// Time the menu must be idle before the demo starts
+    pub fn idle_timeout(&self) -> Duration {
+        Duration::from_secs(self.idle_secs)
+    }
+}
+
+/// Fast-forward engaged for the first seconds of a game
+#[derive(Debug, Clone, Deserialize)]
+pub struct BootTurboConfig {
+    /// Seconds of fast-forward for all the games; 0 to enable it only for the games listed in `games`
+    #[serde(default)]
+    secs: u64,
+    /// Speed multiplier while engaged
+    #[serde(default = "BootTurboConfig::default_speed")]
+    pub speed: f32,
+    /// Seconds of fast-forward by ROM file name, overriding `secs`
+    #[serde(default)]
+    games: HashMap<String, u64>,
+}
+
+impl BootTurboConfig {
+    fn default_speed() -> f32 {
+        4.0
+    }
+
+    /// How long the fast-forward is engaged for the given ROM, if at all
+    pub fn duration(&self, rom_file: &Path) -> Option<Duration> {
+        let secs = rom_file
+            .file_name()
+            .and_then(|name| self.games.get(name.to_string_lossy().as_ref()))
+            .copied()
+            .unwrap_or(self.secs);
+
+        (secs > 0).then(|| Duration::from_secs(secs))
+    }
+}
+
+/// Speed multiplier controlled by a potentiometer read through an ADC
+#[derive(Debug, Clone, Deserialize)]
+pub struct AnalogSpeedConfig {
+    /// Raw value file of the ADC channel, e.g. `/sys/bus/iio/devices/iio:device0/in_voltage0_raw`
+    pub adc: PathBuf,
+    /// Reading giving the minimum speed
+    #[serde(default)]
+    pub raw_min: u32,
+    /// Reading giving the maximum speed
+    #[serde(default = "AnalogSpeedConfig::default_raw_max")]
+    pub raw_max: u32,
+    /// Speed multiplier at `raw_min`
+    #[serde(default = "AnalogSpeedConfig::default_min_speed")]
+    pub min_speed: f32,
+    /// Speed multiplier at `raw_max`
+    #[serde(default = "AnalogSpeedConfig::default_max_speed")]
+    pub max_speed: f32,
+}
+
+impl AnalogSpeedConfig {
+    fn default_raw_max() -> u32 {
+        4095
+    }
+
+    fn default_min_speed() -> f32 {
+        1.0
+    }
+
+    fn default_max_speed() -> f32 {
+        4.0
+    }
+}
+
+/// Hold of the splash screen until a key is pressed
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct SplashHoldConfig {
+    /// Safety timeout after which the menu opens anyway; the splash stays indefinitely if not set
+    pub timeout_secs: Option<u64>,
+}
+
+/// Display defaults of the games of a platform; unset values fall back to the globals
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct PlatformConfig {
+    /// Correct the colors for the LCD
+    pub cgb_color_correct: Option<bool>,
+    /// Invert the colors of the display
+    pub invert_colors: Option<bool>,
+}
+
+/// Settings of a single game
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct GameConfig {
+    /// Highest speed multiplier the game runs at, for the games glitching when fast-forwarded;
+    /// the speed set with the boot turbo, the hotkey or the potentiometer is clamped to it
+    pub max_speed: Option<f32>,
+}
+
+/// Display settings of a game, see [`AppConfig::display`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DisplaySettings {
+    pub cgb_color_correct: bool,
+    pub invert_colors: bool,
+}
+
+/// Output gain and limiter of the audio
+#[derive(Debug, Clone, Deserialize)]
+pub struct AudioConfig {
+    /// Play the audio of the games; they run muted if disabled
+    #[serde(default = "AudioConfig::default_enabled")]
+    pub enabled: bool,
+    /// Gain applied on top of the volume, e.g. above 1.0 to boost quiet games
+    #[serde(default = "AudioConfig::default_gain")]
+    pub gain: f32,
+    /// Level above which the samples are softly limited toward ±1.0 instead of clipping;
+    /// disabled if not set
+    pub limiter_threshold: Option<f32>,
+    /// Audio queued before the playback starts, in milliseconds, avoiding the crackle at startup;
+    /// the playback starts immediately if not set
+    pub prefill_ms: Option<u64>,
+}
+
+impl Default for AudioConfig {
+    fn default() -> Self {
+        Self {
+            enabled: Self::default_enabled(),
+            gain: Self::default_gain(),
+            limiter_threshold: None,
+            prefill_ms: None,
+        }
+    }
+}
+
+impl AudioConfig {
+    fn default_enabled() -> bool {
+        true
+    }
+
+    fn default_gain() -> f32 {
+        1.0
+    }
+}
+
+/// Cores the threads are pinned to, e.g. to keep the emulation on its own core
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct AffinityConfig {
+    /// Cores of the emulation thread
+    pub cpu: Vec<usize>,
+    /// Cores of the input listener thread
+    pub input: Vec<usize>,
+}
+
+/// Secondary framebuffer showing the status
+#[derive(Debug, Clone, Deserialize)]
+pub struct StatusDisplayConfig {
+    /// Framebuffer device, e.g. `/dev/fb2`
+    pub path: PathBuf,
+    pub width: usize,
+    pub height: usize,
+    #[serde(default = "StatusDisplayConfig::default_pixel_format")]
+    pub pixel_format: PixelFormat,
+}
+
+impl StatusDisplayConfig {
+    fn default_pixel_format() -> PixelFormat {
+        PixelFormat::Rgb565
+    }
+}
+
+/// Low battery warning shown over the game
+#[derive(Debug, Clone, Deserialize)]
+pub struct BatteryConfig {
+    /// Charge file of the fuel gauge, e.g. `/sys/class/power_supply/battery/capacity`
+    pub capacity: PathBuf,
+    /// Charge in percent below which the warning is shown
+    #[serde(default = "BatteryConfig::default_low_percent")]
+    pub low_percent: u8,
+    /// Points above `low_percent` the charge must rise to before warning again
+    #[serde(default = "BatteryConfig::default_hysteresis")]
+    pub hysteresis: u8,
+    /// Save the game to the quick slot when the warning is shown
+    #[serde(default)]
+    pub quick_save: bool,
+}
+
+impl BatteryConfig {
+    fn default_low_percent() -> u8 {
+        15
+    }
+
+    fn default_hysteresis() -> u8 {
+        3
+    }
+}
+
+/// Power saving of the display during gameplay: dim, then blank
+#[derive(Debug, Clone, Deserialize)]
+pub struct IdleDimConfig {
+    /// Seconds without input before the display is dimmed
+    dim_secs: u64,
+    /// Brightness of the dimmed display, as a multiplier of the picture brightness
+    #[serde(default = "IdleDimConfig::default_level")]
+    pub level: f32,
+    /// Seconds without input before the display is blanked; it stays dimmed if not set
+    blank_secs: Option<u64>,
+}
+
+impl IdleDimConfig {
+    fn default_level() -> f32 {
+        0.3
+    }
+
+    /// Time without input before the display is dimmed
+    pub fn dim_after(&self) -> Duration {
+        Duration::from_secs(self.dim_secs)
+    }
+
+    /// Time without input before the display is blanked
+    pub fn blank_after(&self) -> Option<Duration> {
+        self.blank_secs.map(Duration::from_secs)
+    }
+}
+
+/// Vibration motor on a GPIO output, driven by the MBC5 rumble
+#[derive(Debug, Clone, Deserialize)]
+pub struct RumbleConfig {
+    /// GPIO driving the motor
+    pub gpio: u8,
+    /// The motor runs when the GPIO is low
+    #[serde(default)]
+    pub active_low: bool,
+    /// Duty cycle (0.0 to 1.0) of a software PWM setting the intensity; full power if not set
+    pub duty_cycle: Option<f64>,
+    /// Shortest time between two changes of the motor, to avoid chatter
+    #[serde(default = "RumbleConfig::default_min_toggle_ms")]
+    min_toggle_ms: u64,
+}
+
+impl RumbleConfig {
+    fn default_min_toggle_ms() -> u64 {
+        30
+    }
+
+    /// Shortest time between two changes of the motor
+    pub fn min_toggle(&self) -> Duration {
+        Duration::from_millis(self.min_toggle_ms)
+    }
+}
+
+/// Deserialize the ROMs directories from a single path or a non empty list of paths
+fn deserialize_roms_directories<'de, D>(deserializer: D) -> Result<Vec<PathBuf>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(PathBuf),
+        Many(Vec<PathBuf>),
+    }
+
+    match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(path) => Ok(vec![path]),
+        OneOrMany::Many(paths) if paths.is_empty() => Err(serde::de::Error::custom(
+            "roms_directory must have at least one path",
+        )),
+        OneOrMany::Many(paths) => Ok(paths),
+    }
 }
 
 #[cfg(test)]
@@ -105,7 +881,7 @@ mod tests {
         assert_eq!(config.default_active_low, true);
         assert_eq!(config.poll_interval_ms, 5);
 
-        assert_eq!(config.roms_directory, PathBuf::from("./roms"));
+        assert_eq!(config.roms_directory(), Path::new("./roms"));
 
         assert_eq!(config.keys.len(), 2);
         assert_eq!(config.keys[0].gpio, 17);
@@ -113,16 +889,32 @@ mod tests {
         assert_eq!(config.keys[0].active_low, Some(true));
         assert_eq!(config.keys[0].debounce_ms, Some(20));
         assert_eq!(config.keys[0].repeat, false);
+        assert_eq!(config.keys[0].initial_state, Some(InitialState::Released));
 
         assert_eq!(config.keys[1].gpio, 22);
         assert_eq!(config.keys[1].keycode.keycode(), KeypadKey::Up);
         assert_eq!(config.keys[1].repeat, true);
         assert_eq!(config.keys[1].repeat_delay_ms, Some(300));
         assert_eq!(config.keys[1].repeat_rate_ms, Some(80));
+        assert_eq!(config.keys[1].initial_state, None);
+        assert_eq!(config.keys[1].filter_samples, Some(3));
+        assert_eq!(config.keys[0].mode, KeyMode::Momentary);
+        assert_eq!(config.keys[1].mode, KeyMode::Toggle);
 
         assert_eq!(config.power_switches.len(), 1);
         assert_eq!(config.power_switches[0].gpio, 27);
         assert_eq!(config.power_switches[0].active_low, Some(false));
+        assert!(!config.no_gpio);
+        assert!(!config.boot_last_game);
+
+        let attract = config.attract.unwrap();
+        assert_eq!(attract.game, PathBuf::from("tetris.gb"));
+        assert_eq!(attract.replay, PathBuf::from("tetris.replay"));
+        assert_eq!(attract.idle_timeout(), Duration::from_secs(60));
+
+        let printer = config.printer.unwrap();
+        assert_eq!(printer.scale, 2);
+        assert_eq!(printer.dithering, rboy::Dithering::FloydSteinberg);
     }
 
     #[test]
@@ -135,6 +927,133 @@ mod tests {
         assert_eq!(config.power_switches.len(), 1);
     }
 
+    #[test]
+    fn test_should_select_scale_filter_per_context() {
+        let config: AppConfig = toml::from_str(DEFAULT_CONFIG).unwrap();
+        assert_eq!(
+            config.scale_filter.for_context(ScaleContext::Menu),
+            ScaleFilter::Smooth
+        );
+        assert_eq!(
+            config.scale_filter.for_context(ScaleContext::Game),
+            ScaleFilter::Nearest
+        );
+
+        let config: AppConfig = toml::from_str(&format!(
+            "{DEFAULT_CONFIG}\n[scale_filter]\nmenu = \"nearest\"\n"
+        ))
+        .unwrap();
+        assert_eq!(
+            config.scale_filter.for_context(ScaleContext::Menu),
+            ScaleFilter::Nearest
+        );
+    }
+
+    #[test]
+    fn test_should_reject_repeat_without_timing() {
+        let tempfile = NamedTempFile::new().unwrap();
+        let config = DEFAULT_CONFIG.replace("repeat_rate_ms = 80\n", "");
+        std::fs::write(tempfile.path(), config).unwrap();
+
+        let err = AppConfig::load_from_file(tempfile.path())
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("key Up (GPIO 22)"), "{err}");
+        assert!(err.contains("repeat_rate_ms"), "{err}");
+    }
+
+    #[test]
+    fn test_should_reject_inverted_picture_range() {
+        let tempfile = NamedTempFile::new().unwrap();
+        let config = DEFAULT_CONFIG.replace("[printer]", "[picture]\ngamma_min = 3.0\n\n[printer]");
+        std::fs::write(tempfile.path(), config).unwrap();
+
+        let err = AppConfig::load_from_file(tempfile.path())
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("gamma_min"), "{err}");
+    }
+
+    #[test]
+    fn test_should_reject_invalid_speed() {
+        let tempfile = NamedTempFile::new().unwrap();
+        for speed in ["0.0", "nan", "-2.0"] {
+            let config = DEFAULT_CONFIG.replace(
+                "[printer]",
+                &format!("[analog_speed]\nadc = \"/dev/null\"\nmax_speed = {speed}\n\n[printer]"),
+            );
+            std::fs::write(tempfile.path(), config).unwrap();
+
+            let err = AppConfig::load_from_file(tempfile.path())
+                .unwrap_err()
+                .to_string();
+            assert!(err.contains("analog_speed"), "{err}");
+        }
+    }
+
+    #[test]
+    fn test_should_parse_compact_keymap() {
+        const BASE: &str = "roms_directory = \"./roms\"\ndefault_debounce_ms = 20\ndefault_active_low = true\npoll_interval_ms = 5\n";
+        let array: AppConfig = toml::from_str(&format!(
+            "{BASE}\n[[key]]\ngpio = 17\nkeycode = \"A\"\nrepeat = false\n\n[[key]]\ngpio = 22\nkeycode = \"UP\"\nrepeat = false\n"
+        ))
+        .unwrap();
+        let compact: AppConfig =
+            toml::from_str(&format!("keymap = {{ 17 = \"A\", 22 = \"UP\" }}\n{BASE}")).unwrap();
+
+        assert_eq!(compact.keys.len(), 2);
+        assert_eq!(format!("{:?}", compact.keys), format!("{:?}", array.keys));
+
+        assert!(
+            toml::from_str::<AppConfig>(&format!("keymap = {{ gpio17 = \"A\" }}\n{BASE}")).is_err()
+        );
+    }
+
+    #[test]
+    fn test_should_merge_platform_display_defaults() {
+        let config: AppConfig = toml::from_str(DEFAULT_CONFIG).unwrap();
+        let settings = |cgb_color_correct, invert_colors| DisplaySettings {
+            cgb_color_correct,
+            invert_colors,
+        };
+
+        assert_eq!(
+            config.display(Some(Platform::GameBoyColor), false),
+            settings(true, false)
+        );
+        assert_eq!(
+            config.display(Some(Platform::GameBoy), true),
+            settings(false, true)
+        );
+        assert_eq!(config.display(None, true), settings(true, false));
+    }
+
+    #[test]
+    fn test_should_disable_optional_subsystems_in_safe_mode() {
+        let mut config: AppConfig = toml::from_str(&format!(
+            "{DEFAULT_CONFIG}\n[audio]\ngain = 2.0\n[rumble]\ngpio = 4\n[idle_dim]\ndim_secs = 60\n"
+        ))
+        .unwrap();
+        assert!(config.audio.enabled);
+        assert!(config.cheats);
+        assert!(config.attract.is_some());
+        assert_eq!(config.settings_directory(), Some(Path::new("./roms")));
+
+        config.safe_mode();
+        assert!(config.settings_directory().is_none());
+        assert!(!config.audio.enabled);
+        assert!(!config.cheats);
+        assert!(config.attract.is_none());
+        assert!(config.printer.is_none());
+        assert!(config.rumble.is_none());
+        assert!(config.idle_dim.is_none());
+        assert!(config.platforms.is_empty());
+        assert!(!config.display(Some(Platform::GameBoy), false).invert_colors);
+        // the input is kept
+        assert_eq!(config.keys.len(), 2);
+        config.validate().unwrap();
+    }
+
     #[test]
     fn test_should_parse_config_without_arrays() {
         let _config: AppConfig = toml::from_str(CONFIG_WNO_ARRAYS).unwrap();
@@ -152,6 +1071,7 @@ keycode = "A"
 active_low = true # `default_active_low` by default
 debounce_ms = 20 # `default_debounce_ms` by default
 repeat = false # disabled by default
+initial_state = "released" # unknown by default
 
 [[key]]
 gpio = 22
@@ -159,10 +1079,27 @@ keycode = "UP"
 repeat = true
 repeat_delay_ms = 300
 repeat_rate_ms = 80
+filter_samples = 3
+mode = "toggle"
 
 [[powerswitch]]
 gpio = 27
 active_low = false
+
+[attract]
+game = "tetris.gb"
+replay = "tetris.replay"
+
+[printer]
+scale = 2
+dithering = "floyd-steinberg"
+
+[platform.gb]
+cgb_color_correct = false
+invert_colors = true
+
+[platform.gbc]
+cgb_color_correct = true
     "#;
 
     const CONFIG_WNO_ARRAYS: &str = r#"
@@ -0,0 +1,62 @@
+use std::fmt;
+
+use serde::Deserialize;
+use serde::de::value::SeqAccessDeserializer;
+use serde::de::{MapAccess, SeqAccess, Visitor};
+
+use super::{KeyConfig, Keycode};
+
+/// Deserialize the keys either as an array of [`KeyConfig`] tables (`[[key]]`),
+/// or as a compact GPIO to [`Keycode`] table (`keymap = { 17 = "A", 22 = "UP" }`).
+///
+/// Keys of the compact form use the default settings.
+pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<KeyConfig>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    deserializer.deserialize_any(KeysVisitor)
+}
+
+struct KeysVisitor;
+
+impl<'de> Visitor<'de> for KeysVisitor {
+    type Value = Vec<KeyConfig>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("an array of keys or a table of GPIO to keycode")
+    }
+
+    fn visit_seq<A>(self, seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        Vec::<KeyConfig>::deserialize(SeqAccessDeserializer::new(seq))
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut keys = vec![];
+        while let Some((gpio, keycode)) = map.next_entry::<String, Keycode>()? {
+            let gpio = gpio.parse().map_err(|_| {
+                serde::de::Error::custom(format!("invalid GPIO `{gpio}` in keymap"))
+            })?;
+            keys.push(KeyConfig {
+                gpio,
+                keycode,
+                debounce_ms: None,
+                active_low: None,
+                repeat: false,
+                repeat_delay_ms: None,
+                repeat_rate_ms: None,
+                initial_state: None,
+                filter_samples: None,
+                mode: Default::default(),
+                haptic_ms: None,
+            });
+        }
+
+        Ok(keys)
+    }
+}
@@ -2,29 +2,101 @@ mod log_level;
 
 use std::path::PathBuf;
 
+use rboy::framebuffer::PixelFormat;
+
 pub use self::log_level::LogLevel;
 
 /// rboy-legogb - rboy emulator adapted to run on Raspberry Pi with framebuffer and GPIO input
 #[derive(argh::FromArgs, Debug)]
 pub struct Args {
-    /// bytes per pixel for the framebuffer (default: 2)
+    /// name (or part of it) of the audio output device to use (default: system default)
+    #[argh(option)]
+    pub audio_device: Option<String>,
+    /// render a test pattern to verify the framebuffer configuration and exit on any key
+    #[argh(switch)]
+    pub calibrate: bool,
+    /// bytes per pixel for the framebuffer: 2 (rgb565), 3 (rgb888) or 4 (xrgb8888) (default: 2)
     #[argh(option, default = "2")]
     pub bytes_per_pixel: usize,
+    /// don't correct the colors of the color games to look like on a Game Boy Color screen, showing them more saturated
+    #[argh(switch)]
+    pub no_cgb_color_correct: bool,
+    /// dither the frames on 16 bits displays, hiding the banding of smooth color fades
+    #[argh(switch)]
+    pub dither: bool,
+    /// scale the frames down on panels narrower than the Game Boy screen scaled to their height, instead of cropping the sides
+    #[argh(switch)]
+    pub fit_width: bool,
+    /// TOML file with a color correction profile of the panel (a 3x3 matrix and the gammas)
+    #[argh(option)]
+    pub color_profile: Option<PathBuf>,
     /// path to config file (default: /etc/rboy-legogb/config.toml)
     #[argh(option, default = "PathBuf::from(\"/etc/rboy-legogb/config.toml\")")]
     pub config: PathBuf,
+    /// check the config, the ROMs directory and the framebuffer, then exit; fails if any problem is found
+    #[argh(switch)]
+    pub dry_run: bool,
+    /// also write the frames, raw RGB24 160x144, to a named pipe at this path (created if missing), e.g. for ffmpeg
+    #[argh(option)]
+    pub frame_pipe: Option<PathBuf>,
     /// path to framebuffer device (default: /dev/fb1)
     #[argh(option, default = "PathBuf::from(\"/dev/fb1\")")]
     pub framebuffer_path: PathBuf,
     /// framebuffer height (default: 240)
     #[argh(option, default = "240")]
     pub height: usize,
+    /// list the available audio output devices and exit
+    #[argh(switch)]
+    pub list_audio_devices: bool,
+    /// measure the latency from the GPIO edges to the key events, reporting min/avg/max until Ctrl-C
+    #[argh(switch)]
+    pub measure_latency: bool,
     /// log level (error, warn, info, debug, trace) (default: info)
     #[argh(option, default = "log_level::LogLevel::Info")]
     pub log_level: log_level::LogLevel,
+    /// don't use GPIO; read input commands (`down|up|press <KEY>`) from stdin instead
+    #[argh(switch)]
+    pub no_gpio: bool,
+    /// framebuffer pixel format (rgb565, bgr565, rgb888, bgr888, argb8888, abgr8888, xrgb8888);
+    /// overrides --bytes-per-pixel
+    #[argh(option)]
+    pub pixel_format: Option<PixelFormat>,
+    /// seed for the power-on RAM contents, for reproducible runs
+    #[argh(option)]
+    pub seed: Option<u32>,
     /// framebuffer stride in pixels (default: 320)
     #[argh(option, default = "320")]
     pub stride_pixels: usize,
+    /// play back a session bundle recorded with --record-session: same ROM (found by hash), config and inputs
+    #[argh(option)]
+    pub play_session: Option<PathBuf>,
+    /// record the inputs of each game, with the config and the ROM hash, to a session bundle for bug reports
+    #[argh(option)]
+    pub record_session: Option<PathBuf>,
+    /// boot minimally, e.g. to isolate a problem: audio, cheats, palettes, rumble, boot ROMs and the other optional features are disabled regardless of the config
+    #[argh(switch)]
+    pub safe_mode: bool,
+    /// show the debug overlay (frame rate, dropped frames, speed) when the game starts
+    #[argh(switch)]
+    pub show_fps: bool,
+    /// report the status (game, frame rate, battery, uptime) as JSON to each client of a Unix socket at this path
+    #[argh(option)]
+    pub status_socket: Option<PathBuf>,
+    /// show a test card with the display geometry and exit on any key
+    #[argh(switch)]
+    pub test_card: bool,
+    /// length in milliseconds of the fade to silence when the audio output underruns; 0 cuts immediately (default: 5)
+    #[argh(option, default = "5")]
+    pub underrun_fade_ms: u64,
+    /// frames buffered between the CPU and the display; higher values absorb jitter at the cost of latency (default: 1)
+    #[argh(option, default = "1")]
+    pub video_queue: usize,
+    /// longest wait in milliseconds for a new frame before the display loop handles the input again (default: 10)
+    #[argh(option, default = "10")]
+    pub display_poll_ms: u64,
+    /// most frames skipped in a row while the emulation falls behind; 0 disables the frame skip (default: 0)
+    #[argh(option, default = "0")]
+    pub max_frameskip: u32,
     /// framebuffer width (default: 320)
     #[argh(option, default = "320")]
     pub width: usize,
@@ -0,0 +1,177 @@
+use std::collections::VecDeque;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use rboy::input::Event;
+
+use crate::stdin_input::parse_command;
+
+/// Change of the attract mode state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trigger {
+    /// The menu has been idle long enough; start the demo
+    StartDemo,
+    /// Real input was received during the demo; go back to the menu
+    StopDemo,
+}
+
+/// Decides when the attract mode demo starts and stops.
+///
+/// The demo starts once no input is received for `timeout`, and any real input stops it.
+#[derive(Debug)]
+pub struct AttractTimer {
+    timeout: Duration,
+    last_input: Instant,
+    demo: bool,
+}
+
+impl AttractTimer {
+    /// Create an [`AttractTimer`] for the menu, idle since `now`
+    pub fn new(timeout: Duration, now: Instant) -> Self {
+        Self {
+            timeout,
+            last_input: now,
+            demo: false,
+        }
+    }
+
+    /// Create an [`AttractTimer`] with the demo already running
+    pub fn demo(timeout: Duration, now: Instant) -> Self {
+        Self {
+            demo: true,
+            ..Self::new(timeout, now)
+        }
+    }
+
+    /// Handle real input received at `now`
+    pub fn input(&mut self, now: Instant) -> Option<Trigger> {
+        self.last_input = now;
+        if self.demo {
+            self.demo = false;
+            Some(Trigger::StopDemo)
+        } else {
+            None
+        }
+    }
+
+    /// Check whether the idle timeout has expired at `now`
+    pub fn poll(&mut self, now: Instant) -> Option<Trigger> {
+        if !self.demo && now.saturating_duration_since(self.last_input) >= self.timeout {
+            self.demo = true;
+            Some(Trigger::StartDemo)
+        } else {
+            None
+        }
+    }
+}
+
+/// Recorded input played back during the demo.
+///
+/// Each line is `<frame> <command>`, where the command is the same as the stdin input
+/// (`down <KEY>`, `up <KEY>` or `press <KEY>`); empty lines and lines starting with `#` are ignored.
+/// The demo ends after the last event.
+#[derive(Debug, Clone, Default)]
+pub struct Replay {
+    events: VecDeque<(u64, Event)>,
+}
+
+impl Replay {
+    /// Load a [`Replay`] from a file
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let replay = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Failed to read replay {:?}: {}", path, e))?;
+        Self::parse(&replay).map_err(|e| anyhow::anyhow!("Invalid replay {:?}: {}", path, e))
+    }
+
+    /// Parse a [`Replay`]
+    pub fn parse(replay: &str) -> Result<Self, String> {
+        let mut events = VecDeque::new();
+        for (n, line) in replay.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((frame, command)) = line.split_once(char::is_whitespace) else {
+                return Err(format!("line {}: expected `<frame> <command>`", n + 1));
+            };
+            let frame: u64 = frame
+                .parse()
+                .map_err(|_| format!("line {}: invalid frame '{frame}'", n + 1))?;
+            if events.back().is_some_and(|(last, _)| *last > frame) {
+                return Err(format!("line {}: frames must be in order", n + 1));
+            }
+            let command_events =
+                parse_command(command).map_err(|e| format!("line {}: {e}", n + 1))?;
+            events.extend(command_events.into_iter().map(|event| (frame, event)));
+        }
+
+        Ok(Self { events })
+    }
+
+    /// Take the next event due at `frame`, if any
+    pub fn next_due(&mut self, frame: u64) -> Option<Event> {
+        if self.events.front()?.0 > frame {
+            return None;
+        }
+        self.events.pop_front().map(|(_, event)| event)
+    }
+
+    /// Whether all the events have been played
+    pub fn is_finished(&self) -> bool {
+        self.events.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use rboy::KeypadKey;
+    use rboy::input::KeyEvent;
+
+    use super::*;
+
+    #[test]
+    fn test_should_start_demo_when_idle_and_stop_on_input() {
+        let timeout = Duration::from_secs(60);
+        let start = Instant::now();
+        let mut timer = AttractTimer::new(timeout, start);
+
+        assert_eq!(timer.poll(start + Duration::from_secs(59)), None);
+        // input resets the idle timer
+        assert_eq!(timer.input(start + Duration::from_secs(30)), None);
+        assert_eq!(timer.poll(start + Duration::from_secs(89)), None);
+        assert_eq!(
+            timer.poll(start + Duration::from_secs(90)),
+            Some(Trigger::StartDemo)
+        );
+        // the demo starts only once
+        assert_eq!(timer.poll(start + Duration::from_secs(200)), None);
+        assert_eq!(
+            timer.input(start + Duration::from_secs(201)),
+            Some(Trigger::StopDemo)
+        );
+        assert_eq!(timer.input(start + Duration::from_secs(202)), None);
+    }
+
+    #[test]
+    fn test_should_parse_replay() {
+        let mut replay =
+            Replay::parse("# title screen\n10 press START\n\n12 down A\n40 up A\n").unwrap();
+
+        assert_eq!(replay.next_due(9), None);
+        assert_eq!(
+            replay.next_due(10),
+            Some((KeyEvent::Down, KeypadKey::Start))
+        );
+        assert_eq!(replay.next_due(10), Some((KeyEvent::Up, KeypadKey::Start)));
+        assert_eq!(replay.next_due(10), None);
+        assert_eq!(replay.next_due(20), Some((KeyEvent::Down, KeypadKey::A)));
+        assert!(!replay.is_finished());
+        assert_eq!(replay.next_due(40), Some((KeyEvent::Up, KeypadKey::A)));
+        assert!(replay.is_finished());
+
+        assert!(Replay::parse("10 hold A").is_err());
+        assert!(Replay::parse("ten down A").is_err());
+        assert!(Replay::parse("20 down A\n10 up A").is_err());
+    }
+}
@@ -0,0 +1,65 @@
+use cpal::traits::{DeviceTrait, HostTrait};
+
+/// Get the output audio device to use.
+///
+/// If `wanted` is set, the first device whose name contains it (case-insensitive) is picked;
+/// otherwise, or if none matches, the default output device is used.
+pub fn output_device(wanted: Option<&str>) -> Option<cpal::Device> {
+    let host = cpal::default_host();
+
+    if let Some(wanted) = wanted {
+        match host.output_devices() {
+            Ok(devices) => {
+                if let Some(device) = select_device(devices, |d| d.name().ok(), wanted) {
+                    return Some(device);
+                }
+                warn!("No audio device matches '{wanted}'; using the default one");
+            }
+            Err(err) => error!("Failed to enumerate audio devices: {err}"),
+        }
+    }
+
+    host.default_output_device()
+}
+
+/// List the names of the available output audio devices
+pub fn output_device_names() -> anyhow::Result<Vec<String>> {
+    let devices = cpal::default_host().output_devices()?;
+
+    Ok(devices.filter_map(|d| d.name().ok()).collect())
+}
+
+/// Pick the first device whose name contains `wanted` (case-insensitive)
+fn select_device<D, F>(devices: impl IntoIterator<Item = D>, name: F, wanted: &str) -> Option<D>
+where
+    F: Fn(&D) -> Option<String>,
+{
+    let wanted = wanted.to_lowercase();
+
+    devices
+        .into_iter()
+        .find(|device| name(device).is_some_and(|name| name.to_lowercase().contains(&wanted)))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_should_select_device_by_name() {
+        let devices = ["bcm2835 HDMI 1", "bcm2835 Headphones", "USB Audio DAC"];
+        let name = |d: &&str| Some(d.to_string());
+
+        assert_eq!(
+            select_device(devices, name, "headphones"),
+            Some("bcm2835 Headphones")
+        );
+        assert_eq!(select_device(devices, name, "USB"), Some("USB Audio DAC"));
+        assert_eq!(
+            select_device(devices, name, "bcm2835"),
+            Some("bcm2835 HDMI 1")
+        );
+        assert_eq!(select_device(devices, name, "spdif"), None);
+    }
+}
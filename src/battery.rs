@@ -0,0 +1,73 @@
+use std::path::Path;
+
+/// Decides when to warn that the battery is low.
+///
+/// The warning triggers once when the charge drops below the threshold, and is armed again
+/// only when the charge rises `hysteresis` points above it, so it doesn't flicker near the threshold.
+#[derive(Debug)]
+pub struct BatteryMonitor {
+    low_percent: u8,
+    hysteresis: u8,
+    warned: bool,
+}
+
+impl BatteryMonitor {
+    pub fn new(low_percent: u8, hysteresis: u8) -> Self {
+        Self {
+            low_percent,
+            hysteresis,
+            warned: false,
+        }
+    }
+
+    /// Handle a charge reading and return whether the low battery warning must be shown
+    pub fn update(&mut self, percent: u8) -> bool {
+        if self.warned {
+            if percent >= self.low_percent.saturating_add(self.hysteresis) {
+                self.warned = false;
+            }
+            false
+        } else if percent < self.low_percent {
+            self.warned = true;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Read the charge in percent from a fuel gauge `capacity` file, e.g. `/sys/class/power_supply/battery/capacity`
+pub fn read_capacity(path: &Path) -> anyhow::Result<u8> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("Failed to read battery capacity {:?}: {}", path, e))?;
+    let percent: u8 = content
+        .trim()
+        .parse()
+        .map_err(|e| anyhow::anyhow!("Invalid battery capacity {:?}: {}", content.trim(), e))?;
+
+    Ok(percent.min(100))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_should_warn_once_below_threshold() {
+        let mut monitor = BatteryMonitor::new(15, 3);
+
+        assert!(!monitor.update(40));
+        assert!(!monitor.update(15));
+        assert!(monitor.update(14));
+        // readings bouncing around the threshold don't warn again
+        assert!(!monitor.update(13));
+        assert!(!monitor.update(16));
+        assert!(!monitor.update(14));
+        assert!(!monitor.update(5));
+
+        // charging above the hysteresis arms it again
+        assert!(!monitor.update(18));
+        assert!(monitor.update(12));
+    }
+}
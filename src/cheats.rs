@@ -0,0 +1,153 @@
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+
+/// Memory a cheat can write to: the cartridge RAM, the work RAM and the high RAM
+const CHEAT_RANGES: [RangeInclusive<u16>; 3] = [0xA000..=0xBFFF, 0xC000..=0xDFFF, 0xFF80..=0xFFFE];
+
+/// A GameShark code, writing a value to the RAM on every frame
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cheat {
+    pub name: String,
+    address: u16,
+    value: u8,
+    pub enabled: bool,
+}
+
+impl Cheat {
+    /// Parse a GameShark code, `01VVLLHH` where `VV` is the value and `HHLL` the address
+    fn parse_gameshark(name: &str, code: &str) -> Result<Self, String> {
+        let bytes = (code.len() == 8)
+            .then(|| {
+                (0..4)
+                    .map(|i| u8::from_str_radix(&code[i * 2..i * 2 + 2], 16).ok())
+                    .collect::<Option<Vec<u8>>>()
+            })
+            .flatten()
+            .ok_or_else(|| format!("invalid GameShark code `{code}`"))?;
+        if bytes[0] != 0x01 {
+            return Err(format!("unsupported GameShark code type `{code}`"));
+        }
+        let address = u16::from_le_bytes([bytes[2], bytes[3]]);
+        if !CHEAT_RANGES.iter().any(|range| range.contains(&address)) {
+            return Err(format!(
+                "GameShark code `{code}` writes to {address:#06x}, outside of the RAM"
+            ));
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            address,
+            value: bytes[1],
+            enabled: false,
+        })
+    }
+}
+
+/// Cheats of a game, loaded from `<game>.cht` next to the ROM.
+///
+/// Each line is `<GameShark code> <name>`; empty lines and lines starting with `#` are ignored.
+/// All the cheats start disabled.
+#[derive(Debug, Clone, Default)]
+pub struct Cheats {
+    cheats: Vec<Cheat>,
+}
+
+impl Cheats {
+    /// Load the cheats of the given ROM, if it has a cheat file
+    pub fn load(rom: &Path) -> anyhow::Result<Option<Self>> {
+        let path = cheats_path(rom);
+        if !path.is_file() {
+            return Ok(None);
+        }
+        let content = std::fs::read_to_string(&path)
+            .map_err(|e| anyhow::anyhow!("Failed to read cheats {:?}: {}", path, e))?;
+        let cheats = Self::parse(&content)
+            .map_err(|e| anyhow::anyhow!("Invalid cheats {:?}: {}", path, e))?;
+        info!(
+            "Loaded {} cheats from {}",
+            cheats.cheats.len(),
+            path.display()
+        );
+
+        Ok(Some(cheats))
+    }
+
+    fn parse(content: &str) -> Result<Self, String> {
+        let mut cheats = vec![];
+        for (n, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (code, name) = line.split_once(char::is_whitespace).unwrap_or((line, line));
+            cheats.push(
+                Cheat::parse_gameshark(name.trim(), code)
+                    .map_err(|e| format!("line {}: {e}", n + 1))?,
+            );
+        }
+
+        Ok(Self { cheats })
+    }
+
+    pub fn cheats(&self) -> &[Cheat] {
+        &self.cheats
+    }
+
+    /// Enable or disable the cheat at `index`
+    pub fn toggle(&mut self, index: usize) {
+        if let Some(cheat) = self.cheats.get_mut(index) {
+            cheat.enabled = !cheat.enabled;
+            info!(
+                "Cheat {}: {}",
+                cheat.name,
+                if cheat.enabled { "on" } else { "off" }
+            );
+        }
+    }
+
+    /// Writes (address, value) of the enabled cheats, to apply on every frame
+    pub fn applied(&self) -> Vec<(u16, u8)> {
+        self.cheats
+            .iter()
+            .filter(|cheat| cheat.enabled)
+            .map(|cheat| (cheat.address, cheat.value))
+            .collect()
+    }
+}
+
+/// Path of the cheat file of the given ROM
+fn cheats_path(rom: &Path) -> PathBuf {
+    rom.with_extension("cht")
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_should_toggle_applied_cheats() {
+        let mut cheats = Cheats::parse("# Tetris\n01099FC0 Level 9\n\n010AA0C0 Lines\n").unwrap();
+        assert_eq!(cheats.cheats().len(), 2);
+        assert_eq!(cheats.cheats()[0].name, "Level 9");
+        assert!(cheats.applied().is_empty());
+
+        cheats.toggle(1);
+        assert_eq!(cheats.applied(), vec![(0xC0A0, 0x0A)]);
+        cheats.toggle(0);
+        assert_eq!(cheats.applied(), vec![(0xC09F, 0x09), (0xC0A0, 0x0A)]);
+        cheats.toggle(1);
+        assert_eq!(cheats.applied(), vec![(0xC09F, 0x09)]);
+        // out of range
+        cheats.toggle(2);
+        assert_eq!(cheats.applied().len(), 1);
+
+        assert!(Cheats::parse("0109 short").is_err());
+        assert!(Cheats::parse("91099FC0 other type").is_err());
+        // ROM and I/O registers
+        assert!(Cheats::parse("01090040 bank switch").is_err());
+        assert!(Cheats::parse("010940FF lcd control").is_err());
+        assert!(Cheats::parse("010980FF high ram").is_ok());
+        assert!(Cheats::parse("010900A0 cartridge ram").is_ok());
+    }
+}
@@ -1,6 +1,7 @@
 use serde::{Deserialize, Serialize};
 
-use crate::mmu::Mmu;
+use crate::gbmode::GbMode;
+use crate::mmu::{CGB_BOOT_ROM_SIZE, DMG_BOOT_ROM_SIZE, Mmu};
 use crate::register::CpuFlag::{C, H, N, Z};
 use crate::register::Registers;
 use crate::serial::SerialCallback;
@@ -55,6 +56,30 @@ impl Cpu {
         })
     }
 
+    /// Run `boot_rom` from power on instead of starting the cartridge directly.
+    ///
+    /// The boot ROM must be a DMG one (256 bytes) in classic mode and a CGB one (2304 bytes) otherwise.
+    pub fn load_boot_rom(&mut self, boot_rom: Vec<u8>) -> StrResult<()> {
+        let expected_size = match self.mmu.gbmode {
+            GbMode::Classic => DMG_BOOT_ROM_SIZE,
+            GbMode::Color | GbMode::ColorAsClassic => CGB_BOOT_ROM_SIZE,
+        };
+        if boot_rom.len() != expected_size {
+            return Err("Invalid boot ROM size");
+        }
+
+        self.reg = Registers::power_on();
+        // the boot ROM turns the LCD on by itself
+        self.mmu.wb(0xFF40, 0);
+        self.mmu.map_boot_rom(boot_rom);
+
+        Ok(())
+    }
+
+    pub fn registers(&self) -> &Registers {
+        &self.reg
+    }
+
     pub fn do_cycle(&mut self) -> u32 {
         let ticks = self.docycle() * 4;
         self.mmu.do_cycle(ticks)
@@ -2602,7 +2627,7 @@ mod test {
         let serial = Arc::new(Mutex::new(Serial { output: Vec::new() }));
 
         {
-            let cart = mbc::FileBackedMBC::new(CPUINSTRS.into(), false).unwrap();
+            let cart = mbc::FileBackedMBC::new(CPUINSTRS.into(), 0, false).unwrap();
             let mut c = match Cpu::new(
                 Box::new(cart),
                 Some(Box::new(SerialWrapper(serial.clone()))),
@@ -2639,7 +2664,7 @@ mod test {
         let serial = Arc::new(Mutex::new(Serial { output: Vec::new() }));
 
         {
-            let cart = mbc::FileBackedMBC::new(CPUINSTRS.into(), false).unwrap();
+            let cart = mbc::FileBackedMBC::new(CPUINSTRS.into(), 0, false).unwrap();
             let mut c = match Cpu::new_cgb(
                 Box::new(cart),
                 Some(Box::new(SerialWrapper(serial.clone()))),
@@ -0,0 +1,116 @@
+use std::time::{Duration, Instant};
+
+use rboy::framebuffer::{Framebuffer, GLYPH_SIZE};
+
+/// Frames per second of the Game Boy at normal speed
+const GB_FPS: f32 = 4_194_304.0 / 70_224.0;
+/// Interval between two updates of the stats
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+const MARGIN: usize = 4;
+const PADDING: usize = 2;
+const COLOR_BACKGROUND: u16 = 0x0000;
+const COLOR_TEXT: u16 = 0x07e0;
+
+/// Debug overlay with the frame rate, the dropped frames and the emulation speed,
+/// shown in the bottom left corner over the game frames.
+#[derive(Debug)]
+pub struct DebugOverlay {
+    visible: bool,
+    /// Frames displayed since `sample_start`
+    frames: u32,
+    /// Frames dropped before `sample_start`
+    dropped: u64,
+    sample_start: Instant,
+    /// Frame rate of the last sample
+    fps: Option<f32>,
+    text: String,
+}
+
+impl DebugOverlay {
+    pub fn new(visible: bool, now: Instant) -> Self {
+        Self {
+            visible,
+            frames: 0,
+            dropped: 0,
+            sample_start: now,
+            fps: None,
+            text: "FPS --".to_string(),
+        }
+    }
+
+    /// Show or hide the overlay; returns whether it's visible
+    pub fn toggle(&mut self) -> bool {
+        self.visible = !self.visible;
+        self.visible
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    /// Frame rate measured over the last sample, once available
+    pub fn fps(&self) -> Option<f32> {
+        self.fps
+    }
+
+    /// Count a frame displayed at `now`, with `dropped` frames dropped since the game started
+    pub fn frame(&mut self, dropped: u64, now: Instant) {
+        self.frames += 1;
+        let elapsed = now.saturating_duration_since(self.sample_start);
+        if elapsed < SAMPLE_INTERVAL {
+            return;
+        }
+
+        let secs = elapsed.as_secs_f32();
+        let dropped_now = dropped.saturating_sub(self.dropped);
+        let fps = self.frames as f32 / secs;
+        // dropped frames have been emulated too
+        let speed = (self.frames as f32 + dropped_now as f32) / secs / GB_FPS;
+        self.text = format!("FPS {fps:.1} DROP {dropped} x{speed:.2}");
+        self.fps = Some(fps);
+        self.frames = 0;
+        self.dropped = dropped;
+        self.sample_start = now;
+    }
+
+    /// Draw the overlay over the last frame written, if visible
+    pub fn draw(&self, framebuffer: &Framebuffer) {
+        if !self.visible {
+            return;
+        }
+
+        let y = framebuffer.height().saturating_sub(MARGIN + GLYPH_SIZE);
+        framebuffer.fill_overlay_rect(
+            MARGIN - PADDING,
+            y - PADDING,
+            self.text.len() * GLYPH_SIZE + PADDING * 2,
+            GLYPH_SIZE + PADDING * 2,
+            COLOR_BACKGROUND,
+        );
+        framebuffer.draw_text(&self.text, MARGIN, y, false, COLOR_TEXT);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_should_toggle_debug_overlay() {
+        let start = Instant::now();
+        let mut overlay = DebugOverlay::new(false, start);
+        assert!(!overlay.is_visible());
+        assert!(overlay.toggle());
+        assert!(overlay.is_visible());
+
+        // 30 frames shown and 30 dropped in a second: full speed at half the frame rate
+        for frame in 1..=30 {
+            overlay.frame(frame, start + SAMPLE_INTERVAL * frame as u32 / 30);
+        }
+        assert_eq!(overlay.text, "FPS 30.0 DROP 30 x1.00");
+
+        assert!(!overlay.toggle());
+        assert!(!overlay.is_visible());
+    }
+}
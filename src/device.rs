@@ -1,3 +1,5 @@
+use std::fmt;
+use std::io::Write as _;
 use std::path::Path;
 
 use serde::{Deserialize, Serialize};
@@ -5,10 +7,50 @@ use serde::{Deserialize, Serialize};
 use crate::cpu::Cpu;
 use crate::gbmode::GbMode;
 use crate::keypad::KeypadKey;
-use crate::printer::GbPrinter;
+use crate::printer::{GbPrinter, PrintOptions};
 use crate::serial::SerialCallback;
 use crate::{StrResult, mbc, serial, sound};
 
+/// Header of the save states, followed by their version
+const STATE_MAGIC: &[u8; 8] = b"RBOYSTAT";
+/// Version of the save states, to bump whenever the layout of the CPU state changes;
+/// the states of the previous versions are migrated in [`decode_state`]
+const STATE_VERSION: u16 = 1;
+
+/// Why a save state can't be loaded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StateError {
+    /// The file can't be read
+    Unreadable,
+    /// The file is truncated or garbled
+    Corrupt,
+    /// The state has been saved with a version of the format which can't be migrated
+    UnsupportedVersion(u16),
+}
+
+impl StateError {
+    pub fn message(&self) -> &'static str {
+        match self {
+            StateError::Unreadable => "Unreadable state",
+            StateError::Corrupt => "Corrupt state",
+            StateError::UnsupportedVersion(_) => "Incompatible state",
+        }
+    }
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StateError::UnsupportedVersion(version) => write!(
+                f,
+                "{} (version {version}, supported up to {STATE_VERSION})",
+                self.message()
+            ),
+            _ => f.write_str(self.message()),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct Device {
     cpu: Cpu,
@@ -18,8 +60,7 @@ pub struct Device {
 impl Drop for Device {
     fn drop(&mut self) {
         if let Some(path) = &self.save_state {
-            let file = std::fs::File::create(path).unwrap();
-            ciborium::into_writer(&self.cpu, file).unwrap();
+            self.save_state_to(Path::new(path)).unwrap();
         }
     }
 }
@@ -39,29 +80,63 @@ impl SerialCallback for StdoutPrinter {
 
 impl Device {
     pub fn load_state(path: &str) -> Option<Box<Device>> {
-        let file = std::fs::File::open(path).ok()?;
-        let cpu = ciborium::de::from_reader(file).ok()?;
+        let cpu = read_state(Path::new(path)).ok()?;
         Some(Box::new(Device {
-            cpu,
+            cpu: *cpu,
             save_state: Some(path.to_string()),
         }))
     }
 
+    /// Write the state of the device to `path`.
+    ///
+    /// The state is written to a temporary file first, so `path` is never left half written.
+    pub fn save_state_to(&self, path: &Path) -> StrResult<()> {
+        let mut state = STATE_MAGIC.to_vec();
+        state.extend_from_slice(&STATE_VERSION.to_le_bytes());
+        ciborium::into_writer(&self.cpu, &mut state).map_err(|_| "Could not write state")?;
+
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        let mut file = std::fs::File::create(&tmp).map_err(|_| "Could not create state file")?;
+        file.write_all(&state)
+            .and_then(|()| file.sync_all())
+            .and_then(|()| std::fs::rename(&tmp, path))
+            .map_err(|_| "Could not write state")
+    }
+
+    /// Check that the state saved at `path` can be loaded, without loading it
+    pub fn check_state(path: &Path) -> Result<(), StateError> {
+        read_state(path).map(|_| ())
+    }
+
+    /// Replace the state of the device with the one saved at `path`, keeping the audio output
+    pub fn load_state_from(&mut self, path: &Path) -> Result<(), StateError> {
+        let mut cpu = read_state(path)?;
+        cpu.mmu.sound = self.cpu.mmu.sound.take();
+        cpu.mmu.gpu.color_correction = self.cpu.mmu.gpu.color_correction;
+        cpu.mmu.gpu.skip_render = self.cpu.mmu.gpu.skip_render;
+        self.cpu = *cpu;
+
+        Ok(())
+    }
+
     pub fn new(
         romname: &Path,
+        save_slot: usize,
         skip_checksum: bool,
         save_state: Option<String>,
     ) -> StrResult<Device> {
-        let cart = mbc::FileBackedMBC::new(romname.to_path_buf(), skip_checksum)?;
+        let cart = mbc::FileBackedMBC::new(romname.to_path_buf(), save_slot, skip_checksum)?;
         Cpu::new(Box::new(cart), None).map(|cpu| Device { cpu, save_state })
     }
 
     pub fn new_cgb(
         romname: &Path,
+        save_slot: usize,
         skip_checksum: bool,
         save_state: Option<String>,
     ) -> StrResult<Device> {
-        let cart = mbc::FileBackedMBC::new(romname.to_path_buf(), skip_checksum)?;
+        let cart = mbc::FileBackedMBC::new(romname.to_path_buf(), save_slot, skip_checksum)?;
         Cpu::new_cgb(Box::new(cart), None).map(|cpu| Device { cpu, save_state })
     }
 
@@ -83,10 +158,93 @@ impl Device {
         Cpu::new_cgb(cart, None).map(|cpu| Device { cpu, save_state })
     }
 
+    /// Correct the colors of the color games to look like on a CGB screen; enabled by default
+    pub fn set_cgb_color_correction(&mut self, enabled: bool) {
+        self.cpu.mmu.gpu.color_correction = enabled;
+    }
+
+    /// Skip drawing the frames, e.g. to catch up with the wall clock; the screen keeps the last frame drawn
+    pub fn set_skip_render(&mut self, skip: bool) {
+        self.cpu.mmu.gpu.skip_render = skip;
+    }
+
+    /// Load the battery-backed RAM of the cartridge, e.g. from a copy of a save file
+    pub fn load_ram(&mut self, ramdata: &[u8]) -> StrResult<()> {
+        self.cpu.mmu.mbc.loadram(ramdata)
+    }
+
+    /// Run the boot ROM before the game, instead of starting the game directly.
+    ///
+    /// Must be called before running the device. The boot ROM must be a DMG one (256 bytes)
+    /// for classic devices and a CGB one (2304 bytes) for color devices;
+    /// the device is left untouched if it's invalid.
+    pub fn load_boot_rom(&mut self, boot_rom: Vec<u8>) -> StrResult<()> {
+        self.cpu.load_boot_rom(boot_rom)
+    }
+
+    /// Describe the state of the device as `key=value` lines between `STATE` and `END`:
+    /// the CPU registers, the frame count, the keys held down and the CRC32 of the screen.
+    pub fn dump_state(&self) -> String {
+        let reg = self.cpu.registers();
+        let keys: Vec<String> = self
+            .cpu
+            .mmu
+            .keypad
+            .pressed()
+            .iter()
+            .map(|key| format!("{key:?}"))
+            .collect();
+
+        format!(
+            "STATE\n\
+             af={:04x}\nbc={:04x}\nde={:04x}\nhl={:04x}\nsp={:04x}\npc={:04x}\n\
+             frame={}\nkeys={}\nscreen={:08x}\n\
+             END\n",
+            reg.af(),
+            reg.bc(),
+            reg.de(),
+            reg.hl(),
+            reg.sp,
+            reg.pc,
+            self.frame_count(),
+            keys.join(","),
+            crc32fast::hash(self.get_gpu_data()),
+        )
+    }
+
+    /// Run an instruction and return its length in ticks of the single-speed clock, so the
+    /// time they take is the same in CGB double-speed mode
     pub fn do_cycle(&mut self) -> u32 {
         self.cpu.do_cycle()
     }
 
+    /// Whether the CGB CPU is running in double-speed mode
+    pub fn is_double_speed(&self) -> bool {
+        self.cpu.mmu.is_double_speed()
+    }
+
+    /// Seed the power-on contents of the RAM, which are otherwise random on real hardware.
+    ///
+    /// Must be called before running the device.
+    pub fn seed(&mut self, seed: u32) {
+        self.cpu.mmu.seed_ram(seed);
+    }
+
+    /// Number of frames rendered since power on
+    pub fn frame_count(&self) -> u64 {
+        self.cpu.mmu.gpu.frame_count
+    }
+
+    /// Whether the game turned on the rumble motor of the cartridge
+    pub fn rumble(&self) -> bool {
+        self.cpu.mmu.mbc.rumble()
+    }
+
+    /// Whether the game is a classic one, drawn in shades of gray
+    pub fn is_classic_game(&self) -> bool {
+        self.cpu.mmu.gbmode != GbMode::Color
+    }
+
     pub fn set_stdout(&mut self, output: bool) {
         if output {
             self.cpu.mmu.serial.set_callback(Box::new(StdoutPrinter));
@@ -96,7 +254,12 @@ impl Device {
     }
 
     pub fn attach_printer(&mut self) {
-        let printer = GbPrinter::new();
+        self.attach_printer_with(PrintOptions::default());
+    }
+
+    /// Attach a Game Boy Printer assembling the printed images with the given options
+    pub fn attach_printer_with(&mut self, options: PrintOptions) {
+        let printer = GbPrinter::with_options(options);
 
         self.cpu.mmu.serial.set_callback(Box::new(printer));
     }
@@ -180,3 +343,215 @@ impl Device {
         self.cpu.write_wide(address, byte)
     }
 }
+
+/// Read the CPU state saved at `path`; states saved without a header, before it was added,
+/// are version 0.
+///
+/// The state is boxed, to keep the large CPU state off the stack of the callers.
+fn read_state(path: &Path) -> Result<Box<Cpu>, StateError> {
+    let state = std::fs::read(path).map_err(|_| StateError::Unreadable)?;
+    match state.strip_prefix(STATE_MAGIC) {
+        Some(versioned) => {
+            let (version, body) = versioned
+                .split_first_chunk::<2>()
+                .ok_or(StateError::Corrupt)?;
+            decode_state(u16::from_le_bytes(*version), body)
+        }
+        None => decode_state(0, &state),
+    }
+}
+
+/// Decode a CPU state saved with the given version of the format, migrating it to the current one
+fn decode_state(version: u16, body: &[u8]) -> Result<Box<Cpu>, StateError> {
+    match version {
+        // the header was added without changing the layout
+        0 | STATE_VERSION => ciborium::de::from_reader(body).map_err(|_| StateError::Corrupt),
+        version => Err(StateError::UnsupportedVersion(version)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::hash::{DefaultHasher, Hash, Hasher};
+
+    use super::*;
+
+    #[test]
+    fn test_should_check_state_version() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("test.state");
+        let mut device = Device::new_from_buffer(test_rom(), true, None).unwrap();
+        device.save_state_to(&path).unwrap();
+        let state = std::fs::read(&path).unwrap();
+        assert!(state.starts_with(STATE_MAGIC));
+
+        while device.frame_count() == 0 {
+            device.do_cycle();
+        }
+        assert_eq!(Device::check_state(&path), Ok(()));
+        device.load_state_from(&path).unwrap();
+        assert_eq!(device.frame_count(), 0);
+
+        // states saved before the header are migrated
+        std::fs::write(&path, &state[STATE_MAGIC.len() + 2..]).unwrap();
+        assert_eq!(Device::check_state(&path), Ok(()));
+
+        let mut future = state.clone();
+        future[STATE_MAGIC.len()..STATE_MAGIC.len() + 2].copy_from_slice(&2u16.to_le_bytes());
+        std::fs::write(&path, future).unwrap();
+        let err = Device::check_state(&path).unwrap_err();
+        assert_eq!(err, StateError::UnsupportedVersion(2));
+        assert_eq!(
+            err.to_string(),
+            "Incompatible state (version 2, supported up to 1)"
+        );
+        assert_eq!(
+            device.load_state_from(&path),
+            Err(StateError::UnsupportedVersion(2))
+        );
+    }
+
+    #[test]
+    fn test_should_run_deterministically_with_same_seed() {
+        let inputs = [
+            (2, true, KeypadKey::Start),
+            (4, false, KeypadKey::Start),
+            (5, true, KeypadKey::A),
+        ];
+
+        assert_eq!(run_replay(42, &inputs), run_replay(42, &inputs));
+        assert_ne!(run_replay(42, &inputs), run_replay(7, &inputs));
+    }
+
+    #[test]
+    fn test_should_boot_from_boot_rom() {
+        // disable the boot ROM, then fall into the cartridge entry point
+        let mut boot_rom = vec![0; 0x100];
+        boot_rom[0xFC..0x100].copy_from_slice(&[0xE0, 0x50, 0x00, 0x00]); // ldh (0x50), a; nop; nop
+        boot_rom[0..3].copy_from_slice(&[0x3E, 0x01, 0xC3]); // ld a, 1; jp 0x00FC
+        boot_rom[3..5].copy_from_slice(&[0xFC, 0x00]);
+
+        let mut device = Device::new_from_buffer(test_rom(), true, None).unwrap();
+        device.load_boot_rom(boot_rom.clone()).unwrap();
+        assert_eq!(device.read_byte(0x0000), 0x3E);
+        for _ in 0..4 {
+            device.do_cycle();
+        }
+        // the cartridge is mapped back
+        assert_eq!(device.read_byte(0x0000), 0x00);
+        assert_eq!(device.read_byte(0x0100), 0x18);
+
+        // a DMG boot ROM doesn't fit a CGB device, and truncated ones are rejected
+        let mut cgb = Device::new_cgb_from_buffer(test_rom(), true, None).unwrap();
+        assert!(cgb.load_boot_rom(boot_rom).is_err());
+        assert!(device.load_boot_rom(vec![0; 0x80]).is_err());
+    }
+
+    #[test]
+    fn test_should_dump_state() {
+        let mut device = Device::new_from_buffer(test_rom(), true, None).unwrap();
+        device.keydown(KeypadKey::Start);
+        device.keydown(KeypadKey::Up);
+
+        let dump = device.dump_state();
+        let fields: Vec<&str> = dump.lines().collect();
+        assert_eq!(
+            fields[..10],
+            [
+                "STATE",
+                "af=01b0",
+                "bc=0013",
+                "de=00d8",
+                "hl=014d",
+                "sp=fffe",
+                "pc=0100",
+                "frame=0",
+                "keys=Up,Start",
+                &format!("screen={:08x}", crc32fast::hash(device.get_gpu_data())),
+            ]
+        );
+        assert_eq!(fields[10], "END");
+        assert_eq!(fields.len(), 11);
+    }
+
+    #[test]
+    fn test_should_expose_mbc5_rumble() {
+        let mut rom = test_rom();
+        rom[0x147] = 0x1C; // MBC5 + rumble
+        // ld a, 0x08; ld (0x4000), a; jr -2
+        rom[0x100..0x107].copy_from_slice(&[0x3E, 0x08, 0xEA, 0x00, 0x40, 0x18, 0xFE]);
+        let mut device = Device::new_from_buffer(rom, true, None).unwrap();
+        assert!(!device.rumble());
+
+        for _ in 0..4 {
+            device.do_cycle();
+        }
+        assert!(device.rumble());
+    }
+
+    /// ROM looping at its entry point
+    /// Ticks run by `device` from the start of a frame to the start of the next one
+    fn ticks_per_frame(device: &mut Device) -> u32 {
+        let frame = device.frame_count() + 1;
+        while device.frame_count() < frame {
+            device.do_cycle();
+        }
+        let mut ticks = 0;
+        while device.frame_count() == frame {
+            ticks += device.do_cycle();
+        }
+        ticks
+    }
+
+    #[test]
+    fn test_should_pace_frames_on_same_ticks_in_double_speed() {
+        let mut single = Device::new_from_buffer(test_rom(), true, None).unwrap();
+        let single_ticks = ticks_per_frame(&mut single);
+
+        let mut rom = test_rom();
+        rom[0x143] = 0x80;
+        // ld a, 1; ldh (KEY1), a; stop; jr -2
+        rom[0x100..0x108].copy_from_slice(&[0x3E, 0x01, 0xE0, 0x4D, 0x10, 0x00, 0x18, 0xFE]);
+        let mut double = Device::new_cgb_from_buffer(rom, true, None).unwrap();
+        let double_ticks = ticks_per_frame(&mut double);
+        assert!(double.is_double_speed());
+        assert!(!single.is_double_speed());
+
+        // the cycle budget counts these ticks: the frames take the same time in both modes
+        assert!(single_ticks.abs_diff(70224) <= 12, "{single_ticks}");
+        assert!(double_ticks.abs_diff(70224) <= 12, "{double_ticks}");
+    }
+
+    fn test_rom() -> Vec<u8> {
+        let mut rom = vec![0; 0x8000];
+        // entry point: jr -2
+        rom[0x100..0x102].copy_from_slice(&[0x18, 0xFE]);
+        rom
+    }
+
+    /// Run 8 frames replaying `inputs` and return the hash of the final frame and RAM
+    fn run_replay(seed: u32, inputs: &[(u64, bool, KeypadKey)]) -> u64 {
+        let mut device = Device::new_from_buffer(test_rom(), true, None).unwrap();
+        device.seed(seed);
+
+        while device.frame_count() < 8 {
+            let frame = device.frame_count();
+            for (_, down, key) in inputs.iter().filter(|(f, ..)| *f == frame) {
+                if *down {
+                    device.keydown(*key);
+                } else {
+                    device.keyup(*key);
+                }
+            }
+            device.do_cycle();
+        }
+
+        let mut hasher = DefaultHasher::new();
+        device.get_gpu_data().hash(&mut hasher);
+        for address in 0xC000..0xE000 {
+            device.read_byte(address).hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+}
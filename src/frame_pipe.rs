@@ -0,0 +1,184 @@
+use std::ffi::CString;
+use std::fs::File;
+use std::io::Write;
+use std::os::fd::AsRawFd;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{FileTypeExt, OpenOptionsExt};
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender, TrySendError};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// Interval between the checks for a reader, or for the exit while no frames come
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Writes the game frames to a named pipe (FIFO), e.g. for `ffmpeg` to record or stream them.
+///
+/// The frames are raw RGB24, 160x144, one after the other. They're written by a thread, which
+/// waits for a reader and waits again for the next one once it goes away; frames are dropped
+/// while there's no reader or it doesn't keep up, so the emulation never waits for it.
+/// The thread stops once `exit` is set or all the senders are gone.
+#[derive(Debug, Clone)]
+pub struct FramePipe {
+    sender: SyncSender<Vec<u8>>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl FramePipe {
+    /// Create the FIFO at `path` if it doesn't exist and start writing the frames sent to it,
+    /// until `exit` is set
+    pub fn spawn(path: &Path, exit: Arc<AtomicBool>) -> anyhow::Result<(Self, JoinHandle<()>)> {
+        match std::fs::metadata(path) {
+            Ok(metadata) if metadata.file_type().is_fifo() => {}
+            Ok(_) => anyhow::bail!("{:?} exists and is not a named pipe", path),
+            Err(_) => {
+                let c_path = CString::new(path.as_os_str().as_bytes())?;
+                if unsafe { libc::mkfifo(c_path.as_ptr(), 0o644) } != 0 {
+                    anyhow::bail!(
+                        "Failed to create named pipe {:?}: {}",
+                        path,
+                        std::io::Error::last_os_error()
+                    );
+                }
+            }
+        }
+        info!("Writing the frames to {}", path.display());
+
+        // a single frame is queued; the next ones are dropped until it's written
+        let (sender, receiver) = mpsc::sync_channel(1);
+        let path = path.to_path_buf();
+        let thread = std::thread::spawn(move || write_frames(&path, receiver, &exit));
+
+        Ok((
+            Self {
+                sender,
+                dropped: Arc::default(),
+            },
+            thread,
+        ))
+    }
+
+    /// Queue a frame, dropping it if the previous one hasn't been written yet
+    pub fn send(&self, frame: &[u8]) {
+        match self.sender.try_send(frame.to_vec()) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+            Err(TrySendError::Disconnected(_)) => {}
+        }
+    }
+
+    /// Frames dropped so far
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+/// Write the frames received to the FIFO at `path` until `exit` is set or all the senders are gone
+fn write_frames(path: &Path, receiver: Receiver<Vec<u8>>, exit: &AtomicBool) {
+    while !exit.load(Ordering::SeqCst) {
+        let mut pipe = match open_writer(path) {
+            Ok(pipe) => pipe,
+            // no reader yet: drop the frames meanwhile
+            Err(err) if err.raw_os_error() == Some(libc::ENXIO) => {
+                match receiver.recv_timeout(POLL_INTERVAL) {
+                    Err(RecvTimeoutError::Disconnected) => return,
+                    Ok(_) | Err(RecvTimeoutError::Timeout) => continue,
+                }
+            }
+            Err(err) => {
+                error!("Failed to open {}: {err}", path.display());
+                return;
+            }
+        };
+        info!("Frame pipe reader connected");
+        // skip the frame queued while waiting for the reader
+        let _ = receiver.try_recv();
+
+        loop {
+            let frame = match receiver.recv_timeout(POLL_INTERVAL) {
+                Ok(frame) => frame,
+                Err(RecvTimeoutError::Timeout) if exit.load(Ordering::SeqCst) => return,
+                Err(RecvTimeoutError::Timeout) => continue,
+                Err(RecvTimeoutError::Disconnected) => return,
+            };
+            if let Err(err) = pipe.write_all(&frame) {
+                info!("Frame pipe reader disconnected: {err}");
+                break;
+            }
+        }
+    }
+}
+
+/// Open the FIFO at `path` for writing without waiting for a reader, failing with `ENXIO` if
+/// there's none; the writes then block until the reader takes the data
+fn open_writer(path: &Path) -> std::io::Result<File> {
+    let pipe = std::fs::OpenOptions::new()
+        .write(true)
+        .custom_flags(libc::O_NONBLOCK)
+        .open(path)?;
+    let fd = pipe.as_raw_fd();
+    let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+    if flags < 0 || unsafe { libc::fcntl(fd, libc::F_SETFL, flags & !libc::O_NONBLOCK) } < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    Ok(pipe)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::io::Read;
+
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_should_write_frames_to_fifo() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("frames");
+        let (pipe, _thread) = FramePipe::spawn(&path, Arc::default()).unwrap();
+        assert!(std::fs::metadata(&path).unwrap().file_type().is_fifo());
+
+        // blocks until the writer opens the pipe
+        let mut reader = File::open(&path).unwrap();
+        let size = rboy::SCREEN_W * rboy::SCREEN_H * 3;
+        let frame: Vec<u8> = (0..size).map(|i| (i % 251) as u8).collect();
+        // the writer may still be connecting: send until a frame makes it through
+        let mut read = vec![0; size];
+        let reading = std::thread::spawn(move || {
+            reader.read_exact(&mut read).unwrap();
+            read
+        });
+        while !reading.is_finished() {
+            pipe.send(&frame);
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        assert_eq!(reading.join().unwrap(), frame);
+    }
+
+    #[test]
+    fn test_should_refuse_regular_file() {
+        let dir = TempDir::new().unwrap();
+        let file = dir.path().join("file");
+        std::fs::write(&file, []).unwrap();
+
+        assert!(FramePipe::spawn(&file, Arc::default()).is_err());
+    }
+
+    #[test]
+    fn test_should_stop_without_reader_on_exit() {
+        let dir = TempDir::new().unwrap();
+        let exit = Arc::new(AtomicBool::new(false));
+        let (pipe, thread) = FramePipe::spawn(&dir.path().join("frames"), exit.clone()).unwrap();
+        pipe.send(&[0; 3]);
+
+        exit.store(true, Ordering::SeqCst);
+        thread.join().unwrap();
+    }
+}
@@ -0,0 +1,158 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{SyncSender, TrySendError};
+use std::time::{Duration, Instant};
+
+/// A frame sent from the CPU to the display
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub data: Vec<u8>,
+    /// Number of frames rendered by the emulator up to this one
+    pub index: u64,
+    /// When the CPU produced the frame
+    pub produced: Instant,
+}
+
+impl Frame {
+    /// Create a [`Frame`] produced now
+    pub fn new(data: Vec<u8>, index: u64) -> Self {
+        Self {
+            data,
+            index,
+            produced: Instant::now(),
+        }
+    }
+}
+
+/// Sends the frames from the CPU to the display without ever blocking the CPU,
+/// counting the frames dropped because the display can't keep up.
+#[derive(Debug, Clone)]
+pub struct FrameSender {
+    sender: SyncSender<Frame>,
+    dropped: Arc<AtomicU64>,
+}
+
+/// The display has hung up
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Disconnected;
+
+impl FrameSender {
+    pub fn new(sender: SyncSender<Frame>) -> Self {
+        Self {
+            sender,
+            dropped: Arc::default(),
+        }
+    }
+
+    /// Counter of the dropped frames, shared with the clones of this sender
+    pub fn dropped(&self) -> Arc<AtomicU64> {
+        self.dropped.clone()
+    }
+
+    /// Queue a frame, dropping it if the queue is full
+    pub fn send(&self, frame: Frame) -> Result<(), Disconnected> {
+        match self.sender.try_send(frame) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(_)) => {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+                Ok(())
+            }
+            Err(TrySendError::Disconnected(_)) => Err(Disconnected),
+        }
+    }
+}
+
+/// Timing of a frame received by the display
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameTiming {
+    /// Time from the production of the previous frame received, if any
+    pub interval: Option<Duration>,
+    /// Time from the production of the frame to its display
+    pub latency: Duration,
+    /// Frames rendered between the previous frame received and this one, which were skipped or dropped
+    pub missed: u64,
+}
+
+/// Measures the timing of the frames received by the display, for sync diagnostics
+#[derive(Debug, Default)]
+pub struct FrameClock {
+    last: Option<(u64, Instant)>,
+}
+
+impl FrameClock {
+    /// Measure the timing of `frame`, displayed at `now`
+    pub fn observe(&mut self, frame: &Frame, now: Instant) -> FrameTiming {
+        let timing = FrameTiming {
+            interval: self
+                .last
+                .map(|(_, produced)| frame.produced.saturating_duration_since(produced)),
+            latency: now.saturating_duration_since(frame.produced),
+            missed: self
+                .last
+                .map(|(index, _)| frame.index.saturating_sub(index + 1))
+                .unwrap_or_default(),
+        };
+        self.last = Some((frame.index, frame.produced));
+
+        timing
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::sync::mpsc;
+
+    use super::*;
+
+    fn frame(index: u64) -> Frame {
+        Frame::new(vec![index as u8], index)
+    }
+
+    #[test]
+    fn test_should_count_dropped_frames() {
+        let (sender, receiver) = mpsc::sync_channel(1);
+        let sender = FrameSender::new(sender);
+        let dropped = sender.dropped();
+
+        assert_eq!(sender.send(frame(1)), Ok(()));
+        // the queue is full: the frame is dropped without blocking
+        assert_eq!(sender.send(frame(2)), Ok(()));
+        assert_eq!(sender.send(frame(3)), Ok(()));
+        assert_eq!(dropped.load(Ordering::Relaxed), 2);
+        assert_eq!(receiver.recv().unwrap().index, 1);
+
+        assert_eq!(sender.send(frame(4)), Ok(()));
+        assert_eq!(dropped.load(Ordering::Relaxed), 2);
+
+        drop(receiver);
+        assert_eq!(sender.send(frame(5)), Err(Disconnected));
+    }
+
+    #[test]
+    fn test_should_measure_frame_intervals() {
+        let start = Instant::now();
+        let frame = |index: u64, ms: u64| Frame {
+            data: vec![],
+            index,
+            produced: start + Duration::from_millis(ms),
+        };
+        let mut clock = FrameClock::default();
+
+        let timing = clock.observe(&frame(1, 0), start + Duration::from_millis(2));
+        assert_eq!(timing.interval, None);
+        assert_eq!(timing.latency, Duration::from_millis(2));
+        assert_eq!(timing.missed, 0);
+
+        let timing = clock.observe(&frame(2, 17), start + Duration::from_millis(20));
+        assert_eq!(timing.interval, Some(Duration::from_millis(17)));
+        assert_eq!(timing.latency, Duration::from_millis(3));
+        assert_eq!(timing.missed, 0);
+
+        // frames 3 and 4 were dropped
+        let timing = clock.observe(&frame(5, 67), start + Duration::from_millis(68));
+        assert_eq!(timing.interval, Some(Duration::from_millis(50)));
+        assert_eq!(timing.latency, Duration::from_millis(1));
+        assert_eq!(timing.missed, 2);
+    }
+}
@@ -1,142 +1,1018 @@
+mod color_profile;
+mod dither;
+mod draw;
+mod lut;
+mod palette;
+mod pixel_format;
+mod scale_filter;
+mod scaling;
+mod screeninfo;
+pub mod test_pattern;
+
+use std::cell::{Cell, RefCell};
+use std::fs::File;
 use std::os::fd::AsRawFd;
 use std::path::PathBuf;
 
+pub use self::color_profile::ColorProfile;
+pub use self::draw::{GLYPH_SIZE, blend, scaled_glyph_size};
+pub use self::lut::ColorLut;
+pub use self::palette::{DMG_PALETTES, DmgPalette};
+pub use self::pixel_format::PixelFormat;
+pub use self::scale_filter::{ScaleContext, ScaleFilter};
+pub use self::scaling::ScalingMode;
+
 pub struct FramebufferConfig {
     pub path: PathBuf,
     pub width: usize,
     pub height: usize,
-    pub bytes_per_pixel: usize,
+    pub pixel_format: PixelFormat,
     pub stride_pixels: usize,
 }
 
+/// Dimensions of the framebuffer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Geometry {
+    pub width: usize,
+    pub height: usize,
+    /// The number of bytes in a single row of the framebuffer.
+    pub stride_bytes: usize,
+}
+
+/// Rectangle of the framebuffer, in native pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Rect {
+    pub fn new(x: usize, y: usize, width: usize, height: usize) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// Whether the pixel at (`x`, `y`) is inside the rectangle
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        x >= self.x && y >= self.y && x - self.x < self.width && y - self.y < self.height
+    }
+}
+
 /// Represents a memory-mapped framebuffer.
+///
+/// Pixels are packed according to its [`PixelFormat`].
+/// The geometry can change at runtime (e.g. after a display mode switch), see [`Framebuffer::refresh_geometry`].
 pub struct Framebuffer {
-    width: usize,
-    height: usize,
-    ptr: *mut u16,
-    /// The number of pixels in a single row of the framebuffer.
-    stride: usize,
+    file: File,
+    geometry: Cell<Geometry>,
+    /// Geometry and bits per pixel reported by the device when it was last checked
+    last_mode: Cell<Option<(Geometry, u32)>>,
+    pixel_format: Cell<PixelFormat>,
+    bytes_per_pixel: Cell<usize>,
+    ptr: Cell<*mut u8>,
+    /// Color adjustment applied to the frames written with [`Framebuffer::write`]
+    lut: Cell<ColorLut>,
+    scale_filter: Cell<ScaleFilter>,
+    /// Color correction profile of the panel
+    color_profile: RefCell<Option<ColorProfile>>,
+    /// Opacity of the overlay backgrounds, see [`Framebuffer::fill_overlay_rect`]
+    overlay_alpha: Cell<u8>,
+    /// Pixels outside of the clip rectangle are not written
+    clip: Cell<Option<Rect>>,
+    /// Whether the colors are inverted when packed, for a high contrast
+    invert_colors: Cell<bool>,
+    /// Whether the frames are dithered when packed to a 16 bits format
+    dither: Cell<bool>,
+    /// How the frames are scaled to the panel
+    scaling: Cell<ScalingMode>,
+    /// Whether every other line of the frames is darkened, like on a CRT
+    scanlines: Cell<bool>,
+    /// Colorization of the classic games
+    palette: Cell<Option<DmgPalette>>,
 }
 
 impl Framebuffer {
     /// Creates a new [`Framebuffer`] mapped to the given path with the specified width and height.
     pub fn new(config: FramebufferConfig) -> anyhow::Result<Framebuffer> {
+        let bytes_per_pixel = config.pixel_format.bytes_per_pixel();
+
         // open framebuffer
         let file = std::fs::OpenOptions::new()
             .read(true)
             .write(true)
             .open(config.path)?;
 
-        let fd = file.as_raw_fd();
-
-        let size = config.stride_pixels * config.height * config.bytes_per_pixel;
-
-        let ptr = unsafe {
-            libc::mmap(
-                std::ptr::null_mut(),
-                size,
-                libc::PROT_READ | libc::PROT_WRITE,
-                libc::MAP_SHARED,
-                fd,
-                0,
-            )
-        } as *mut u16;
-
-        if ptr == libc::MAP_FAILED as *mut u16 {
-            return Err(anyhow::anyhow!("Failed to mmap framebuffer"));
-        }
-        Ok(Framebuffer {
+        let geometry = Geometry {
             width: config.width,
             height: config.height,
-            ptr,
-            stride: config.stride_pixels,
+            stride_bytes: config.stride_pixels * bytes_per_pixel,
+        };
+        let ptr = map(&file, geometry)?;
+        let last_mode = detect_mode(&file);
+
+        Ok(Framebuffer {
+            file,
+            geometry: Cell::new(geometry),
+            last_mode: Cell::new(last_mode),
+            pixel_format: Cell::new(config.pixel_format),
+            bytes_per_pixel: Cell::new(bytes_per_pixel),
+            ptr: Cell::new(ptr),
+            lut: Cell::new(ColorLut::default()),
+            scale_filter: Cell::new(ScaleFilter::default()),
+            color_profile: RefCell::new(None),
+            overlay_alpha: Cell::new(u8::MAX),
+            clip: Cell::new(None),
+            invert_colors: Cell::new(false),
+            dither: Cell::new(false),
+            scaling: Cell::new(ScalingMode::default()),
+            scanlines: Cell::new(false),
+            palette: Cell::new(None),
         })
     }
 
+    /// Native width of the panel in pixels
     pub fn width(&self) -> usize {
-        self.width
+        self.geometry.get().width
     }
 
+    /// Native height of the panel in pixels
     pub fn height(&self) -> usize {
-        self.height
+        self.geometry.get().height
+    }
+
+    pub fn geometry(&self) -> Geometry {
+        self.geometry.get()
+    }
+
+    pub fn pixel_format(&self) -> PixelFormat {
+        self.pixel_format.get()
+    }
+
+    pub fn bytes_per_pixel(&self) -> usize {
+        self.bytes_per_pixel.get()
+    }
+
+    /// The number of pixels in a single row of the framebuffer.
+    pub fn stride_pixels(&self) -> usize {
+        self.stride_bytes() / self.bytes_per_pixel.get()
+    }
+
+    /// The number of bytes in a single row of the framebuffer.
+    pub fn stride_bytes(&self) -> usize {
+        self.geometry.get().stride_bytes
+    }
+
+    /// Query the kernel for the current geometry of the framebuffer device.
+    ///
+    /// Returns `None` if the device can't be queried (e.g. it's not a framebuffer device).
+    pub fn detect_geometry(&self) -> Option<Geometry> {
+        detect_mode(&self.file).map(|(geometry, _)| geometry)
+    }
+
+    /// Check whether the geometry or the depth of the device changed since the last check (e.g. after
+    /// a HDMI mode switch) and remap it if so; on a new depth, the default [`PixelFormat`] of the
+    /// depth is used.
+    ///
+    /// Returns whether the framebuffer has been remapped; the content must then be redrawn.
+    pub fn refresh_geometry(&self) -> bool {
+        match detect_mode(&self.file) {
+            Some(mode) => self.apply_mode(mode),
+            None => false,
+        }
+    }
+
+    /// Apply the geometry and the bits per pixel reported by the device, if they differ from the
+    /// ones it reported last
+    fn apply_mode(&self, mode: (Geometry, u32)) -> bool {
+        if self.last_mode.replace(Some(mode)) == Some(mode) {
+            return false;
+        }
+        let (geometry, bits_per_pixel) = mode;
+        let bytes_per_pixel = bits_per_pixel as usize / 8;
+        let depth_changed = bytes_per_pixel != self.bytes_per_pixel.get();
+        if depth_changed {
+            let Some(pixel_format) = PixelFormat::from_bytes_per_pixel(bytes_per_pixel) else {
+                warn!("Ignoring unsupported framebuffer depth: {bits_per_pixel} bits per pixel");
+                return false;
+            };
+            info!(
+                "Framebuffer depth changed to {bits_per_pixel} bits per pixel; using {pixel_format}"
+            );
+            self.pixel_format.set(pixel_format);
+            self.bytes_per_pixel.set(bytes_per_pixel);
+        }
+
+        self.apply_geometry(geometry) || depth_changed
+    }
+
+    /// Remap the framebuffer if `geometry` differs from the current one
+    fn apply_geometry(&self, geometry: Geometry) -> bool {
+        let current = self.geometry.get();
+        if geometry == current {
+            return false;
+        }
+        if geometry.stride_bytes < geometry.width * self.bytes_per_pixel.get() {
+            warn!("Ignoring invalid framebuffer geometry: {geometry:?}");
+            return false;
+        }
+
+        info!("Framebuffer geometry changed from {current:?} to {geometry:?}; remapping");
+        let ptr = match map(&self.file, geometry) {
+            Ok(ptr) => ptr,
+            Err(err) => {
+                error!("Failed to remap framebuffer: {err}");
+                return false;
+            }
+        };
+        unsafe {
+            libc::munmap(
+                self.ptr.get() as *mut libc::c_void,
+                current.stride_bytes * current.height,
+            );
+        }
+        self.ptr.set(ptr);
+        self.geometry.set(geometry);
+
+        true
+    }
+
+    /// Query the kernel for the actual line length in bytes of the framebuffer device.
+    ///
+    /// Returns `None` if the device can't be queried (e.g. it's not a framebuffer device).
+    pub fn detect_stride_bytes(&self) -> Option<usize> {
+        screeninfo::fix_screen_info(&self.file)
+            .ok()
+            .map(|info| info.line_length as usize)
+    }
+
+    /// Check whether the configured stride matches the one reported by the kernel.
+    ///
+    /// Logs a warning with the suggested stride if they don't match.
+    pub fn check_stride(&self) -> bool {
+        match self.detect_stride_bytes() {
+            Some(detected) if detected != self.stride_bytes() => {
+                warn!(
+                    "Configured stride ({configured} bytes) doesn't match the framebuffer line length ({detected} bytes); try with --stride-pixels {suggested}",
+                    configured = self.stride_bytes(),
+                    suggested = detected / self.bytes_per_pixel.get(),
+                );
+                false
+            }
+            Some(_) => {
+                debug!("Framebuffer stride is correct");
+                true
+            }
+            None => {
+                debug!("Could not query framebuffer line length; skipping stride check");
+                true
+            }
+        }
+    }
+
+    /// Draw vertical color bars on the entire framebuffer.
+    ///
+    /// With a wrong stride the bars will be skewed.
+    pub fn draw_test_pattern(&self) {
+        for y in 0..self.height() {
+            for x in 0..self.width() {
+                let (r, g, b) = test_pattern::color_bar(x, self.width());
+                self.put_pixel(x, y, rgb565(r, g, b));
+            }
+        }
+    }
+
+    /// Set the color adjustment applied to the frames written with [`Framebuffer::write`]
+    pub fn set_color_lut(&self, lut: ColorLut) {
+        self.lut.set(lut);
+    }
+
+    pub fn color_lut(&self) -> ColorLut {
+        self.lut.get()
+    }
+
+    /// Set how the pixels are sampled when the frames and the glyphs are scaled
+    pub fn set_scale_filter(&self, filter: ScaleFilter) {
+        self.scale_filter.set(filter);
+    }
+
+    pub fn scale_filter(&self) -> ScaleFilter {
+        self.scale_filter.get()
+    }
+
+    /// Correct the frames written with [`Framebuffer::write`] with a profile of the panel, or
+    /// stop correcting them with `None`.
+    ///
+    /// The profile is applied after the CGB color correction and before the color lookup table.
+    pub fn set_color_profile(&self, profile: Option<ColorProfile>) {
+        self.color_profile.replace(profile);
+    }
+
+    /// Set the opacity of the overlay backgrounds drawn over the game; 255 is opaque
+    pub fn set_overlay_alpha(&self, alpha: u8) {
+        self.overlay_alpha.set(alpha);
+    }
+
+    /// Confine the drawing to a rectangle, or allow it on the whole framebuffer again with `None`.
+    ///
+    /// The clip applies to [`Framebuffer::write`] and to the pixel and drawing primitives.
+    pub fn set_clip(&self, clip: Option<Rect>) {
+        self.clip.set(clip);
+    }
+
+    /// Invert the colors of everything drawn afterwards, frames and menus alike
+    pub fn set_invert_colors(&self, enabled: bool) {
+        self.invert_colors.set(enabled);
+    }
+
+    pub fn invert_colors(&self) -> bool {
+        self.invert_colors.get()
+    }
+
+    /// Colorize the frames written with [`Framebuffer::write`], which must be in shades of gray,
+    /// or show them as they are with `None`.
+    ///
+    /// The palette is applied before the color correction.
+    pub fn set_palette(&self, palette: Option<DmgPalette>) {
+        self.palette.set(palette);
+    }
+
+    /// Dither the frames written with [`Framebuffer::write`] on 16 bits formats, hiding the
+    /// banding of smooth gradients
+    pub fn set_dither(&self, enabled: bool) {
+        self.dither.set(enabled);
+    }
+
+    /// Set how the frames written with [`Framebuffer::write`] are scaled to the panel; it applies
+    /// from the next frame, so the previous one may have to be cleared
+    pub fn set_scaling(&self, mode: ScalingMode) {
+        self.scaling.set(mode);
+    }
+
+    pub fn scaling(&self) -> ScalingMode {
+        self.scaling.get()
+    }
+
+    /// Darken every other line of the frames written with [`Framebuffer::write`], like on a CRT
+    pub fn set_scanlines(&self, enabled: bool) {
+        self.scanlines.set(enabled);
     }
 
     pub fn write(&self, buf: &[u8]) {
+        let lut = self.lut.get();
+        let color_profile = self.color_profile.borrow();
+        let palette = self.palette.get();
+        let scale_filter = self.scale_filter.get();
+        let clip = self.clip.get();
+        let dither = self.dither.get() && self.bytes_per_pixel.get() == 2;
+        let scanlines = self.scanlines.get();
+
         let src_w = crate::SCREEN_W as f32;
         let src_h = crate::SCREEN_H as f32;
 
-        let dst_h = self.height as f32;
+        let dst_w = self.width() as f32;
+        let dst_h = self.height() as f32;
 
-        // Scale factor to fit height
-        let scale = dst_h / src_h;
+        // the frame is cropped where it overflows the panel
+        let scale = self.scaling.get().scale((src_w, src_h), (dst_w, dst_h));
 
-        let scaled_w = (src_w * scale).round() as usize;
-        let x_offset = (self.width - scaled_w) / 2;
+        let scaled_w = (src_w * scale).round() as isize;
+        let scaled_h = (src_h * scale).round() as isize;
+        // negative when the frame is cropped
+        let x_offset = (self.width() as isize - scaled_w) / 2;
+        let y_offset = (self.height() as isize - scaled_h) / 2;
+        let columns =
+            x_offset.max(0) as usize..(x_offset + scaled_w).min(self.width() as isize) as usize;
+        let rows =
+            y_offset.max(0) as usize..(y_offset + scaled_h).min(self.height() as isize) as usize;
 
-        for dy in 0..self.height {
-            // map dy to sy in source buffer
-            let sy = (dy as f32 / scale).floor() as usize;
+        for y in rows {
+            // map y to sy in source buffer
+            let sy = ((y as isize - y_offset) as f32 / scale).floor() as usize;
             if sy >= crate::SCREEN_H {
                 continue;
             }
+            let scanline = scanlines && (y as isize - y_offset) % 2 == 1;
 
-            unsafe {
-                let row = self.ptr.add(dy * self.stride);
+            for x in columns.clone() {
+                let sx = ((x as isize - x_offset) as f32 / scale).floor() as usize;
+                if sx >= crate::SCREEN_W {
+                    continue;
+                }
 
-                for dx in 0..scaled_w {
-                    let sx = (dx as f32 / scale).floor() as usize;
-                    if sx >= crate::SCREEN_W {
-                        continue;
-                    }
+                if clip.is_some_and(|clip| !clip.contains(x, y)) {
+                    continue;
+                }
 
+                let sample = |sx: usize, sy: usize| {
                     let i = (sy * crate::SCREEN_W + sx) * 3;
+                    match &palette {
+                        Some(palette) => palette.map(buf[i]),
+                        None => (buf[i], buf[i + 1], buf[i + 2]),
+                    }
+                };
+                let (r, g, b) = match scale_filter {
+                    ScaleFilter::Nearest => sample(sx, sy),
+                    // sampled at the center of the panel pixel
+                    ScaleFilter::Smooth => scale_filter::bilinear(
+                        ((x as isize - x_offset) as f32 + 0.5) / scale - 0.5,
+                        ((y as isize - y_offset) as f32 + 0.5) / scale - 0.5,
+                        crate::SCREEN_W,
+                        crate::SCREEN_H,
+                        sample,
+                    ),
+                };
+                let (r, g, b) = match color_profile.as_ref() {
+                    Some(profile) => profile.apply(r, g, b),
+                    None => (r, g, b),
+                };
+                let (r, g, b) = (lut.apply(r), lut.apply(g), lut.apply(b));
+                let (r, g, b) = if scanline {
+                    (scanline_level(r), scanline_level(g), scanline_level(b))
+                } else {
+                    (r, g, b)
+                };
+                let (r, g, b) = if dither {
+                    dither::dither565(r, g, b, x, y)
+                } else {
+                    (r, g, b)
+                };
 
-                    let r = buf[i];
-                    let g = buf[i + 1];
-                    let b = buf[i + 2];
-
-                    let rgb565: u16 =
-                        ((r as u16 >> 3) << 11) | ((g as u16 >> 2) << 5) | (b as u16 >> 3);
-
-                    *row.add(x_offset + dx) = rgb565;
+                unsafe {
+                    self.store(self.offset(x, y), r, g, b);
                 }
             }
         }
     }
 
-    /// Fills the entire framebuffer with zeros.
+    /// Fills the entire framebuffer with black, which is packed to zeros unless the colors are inverted.
     pub fn zero(&self) {
-        let pixels = self.stride * self.height;
+        if self.invert_colors.get() {
+            self.fill(0, 0, 0);
+            return;
+        }
+
+        let bytes = self.stride_bytes() * self.height();
         unsafe {
-            std::ptr::write_bytes(self.ptr, 0, pixels);
+            std::ptr::write_bytes(self.ptr.get(), 0, bytes);
         }
     }
 
-    /// Fills the entire framebuffer with zeros.
+    /// Fills the entire framebuffer with the given color.
     pub fn fill(&self, red: u8, green: u8, blue: u8) {
-        let rgb565: u16 =
-            ((red as u16 >> 3) << 11) | ((green as u16 >> 2) << 5) | (blue as u16 >> 3);
-
-        // fill
-        for y in 0..self.height {
-            unsafe {
-                let row = self.ptr.add(y * self.stride);
-
-                for x in 0..self.width {
-                    *row.add(x) = rgb565;
+        for y in 0..self.height() {
+            for x in 0..self.width() {
+                unsafe {
+                    self.store(self.offset(x, y), red, green, blue);
                 }
             }
         }
     }
 
+    /// Write a single RGB888 pixel of the framebuffer; out of bounds pixels are ignored
+    pub fn put_pixel_rgb(&self, x: usize, y: usize, red: u8, green: u8, blue: u8) {
+        if !self.is_writable(x, y) {
+            return;
+        }
+
+        unsafe {
+            self.store(self.offset(x, y), red, green, blue);
+        }
+    }
+
     /// Write a single pixel of the framebuffer
+    ///
+    /// The color is RGB565; it's expanded if the framebuffer has a larger depth.
     pub fn put_pixel(&self, x: usize, y: usize, color: u16) {
-        if x >= self.width || y >= self.height {
+        if !self.is_writable(x, y) {
             return;
         }
 
+        let (r, g, b) = rgb888(color);
         unsafe {
-            *self.ptr.add(y * self.stride + x) = color;
+            self.store(self.offset(x, y), r, g, b);
         }
     }
+
+    /// Write a single pixel of the framebuffer, reporting whether it was actually written.
+    ///
+    /// Returns `false` if the coordinates are out of the framebuffer bounds or of the clip rectangle and the pixel has been clipped.
+    pub fn try_put_pixel(&self, x: usize, y: usize, color: u16) -> bool {
+        if !self.is_writable(x, y) {
+            return false;
+        }
+
+        self.put_pixel(x, y, color);
+        true
+    }
+
+    /// Read a single pixel of the framebuffer as RGB888; `None` if out of bounds
+    pub fn get_pixel_rgb(&self, x: usize, y: usize) -> Option<(u8, u8, u8)> {
+        if x >= self.width() || y >= self.height() {
+            return None;
+        }
+
+        let mut packed = [0u8; 4];
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                self.ptr.get().add(self.offset(x, y)),
+                packed.as_mut_ptr(),
+                self.bytes_per_pixel.get(),
+            );
+        }
+        let (r, g, b) = self.pixel_format.get().unpack(&packed);
+        if self.invert_colors.get() {
+            Some((!r, !g, !b))
+        } else {
+            Some((r, g, b))
+        }
+    }
+
+    /// Whether the pixel at (`x`, `y`) is in bounds and inside the clip rectangle, if any
+    fn is_writable(&self, x: usize, y: usize) -> bool {
+        x < self.width()
+            && y < self.height()
+            && self.clip.get().is_none_or(|clip| clip.contains(x, y))
+    }
+
+    /// Byte offset of the pixel at (`x`, `y`) from the start of the mapping
+    fn offset(&self, x: usize, y: usize) -> usize {
+        y * self.stride_bytes() + x * self.bytes_per_pixel.get()
+    }
+
+    /// Pack and store a color at the given byte offset.
+    ///
+    /// # Safety
+    ///
+    /// `offset` must point to a pixel inside the mapping.
+    unsafe fn store(&self, offset: usize, red: u8, green: u8, blue: u8) {
+        let mut packed = [0u8; 4];
+        if self.invert_colors.get() {
+            self.pixel_format
+                .get()
+                .pack(!red, !green, !blue, &mut packed);
+        } else {
+            self.pixel_format.get().pack(red, green, blue, &mut packed);
+        }
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                packed.as_ptr(),
+                self.ptr.get().add(offset),
+                self.bytes_per_pixel.get(),
+            );
+        }
+    }
+}
+
+/// Map `geometry` of the framebuffer file into memory
+fn map(file: &File, geometry: Geometry) -> anyhow::Result<*mut u8> {
+    let ptr = unsafe {
+        libc::mmap(
+            std::ptr::null_mut(),
+            geometry.stride_bytes * geometry.height,
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_SHARED,
+            file.as_raw_fd(),
+            0,
+        )
+    } as *mut u8;
+
+    if ptr == libc::MAP_FAILED as *mut u8 {
+        return Err(anyhow::anyhow!("Failed to mmap framebuffer"));
+    }
+
+    Ok(ptr)
+}
+
+/// Query the geometry and the bits per pixel of the framebuffer device; `None` if it's not one
+fn detect_mode(file: &File) -> Option<(Geometry, u32)> {
+    let var = screeninfo::var_screen_info(file).ok()?;
+    let fix = screeninfo::fix_screen_info(file).ok()?;
+    let geometry = Geometry {
+        width: var.xres as usize,
+        height: var.yres as usize,
+        stride_bytes: fix.line_length as usize,
+    };
+
+    Some((geometry, var.bits_per_pixel))
+}
+
+/// Darken a component of a scanline to three quarters
+fn scanline_level(component: u8) -> u8 {
+    (component as u16 * 3 / 4) as u8
+}
+
+/// Pack an RGB888 color into RGB565
+fn rgb565(red: u8, green: u8, blue: u8) -> u16 {
+    ((red as u16 >> 3) << 11) | ((green as u16 >> 2) << 5) | (blue as u16 >> 3)
+}
+
+/// Expand an RGB565 color into RGB888
+fn rgb888(color: u16) -> (u8, u8, u8) {
+    let red = ((color >> 11) & 0x1f) as u8;
+    let green = ((color >> 5) & 0x3f) as u8;
+    let blue = (color & 0x1f) as u8;
+
+    (
+        (red << 3) | (red >> 2),
+        (green << 2) | (green >> 4),
+        (blue << 3) | (blue >> 2),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+
+    use tempfile::NamedTempFile;
+
+    use super::*;
+
+    #[test]
+    fn test_should_report_clipped_pixels() {
+        let (_file, framebuffer) = mock_framebuffer(32, 16, PixelFormat::Rgb565, 32);
+
+        assert!(framebuffer.try_put_pixel(0, 0, 0xffff));
+        assert!(framebuffer.try_put_pixel(31, 15, 0xffff));
+        assert!(!framebuffer.try_put_pixel(32, 0, 0xffff));
+        assert!(!framebuffer.try_put_pixel(0, 16, 0xffff));
+        assert!(!framebuffer.try_put_pixel(usize::MAX, usize::MAX, 0xffff));
+    }
+
+    #[test]
+    fn test_should_dither_flat_colors() {
+        let (file, framebuffer) = mock_framebuffer(
+            crate::SCREEN_W,
+            crate::SCREEN_H,
+            PixelFormat::Rgb565,
+            crate::SCREEN_W,
+        );
+        framebuffer.set_dither(true);
+
+        // halfway between two RGB565 levels
+        framebuffer.write(&vec![0x84; crate::SCREEN_W * crate::SCREEN_H * 3]);
+
+        let data = std::fs::read(file.path()).unwrap();
+        let pixel = |x: usize| u16::from_ne_bytes([data[x * 2], data[x * 2 + 1]]);
+        assert_ne!(pixel(0), pixel(1));
+        assert_eq!(pixel(0), pixel(2));
+        assert_eq!(pixel(1), pixel(3));
+        assert_eq!(pixel(0), pixel(4));
+    }
+
+    #[test]
+    fn test_should_darken_scanlines() {
+        let (file, framebuffer) = mock_framebuffer(
+            crate::SCREEN_W,
+            crate::SCREEN_H,
+            PixelFormat::Rgb888,
+            crate::SCREEN_W,
+        );
+        framebuffer.set_scanlines(true);
+
+        framebuffer.write(&vec![0xff; crate::SCREEN_W * crate::SCREEN_H * 3]);
+
+        let data = std::fs::read(file.path()).unwrap();
+        let row = crate::SCREEN_W * 3;
+        assert_eq!(&data[..3], &[0xff, 0xff, 0xff]);
+        assert_eq!(&data[row..row + 3], &[0xbf, 0xbf, 0xbf]);
+        assert_eq!(&data[row * 2..row * 2 + 3], &[0xff, 0xff, 0xff]);
+    }
+
+    #[test]
+    fn test_should_write_packed_rgb888() {
+        let stride = crate::SCREEN_W + 16;
+        let (file, framebuffer) = mock_framebuffer(
+            crate::SCREEN_W,
+            crate::SCREEN_H,
+            PixelFormat::Rgb888,
+            stride,
+        );
+
+        let mut buf = vec![0; crate::SCREEN_W * crate::SCREEN_H * 3];
+        let i = (2 * crate::SCREEN_W + 5) * 3;
+        buf[i..i + 3].copy_from_slice(&[0x12, 0x34, 0x56]);
+        framebuffer.write(&buf);
+
+        let data = std::fs::read(file.path()).unwrap();
+        let offset = (2 * stride + 5) * 3;
+        assert_eq!(&data[offset..offset + 3], &[0x12, 0x34, 0x56]);
+        // neighbours are untouched
+        assert_eq!(&data[offset - 3..offset], &[0, 0, 0]);
+        assert_eq!(&data[offset + 3..offset + 6], &[0, 0, 0]);
+    }
+
+    #[test]
+    fn test_should_write_frame_as_xrgb8888() {
+        let (file, framebuffer) = mock_framebuffer(
+            crate::SCREEN_W,
+            crate::SCREEN_H,
+            PixelFormat::Xrgb8888,
+            crate::SCREEN_W,
+        );
+
+        // the last pixel of the frame lands in the last pixel of the panel, not halfway
+        let mut buf = vec![0; crate::SCREEN_W * crate::SCREEN_H * 3];
+        let last = buf.len() - 3;
+        buf[last..].copy_from_slice(&[0x12, 0x34, 0x56]);
+        framebuffer.write(&buf);
+
+        let data = std::fs::read(file.path()).unwrap();
+        assert_eq!(data.len(), crate::SCREEN_W * crate::SCREEN_H * 4);
+        let pixel = u32::from_ne_bytes(data[data.len() - 4..].try_into().unwrap());
+        assert_eq!(pixel, 0x00123456);
+        assert_eq!(data.iter().filter(|b| **b != 0).count(), 3);
+
+        framebuffer.zero();
+        let data = std::fs::read(file.path()).unwrap();
+        assert!(data.iter().all(|b| *b == 0));
+    }
+
+    #[test]
+    fn test_should_report_configured_geometry() {
+        let (_file, framebuffer) = mock_framebuffer(64, 48, PixelFormat::Rgb565, 80);
+
+        assert_eq!(framebuffer.width(), 64);
+        assert_eq!(framebuffer.height(), 48);
+        assert_eq!(framebuffer.stride_pixels(), 80);
+    }
+
+    #[test]
+    fn test_should_remap_on_geometry_change() {
+        let (file, framebuffer) = mock_framebuffer(32, 16, PixelFormat::Rgb565, 32);
+        let geometry = Geometry {
+            width: 64,
+            height: 24,
+            stride_bytes: 160,
+        };
+        file.as_file().set_len((160 * 24) as u64).unwrap();
+
+        assert!(!framebuffer.apply_geometry(framebuffer.geometry()));
+        assert!(framebuffer.apply_geometry(geometry));
+        assert_eq!(framebuffer.geometry(), geometry);
+        assert_eq!(framebuffer.width(), 64);
+        assert_eq!(framebuffer.height(), 24);
+        assert_eq!(framebuffer.stride_pixels(), 80);
+        assert!(!framebuffer.apply_geometry(geometry));
+
+        // the new mapping is used
+        framebuffer.put_pixel(63, 23, 0xffff);
+        let data = std::fs::read(file.path()).unwrap();
+        let offset = 23 * 160 + 63 * 2;
+        assert_eq!(&data[offset..offset + 2], &[0xff, 0xff]);
+    }
+
+    #[test]
+    fn test_should_remap_only_when_device_mode_changes() {
+        let (file, framebuffer) = mock_framebuffer(32, 16, PixelFormat::Rgb565, 32);
+        let configured = framebuffer.geometry();
+        let device = Geometry {
+            width: 64,
+            height: 24,
+            stride_bytes: 128,
+        };
+        file.as_file().set_len((256 * 24) as u64).unwrap();
+        framebuffer.last_mode.set(Some((device, 16)));
+
+        // the configured geometry is kept while the device reports the same mode
+        assert!(!framebuffer.apply_mode((device, 16)));
+        assert_eq!(framebuffer.geometry(), configured);
+
+        // a new depth switches the pixel format
+        let wide = Geometry {
+            stride_bytes: 256,
+            ..device
+        };
+        assert!(framebuffer.apply_mode((wide, 32)));
+        assert_eq!(framebuffer.pixel_format(), PixelFormat::Xrgb8888);
+        assert_eq!(framebuffer.geometry(), wide);
+        assert_eq!(framebuffer.stride_pixels(), 64);
+        assert!(!framebuffer.apply_mode((wide, 32)));
+    }
+
+    #[test]
+    fn test_should_draw_text_at_native_coordinates() {
+        use font8x8::{BASIC_FONTS, UnicodeFonts};
+
+        // a panel larger than the game screen, where `write` would scale
+        let (file, framebuffer) = mock_framebuffer(480, 320, PixelFormat::Rgb565, 480);
+        assert_eq!(framebuffer.width(), 480);
+        assert_eq!(framebuffer.height(), 320);
+
+        framebuffer.draw_text("A", 101, 53, false, 0xffff);
+
+        let data = std::fs::read(file.path()).unwrap();
+        let pixel = |x: usize, y: usize| {
+            let offset = (y * 480 + x) * 2;
+            u16::from_ne_bytes([data[offset], data[offset + 1]])
+        };
+        let glyph = BASIC_FONTS.get('A').unwrap();
+        for y in 50..50 + GLYPH_SIZE + 6 {
+            for x in 98..98 + GLYPH_SIZE + 6 {
+                let inside =
+                    (101..101 + GLYPH_SIZE).contains(&x) && (53..53 + GLYPH_SIZE).contains(&y);
+                let set = inside && glyph[y - 53] & (1 << (x - 101)) != 0;
+                assert_eq!(
+                    pixel(x, y),
+                    if set { 0xffff } else { 0 },
+                    "pixel ({x}, {y})"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_should_compute_byte_offsets() {
+        for pixel_format in PixelFormat::ALL {
+            let bytes_per_pixel = pixel_format.bytes_per_pixel();
+            let (_file, framebuffer) = mock_framebuffer(32, 16, pixel_format, 40);
+            assert_eq!(framebuffer.stride_bytes(), 40 * bytes_per_pixel);
+            assert_eq!(framebuffer.stride_pixels(), 40);
+
+            assert_eq!(framebuffer.offset(0, 0), 0);
+            assert_eq!(framebuffer.offset(1, 0), bytes_per_pixel);
+            assert_eq!(framebuffer.offset(0, 1), 40 * bytes_per_pixel);
+            assert_eq!(
+                framebuffer.offset(31, 15),
+                15 * 40 * bytes_per_pixel + 31 * bytes_per_pixel
+            );
+        }
+    }
+
+    #[test]
+    fn test_should_put_pixel_at_byte_offset() {
+        let (file, framebuffer) = mock_framebuffer(32, 16, PixelFormat::Rgb565, 40);
+        framebuffer.put_pixel(3, 2, 0xabcd);
+
+        let data = std::fs::read(file.path()).unwrap();
+        let offset = 2 * 40 * 2 + 3 * 2;
+        assert_eq!(u16::from_ne_bytes([data[offset], data[offset + 1]]), 0xabcd);
+    }
+
+    #[test]
+    fn test_should_put_pixel_as_xrgb8888() {
+        let (file, framebuffer) = mock_framebuffer(32, 16, PixelFormat::Xrgb8888, 32);
+        framebuffer.put_pixel(1, 1, 0xffff);
+
+        let data = std::fs::read(file.path()).unwrap();
+        let offset = (32 + 1) * 4;
+        assert_eq!(
+            u32::from_ne_bytes(data[offset..offset + 4].try_into().unwrap()),
+            0x00ffffff
+        );
+    }
+
+    #[test]
+    fn test_should_convert_rgb565_to_rgb888() {
+        assert_eq!(rgb888(0xffff), (0xff, 0xff, 0xff));
+        assert_eq!(rgb888(0x0000), (0, 0, 0));
+        // expanding and packing again is lossless
+        let color = rgb565(0x12, 0x34, 0x56);
+        let (r, g, b) = rgb888(color);
+        assert_eq!(rgb565(r, g, b), color);
+    }
+
+    #[test]
+    fn test_should_blend_overlay_over_frame() {
+        assert_eq!(blend((255, 255, 255), (0, 0, 0), 128), (128, 128, 128));
+        assert_eq!(blend((200, 100, 0), (0, 100, 200), 64), (50, 100, 150));
+        assert_eq!(blend((200, 100, 0), (0, 100, 200), 0), (0, 100, 200));
+        assert_eq!(blend((200, 100, 0), (0, 100, 200), 255), (200, 100, 0));
+
+        for format in PixelFormat::ALL {
+            let (_file, framebuffer) = mock_framebuffer(8, 8, format, 8);
+            framebuffer.fill(0xff, 0xff, 0xff);
+            framebuffer.set_overlay_alpha(128);
+            framebuffer.fill_overlay_rect(0, 0, 4, 8, 0x0000);
+            // 16 bits formats lose the low bits
+            let (r, g, b) = framebuffer.get_pixel_rgb(0, 0).unwrap();
+            assert!([r, g, b].iter().all(|c| c.abs_diff(127) < 8), "{format}");
+            assert_eq!(
+                framebuffer.get_pixel_rgb(4, 0),
+                Some((255, 255, 255)),
+                "{format}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_should_invert_colors() {
+        let (file, framebuffer) = mock_framebuffer(
+            crate::SCREEN_W,
+            crate::SCREEN_H,
+            PixelFormat::Rgb565,
+            crate::SCREEN_W,
+        );
+        framebuffer.set_invert_colors(true);
+
+        let mut buf = vec![0; crate::SCREEN_W * crate::SCREEN_H * 3];
+        buf[3..6].copy_from_slice(&[0xff, 0xff, 0xff]);
+        framebuffer.write(&buf);
+
+        let data = std::fs::read(file.path()).unwrap();
+        // black packs to white and white to black
+        assert_eq!(u16::from_ne_bytes([data[0], data[1]]), 0xffff);
+        assert_eq!(u16::from_ne_bytes([data[2], data[3]]), 0x0000);
+        // reading back gives the source color
+        assert_eq!(framebuffer.get_pixel_rgb(0, 0), Some((0, 0, 0)));
+
+        // cleared to inverted black too
+        framebuffer.zero();
+        let data = std::fs::read(file.path()).unwrap();
+        assert_eq!(u16::from_ne_bytes([data[2], data[3]]), 0xffff);
+    }
+
+    #[test]
+    fn test_should_not_write_outside_clip() {
+        let (file, framebuffer) = mock_framebuffer(32, 16, PixelFormat::Rgb565, 32);
+        framebuffer.set_clip(Some(Rect::new(4, 2, 8, 4)));
+        framebuffer.fill_rect(0, 0, 32, 16, 0xffff);
+        assert!(!framebuffer.try_put_pixel(20, 10, 0xffff));
+        assert!(framebuffer.try_put_pixel(11, 5, 0x1234));
+
+        let data = std::fs::read(file.path()).unwrap();
+        let pixel = |x: usize, y: usize| {
+            let offset = (y * 32 + x) * 2;
+            u16::from_ne_bytes([data[offset], data[offset + 1]])
+        };
+        for y in 0..16 {
+            for x in 0..32 {
+                let expected = match (x, y) {
+                    (11, 5) => 0x1234,
+                    (4..12, 2..6) => 0xffff,
+                    _ => 0,
+                };
+                assert_eq!(pixel(x, y), expected, "({x}, {y})");
+            }
+        }
+
+        // the game frame is clipped too; scaled to the height, it covers x from 7 to 24
+        framebuffer.zero();
+        framebuffer.write(&vec![0xff; crate::SCREEN_W * crate::SCREEN_H * 3]);
+        let data = std::fs::read(file.path()).unwrap();
+        assert_eq!(data.iter().filter(|b| **b != 0).count(), 5 * 4 * 2);
+
+        framebuffer.set_clip(None);
+        framebuffer.put_pixel(20, 10, 0xffff);
+        assert_eq!(framebuffer.get_pixel_rgb(20, 10), Some((0xff, 0xff, 0xff)));
+    }
+
+    #[test]
+    fn test_should_write_frame_on_narrow_panel() {
+        // scaled to its height, the frame would be 160 pixels wide
+        let (file, framebuffer) = mock_framebuffer(120, 144, PixelFormat::Rgb565, 120);
+        let mut frame = vec![0; crate::SCREEN_W * crate::SCREEN_H * 3];
+        // mark the leftmost visible column once cropped
+        for y in 0..crate::SCREEN_H {
+            let i = (y * crate::SCREEN_W + 20) * 3;
+            frame[i..i + 3].copy_from_slice(&[0xff, 0xff, 0xff]);
+        }
+
+        framebuffer.write(&frame);
+        assert_eq!(framebuffer.get_pixel_rgb(0, 0), Some((0xff, 0xff, 0xff)));
+        assert_eq!(framebuffer.get_pixel_rgb(0, 143), Some((0xff, 0xff, 0xff)));
+        assert_eq!(framebuffer.get_pixel_rgb(1, 0), Some((0, 0, 0)));
+
+        // scaled down to 120x108, centered vertically
+        framebuffer.set_scaling(ScalingMode::FitBoth);
+        framebuffer.zero();
+        framebuffer.write(&vec![0xff; crate::SCREEN_W * crate::SCREEN_H * 3]);
+        let data = std::fs::read(file.path()).unwrap();
+        assert_eq!(data.iter().filter(|b| **b != 0).count(), 120 * 108 * 2);
+        assert_eq!(framebuffer.get_pixel_rgb(0, 17), Some((0, 0, 0)));
+        assert_eq!(framebuffer.get_pixel_rgb(0, 18), Some((0xff, 0xff, 0xff)));
+        assert_eq!(
+            framebuffer.get_pixel_rgb(119, 125),
+            Some((0xff, 0xff, 0xff))
+        );
+        assert_eq!(framebuffer.get_pixel_rgb(119, 126), Some((0, 0, 0)));
+    }
+
+    /// Create a [`Framebuffer`] backed by a temporary file
+    fn mock_framebuffer(
+        width: usize,
+        height: usize,
+        pixel_format: PixelFormat,
+        stride_pixels: usize,
+    ) -> (NamedTempFile, Framebuffer) {
+        let file = NamedTempFile::new().unwrap();
+        file.as_file()
+            .set_len((stride_pixels * height * pixel_format.bytes_per_pixel()) as u64)
+            .unwrap();
+
+        let framebuffer = Framebuffer::new(FramebufferConfig {
+            path: file.path().to_path_buf(),
+            width,
+            height,
+            pixel_format,
+            stride_pixels,
+        })
+        .unwrap();
+
+        (file, framebuffer)
+    }
 }
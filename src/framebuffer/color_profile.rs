@@ -0,0 +1,134 @@
+//! Color correction profiles loaded from files, for tuning the colors to a panel
+
+use std::path::Path;
+
+use serde::Deserialize;
+
+/// Steps of the encoding table, indexed by the square root of the linear light to keep the darks precise
+const ENCODE_STEPS: usize = 4096;
+
+/// Color correction profile, e.g.:
+///
+/// ```toml
+/// # rows mix the red, green and blue components of the input into the output ones
+/// matrix = [
+///   [0.82, 0.24, -0.06],
+///   [0.12, 0.67, 0.21],
+///   [0.06, 0.09, 0.85],
+/// ]
+/// gamma = 2.2 # gamma of the input, decoded before the matrix is applied (default: 1.0)
+/// display_gamma = 2.0 # gamma encoded after the matrix is applied (default: `gamma`)
+/// ```
+///
+/// The gamma curves are precomputed when the profile is loaded, and the matrix is applied
+/// to the full 8-bit components.
+pub struct ColorProfile {
+    matrix: [[f32; 3]; 3],
+    /// Linear light of each 8-bit component
+    decode: [f32; 256],
+    /// 8-bit component of the linear light, see [`ENCODE_STEPS`]
+    encode: Box<[u8; ENCODE_STEPS]>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProfileFile {
+    matrix: [[f32; 3]; 3],
+    #[serde(default = "ProfileFile::default_gamma")]
+    gamma: f32,
+    display_gamma: Option<f32>,
+}
+
+impl ProfileFile {
+    fn default_gamma() -> f32 {
+        1.0
+    }
+}
+
+impl ColorProfile {
+    /// Load a [`ColorProfile`] from a TOML file
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let profile = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Failed to read color profile {:?}: {}", path, e))?;
+        Self::parse(&profile)
+            .map_err(|e| anyhow::anyhow!("Invalid color profile {:?}: {}", path, e))
+    }
+
+    /// Parse a [`ColorProfile`]
+    pub fn parse(profile: &str) -> Result<Self, String> {
+        let profile: ProfileFile = toml::from_str(profile).map_err(|e| e.to_string())?;
+        let display_gamma = profile.display_gamma.unwrap_or(profile.gamma);
+        if profile.gamma <= 0.0 || display_gamma <= 0.0 {
+            return Err("the gamma must be positive".to_string());
+        }
+        if profile.matrix.iter().flatten().any(|v| !v.is_finite()) {
+            return Err("the matrix must be finite".to_string());
+        }
+
+        let decode = std::array::from_fn(|c| (c as f32 / 255.0).powf(profile.gamma));
+        let encode: Vec<u8> = (0..ENCODE_STEPS)
+            .map(|i| {
+                let linear = (i as f32 / (ENCODE_STEPS - 1) as f32).powi(2);
+                (linear.powf(1.0 / display_gamma) * 255.0).round() as u8
+            })
+            .collect();
+
+        Ok(Self {
+            matrix: profile.matrix,
+            decode,
+            encode: encode.into_boxed_slice().try_into().expect("table size"),
+        })
+    }
+
+    /// Map a color to its corrected one
+    #[inline]
+    pub fn apply(&self, red: u8, green: u8, blue: u8) -> (u8, u8, u8) {
+        let input = [
+            self.decode[red as usize],
+            self.decode[green as usize],
+            self.decode[blue as usize],
+        ];
+        let mix = |row: &[f32; 3]| {
+            let linear: f32 = row.iter().zip(input).map(|(k, c)| k * c).sum();
+            self.encode
+                [(linear.clamp(0.0, 1.0).sqrt() * (ENCODE_STEPS - 1) as f32).round() as usize]
+        };
+
+        (
+            mix(&self.matrix[0]),
+            mix(&self.matrix[1]),
+            mix(&self.matrix[2]),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use tempfile::NamedTempFile;
+
+    use super::*;
+
+    #[test]
+    fn test_should_load_color_profile() {
+        let file = NamedTempFile::new().unwrap();
+        std::fs::write(
+            file.path(),
+            "matrix = [[0.5, 0, 0], [0, 0, 1], [0, 1, 0]]\ngamma = 2.2\n",
+        )
+        .unwrap();
+        let profile = ColorProfile::load(file.path()).unwrap();
+
+        // half the linear light of the red, with green and blue swapped
+        assert_eq!(profile.apply(255, 255, 0), (186, 0, 255));
+        assert_eq!(profile.apply(0, 0, 0), (0, 0, 0));
+        // the 8-bit components aren't truncated
+        let identity =
+            ColorProfile::parse("matrix = [[1, 0, 0], [0, 1, 0], [0, 0, 1]]\ngamma = 2.2").unwrap();
+        assert!((0..=255).all(|v| identity.apply(v, v / 2, 255 - v) == (v, v / 2, 255 - v)));
+
+        assert!(ColorProfile::parse("matrix = [[1, 0, 0]]").is_err());
+        assert!(
+            ColorProfile::parse("matrix = [[1, 0, 0], [0, 1, 0], [0, 0, 1]]\ngamma = 0").is_err()
+        );
+    }
+}
@@ -0,0 +1,24 @@
+//! Ordered dithering, hiding the banding of the colors reduced to RGB565
+
+/// 4x4 Bayer matrix, the thresholds in 0..16
+const BAYER: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+/// Dither a component of the pixel at `x`, `y` which loses its `bits` low bits when packed.
+///
+/// The threshold of the pixel in the matrix is added in place of the truncated bits, so
+/// an intermediate level is rendered by a pattern of the two nearest ones.
+#[inline]
+pub fn dither(value: u8, bits: u32, x: usize, y: usize) -> u8 {
+    let threshold = BAYER[y % 4][x % 4] as u32;
+    value.saturating_add(((threshold << bits) >> 4) as u8)
+}
+
+/// Dither an RGB888 color for RGB565 at `x`, `y`
+#[inline]
+pub fn dither565(red: u8, green: u8, blue: u8, x: usize, y: usize) -> (u8, u8, u8) {
+    (
+        dither(red, 3, x, y),
+        dither(green, 2, x, y),
+        dither(blue, 3, x, y),
+    )
+}
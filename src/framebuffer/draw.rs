@@ -0,0 +1,157 @@
+//! Drawing primitives for the framebuffer
+//!
+//! Coordinates are native panel pixels: unlike [`Framebuffer::write`], the panel isn't scaled;
+//! only the glyphs drawn with [`Framebuffer::draw_text_scaled`] are.
+
+use font8x8::{BASIC_FONTS, UnicodeFonts};
+
+use super::scale_filter::{self, ScaleFilter};
+use super::{Framebuffer, rgb888};
+
+/// Size in pixels of a glyph of the font
+pub const GLYPH_SIZE: usize = 8;
+
+/// Size in pixels of a glyph drawn with [`Framebuffer::draw_text_scaled`]
+pub fn scaled_glyph_size(scale: f32) -> usize {
+    (GLYPH_SIZE as f32 * scale).round() as usize
+}
+
+impl Framebuffer {
+    /// Fill a rectangle with the given RGB565 color.
+    ///
+    /// The rectangle is clipped to the framebuffer bounds.
+    pub fn fill_rect(&self, x: usize, y: usize, width: usize, height: usize, color: u16) {
+        let x_end = usize::min(x.saturating_add(width), self.width());
+        let y_end = usize::min(y.saturating_add(height), self.height());
+
+        for y in y..y_end {
+            for x in x..x_end {
+                self.put_pixel(x, y, color);
+            }
+        }
+    }
+
+    /// Fill a rectangle of an overlay background with the given RGB565 color.
+    ///
+    /// The color is blended with the pixels below it at the overlay alpha, so the game shows through.
+    pub fn fill_overlay_rect(&self, x: usize, y: usize, width: usize, height: usize, color: u16) {
+        let alpha = self.overlay_alpha.get();
+        if alpha == u8::MAX {
+            return self.fill_rect(x, y, width, height, color);
+        }
+
+        let foreground = rgb888(color);
+        let x_end = usize::min(x.saturating_add(width), self.width());
+        let y_end = usize::min(y.saturating_add(height), self.height());
+        for y in y..y_end {
+            for x in x..x_end {
+                if let Some(background) = self.get_pixel_rgb(x, y) {
+                    let (r, g, b) = blend(foreground, background, alpha);
+                    self.put_pixel_rgb(x, y, r, g, b);
+                }
+            }
+        }
+    }
+
+    /// Draw a text on a single line starting at (`x`, `y`).
+    ///
+    /// If `invert` is set, the glyph background is drawn instead of the glyph.
+    pub fn draw_text(&self, text: &str, mut x: usize, y: usize, invert: bool, color: u16) {
+        debug!("Drawing text '{text}' at ({x}, {y}); invert: {invert}");
+        for glyph in text.chars() {
+            self.draw_char(x, y, glyph, invert, color);
+            x += GLYPH_SIZE;
+        }
+    }
+
+    /// Draw a character in the framebuffer
+    pub fn draw_char(&self, x: usize, y: usize, c: char, invert: bool, color: u16) {
+        let glyph = BASIC_FONTS.get(c).unwrap_or([0u8; GLYPH_SIZE]);
+        debug!("Glyph for {c} ({x}, {y}): {glyph:?}");
+
+        let mut clipped = false;
+        for (row, bits) in glyph.iter().enumerate() {
+            for col in 0..GLYPH_SIZE {
+                let mask = bits & (1 << col);
+                if (!invert && mask != 0) || (invert && mask == 0) {
+                    clipped |= !self.try_put_pixel(x + col, y + row, color);
+                }
+            }
+        }
+
+        if cfg!(debug_assertions) && clipped {
+            warn!("Glyph for {c} at ({x}, {y}) was drawn out of the framebuffer bounds");
+        }
+    }
+
+    /// Draw a text on a single line starting at (`x`, `y`), with the glyphs scaled by `scale`.
+    ///
+    /// The glyphs are sampled with the [`ScaleFilter`] of the framebuffer; the smooth one blends
+    /// their edges toward black, the background of the menus.
+    pub fn draw_text_scaled(&self, text: &str, mut x: usize, y: usize, scale: f32, color: u16) {
+        for glyph in text.chars() {
+            self.draw_char_scaled(x, y, glyph, scale, color);
+            x += scaled_glyph_size(scale);
+        }
+    }
+
+    /// Draw a character scaled by `scale`, see [`Framebuffer::draw_text_scaled`]
+    pub fn draw_char_scaled(&self, x: usize, y: usize, c: char, scale: f32, color: u16) {
+        let glyph = BASIC_FONTS.get(c).unwrap_or([0u8; GLYPH_SIZE]);
+        let is_set = |col: usize, row: usize| glyph[row] & (1 << col) != 0;
+        let (red, green, blue) = rgb888(color);
+        let size = scaled_glyph_size(scale);
+
+        for row in 0..size {
+            for col in 0..size {
+                let coverage = match self.scale_filter.get() {
+                    ScaleFilter::Nearest => {
+                        let (gx, gy) = (col as f32 / scale, row as f32 / scale);
+                        if is_set(
+                            (gx as usize).min(GLYPH_SIZE - 1),
+                            (gy as usize).min(GLYPH_SIZE - 1),
+                        ) {
+                            255
+                        } else {
+                            0
+                        }
+                    }
+                    ScaleFilter::Smooth => {
+                        let sample = |col, row| match is_set(col, row) {
+                            true => (255, 255, 255),
+                            false => (0, 0, 0),
+                        };
+                        scale_filter::bilinear(
+                            (col as f32 + 0.5) / scale - 0.5,
+                            (row as f32 + 0.5) / scale - 0.5,
+                            GLYPH_SIZE,
+                            GLYPH_SIZE,
+                            sample,
+                        )
+                        .0
+                    }
+                };
+                if coverage == 0 {
+                    continue;
+                }
+
+                let shade = |channel: u8| (channel as u16 * coverage as u16 / 255) as u8;
+                self.put_pixel_rgb(x + col, y + row, shade(red), shade(green), shade(blue));
+            }
+        }
+    }
+}
+
+/// Blend the `foreground` color over the `background` with the given alpha (0 transparent, 255 opaque)
+pub fn blend(foreground: (u8, u8, u8), background: (u8, u8, u8), alpha: u8) -> (u8, u8, u8) {
+    let mix = |fg: u8, bg: u8| {
+        let alpha = alpha as u32;
+        ((fg as u32 * alpha + bg as u32 * (255 - alpha) + 127) / 255) as u8
+    };
+
+    (
+        mix(foreground.0, background.0),
+        mix(foreground.1, background.1),
+        mix(foreground.2, background.2),
+    )
+}
@@ -0,0 +1,71 @@
+//! Color lookup table applied to the game frames
+
+/// Lookup table mapping each color component to its adjusted value
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorLut {
+    brightness: f32,
+    gamma: f32,
+    table: [u8; 256],
+}
+
+impl Default for ColorLut {
+    fn default() -> Self {
+        Self::new(1.0, 1.0)
+    }
+}
+
+impl ColorLut {
+    /// Build the table for the given brightness (multiplier) and gamma.
+    ///
+    /// A gamma greater than 1 brightens the mid tones.
+    pub fn new(brightness: f32, gamma: f32) -> Self {
+        let mut table = [0u8; 256];
+        for (i, value) in table.iter_mut().enumerate() {
+            let normalized = (i as f32 / 255.0).powf(1.0 / gamma);
+            *value = (normalized * brightness * 255.0).round().clamp(0.0, 255.0) as u8;
+        }
+
+        Self {
+            brightness,
+            gamma,
+            table,
+        }
+    }
+
+    pub fn brightness(&self) -> f32 {
+        self.brightness
+    }
+
+    pub fn gamma(&self) -> f32 {
+        self.gamma
+    }
+
+    /// Adjust a color component
+    #[inline]
+    pub fn apply(&self, value: u8) -> u8 {
+        self.table[value as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_should_build_color_lut() {
+        let identity = ColorLut::default();
+        assert!((0..=255).all(|v| identity.apply(v) == v));
+
+        let dimmed = ColorLut::new(0.5, 1.0);
+        assert_eq!(dimmed.apply(255), 128);
+        assert_eq!(dimmed.apply(0), 0);
+
+        let bright = ColorLut::new(2.0, 1.0);
+        assert_eq!(bright.apply(200), 255);
+
+        let gamma = ColorLut::new(1.0, 2.0);
+        assert!(gamma.apply(64) > 64);
+        assert_eq!(gamma.apply(255), 255);
+    }
+}
@@ -0,0 +1,69 @@
+//! Colorization palettes of the classic games
+
+/// Palette mapping the four shades of gray of a classic game to colors, lightest first
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DmgPalette {
+    pub name: &'static str,
+    pub colors: [(u8, u8, u8); 4],
+}
+
+/// The available palettes: the original grays, the DMG and Pocket screens, and the CGB colorizations
+pub const DMG_PALETTES: [DmgPalette; 8] = [
+    DmgPalette {
+        name: "Gray",
+        colors: [(255, 255, 255), (192, 192, 192), (96, 96, 96), (0, 0, 0)],
+    },
+    DmgPalette {
+        name: "DMG",
+        colors: [
+            (0x9b, 0xbc, 0x0f),
+            (0x8b, 0xac, 0x0f),
+            (0x30, 0x62, 0x30),
+            (0x0f, 0x38, 0x0f),
+        ],
+    },
+    DmgPalette {
+        name: "Pocket",
+        colors: [
+            (0xc4, 0xcf, 0xa1),
+            (0x8b, 0x95, 0x6d),
+            (0x4d, 0x53, 0x3c),
+            (0x1f, 0x1f, 0x1f),
+        ],
+    },
+    DmgPalette {
+        name: "Brown",
+        colors: [(255, 255, 255), (255, 173, 99), (131, 49, 0), (0, 0, 0)],
+    },
+    DmgPalette {
+        name: "Blue",
+        colors: [(255, 255, 255), (101, 164, 155), (0, 0, 254), (0, 0, 0)],
+    },
+    DmgPalette {
+        name: "Red",
+        colors: [(255, 255, 255), (255, 133, 132), (148, 58, 58), (0, 0, 0)],
+    },
+    DmgPalette {
+        name: "Pastel",
+        colors: [(255, 255, 165), (254, 148, 148), (147, 148, 254), (0, 0, 0)],
+    },
+    DmgPalette {
+        name: "Inverted",
+        colors: [(0, 0, 0), (0, 132, 134), (255, 222, 0), (255, 255, 255)],
+    },
+];
+
+impl DmgPalette {
+    /// Map a shade of gray of the emulator output to its color
+    #[inline]
+    pub fn map(&self, gray: u8) -> (u8, u8, u8) {
+        // the emulator draws the shades as 255, 192, 96 and 0
+        let shade = match gray {
+            224.. => 0,
+            144.. => 1,
+            48.. => 2,
+            _ => 3,
+        };
+        self.colors[shade]
+    }
+}
@@ -0,0 +1,207 @@
+//! Pixel formats supported by the framebuffer
+
+use std::fmt;
+use std::str::FromStr;
+
+/// Layout of a pixel in the framebuffer memory.
+///
+/// 16 and 32 bits formats are stored as native-endian words,
+/// while 24 bits formats are stored byte by byte in the named order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PixelFormat {
+    Rgb565,
+    Bgr565,
+    Rgb888,
+    Bgr888,
+    Argb8888,
+    Abgr8888,
+    Xrgb8888,
+}
+
+impl PixelFormat {
+    /// All the supported pixel formats
+    pub const ALL: [PixelFormat; 7] = [
+        PixelFormat::Rgb565,
+        PixelFormat::Bgr565,
+        PixelFormat::Rgb888,
+        PixelFormat::Bgr888,
+        PixelFormat::Argb8888,
+        PixelFormat::Abgr8888,
+        PixelFormat::Xrgb8888,
+    ];
+
+    /// Default pixel format for the given depth
+    pub fn from_bytes_per_pixel(bytes_per_pixel: usize) -> Option<Self> {
+        match bytes_per_pixel {
+            2 => Some(PixelFormat::Rgb565),
+            3 => Some(PixelFormat::Rgb888),
+            4 => Some(PixelFormat::Xrgb8888),
+            _ => None,
+        }
+    }
+
+    /// Size in bytes of a pixel
+    pub fn bytes_per_pixel(&self) -> usize {
+        match self {
+            PixelFormat::Rgb565 | PixelFormat::Bgr565 => 2,
+            PixelFormat::Rgb888 | PixelFormat::Bgr888 => 3,
+            PixelFormat::Argb8888 | PixelFormat::Abgr8888 | PixelFormat::Xrgb8888 => 4,
+        }
+    }
+
+    /// Pack an RGB888 color into `out`, which must be at least [`Self::bytes_per_pixel`] long
+    pub fn pack(&self, red: u8, green: u8, blue: u8, out: &mut [u8]) {
+        match self {
+            PixelFormat::Rgb565 => {
+                out[..2].copy_from_slice(&pack565(red, green, blue).to_ne_bytes())
+            }
+            PixelFormat::Bgr565 => {
+                out[..2].copy_from_slice(&pack565(blue, green, red).to_ne_bytes())
+            }
+            PixelFormat::Rgb888 => out[..3].copy_from_slice(&[red, green, blue]),
+            PixelFormat::Bgr888 => out[..3].copy_from_slice(&[blue, green, red]),
+            PixelFormat::Argb8888 => {
+                out[..4].copy_from_slice(&pack8888(0xff, red, green, blue).to_ne_bytes())
+            }
+            PixelFormat::Abgr8888 => {
+                out[..4].copy_from_slice(&pack8888(0xff, blue, green, red).to_ne_bytes())
+            }
+            PixelFormat::Xrgb8888 => {
+                out[..4].copy_from_slice(&pack8888(0x00, red, green, blue).to_ne_bytes())
+            }
+        }
+    }
+}
+
+impl PixelFormat {
+    /// Unpack the RGB888 color stored in `bytes`, which must be at least [`Self::bytes_per_pixel`] long
+    pub fn unpack(&self, bytes: &[u8]) -> (u8, u8, u8) {
+        let word16 = || u16::from_ne_bytes([bytes[0], bytes[1]]);
+        let word32 = || u32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]).to_be_bytes();
+        match self {
+            PixelFormat::Rgb565 => unpack565(word16()),
+            PixelFormat::Bgr565 => {
+                let (blue, green, red) = unpack565(word16());
+                (red, green, blue)
+            }
+            PixelFormat::Rgb888 => (bytes[0], bytes[1], bytes[2]),
+            PixelFormat::Bgr888 => (bytes[2], bytes[1], bytes[0]),
+            PixelFormat::Argb8888 | PixelFormat::Xrgb8888 => {
+                let [_, red, green, blue] = word32();
+                (red, green, blue)
+            }
+            PixelFormat::Abgr8888 => {
+                let [_, blue, green, red] = word32();
+                (red, green, blue)
+            }
+        }
+    }
+}
+
+impl fmt::Display for PixelFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            PixelFormat::Rgb565 => "rgb565",
+            PixelFormat::Bgr565 => "bgr565",
+            PixelFormat::Rgb888 => "rgb888",
+            PixelFormat::Bgr888 => "bgr888",
+            PixelFormat::Argb8888 => "argb8888",
+            PixelFormat::Abgr8888 => "abgr8888",
+            PixelFormat::Xrgb8888 => "xrgb8888",
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl FromStr for PixelFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.to_ascii_lowercase();
+        Self::ALL
+            .into_iter()
+            .find(|format| format.to_string() == s)
+            .ok_or_else(|| format!("unsupported pixel format: {s}"))
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for PixelFormat {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        PixelFormat::from_str(&s).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Pack the three components in a 5-6-5 word, the first one in the high bits
+fn pack565(high: u8, mid: u8, low: u8) -> u16 {
+    ((high as u16 >> 3) << 11) | ((mid as u16 >> 2) << 5) | (low as u16 >> 3)
+}
+
+/// Expand a 5-6-5 word into its three components, the first one from the high bits
+fn unpack565(word: u16) -> (u8, u8, u8) {
+    let high = ((word >> 11) & 0x1f) as u8;
+    let mid = ((word >> 5) & 0x3f) as u8;
+    let low = (word & 0x1f) as u8;
+
+    (
+        (high << 3) | (high >> 2),
+        (mid << 2) | (mid >> 4),
+        (low << 3) | (low >> 2),
+    )
+}
+
+/// Pack the four components in a 32 bits word, the first one in the high bits
+fn pack8888(a: u8, b: u8, c: u8, d: u8) -> u32 {
+    u32::from_be_bytes([a, b, c, d])
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_should_pack_color_in_each_format() {
+        let (r, g, b) = (0xf8, 0x84, 0x10);
+        let expected: [(PixelFormat, Vec<u8>); 7] = [
+            (PixelFormat::Rgb565, 0xfc22u16.to_ne_bytes().to_vec()),
+            (PixelFormat::Bgr565, 0x143fu16.to_ne_bytes().to_vec()),
+            (PixelFormat::Rgb888, vec![0xf8, 0x84, 0x10]),
+            (PixelFormat::Bgr888, vec![0x10, 0x84, 0xf8]),
+            (PixelFormat::Argb8888, 0xfff88410u32.to_ne_bytes().to_vec()),
+            (PixelFormat::Abgr8888, 0xff1084f8u32.to_ne_bytes().to_vec()),
+            (PixelFormat::Xrgb8888, 0x00f88410u32.to_ne_bytes().to_vec()),
+        ];
+
+        for (format, bytes) in expected {
+            let mut out = [0u8; 4];
+            format.pack(r, g, b, &mut out);
+            assert_eq!(
+                &out[..format.bytes_per_pixel()],
+                bytes.as_slice(),
+                "{format}"
+            );
+            assert_eq!(format.to_string().parse::<PixelFormat>(), Ok(format));
+        }
+    }
+
+    #[test]
+    fn test_should_get_format_from_depth() {
+        assert_eq!(
+            PixelFormat::from_bytes_per_pixel(2),
+            Some(PixelFormat::Rgb565)
+        );
+        assert_eq!(
+            PixelFormat::from_bytes_per_pixel(3),
+            Some(PixelFormat::Rgb888)
+        );
+        assert_eq!(
+            PixelFormat::from_bytes_per_pixel(4),
+            Some(PixelFormat::Xrgb8888)
+        );
+        assert_eq!(PixelFormat::from_bytes_per_pixel(5), None);
+    }
+}
@@ -0,0 +1,68 @@
+//! Sampling of the scaled frames and glyphs
+
+use serde::Deserialize;
+
+/// How the pixels are sampled when the frames and the glyphs are scaled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScaleFilter {
+    /// Nearest pixel, keeping the pixels sharp
+    #[default]
+    Nearest,
+    /// Bilinear blend of the four nearest pixels, smoothing the uneven scales
+    Smooth,
+}
+
+/// What's on the panel, selecting the [`ScaleFilter`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScaleContext {
+    /// The game is running
+    Game,
+    /// The game list or the in-game menu
+    Menu,
+}
+
+/// Bilinear sample at `(fx, fy)`, in source pixels, of a `width` x `height` image read with `sample`
+pub fn bilinear(
+    fx: f32,
+    fy: f32,
+    width: usize,
+    height: usize,
+    sample: impl Fn(usize, usize) -> (u8, u8, u8),
+) -> (u8, u8, u8) {
+    let fx = fx.clamp(0.0, (width - 1) as f32);
+    let fy = fy.clamp(0.0, (height - 1) as f32);
+    let (x0, y0) = (fx as usize, fy as usize);
+    let (x1, y1) = ((x0 + 1).min(width - 1), (y0 + 1).min(height - 1));
+    let (tx, ty) = (fx - x0 as f32, fy - y0 as f32);
+
+    let (top_left, top_right) = (sample(x0, y0), sample(x1, y0));
+    let (bottom_left, bottom_right) = (sample(x0, y1), sample(x1, y1));
+    let channel = |tl: u8, tr: u8, bl: u8, br: u8| {
+        let lerp = |a: u8, b: u8| a as f32 + (b as f32 - a as f32) * tx;
+        let (top, bottom) = (lerp(tl, tr), lerp(bl, br));
+        (top + (bottom - top) * ty).round() as u8
+    };
+
+    (
+        channel(top_left.0, top_right.0, bottom_left.0, bottom_right.0),
+        channel(top_left.1, top_right.1, bottom_left.1, bottom_right.1),
+        channel(top_left.2, top_right.2, bottom_left.2, bottom_right.2),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_should_blend_neighbour_pixels() {
+        // halfway between a black and a white pixel
+        let sample = |x: usize, _| if x == 0 { (0, 0, 0) } else { (255, 255, 255) };
+        assert_eq!(bilinear(0.5, 0.0, 2, 1, sample), (128, 128, 128));
+        assert_eq!(bilinear(1.0, 0.0, 2, 1, sample), (255, 255, 255));
+        // clamped to the edges
+        assert_eq!(bilinear(-1.0, 0.0, 2, 1, sample), (0, 0, 0));
+    }
+}
@@ -0,0 +1,85 @@
+//! Scaling of the game frames to the panel
+
+use serde::{Deserialize, Serialize};
+
+/// How the game frames are scaled to the panel; they're always centered
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScalingMode {
+    /// Fill the height, cropping the sides on narrow panels
+    #[default]
+    FitHeight,
+    /// Largest integer scale fitting the panel, keeping the pixels sharp
+    Integer,
+    /// Fill the width, cropping the top and the bottom on wide panels
+    FitWidth,
+    /// Largest scale fitting the panel, leaving borders on one side
+    FitBoth,
+}
+
+impl ScalingMode {
+    /// All the modes, in the order they're cycled
+    pub const ALL: [ScalingMode; 4] = [
+        ScalingMode::FitHeight,
+        ScalingMode::Integer,
+        ScalingMode::FitWidth,
+        ScalingMode::FitBoth,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ScalingMode::FitHeight => "Fit height",
+            ScalingMode::Integer => "Integer",
+            ScalingMode::FitWidth => "Fit width",
+            ScalingMode::FitBoth => "Fit both",
+        }
+    }
+
+    /// The mode after this one, wrapping to the first
+    pub fn next(&self) -> Self {
+        let i = Self::ALL.iter().position(|mode| mode == self).unwrap_or(0);
+        Self::ALL[(i + 1) % Self::ALL.len()]
+    }
+
+    /// The mode before this one, wrapping to the last
+    pub fn previous(&self) -> Self {
+        let i = Self::ALL.iter().position(|mode| mode == self).unwrap_or(0);
+        Self::ALL[(i + Self::ALL.len() - 1) % Self::ALL.len()]
+    }
+
+    /// Scale factor of a frame of `src` (width, height) pixels on a panel of `dst` pixels
+    pub fn scale(&self, src: (f32, f32), dst: (f32, f32)) -> f32 {
+        let (scale_w, scale_h) = (dst.0 / src.0, dst.1 / src.1);
+        match self {
+            ScalingMode::FitHeight => scale_h,
+            ScalingMode::Integer => scale_w.min(scale_h).floor().max(1.0),
+            ScalingMode::FitWidth => scale_w,
+            ScalingMode::FitBoth => scale_w.min(scale_h),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_should_cycle_scaling_modes() {
+        let mut mode = ScalingMode::default();
+        let mut seen = vec![mode];
+        for _ in 1..ScalingMode::ALL.len() {
+            mode = mode.next();
+            seen.push(mode);
+        }
+        assert_eq!(seen, ScalingMode::ALL);
+        assert_eq!(mode.next(), ScalingMode::FitHeight);
+
+        let (src, dst) = ((160.0, 144.0), (320.0, 480.0));
+        assert_eq!(ScalingMode::FitHeight.scale(src, dst), 480.0 / 144.0);
+        assert_eq!(ScalingMode::Integer.scale(src, dst), 2.0);
+        assert_eq!(ScalingMode::FitWidth.scale(src, dst), 2.0);
+        assert_eq!(ScalingMode::FitBoth.scale(src, dst), 2.0);
+        assert_eq!(ScalingMode::Integer.scale(src, (120.0, 100.0)), 1.0);
+    }
+}
@@ -0,0 +1,100 @@
+//! Linux framebuffer screen info ioctls.
+//!
+//! See `linux/fb.h` for the original definitions.
+
+use std::fs::File;
+use std::os::fd::AsRawFd;
+
+const FBIOGET_VSCREENINFO: libc::c_ulong = 0x4600;
+const FBIOGET_FSCREENINFO: libc::c_ulong = 0x4602;
+
+/// Position of a color channel in a pixel (`struct fb_bitfield`)
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Bitfield {
+    pub offset: u32,
+    pub length: u32,
+    pub msb_right: u32,
+}
+
+/// Variable screen info (`struct fb_var_screeninfo`)
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct VarScreenInfo {
+    pub xres: u32,
+    pub yres: u32,
+    pub xres_virtual: u32,
+    pub yres_virtual: u32,
+    pub xoffset: u32,
+    pub yoffset: u32,
+    pub bits_per_pixel: u32,
+    pub grayscale: u32,
+    pub red: Bitfield,
+    pub green: Bitfield,
+    pub blue: Bitfield,
+    pub transp: Bitfield,
+    pub nonstd: u32,
+    pub activate: u32,
+    pub height: u32,
+    pub width: u32,
+    pub accel_flags: u32,
+    pub pixclock: u32,
+    pub left_margin: u32,
+    pub right_margin: u32,
+    pub upper_margin: u32,
+    pub lower_margin: u32,
+    pub hsync_len: u32,
+    pub vsync_len: u32,
+    pub sync: u32,
+    pub vmode: u32,
+    pub rotate: u32,
+    pub colorspace: u32,
+    pub reserved: [u32; 4],
+}
+
+/// Fixed screen info (`struct fb_fix_screeninfo`)
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FixScreenInfo {
+    pub id: [u8; 16],
+    pub smem_start: libc::c_ulong,
+    pub smem_len: u32,
+    pub type_: u32,
+    pub type_aux: u32,
+    pub visual: u32,
+    pub xpanstep: u16,
+    pub ypanstep: u16,
+    pub ywrapstep: u16,
+    pub line_length: u32,
+    pub mmio_start: libc::c_ulong,
+    pub mmio_len: u32,
+    pub accel: u32,
+    pub capabilities: u16,
+    pub reserved: [u16; 2],
+}
+
+/// Query the variable screen info (resolution, depth) for the framebuffer device.
+///
+/// Fails if the file is not a framebuffer device.
+pub fn var_screen_info(file: &File) -> std::io::Result<VarScreenInfo> {
+    let mut info = VarScreenInfo::default();
+    let res = unsafe { libc::ioctl(file.as_raw_fd(), FBIOGET_VSCREENINFO, &mut info) };
+    if res < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    Ok(info)
+}
+
+/// Query the fixed screen info for the framebuffer device.
+///
+/// Fails if the file is not a framebuffer device.
+pub fn fix_screen_info(file: &File) -> std::io::Result<FixScreenInfo> {
+    let mut info = FixScreenInfo::default();
+    let res = unsafe { libc::ioctl(file.as_raw_fd(), FBIOGET_FSCREENINFO, &mut info) };
+    if res < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    Ok(info)
+}
@@ -0,0 +1,56 @@
+//! Test patterns used to verify the framebuffer configuration.
+
+/// Colors of the vertical bars in the test pattern (SMPTE-like order)
+pub const COLOR_BARS: [(u8, u8, u8); 8] = [
+    (0xff, 0xff, 0xff), // white
+    (0xff, 0xff, 0x00), // yellow
+    (0x00, 0xff, 0xff), // cyan
+    (0x00, 0xff, 0x00), // green
+    (0xff, 0x00, 0xff), // magenta
+    (0xff, 0x00, 0x00), // red
+    (0x00, 0x00, 0xff), // blue
+    (0x00, 0x00, 0x00), // black
+];
+
+/// Get the index of the color bar at column `x` for a screen `width` pixels wide.
+///
+/// The bars evenly split the screen; if the width is not a multiple of the number of bars,
+/// the remainder is distributed among the bars.
+pub fn color_bar_index(x: usize, width: usize) -> usize {
+    if width == 0 {
+        return 0;
+    }
+
+    usize::min(x * COLOR_BARS.len() / width, COLOR_BARS.len() - 1)
+}
+
+/// Get the color of the color bar at column `x` for a screen `width` pixels wide.
+pub fn color_bar(x: usize, width: usize) -> (u8, u8, u8) {
+    COLOR_BARS[color_bar_index(x, width)]
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_should_split_color_bars_evenly() {
+        // 320 / 8 = 40 pixels per bar
+        assert_eq!(color_bar_index(0, 320), 0);
+        assert_eq!(color_bar_index(39, 320), 0);
+        assert_eq!(color_bar_index(40, 320), 1);
+        assert_eq!(color_bar_index(279, 320), 6);
+        assert_eq!(color_bar_index(280, 320), 7);
+        assert_eq!(color_bar_index(319, 320), 7);
+
+        assert_eq!(color_bar(0, 320), COLOR_BARS[0]);
+        assert_eq!(color_bar(319, 320), COLOR_BARS[7]);
+    }
+
+    #[test]
+    fn test_should_clamp_color_bars_out_of_range() {
+        assert_eq!(color_bar_index(1000, 320), COLOR_BARS.len() - 1);
+        assert_eq!(color_bar_index(10, 0), 0);
+    }
+}
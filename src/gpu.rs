@@ -61,10 +61,19 @@ pub struct Gpu {
     #[serde(with = "serde_arrays")]
     bgprio: [PrioType; SCREEN_W],
     pub updated: bool,
+    /// Number of frames rendered since power on
+    #[serde(default)]
+    pub frame_count: u64,
     pub interrupt: u8,
     pub gbmode: GbMode,
     hblanking: bool,
     first_frame: bool,
+    /// Whether the colors of the color games are corrected to look like on a CGB screen
+    #[serde(skip, default = "Gpu::default_color_correction")]
+    pub color_correction: bool,
+    /// Whether the frames aren't drawn, e.g. while they're skipped to catch up
+    #[serde(skip)]
+    pub skip_render: bool,
 }
 
 impl Gpu {
@@ -103,6 +112,7 @@ impl Gpu {
             data: vec![0; SCREEN_W * SCREEN_H * 3],
             bgprio: [PrioType::Normal; SCREEN_W],
             updated: false,
+            frame_count: 0,
             interrupt: 0,
             gbmode: GbMode::Classic,
             cbgpal_inc: false,
@@ -114,9 +124,15 @@ impl Gpu {
             vrambank: 0,
             hblanking: false,
             first_frame: false,
+            color_correction: Gpu::default_color_correction(),
+            skip_render: false,
         }
     }
 
+    fn default_color_correction() -> bool {
+        true
+    }
+
     pub fn new_cgb() -> Gpu {
         Gpu::new()
     }
@@ -187,6 +203,7 @@ impl Gpu {
                 self.wy_trigger = false;
                 self.interrupt |= 0x01;
                 self.updated = true;
+                self.frame_count += 1;
                 self.first_frame = false;
                 self.m1_inte
             }
@@ -411,6 +428,11 @@ impl Gpu {
             // The first frame from when lcd_on is set should not be drawn.
             return;
         }
+        if self.skip_render {
+            // the window keeps its own line counter, drawn or not
+            self.window_line();
+            return;
+        }
 
         for x in 0..SCREEN_W {
             self.setcolor(x, 255);
@@ -427,11 +449,18 @@ impl Gpu {
     }
 
     fn setrgb(&mut self, x: usize, r: u8, g: u8, b: u8) {
-        // Gameboy Color RGB correction
-        // Taken from the Gambatte emulator
         // assume r, g and b are between 0 and 1F
         let baseidx = self.line as usize * SCREEN_W * 3 + x * 3;
 
+        if !self.color_correction {
+            self.data[baseidx] = (r << 3) | (r >> 2);
+            self.data[baseidx + 1] = (g << 3) | (g >> 2);
+            self.data[baseidx + 2] = (b << 3) | (b >> 2);
+            return;
+        }
+
+        // Gameboy Color RGB correction
+        // Taken from the Gambatte emulator
         let r = r as u32;
         let g = g as u32;
         let b = b as u32;
@@ -441,16 +470,21 @@ impl Gpu {
         self.data[baseidx + 2] = ((r * 3 + g * 2 + b * 11) >> 1) as u8;
     }
 
-    fn draw_bg(&mut self) {
-        let drawbg = self.gbmode == GbMode::Color || self.lcdc0;
-
+    /// Line of the window on the current line, or -1 if the window isn't visible
+    fn window_line(&mut self) -> i32 {
         let wx_trigger = self.winx <= 166;
-        let winy = if self.win_on && self.wy_trigger && wx_trigger {
+        if self.win_on && self.wy_trigger && wx_trigger {
             self.wy_pos += 1;
             self.wy_pos
         } else {
             -1
-        };
+        }
+    }
+
+    fn draw_bg(&mut self) {
+        let drawbg = self.gbmode == GbMode::Color || self.lcdc0;
+
+        let winy = self.window_line();
 
         if winy < 0 && !drawbg {
             return;
@@ -659,3 +693,40 @@ fn cgb_sprite_order(a: &(i32, i32, u8), b: &(i32, i32, u8)) -> Ordering {
     // CGB order: only prioritize based on OAM position.
     b.2.cmp(&a.2)
 }
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_should_desaturate_green() {
+        let mut gpu = Gpu::new_cgb();
+        gpu.setrgb(0, 0, 0x1f, 0);
+        assert_eq!(gpu.data[0..3], [31, 186, 31]);
+        // white stays neutral
+        gpu.setrgb(1, 0x1f, 0x1f, 0x1f);
+        assert_eq!(gpu.data[3..6], [248, 248, 248]);
+
+        gpu.color_correction = false;
+        gpu.setrgb(0, 0, 0x1f, 0);
+        assert_eq!(gpu.data[0..3], [0, 255, 0]);
+    }
+
+    #[test]
+    fn test_should_not_draw_skipped_frames() {
+        let mut gpu = Gpu::new();
+        gpu.win_on = true;
+        gpu.wy_trigger = true;
+        gpu.palb = [96; 4];
+        gpu.skip_render = true;
+        gpu.renderscan();
+        assert!(gpu.data.iter().all(|&v| v == 0));
+        assert_eq!(gpu.wy_pos, 0);
+
+        gpu.skip_render = false;
+        gpu.renderscan();
+        assert!(gpu.data[0..SCREEN_W * 3].iter().all(|&v| v == 96));
+        assert_eq!(gpu.wy_pos, 1);
+    }
+}
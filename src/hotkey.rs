@@ -0,0 +1,144 @@
+use rboy::KeypadKey;
+use rboy::input::KeyEvent;
+
+use crate::app_config::HotkeysConfig;
+use crate::picture::PictureAdjust;
+
+/// Action triggered by a hotkey
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// Adjust the picture settings
+    Picture(PictureAdjust),
+    /// Pause the game and open the in-game menu
+    Menu,
+    /// Switch to the next key profile
+    NextProfile,
+    /// Print a screenshot to stdout
+    Screenshot,
+    /// Save the state to the quick slot
+    QuickSave,
+    /// Load the state from the quick slot
+    QuickLoad,
+    /// Show or hide the debug overlay
+    DebugOverlay,
+    /// Invert the colors of the display
+    InvertColors,
+    /// Switch to the next colorization palette of the classic games
+    NextPalette,
+    /// Switch to the next scaling mode of the frames
+    NextScaling,
+    /// Switch to the next emulation speed
+    NextSpeed,
+}
+
+/// Detects hotkeys, which are combinations of keys held together, during gameplay.
+#[derive(Debug, Default)]
+pub struct Hotkeys {
+    bindings: Vec<(Action, Vec<KeypadKey>)>,
+    pressed: Vec<KeypadKey>,
+}
+
+impl Hotkeys {
+    /// Create [`Hotkeys`] from the configuration
+    pub fn new(config: &HotkeysConfig) -> Self {
+        let mut bindings = vec![];
+        for (action, keys) in [
+            (Action::Menu, &config.menu),
+            (Action::NextProfile, &config.next_profile),
+            (Action::Screenshot, &config.screenshot),
+            (Action::QuickSave, &config.quick_save),
+            (Action::QuickLoad, &config.quick_load),
+            (Action::DebugOverlay, &config.debug_overlay),
+            (Action::InvertColors, &config.invert_colors),
+            (Action::NextPalette, &config.next_palette),
+            (Action::NextScaling, &config.next_scaling),
+            (Action::NextSpeed, &config.next_speed),
+            (
+                Action::Picture(PictureAdjust::BrightnessUp),
+                &config.brightness_up,
+            ),
+            (
+                Action::Picture(PictureAdjust::BrightnessDown),
+                &config.brightness_down,
+            ),
+            (Action::Picture(PictureAdjust::GammaUp), &config.gamma_up),
+            (
+                Action::Picture(PictureAdjust::GammaDown),
+                &config.gamma_down,
+            ),
+        ] {
+            if let Some(keys) = keys {
+                bindings.push((action, keys.iter().map(|k| k.keycode()).collect()));
+            }
+        }
+
+        Self {
+            bindings,
+            pressed: vec![],
+        }
+    }
+
+    /// Handle a key event and return the [`Action`] triggered, if any.
+    ///
+    /// An action is triggered when the last key of its combination is pressed.
+    pub fn handle_event(&mut self, event: KeyEvent, key: KeypadKey) -> Option<Action> {
+        match event {
+            KeyEvent::Up => {
+                self.pressed.retain(|k| *k != key);
+                None
+            }
+            KeyEvent::Down if self.pressed.contains(&key) => None, // repeat
+            KeyEvent::Down => {
+                self.pressed.push(key);
+                self.bindings
+                    .iter()
+                    .find(|(_, keys)| {
+                        !keys.is_empty()
+                            && keys.contains(&key)
+                            && keys.iter().all(|k| self.pressed.contains(k))
+                    })
+                    .map(|(action, _)| *action)
+            }
+        }
+    }
+
+    /// Forget the keys held, e.g. when the input is restarted; returns them to release them
+    pub fn release_all(&mut self) -> Vec<KeypadKey> {
+        std::mem::take(&mut self.pressed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_should_trigger_hotkey_on_combination() {
+        let config: HotkeysConfig = toml::from_str(r#"screenshot = ["SELECT", "B"]"#).unwrap();
+        let mut hotkeys = Hotkeys::new(&config);
+
+        assert_eq!(
+            hotkeys.handle_event(KeyEvent::Down, KeypadKey::Select),
+            None
+        );
+        assert_eq!(
+            hotkeys.handle_event(KeyEvent::Down, KeypadKey::B),
+            Some(Action::Screenshot)
+        );
+        // repeat doesn't trigger it again
+        assert_eq!(hotkeys.handle_event(KeyEvent::Down, KeypadKey::B), None);
+        assert_eq!(hotkeys.handle_event(KeyEvent::Up, KeypadKey::B), None);
+        assert_eq!(hotkeys.handle_event(KeyEvent::Up, KeypadKey::Select), None);
+        // a single key of the combination doesn't trigger it
+        assert_eq!(hotkeys.handle_event(KeyEvent::Down, KeypadKey::B), None);
+
+        // the keys released apart don't complete a combination anymore
+        assert_eq!(hotkeys.release_all(), vec![KeypadKey::B]);
+        assert_eq!(
+            hotkeys.handle_event(KeyEvent::Down, KeypadKey::Select),
+            None
+        );
+        assert!(hotkeys.release_all().contains(&KeypadKey::Select));
+    }
+}
@@ -0,0 +1,97 @@
+use std::time::{Duration, Instant};
+
+/// Power saving stage of the display
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdleStage {
+    /// Full brightness
+    Active,
+    /// Dimmed after a short idle time
+    Dim,
+    /// Blanked after a longer idle time
+    Blank,
+}
+
+/// Two-stage power saving of the display: dims it when no input is received for a while,
+/// then blanks it; any input restores full brightness.
+#[derive(Debug)]
+pub struct IdleDimmer {
+    dim_after: Duration,
+    blank_after: Option<Duration>,
+    last_input: Instant,
+    stage: IdleStage,
+}
+
+impl IdleDimmer {
+    /// Create an [`IdleDimmer`], active since `now`
+    pub fn new(dim_after: Duration, blank_after: Option<Duration>, now: Instant) -> Self {
+        Self {
+            dim_after,
+            blank_after,
+            last_input: now,
+            stage: IdleStage::Active,
+        }
+    }
+
+    /// Handle input received at `now`; returns [`IdleStage::Active`] if the display must be restored
+    pub fn input(&mut self, now: Instant) -> Option<IdleStage> {
+        self.last_input = now;
+        self.enter(IdleStage::Active)
+    }
+
+    /// Check the idle time at `now`; returns the new stage if it changes
+    pub fn poll(&mut self, now: Instant) -> Option<IdleStage> {
+        let idle = now.saturating_duration_since(self.last_input);
+        if self
+            .blank_after
+            .is_some_and(|blank_after| idle >= blank_after)
+        {
+            self.enter(IdleStage::Blank)
+        } else if idle >= self.dim_after {
+            self.enter(IdleStage::Dim)
+        } else {
+            None
+        }
+    }
+
+    fn enter(&mut self, stage: IdleStage) -> Option<IdleStage> {
+        if self.stage == stage {
+            return None;
+        }
+        debug!("Display idle stage: {stage:?}");
+        self.stage = stage;
+        Some(stage)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_should_dim_then_blank_when_idle() {
+        let start = Instant::now();
+        let secs = |secs| start + Duration::from_secs(secs);
+        let mut dimmer = IdleDimmer::new(
+            Duration::from_secs(30),
+            Some(Duration::from_secs(120)),
+            start,
+        );
+
+        assert_eq!(dimmer.poll(secs(29)), None);
+        assert_eq!(dimmer.poll(secs(30)), Some(IdleStage::Dim));
+        assert_eq!(dimmer.poll(secs(60)), None);
+        assert_eq!(dimmer.poll(secs(120)), Some(IdleStage::Blank));
+        assert_eq!(dimmer.poll(secs(200)), None);
+
+        assert_eq!(dimmer.input(secs(201)), Some(IdleStage::Active));
+        assert_eq!(dimmer.input(secs(202)), None);
+        assert_eq!(dimmer.poll(secs(231)), None);
+        assert_eq!(dimmer.poll(secs(232)), Some(IdleStage::Dim));
+
+        // without a blank timeout the display stays dimmed
+        let mut dimmer = IdleDimmer::new(Duration::from_secs(30), None, start);
+        assert_eq!(dimmer.poll(secs(30)), Some(IdleStage::Dim));
+        assert_eq!(dimmer.poll(secs(10_000)), None);
+    }
+}
@@ -1,15 +1,27 @@
+pub mod adc;
 pub mod config;
+mod event_log;
+mod filter;
 pub mod gpio;
+mod latency;
+pub mod power;
+mod rate_limit;
 pub mod state;
 
-use std::sync::Arc;
 use std::sync::atomic::AtomicBool;
 use std::sync::mpsc::Sender;
-use std::time::Duration;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
-pub use self::config::{InputListenerConfig, KeyConfig, PowerSwitch, RepeatConfig};
-use self::gpio::{Gpio, GpioValue};
-use self::state::{KeyState, OutEvent};
+pub use self::config::{
+    InitialState, InputListenerConfig, KeyConfig, KeyMode, PowerSwitch, RepeatConfig,
+};
+pub use self::event_log::EventLog;
+pub use self::filter::MedianFilter;
+use self::gpio::Gpio;
+pub use self::latency::LatencyStats;
+use self::rate_limit::RateLimiter;
+use self::state::{KeyState, OutEvent, PowerSwitchState};
 use crate::KeypadKey;
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
@@ -30,8 +42,11 @@ where
     exit: Arc<AtomicBool>,
     event_sender: Sender<Event>,
     keys: Vec<KeyState<GPIO>>,
-    power_switches: Vec<PowerSwitch<GPIO>>,
+    power_switches: Vec<PowerSwitchState<GPIO>>,
     poll_interval: Duration,
+    event_log: EventLog,
+    dump_event_log: Arc<AtomicBool>,
+    latency: Option<Arc<Mutex<LatencyStats>>>,
 }
 
 impl<G> InputListener<G>
@@ -43,9 +58,24 @@ where
         InputListener {
             exit: config.exit,
             event_sender,
-            keys: config.keys.into_iter().map(KeyState::from).collect(),
-            power_switches: config.power_switches,
+            keys: config
+                .keys
+                .into_iter()
+                .map(KeyState::from)
+                .map(|mut key| {
+                    key.rate_limit = config.max_key_events_per_sec.map(RateLimiter::new);
+                    key
+                })
+                .collect(),
+            power_switches: config
+                .power_switches
+                .into_iter()
+                .map(PowerSwitchState::from)
+                .collect(),
             poll_interval: config.poll_interval,
+            event_log: EventLog::new(config.event_log_size),
+            dump_event_log: config.dump_event_log,
+            latency: config.latency,
         }
     }
 
@@ -53,7 +83,18 @@ where
     pub fn run(mut self) {
         while !self.exit.load(std::sync::atomic::Ordering::SeqCst) {
             for key in &mut self.keys {
-                Self::handle_key_poll(key, &mut self.event_sender);
+                Self::handle_key_poll(
+                    key,
+                    &mut self.event_sender,
+                    &mut self.event_log,
+                    self.latency.as_deref(),
+                );
+            }
+            if self
+                .dump_event_log
+                .swap(false, std::sync::atomic::Ordering::SeqCst)
+            {
+                self.event_log.dump();
             }
             for switch in &mut self.power_switches {
                 Self::handle_power_switch_poll(switch, &self.exit);
@@ -64,7 +105,12 @@ where
     }
 
     /// Handle polling of a single key
-    fn handle_key_poll(key: &mut KeyState<G>, sender: &mut Sender<Event>) {
+    fn handle_key_poll(
+        key: &mut KeyState<G>,
+        sender: &mut Sender<Event>,
+        log: &mut EventLog,
+        latency: Option<&Mutex<LatencyStats>>,
+    ) {
         // read value
         trace!("Polling key {:?}", key.keycode);
         let Ok(value) = key.gpio.read() else {
@@ -72,29 +118,61 @@ where
             return;
         };
         trace!("Read GPIO value {:?} for key {:?}", value, key.keycode);
+        key.track_edge(value);
+        let value = match key.filter.as_mut() {
+            Some(filter) => filter.push(value),
+            None => value,
+        };
         // handle value
-        let res = match key.handle_gpio_value(value) {
-            OutEvent::None => Ok(()),
+        let out_event = key.handle_gpio_value(value);
+        let event = match out_event {
+            OutEvent::None => return,
             OutEvent::Press => {
                 info!("Key {:?} pressed", key.keycode);
-                sender.send((KeyEvent::Down, key.keycode))
+                (KeyEvent::Down, key.keycode)
             }
             OutEvent::Release => {
                 info!("Key {:?} released", key.keycode);
-                sender.send((KeyEvent::Up, key.keycode))
+                (KeyEvent::Up, key.keycode)
             }
             OutEvent::Repeat => {
                 info!("Key {:?} repeat", key.keycode);
-                sender.send((KeyEvent::Down, key.keycode))
+                (KeyEvent::Down, key.keycode)
             }
         };
+        // repeats aren't caused by an edge
+        if out_event != OutEvent::Repeat
+            && let Some(latency) = latency
+            && let Some(edge) = key.take_edge()
+        {
+            let elapsed = edge.elapsed();
+            debug!("Key {:?} latency: {elapsed:?}", key.keycode);
+            if let Ok(mut stats) = latency.lock() {
+                stats.record(elapsed);
+            }
+        }
+        // only the presses are limited: a dropped release would leave the key stuck down
+        if let Some(limiter) = key.rate_limit.as_mut()
+            && event.0 == KeyEvent::Down
+            && !limiter.allow(Instant::now())
+        {
+            if limiter.dropped() == 1 {
+                warn!(
+                    "Key {:?} exceeds its event rate; dropping presses",
+                    key.keycode
+                );
+            }
+            return;
+        }
+        log.push(event);
+        let res = sender.send(event);
         if let Err(e) = res {
             error!("Failed to send key event for key {:?}: {}", key.keycode, e);
         }
     }
 
     /// Handle polling of a single power switch
-    fn handle_power_switch_poll(switch: &mut PowerSwitch<G>, exit: &Arc<AtomicBool>) {
+    fn handle_power_switch_poll(switch: &mut PowerSwitchState<G>, exit: &Arc<AtomicBool>) {
         let value = match switch.gpio.read() {
             Ok(v) => v,
             Err(e) => {
@@ -102,12 +180,12 @@ where
                 return;
             }
         };
-        if value == GpioValue::Enabled {
+        if switch.handle_gpio_value(value) {
             warn!("Power switch activated, shutting down system");
             #[cfg(target_os = "linux")]
             {
-                use std::process::Command;
-                if let Err(e) = Command::new("shutdown").arg("-h").arg("now").spawn() {
+                use self::power::{PowerAction, PowerRunner, SystemPowerRunner};
+                if let Err(e) = SystemPowerRunner.run(PowerAction::Shutdown) {
                     error!("Failed to execute shutdown command: {}", e);
                 }
                 exit.store(true, std::sync::atomic::Ordering::SeqCst);
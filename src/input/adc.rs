@@ -0,0 +1,36 @@
+use std::path::{Path, PathBuf};
+
+/// ADC trait abstraction
+pub trait Adc {
+    /// Read the current raw value of the channel
+    fn read(&mut self) -> anyhow::Result<u32>;
+}
+
+/// ADC channel exposed by the Linux IIO subsystem,
+/// e.g. `/sys/bus/iio/devices/iio:device0/in_voltage0_raw`
+pub struct IioAdc {
+    path: PathBuf,
+}
+
+impl IioAdc {
+    /// Create an [`IioAdc`] reading the raw value file at `path`
+    pub fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+        }
+    }
+}
+
+impl Adc for IioAdc {
+    fn read(&mut self) -> anyhow::Result<u32> {
+        let value = std::fs::read_to_string(&self.path)
+            .map_err(|e| anyhow::anyhow!("Failed to read ADC {:?}: {}", self.path, e))?;
+        let value = value
+            .trim()
+            .parse()
+            .map_err(|e| anyhow::anyhow!("Invalid ADC value {:?}: {}", value.trim(), e))?;
+        trace!("Read ADC {:?} value: {value}", self.path);
+
+        Ok(value)
+    }
+}
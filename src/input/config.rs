@@ -1,7 +1,10 @@
-use std::sync::Arc;
 use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
+use serde::Deserialize;
+
+use crate::input::LatencyStats;
 use crate::input::gpio::Gpio;
 
 /// Configuration for an individual key binding
@@ -13,6 +16,34 @@ where
     pub keycode: crate::KeypadKey,
     pub debounce: Duration,
     pub repeat: Option<RepeatConfig>,
+    /// Expected state of the key at startup, if known
+    pub initial_state: Option<InitialState>,
+    /// Number of raw samples to median-filter before debounce, if any
+    pub filter_samples: Option<usize>,
+    /// Whether the key is held only while pressed or latches on and off
+    pub mode: KeyMode,
+}
+
+/// How a key reacts to being pressed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyMode {
+    /// The key is held while it's physically pressed
+    #[default]
+    Momentary,
+    /// Each press toggles the key between held and released; physical releases are ignored
+    Toggle,
+}
+
+/// Expected state of a key at startup.
+///
+/// Prevents a key which is already active at boot (e.g. a normally-closed switch)
+/// from emitting a spurious event on the first poll.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InitialState {
+    Released,
+    Pressed,
 }
 
 /// Configuration for key auto-repeat
@@ -27,6 +58,8 @@ where
     GPIO: Gpio,
 {
     pub gpio: GPIO,
+    /// Time the switch must stay in a state before the change is accepted
+    pub debounce: Duration,
 }
 
 /// Configuration for the input listener
@@ -38,4 +71,12 @@ where
     pub keys: Vec<KeyConfig<GPIO>>,
     pub power_switches: Vec<PowerSwitch<GPIO>>,
     pub poll_interval: Duration,
+    /// Number of emitted events retained in the event log
+    pub event_log_size: usize,
+    /// When set, the listener dumps its event log and clears the flag
+    pub dump_event_log: Arc<AtomicBool>,
+    /// When set, the latency from each GPIO edge to its key event is recorded here
+    pub latency: Option<Arc<Mutex<LatencyStats>>>,
+    /// Maximum presses a single key can emit per second; excess presses are dropped, releases never are
+    pub max_key_events_per_sec: Option<u32>,
 }
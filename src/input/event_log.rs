@@ -0,0 +1,79 @@
+use std::collections::VecDeque;
+use std::time::Instant;
+
+use super::Event;
+
+/// Ring buffer of the last events emitted by the input listener.
+///
+/// Useful to tell debounce issues apart from wiring issues.
+#[derive(Debug)]
+pub struct EventLog {
+    capacity: usize,
+    entries: VecDeque<(Instant, Event)>,
+}
+
+impl EventLog {
+    /// Create a new [`EventLog`] retaining up to `capacity` events
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    /// Record an event, discarding the oldest one if the log is full
+    pub fn push(&mut self, event: Event) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back((Instant::now(), event));
+    }
+
+    /// Iterate over the recorded events, from the oldest to the most recent
+    pub fn iter(&self) -> impl Iterator<Item = &(Instant, Event)> {
+        self.entries.iter()
+    }
+
+    /// Write the recorded events to the log
+    pub fn dump(&self) {
+        let now = Instant::now();
+        info!("Input event log ({} events):", self.entries.len());
+        for (at, (event, key)) in self.iter() {
+            info!(
+                "  -{:>6}ms {event:?} {key:?}",
+                now.duration_since(*at).as_millis()
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::KeypadKey;
+    use crate::input::KeyEvent;
+
+    #[test]
+    fn test_should_retain_most_recent_events() {
+        let mut log = EventLog::new(3);
+        log.push((KeyEvent::Down, KeypadKey::A));
+        log.push((KeyEvent::Up, KeypadKey::A));
+        log.push((KeyEvent::Down, KeypadKey::B));
+        log.push((KeyEvent::Up, KeypadKey::B));
+
+        let events: Vec<Event> = log.iter().map(|(_, event)| *event).collect();
+        assert_eq!(
+            events,
+            vec![
+                (KeyEvent::Up, KeypadKey::A),
+                (KeyEvent::Down, KeypadKey::B),
+                (KeyEvent::Up, KeypadKey::B),
+            ]
+        );
+        assert!(log.iter().zip(log.iter().skip(1)).all(|(a, b)| a.0 <= b.0));
+    }
+}
@@ -0,0 +1,74 @@
+use std::collections::VecDeque;
+
+use crate::input::gpio::GpioValue;
+
+/// Median filter over the last raw GPIO samples of a key.
+///
+/// It filters the signal level before debounce, rejecting glitches shorter than half the window.
+#[derive(Debug, Clone)]
+pub struct MedianFilter {
+    window: usize,
+    samples: VecDeque<GpioValue>,
+}
+
+impl MedianFilter {
+    /// Create a new [`MedianFilter`] over `window` samples; the window is rounded up to an odd size
+    pub fn new(window: usize) -> Self {
+        let window = window.max(1) | 1;
+        Self {
+            window,
+            samples: VecDeque::with_capacity(window),
+        }
+    }
+
+    /// Push a raw sample and return the filtered value
+    pub fn push(&mut self, value: GpioValue) -> GpioValue {
+        if self.samples.len() == self.window {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(value);
+
+        // the median of binary samples is the majority
+        let enabled = self
+            .samples
+            .iter()
+            .filter(|v| **v == GpioValue::Enabled)
+            .count();
+        if enabled * 2 > self.samples.len() {
+            GpioValue::Enabled
+        } else {
+            GpioValue::Disabled
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_should_reject_single_sample_glitch() {
+        use GpioValue::{Disabled, Enabled};
+
+        let mut filter = MedianFilter::new(3);
+        let raw = [
+            Disabled, Disabled, Enabled, Disabled, Disabled, Enabled, Enabled, Enabled,
+        ];
+        let filtered: Vec<GpioValue> = raw.iter().map(|v| filter.push(*v)).collect();
+
+        assert_eq!(
+            filtered,
+            vec![
+                Disabled, Disabled, Disabled, Disabled, Disabled, Disabled, Enabled, Enabled
+            ]
+        );
+    }
+
+    #[test]
+    fn test_should_round_window_to_odd_size() {
+        assert_eq!(MedianFilter::new(0).window, 1);
+        assert_eq!(MedianFilter::new(2).window, 3);
+        assert_eq!(MedianFilter::new(5).window, 5);
+    }
+}
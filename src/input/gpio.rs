@@ -29,3 +29,21 @@ pub trait Gpio {
     /// Read the current GPIO value
     fn read(&mut self) -> anyhow::Result<GpioValue>;
 }
+
+/// Output GPIO, driving e.g. LEDs, motors or a backlight
+pub trait GpioOut {
+    /// Drive the line to the given value, stopping the PWM if running
+    fn write(&mut self, value: GpioValue) -> anyhow::Result<()>;
+
+    /// Drive the line with a software PWM at `frequency` Hz, enabled for the `duty_cycle`
+    /// fraction (0.0 to 1.0) of each period
+    fn pwm(&mut self, frequency: f64, duty_cycle: f64) -> anyhow::Result<()> {
+        let _ = (frequency, duty_cycle);
+        anyhow::bail!("PWM is not supported on this GPIO")
+    }
+}
+
+/// Electrical level of a line with the given value: `true` is high
+fn level(value: GpioValue, active_low: bool) -> bool {
+    (value == GpioValue::Enabled) != active_low
+}
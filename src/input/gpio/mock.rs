@@ -1,4 +1,4 @@
-use super::{Gpio, GpioValue};
+use super::{Gpio, GpioOut, GpioValue};
 
 /// Mock GPIO implementation for testing
 pub struct MockGpio {
@@ -26,6 +26,13 @@ impl Gpio for MockGpio {
     }
 }
 
+impl GpioOut for MockGpio {
+    fn write(&mut self, value: GpioValue) -> anyhow::Result<()> {
+        self.value = super::level(value, self.active_low);
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -44,4 +51,19 @@ mod tests {
         let mut gpio_active_low_high = MockGpio::new(false, true);
         assert_eq!(gpio_active_low_high.read().unwrap(), GpioValue::Enabled);
     }
+
+    #[test]
+    fn test_should_drive_active_low_output_low() {
+        let mut gpio = MockGpio::new(true, true);
+        gpio.write(GpioValue::Enabled).unwrap();
+        assert!(!gpio.value);
+        assert_eq!(gpio.read().unwrap(), GpioValue::Enabled);
+
+        gpio.write(GpioValue::Disabled).unwrap();
+        assert!(gpio.value);
+
+        let mut gpio = MockGpio::new(false, false);
+        gpio.write(GpioValue::Enabled).unwrap();
+        assert!(gpio.value);
+    }
 }
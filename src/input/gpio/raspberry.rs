@@ -1,29 +1,63 @@
-use rppal::gpio::{Gpio as RrppalGpio, InputPin};
+use rppal::gpio::{Gpio as RrppalGpio, InputPin, Level, Mode, OutputPin};
 
-use super::{Gpio, GpioValue};
+use super::{Gpio, GpioOut, GpioValue};
 
 pub struct RaspberryGpio {
     active_low: bool,
-    pin: InputPin,
+    pin: Line,
+}
+
+/// Line of a [`RaspberryGpio`], configured as input or output
+enum Line {
+    Input(InputPin),
+    Output(OutputPin),
 }
 
 impl RaspberryGpio {
     /// Create a new [`RaspberryGpio`] instance for the specified GPIO pin
     pub fn try_new(gpio: u8, active_low: bool) -> anyhow::Result<Self> {
-        let pin = RrppalGpio::new()
+        let pin = Self::pin(gpio)?.into_input_pullup();
+
+        Ok(RaspberryGpio {
+            active_low,
+            pin: Line::Input(pin),
+        })
+    }
+
+    /// Create a new [`RaspberryGpio`] output for the specified GPIO pin, starting disabled
+    pub fn try_new_output(gpio: u8, active_low: bool) -> anyhow::Result<Self> {
+        let pin = match super::level(GpioValue::Disabled, active_low) {
+            true => Self::pin(gpio)?.into_output_high(),
+            false => Self::pin(gpio)?.into_output_low(),
+        };
+
+        Ok(RaspberryGpio {
+            active_low,
+            pin: Line::Output(pin),
+        })
+    }
+
+    /// Whether the GPIO pin is an unused input, i.e. not driven as output nor taken by a
+    /// peripheral (SPI, I2C, UART...)
+    pub fn is_free(gpio: u8) -> bool {
+        Self::pin(gpio).is_ok_and(|pin| pin.mode() == Mode::Input)
+    }
+
+    fn pin(gpio: u8) -> anyhow::Result<rppal::gpio::Pin> {
+        RrppalGpio::new()
             .map_err(|e| anyhow::anyhow!("Failed to access GPIO: {}", e))?
             .get(gpio)
-            .map_err(|e| anyhow::anyhow!("Failed to get GPIO pin {}: {}", gpio, e))?
-            .into_input_pullup();
-
-        Ok(RaspberryGpio { active_low, pin })
+            .map_err(|e| anyhow::anyhow!("Failed to get GPIO pin {}: {}", gpio, e))
     }
 }
 
 impl Gpio for RaspberryGpio {
     fn read(&mut self) -> anyhow::Result<GpioValue> {
-        let value = self.pin.read();
-        trace!("Read GPIO {gpio} value: {value}", gpio = self.pin.pin());
+        let (gpio, value) = match &self.pin {
+            Line::Input(pin) => (pin.pin(), pin.read()),
+            Line::Output(pin) => (pin.pin(), Level::from(pin.is_set_high())),
+        };
+        trace!("Read GPIO {gpio} value: {value}");
         match (value, self.active_low) {
             (rppal::gpio::Level::Low, false) | (rppal::gpio::Level::High, true) => {
                 Ok(GpioValue::Disabled)
@@ -34,3 +68,39 @@ impl Gpio for RaspberryGpio {
         }
     }
 }
+
+impl GpioOut for RaspberryGpio {
+    fn write(&mut self, value: GpioValue) -> anyhow::Result<()> {
+        let Line::Output(pin) = &mut self.pin else {
+            anyhow::bail!("GPIO is configured as input");
+        };
+        trace!("Write GPIO {gpio} value: {value:?}", gpio = pin.pin());
+        pin.clear_pwm()
+            .map_err(|e| anyhow::anyhow!("Failed to stop PWM on GPIO {}: {}", pin.pin(), e))?;
+        match super::level(value, self.active_low) {
+            true => pin.set_high(),
+            false => pin.set_low(),
+        }
+
+        Ok(())
+    }
+
+    fn pwm(&mut self, frequency: f64, duty_cycle: f64) -> anyhow::Result<()> {
+        let Line::Output(pin) = &mut self.pin else {
+            anyhow::bail!("GPIO is configured as input");
+        };
+        let duty_cycle = duty_cycle.clamp(0.0, 1.0);
+        // active low lines are enabled while low
+        let duty_cycle = if self.active_low {
+            1.0 - duty_cycle
+        } else {
+            duty_cycle
+        };
+        trace!(
+            "PWM GPIO {gpio} at {frequency}Hz, duty cycle {duty_cycle}",
+            gpio = pin.pin()
+        );
+        pin.set_pwm_frequency(frequency, duty_cycle)
+            .map_err(|e| anyhow::anyhow!("Failed to start PWM on GPIO {}: {}", pin.pin(), e))
+    }
+}
@@ -0,0 +1,80 @@
+use std::fmt;
+use std::time::Duration;
+
+/// Statistics of the latency between a GPIO edge and the emission of the key event
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LatencyStats {
+    count: u32,
+    total: Duration,
+    min: Option<Duration>,
+    max: Option<Duration>,
+}
+
+impl LatencyStats {
+    /// Record a latency sample
+    pub fn record(&mut self, latency: Duration) {
+        self.count += 1;
+        self.total += latency;
+        self.min = Some(self.min.map_or(latency, |min| min.min(latency)));
+        self.max = Some(self.max.map_or(latency, |max| max.max(latency)));
+    }
+
+    /// Number of samples recorded
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    pub fn min(&self) -> Option<Duration> {
+        self.min
+    }
+
+    pub fn max(&self) -> Option<Duration> {
+        self.max
+    }
+
+    /// Average latency, if any sample has been recorded
+    pub fn avg(&self) -> Option<Duration> {
+        (self.count > 0).then(|| self.total / self.count)
+    }
+}
+
+impl fmt::Display for LatencyStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.min, self.avg(), self.max) {
+            (Some(min), Some(avg), Some(max)) => write!(
+                f,
+                "{} samples, min {:.1}ms, avg {:.1}ms, max {:.1}ms",
+                self.count,
+                min.as_secs_f64() * 1000.0,
+                avg.as_secs_f64() * 1000.0,
+                max.as_secs_f64() * 1000.0
+            ),
+            _ => write!(f, "no samples"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_should_compute_latency_stats() {
+        let mut stats = LatencyStats::default();
+        assert_eq!(stats.avg(), None);
+        assert_eq!(stats.to_string(), "no samples");
+
+        for ms in [12, 5, 30, 9] {
+            stats.record(Duration::from_millis(ms));
+        }
+        assert_eq!(stats.count(), 4);
+        assert_eq!(stats.min(), Some(Duration::from_millis(5)));
+        assert_eq!(stats.max(), Some(Duration::from_millis(30)));
+        assert_eq!(stats.avg(), Some(Duration::from_millis(14)));
+        assert_eq!(
+            stats.to_string(),
+            "4 samples, min 5.0ms, avg 14.0ms, max 30.0ms"
+        );
+    }
+}
@@ -0,0 +1,35 @@
+use std::process::Command;
+
+/// Power action run on the host system
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerAction {
+    Shutdown,
+    Reboot,
+}
+
+impl PowerAction {
+    /// Program and arguments running the action
+    pub fn command(&self) -> (&'static str, &'static [&'static str]) {
+        match self {
+            PowerAction::Shutdown => ("shutdown", &["-h", "now"]),
+            PowerAction::Reboot => ("shutdown", &["-r", "now"]),
+        }
+    }
+}
+
+/// Runs the [`PowerAction`]s, e.g. from the power switch or the menu
+pub trait PowerRunner {
+    fn run(&mut self, action: PowerAction) -> std::io::Result<()>;
+}
+
+/// Runs the [`PowerAction`]s with the system `shutdown` command
+#[derive(Debug, Default)]
+pub struct SystemPowerRunner;
+
+impl PowerRunner for SystemPowerRunner {
+    fn run(&mut self, action: PowerAction) -> std::io::Result<()> {
+        let (program, args) = action.command();
+        info!("Running {action:?}: {program} {}", args.join(" "));
+        Command::new(program).args(args).spawn().map(|_| ())
+    }
+}
@@ -0,0 +1,75 @@
+use std::time::{Duration, Instant};
+
+const WINDOW: Duration = Duration::from_secs(1);
+
+/// Caps the events a single key can emit per second, protecting the emulator from a flapping line.
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    max_per_sec: u32,
+    window_start: Option<Instant>,
+    count: u32,
+    dropped: u32,
+}
+
+impl RateLimiter {
+    /// Create a [`RateLimiter`] allowing at most `max_per_sec` events per second
+    pub fn new(max_per_sec: u32) -> Self {
+        Self {
+            max_per_sec,
+            window_start: None,
+            count: 0,
+            dropped: 0,
+        }
+    }
+
+    /// Whether an event emitted at `now` is allowed; excess events must be dropped
+    pub fn allow(&mut self, now: Instant) -> bool {
+        if self
+            .window_start
+            .is_none_or(|start| now.saturating_duration_since(start) >= WINDOW)
+        {
+            self.window_start = Some(now);
+            self.count = 0;
+            self.dropped = 0;
+        }
+        if self.count < self.max_per_sec {
+            self.count += 1;
+            true
+        } else {
+            self.dropped += 1;
+            false
+        }
+    }
+
+    /// Number of events dropped in the current window
+    pub fn dropped(&self) -> u32 {
+        self.dropped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_should_cap_events_per_second() {
+        let mut limiter = RateLimiter::new(20);
+        let start = Instant::now();
+        // a key flipping every millisecond for two seconds
+        let allowed: Vec<Instant> = (0..2000)
+            .map(|ms| start + Duration::from_millis(ms))
+            .filter(|now| limiter.allow(*now))
+            .collect();
+
+        assert_eq!(allowed.len(), 40);
+        for now in &allowed {
+            let in_second = allowed
+                .iter()
+                .filter(|t| **t >= *now && t.duration_since(*now) < WINDOW)
+                .count();
+            assert!(in_second <= 20);
+        }
+        assert_eq!(limiter.dropped(), 980);
+    }
+}
@@ -1,7 +1,9 @@
 use std::time::{Duration, Instant};
 
-use crate::input::config::{KeyConfig, RepeatConfig};
+use crate::input::config::{InitialState, KeyConfig, KeyMode, PowerSwitch, RepeatConfig};
+use crate::input::filter::MedianFilter;
 use crate::input::gpio::{Gpio, GpioValue};
+use crate::input::rate_limit::RateLimiter;
 
 /// Configuration for an individual key binding
 pub struct KeyState<GPIO>
@@ -12,6 +14,16 @@ where
     pub keycode: crate::KeypadKey,
     pub debounce: Duration,
     pub repeat: Option<RepeatConfig>,
+    pub filter: Option<MedianFilter>,
+    pub mode: KeyMode,
+    /// Whether a toggle key is currently latched on
+    pub latched: bool,
+    /// Cap of the events emitted per second, if any
+    pub rate_limit: Option<RateLimiter>,
+    /// Last raw value read from the gpio
+    last_value: Option<GpioValue>,
+    /// When the raw value last changed, if no event has been emitted for it yet
+    edge_at: Option<Instant>,
     pub state: State,
 }
 
@@ -35,7 +47,39 @@ where
         }
         self.state = new_state;
 
-        out_event
+        match self.mode {
+            KeyMode::Momentary => out_event,
+            KeyMode::Toggle => self.toggle(out_event),
+        }
+    }
+
+    /// Track the raw value read from the gpio, before any filtering, to time its edges
+    pub fn track_edge(&mut self, value: GpioValue) {
+        if self.last_value.is_some_and(|last| last != value) {
+            self.edge_at = Some(Instant::now());
+        }
+        self.last_value = Some(value);
+    }
+
+    /// Take the time of the last edge, to be called when its event is emitted
+    pub fn take_edge(&mut self) -> Option<Instant> {
+        self.edge_at.take()
+    }
+
+    /// Translate the physical [`OutEvent`] of a toggle key into the latched one
+    fn toggle(&mut self, out_event: OutEvent) -> OutEvent {
+        match out_event {
+            OutEvent::Press => {
+                self.latched = !self.latched;
+                if self.latched {
+                    OutEvent::Press
+                } else {
+                    OutEvent::Release
+                }
+            }
+            // physical releases and repeats don't change the latch
+            OutEvent::Release | OutEvent::Repeat | OutEvent::None => OutEvent::None,
+        }
     }
 }
 
@@ -49,6 +93,51 @@ where
             keycode: config.keycode,
             debounce: config.debounce,
             repeat: config.repeat,
+            filter: config.filter_samples.map(MedianFilter::new),
+            mode: config.mode,
+            latched: false,
+            rate_limit: None,
+            last_value: None,
+            edge_at: None,
+            state: State::initial(config.initial_state),
+        }
+    }
+}
+
+/// State of a power switch
+pub struct PowerSwitchState<GPIO>
+where
+    GPIO: Gpio,
+{
+    pub gpio: GPIO,
+    pub debounce: Duration,
+    pub state: State,
+}
+
+impl<G> PowerSwitchState<G>
+where
+    G: Gpio,
+{
+    /// Handle a value read from the gpio.
+    ///
+    /// Returns whether the switch has just been activated; a switch held active
+    /// doesn't trigger again until it's released.
+    pub fn handle_gpio_value(&mut self, value: GpioValue) -> bool {
+        let (new_state, out_event) = self.state.handle_gpio_value(value, None, self.debounce);
+        self.state = new_state;
+
+        out_event == OutEvent::Press
+    }
+}
+
+impl<GPIO> From<PowerSwitch<GPIO>> for PowerSwitchState<GPIO>
+where
+    GPIO: Gpio,
+{
+    fn from(config: PowerSwitch<GPIO>) -> Self {
+        PowerSwitchState {
+            gpio: config.gpio,
+            debounce: config.debounce,
             state: State::Unknown,
         }
     }
@@ -77,6 +166,16 @@ pub enum State {
 }
 
 impl State {
+    /// Get the state a key starts from, given its expected initial state
+    pub fn initial(initial_state: Option<InitialState>) -> Self {
+        let since = Instant::now();
+        match initial_state {
+            None => Self::Unknown,
+            Some(InitialState::Released) => Self::Released { since },
+            Some(InitialState::Pressed) => Self::Pressed { since },
+        }
+    }
+
     /// Handle an event and return the new state and the [`OutEvent`]
     ///
     /// This function works as a state machine transition handler.
@@ -351,6 +450,69 @@ mod tests {
         assert_eq!(out_event, OutEvent::None);
     }
 
+    #[test]
+    fn test_should_not_emit_event_on_first_poll_matching_initial_state() {
+        let debounce = Duration::from_millis(100);
+
+        let state = State::initial(Some(InitialState::Pressed));
+        let (new_state, out_event) = state.handle_gpio_value(GpioValue::Enabled, None, debounce);
+        assert!(
+            matches!(new_state, State::Pressed { .. }),
+            "Expected state to remain Pressed"
+        );
+        assert_eq!(out_event, OutEvent::None);
+
+        let state = State::initial(Some(InitialState::Released));
+        let (new_state, out_event) = state.handle_gpio_value(GpioValue::Disabled, None, debounce);
+        assert!(
+            matches!(new_state, State::Released { .. }),
+            "Expected state to remain Released"
+        );
+        assert_eq!(out_event, OutEvent::None);
+
+        assert_eq!(State::initial(None), State::Unknown);
+    }
+
+    #[test]
+    fn test_should_trigger_power_switch_once_while_held() {
+        let mut switch = PowerSwitchState::from(PowerSwitch {
+            gpio: crate::input::gpio::MockGpio::new(true, false),
+            debounce: Duration::ZERO,
+        });
+
+        let triggers = (0..100)
+            .filter(|_| switch.handle_gpio_value(GpioValue::Enabled))
+            .count();
+        assert_eq!(triggers, 1);
+
+        assert!(!switch.handle_gpio_value(GpioValue::Disabled));
+        assert!(switch.handle_gpio_value(GpioValue::Enabled));
+        assert!(!switch.handle_gpio_value(GpioValue::Enabled));
+    }
+
+    #[test]
+    fn test_should_latch_toggle_key() {
+        let mut key = KeyState::from(KeyConfig {
+            gpio: crate::input::gpio::MockGpio::new(true, false),
+            keycode: crate::KeypadKey::B,
+            debounce: Duration::ZERO,
+            repeat: None,
+            initial_state: Some(InitialState::Released),
+            filter_samples: None,
+            mode: KeyMode::Toggle,
+        });
+
+        // first activation holds the key
+        assert_eq!(key.handle_gpio_value(GpioValue::Enabled), OutEvent::Press);
+        assert_eq!(key.handle_gpio_value(GpioValue::Enabled), OutEvent::None);
+        // physical release is suppressed
+        assert_eq!(key.handle_gpio_value(GpioValue::Disabled), OutEvent::None);
+        // second activation releases it
+        assert_eq!(key.handle_gpio_value(GpioValue::Enabled), OutEvent::Release);
+        assert_eq!(key.handle_gpio_value(GpioValue::Disabled), OutEvent::None);
+        assert_eq!(key.handle_gpio_value(GpioValue::Enabled), OutEvent::Press);
+    }
+
     #[test]
     fn test_should_tell_whether_is_debounced() {
         let now = Instant::now();
@@ -8,7 +8,7 @@ pub struct Keypad {
     pub interrupt: u8,
 }
 
-#[derive(Copy, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Copy, Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum KeypadKey {
     Right,
     Left,
@@ -57,6 +57,23 @@ impl Keypad {
         self.data = (self.data & 0xF0) | new_values;
     }
 
+    /// Keys currently held down
+    pub fn pressed(&self) -> Vec<KeypadKey> {
+        use KeypadKey::*;
+        [Right, Left, Up, Down]
+            .into_iter()
+            .enumerate()
+            .filter(|(bit, _)| self.row0 & (1 << bit) == 0)
+            .chain(
+                [A, B, Select, Start]
+                    .into_iter()
+                    .enumerate()
+                    .filter(|(bit, _)| self.row1 & (1 << bit) == 0),
+            )
+            .map(|(_, key)| key)
+            .collect()
+    }
+
     pub fn keydown(&mut self, key: KeypadKey) {
         match key {
             KeypadKey::Right => self.row0 &= !(1 << 0),
@@ -6,6 +6,8 @@ extern crate log;
 
 pub use crate::gpu::{SCREEN_H, SCREEN_W};
 pub use crate::keypad::KeypadKey;
+pub use crate::mbc::{battery_save_path, battery_save_slot_path};
+pub use crate::printer::{Dithering, PrintOptions};
 pub use crate::serial::SerialCallback;
 pub use crate::sound::AudioPlayer;
 
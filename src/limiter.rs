@@ -0,0 +1,59 @@
+/// Soft clipping limiter for the audio output.
+///
+/// Samples within the threshold pass unchanged; above it they are bent with a tanh curve
+/// toward ±1.0, so boosted audio saturates smoothly instead of clipping hard.
+#[derive(Debug, Clone, Copy)]
+pub struct SoftLimiter {
+    threshold: f32,
+}
+
+impl SoftLimiter {
+    /// Create a [`SoftLimiter`] engaging above `threshold`, clamped to `0.0..1.0`
+    pub fn new(threshold: f32) -> Self {
+        Self {
+            threshold: threshold.clamp(0.0, 0.99),
+        }
+    }
+
+    /// Limit a sample to ±1.0
+    pub fn limit(&self, sample: f32) -> f32 {
+        let level = sample.abs();
+        if level <= self.threshold {
+            return sample;
+        }
+        let headroom = 1.0 - self.threshold;
+        let limited = self.threshold + headroom * ((level - self.threshold) / headroom).tanh();
+        limited.copysign(sample)
+    }
+
+    /// Limit both channels of the frames
+    pub fn apply(&self, frames: &mut [(f32, f32)]) {
+        for (left, right) in frames {
+            *left = self.limit(*left);
+            *right = self.limit(*right);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_should_soft_limit_over_unity_samples() {
+        let limiter = SoftLimiter::new(0.8);
+        assert_eq!(limiter.limit(0.5), 0.5);
+        assert_eq!(limiter.limit(-0.8), -0.8);
+
+        let limited = limiter.limit(1.5);
+        assert!(limited > 0.8 && limited < 1.0);
+        // a hard clip would flatten both to 1.0
+        assert!(limiter.limit(1.2) < limited);
+        assert!(limiter.limit(100.0) <= 1.0);
+
+        let mut frames = [(1.5, -1.5)];
+        limiter.apply(&mut frames);
+        assert_eq!(frames, [(limited, -limited)]);
+    }
+}
@@ -1,103 +1,671 @@
 #[macro_use]
 extern crate log;
 
+mod affinity;
+mod analog_speed;
 mod app_config;
 mod args;
+mod attract;
+mod audio_device;
+mod battery;
+mod cheats;
+mod debug_overlay;
+mod frame_pipe;
+mod frame_sender;
+mod hotkey;
+mod idle_dim;
+mod limiter;
+mod memory_guard;
 mod menu;
-
+mod osd;
+mod overlay;
+mod pacing;
+mod palette;
+mod picture;
+mod prefill;
+mod preflight;
+mod profile;
+mod retry;
+mod rotation;
+mod rumble;
+mod save_state;
+mod saves;
+mod screenshot;
+mod session;
+mod session_bundle;
+mod splash;
+mod status_display;
+mod status_socket;
+mod stdin_input;
+mod supervisor;
+mod test_card;
+#[cfg(test)]
+mod test_util;
+mod underrun;
+mod volume;
+mod wizard;
+
+use std::collections::HashMap;
+use std::io::Write as _;
 use std::path::{Path, PathBuf};
 use std::rc::Rc;
 use std::sync::atomic::AtomicBool;
-use std::sync::mpsc::{self, Receiver, Sender, SyncSender, TryRecvError, TrySendError};
-use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender, SyncSender, TryRecvError};
+use std::sync::{Arc, Mutex, OnceLock};
 use std::thread;
 use std::thread::JoinHandle;
 
-use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::traits::{DeviceTrait, StreamTrait};
 use cpal::{FromSample, Sample};
-use rboy::device::Device;
-use rboy::framebuffer::{Framebuffer, FramebufferConfig};
+use rboy::device::{Device, StateError};
+use rboy::framebuffer::{
+    ColorProfile, Framebuffer, FramebufferConfig, GLYPH_SIZE, PixelFormat, ScaleContext,
+    ScalingMode,
+};
 use rboy::input::gpio::RaspberryGpio;
-use rboy::input::{InputListener, InputListenerConfig, KeyConfig, KeyEvent, PowerSwitch};
-
-use self::app_config::AppConfig;
+use rboy::input::{
+    InputListener, InputListenerConfig, KeyConfig, KeyEvent, LatencyStats, PowerSwitch,
+};
+
+use self::app_config::{AppConfig, AudioConfig, GpioInitConfig};
+use self::attract::{AttractTimer, Replay, Trigger};
+use self::battery::BatteryMonitor;
+use self::cheats::Cheats;
+use self::debug_overlay::DebugOverlay;
+use self::frame_pipe::FramePipe;
+use self::frame_sender::{Frame, FrameClock, FrameSender};
+use self::hotkey::Hotkeys;
+use self::idle_dim::{IdleDimmer, IdleStage};
+use self::limiter::SoftLimiter;
+use self::menu::{GameCache, Launch, LaunchMode, LaunchOptions, Platform};
+use self::osd::Osd;
+use self::overlay::{
+    CheatsPage, CheatsPick, Overlay, OverlayAction, Setting, SettingsPage, SettingsPick, SlotMode,
+    SlotPick, SlotPicker,
+};
+use self::pacing::{
+    AutoFrameSkip, BatchOutcome, BootTurbo, CycleBudget, SpeedPercent, TurboChange,
+};
+use self::palette::PaletteCycle;
+use self::picture::PictureSettings;
+use self::prefill::Prefill;
+use self::profile::ActiveProfile;
+use self::rumble::{GpioMotor, Rumble};
+use self::session::{Boot, Session};
+use self::session_bundle::{InputRecorder, SessionBundle};
+use self::status_display::StatusDisplay;
+use self::status_socket::{Status, StatusServer};
+use self::underrun::UnderrunFade;
+use self::volume::Volume;
+
+/// Interval between the checks for a change of the framebuffer geometry
+const GEOMETRY_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+/// Interval between the reports of the frames dropped because the display can't keep up
+const DROPPED_FRAMES_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(10);
+/// Interval between two readings of the battery charge
+const BATTERY_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// Number of input events retained for debugging
+const INPUT_EVENT_LOG_SIZE: usize = 64;
+
+/// Set on SIGUSR1 to request a dump of the input event log
+static DUMP_INPUT_EVENT_LOG: OnceLock<Arc<AtomicBool>> = OnceLock::new();
+/// When set, the CPU thread prints the device state to stdout and clears the flag
+static DUMP_DEVICE_STATE: AtomicBool = AtomicBool::new(false);
+/// Input latency statistics, recorded only while measuring the input latency
+static INPUT_LATENCY: OnceLock<Arc<Mutex<LatencyStats>>> = OnceLock::new();
 
 enum GBEvent {
     KeyUp(rboy::KeypadKey),
     KeyDown(rboy::KeypadKey),
+    /// Stop running the CPU until [`GBEvent::Resume`] is received
+    Pause,
+    Resume,
+    /// Write the state of the device to the given file and report the outcome
+    SaveState {
+        path: PathBuf,
+        outcome: Sender<rboy::StrResult<()>>,
+    },
+    /// Replace the state of the device with the one in the given file and report the outcome;
+    /// if `backup` is set, a state which can't be loaded is moved aside
+    LoadState {
+        path: PathBuf,
+        backup: bool,
+        outcome: Sender<Result<(), StateError>>,
+    },
+    /// Run the CPU at the given multiple of its normal speed
+    Speed(f32),
+    /// Never run the CPU faster than the given multiple of its normal speed, or remove the cap
+    MaxSpeed(Option<f32>),
+    /// Write these (address, value) on every frame, replacing the previous cheats
+    Cheats(Vec<(u16, u8)>),
+    /// Pulse the rumble motor for the given length on the presses of these keys
+    Haptics(HashMap<rboy::KeypadKey, std::time::Duration>),
+}
+
+/// Emulator options taken from the command line
+#[derive(Debug, Clone, Default)]
+struct EmulatorOptions {
+    /// Name of the audio output device
+    audio_device: Option<String>,
+    /// Seed for the power-on RAM contents
+    seed: Option<u32>,
+    /// Frames buffered between the CPU and the display
+    video_queue: usize,
+    /// Longest wait for a new frame in the display loop
+    display_poll_ms: u64,
+    /// Most frames skipped in a row while the emulation falls behind
+    max_frameskip: u32,
+    /// Show the debug overlay when the game starts
+    show_fps: bool,
+    /// Configuration file, saved in the session bundles
+    config_file: PathBuf,
+    /// Record the inputs of the games to a session bundle at this path
+    record_session: Option<PathBuf>,
+    /// Length of the fade to silence on audio underruns
+    underrun_fade_ms: u64,
+    /// Status reported on the status socket, if enabled
+    status: Option<Arc<Mutex<Status>>>,
+    /// Named pipe the frames are also written to, if enabled
+    frame_pipe: Option<FramePipe>,
+    /// Correct the colors of the color games to look like on a CGB screen, unless their platform overrides it
+    cgb_color_correct: bool,
+}
+
+impl EmulatorOptions {
+    /// Options given on the command line; the seed, the status and the frame pipe are set up apart
+    fn from_args(args: &args::Args) -> Self {
+        Self {
+            audio_device: args.audio_device.clone(),
+            seed: args.seed,
+            video_queue: args.video_queue,
+            display_poll_ms: args.display_poll_ms,
+            max_frameskip: args.max_frameskip,
+            show_fps: args.show_fps,
+            config_file: args.config.clone(),
+            record_session: args.record_session.clone(),
+            underrun_fade_ms: args.underrun_fade_ms,
+            status: None,
+            frame_pipe: None,
+            cgb_color_correct: !args.no_cgb_color_correct,
+        }
+    }
 }
 
 /// The Application state.
 #[derive(Debug, Clone)]
 enum AppState {
+    /// Play the attract mode demo
+    Attract {
+        config: Rc<AppConfig>,
+    },
+    /// Play back a session bundle
+    Playback {
+        config: Rc<AppConfig>,
+        rom_file: PathBuf,
+        mode: LaunchMode,
+        demo: Demo,
+    },
+    Calibrate {
+        config: Rc<AppConfig>,
+    },
     Emulator {
         config: Rc<AppConfig>,
         rom_file: PathBuf,
+        mode: LaunchMode,
+        /// Battery save file the game uses
+        save_slot: usize,
+        /// Continue from the resume state of the game, see [`save_state::resume_path`]
+        resume: bool,
     },
     Menu {
         config: Rc<AppConfig>,
     },
+    TestCard {
+        config: Rc<AppConfig>,
+    },
+    LatencyTest {
+        config: Rc<AppConfig>,
+    },
     Exit,
 }
 
 fn main() -> anyhow::Result<()> {
+    let started = std::time::Instant::now();
     let args: args::Args = argh::from_env();
 
     init_app_log(args.log_level)?;
     info!("rboy-lego starting...");
 
-    // read config
-    let config = Rc::new(AppConfig::load_from_file(&args.config)?);
-    log_config(&config);
+    if args.list_audio_devices {
+        for name in audio_device::output_device_names()? {
+            println!("{name}");
+        }
+        return Ok(());
+    }
+
+    if args.dry_run {
+        let problems = preflight::run(&args.config, &args.framebuffer_path);
+        for problem in &problems {
+            error!("{problem}");
+        }
+        if !problems.is_empty() {
+            anyhow::bail!("Dry run found {} problem(s)", problems.len());
+        }
+        info!("Dry run completed; no problems found");
+        return Ok(());
+    }
 
     // open framebuffer
-    debug!("Opening framebuffer...");
+    let pixel_format = match args.pixel_format {
+        Some(pixel_format) => pixel_format,
+        None => PixelFormat::from_bytes_per_pixel(args.bytes_per_pixel).ok_or_else(|| {
+            anyhow::anyhow!(
+                "Unsupported framebuffer depth: {} bytes per pixel",
+                args.bytes_per_pixel
+            )
+        })?,
+    };
+    debug!("Opening framebuffer with pixel format {pixel_format}...");
     let framebuffer = Rc::new(Framebuffer::new(FramebufferConfig {
-        path: args.framebuffer_path,
+        path: args.framebuffer_path.clone(),
         width: args.width,
         height: args.height,
-        bytes_per_pixel: args.bytes_per_pixel,
+        pixel_format,
         stride_pixels: args.stride_pixels,
     })?);
     info!("Framebuffer opened.");
+    framebuffer.check_stride();
+    framebuffer.set_dither(args.dither);
+    if let Some(path) = args.color_profile.as_deref().filter(|_| !args.safe_mode) {
+        match ColorProfile::load(path) {
+            Ok(profile) => {
+                framebuffer.set_color_profile(Some(profile));
+                info!("Color profile loaded from {}", path.display());
+            }
+            Err(err) => warn!("Color profile disabled: {err}"),
+        }
+    }
+    if args.fit_width {
+        framebuffer.set_scaling(ScalingMode::FitBoth);
+    }
 
-    // init state
-    let mut app_state = match &args.rom_path {
-        Some(rom_path) => AppState::Emulator {
-            config: config.clone(),
-            rom_file: rom_path.clone(),
-        },
-        None => AppState::Menu {
-            config: config.clone(),
-        },
-    };
-    debug!("Initial AppState: {app_state:?}",);
-
-    // setup control c handler
+    // stop cleanly on SIGINT, SIGTERM (e.g. `systemctl stop`) and SIGHUP
     let exit = Arc::new(AtomicBool::new(false));
     {
         let exit = exit.clone();
         ctrlc::set_handler(move || {
             exit.store(true, std::sync::atomic::Ordering::SeqCst);
         })
-        .expect("Error setting Ctrl-C handler");
+        .expect("Error setting the shutdown signal handler");
+    }
+
+    // read config; on first boot, the setup writes it
+    if !args.config.exists() && !args.no_gpio && !args.safe_mode {
+        wizard::run(&framebuffer, &args.config, &exit)?;
+    }
+    let mut config = AppConfig::load_from_file(&args.config)?;
+    config.no_gpio |= args.no_gpio;
+    if args.safe_mode {
+        warn!("Safe mode: optional subsystems disabled");
+        config.safe_mode();
     }
+    let config = Rc::new(config);
+    log_config(&config);
+    framebuffer.set_overlay_alpha(config.overlay_alpha);
+    framebuffer.set_invert_colors(config.invert_colors);
+
+    let session = Session::new(config.roms_directory());
+    let crashed = session.start();
+    if crashed {
+        warn!("The previous session didn't shut down cleanly");
+    }
+    let boot = session::boot_decision(config.boot_last_game, crashed, session.last_game());
+    let mut notice =
+        matches!(boot, Boot::Menu { crashed: true }).then_some("The last session crashed");
+
+    // play back a session bundle with its own configuration
+    let playback = args
+        .play_session
+        .as_deref()
+        .map(SessionBundle::load)
+        .transpose()?;
+    let seed = playback
+        .as_ref()
+        .and_then(|bundle| bundle.seed)
+        .or(args.seed);
+    let playback = playback
+        .map(|bundle| {
+            let candidates: Vec<PathBuf> = args.rom_path.iter().cloned().collect();
+            Ok::<_, anyhow::Error>(AppState::Playback {
+                config: Rc::new(bundle.app_config()?),
+                rom_file: bundle.find_rom(&candidates, config.roms_directory())?,
+                mode: bundle.mode,
+                demo: Demo {
+                    replay: bundle.replay()?,
+                    ram: bundle.ram()?,
+                },
+            })
+        })
+        .transpose()?;
+
+    // init state; without a ROM, boot into the menu (or the last game, if configured)
+    let mut app_state = initial_state(&args, &config, playback, boot);
+    debug!("Initial AppState: {app_state:?}",);
+
+    // dump the input event log on SIGUSR1
+    DUMP_INPUT_EVENT_LOG.get_or_init(Default::default);
+    unsafe {
+        libc::signal(libc::SIGUSR1, on_sigusr1 as *const () as libc::sighandler_t);
+    }
+
+    let status_socket = args.status_socket.as_deref().filter(|_| !args.safe_mode);
+    let status_server = status_socket.and_then(|path| {
+        let server =
+            StatusServer::new(config.battery.as_ref().map(|b| b.capacity.clone()), started);
+        let status = server.status();
+        match server.spawn(path, exit.clone()) {
+            Ok(thread) => Some((status, thread)),
+            Err(err) => {
+                warn!("Status socket disabled: {err}");
+                None
+            }
+        }
+    });
+
+    let frame_pipe = args
+        .frame_pipe
+        .as_deref()
+        .filter(|_| !args.safe_mode)
+        .and_then(|path| match FramePipe::spawn(path, exit.clone()) {
+            Ok(pipe) => Some(pipe),
+            Err(err) => {
+                warn!("Frame pipe disabled: {err}");
+                None
+            }
+        });
+
+    let options = EmulatorOptions {
+        seed,
+        status: status_server.as_ref().map(|(status, _)| status.clone()),
+        frame_pipe: frame_pipe.as_ref().map(|(pipe, _)| pipe.clone()),
+        ..EmulatorOptions::from_args(&args)
+    };
+
+    let status_display = config.status_display.as_ref().and_then(|status| {
+        let battery = config.battery.as_ref().map(|b| b.capacity.clone());
+        match StatusDisplay::open(status, battery) {
+            Ok(display) => Some(display),
+            Err(err) => {
+                warn!("Status display disabled: {err}");
+                None
+            }
+        }
+    });
 
     // loop through state machine
 
     loop {
+        app_state = next_state(app_state, &exit);
+        if let (Some(display), Some(title)) = (&status_display, status_title(&app_state)) {
+            display.update(&title);
+        }
+        if let Some(Ok(mut status)) = options.status.as_ref().map(|s| s.lock()) {
+            *status = Status {
+                game: status_title(&app_state)
+                    .filter(|_| matches!(app_state, AppState::Emulator { .. })),
+                fps: None,
+            };
+        }
+        // each game inherits the display defaults of its platform
+        let display = config.display(
+            state_platform(&config, &app_state),
+            options.cgb_color_correct,
+        );
+        framebuffer.set_invert_colors(display.invert_colors);
         app_state = match app_state {
-            AppState::Emulator { config, rom_file } => {
-                run_emulator(&rom_file, config, framebuffer.clone(), exit.clone())?
+            AppState::Attract { config } => {
+                run_attract(config, framebuffer.clone(), exit.clone(), &options)?
+            }
+            AppState::Playback {
+                config,
+                rom_file,
+                mode,
+                demo,
+            } => {
+                info!("Playing back the session of {}", rom_file.display());
+                run_emulator(
+                    &rom_file,
+                    Launch::new(mode),
+                    config,
+                    framebuffer.clone(),
+                    exit.clone(),
+                    &options,
+                    Some(demo),
+                )?
+            }
+            AppState::Calibrate { config } => {
+                run_calibration(config, framebuffer.clone(), exit.clone())?
+            }
+            AppState::Emulator {
+                config,
+                rom_file,
+                mode,
+                save_slot,
+                resume,
+            } => {
+                session.set_last_game(&rom_file);
+                run_emulator(
+                    &rom_file,
+                    Launch {
+                        mode,
+                        save_slot,
+                        resume,
+                    },
+                    config,
+                    framebuffer.clone(),
+                    exit.clone(),
+                    &options,
+                    None,
+                )?
+            }
+            AppState::Menu { config } => {
+                run_menu(config, framebuffer.clone(), exit.clone(), notice.take())?
+            }
+            AppState::TestCard { config } => {
+                run_test_card(config, framebuffer.clone(), exit.clone())?
+            }
+            AppState::LatencyTest { config } => {
+                run_latency_test(config, framebuffer.clone(), exit.clone())?
             }
-            AppState::Menu { config } => run_menu(config, framebuffer.clone(), exit.clone())?,
             AppState::Exit => break,
         };
         debug!("New AppState: {app_state:?}",);
     }
 
-    // zero fb
+    // zero fb; inverted, black would leave the panel white
+    framebuffer.set_invert_colors(false);
     framebuffer.zero();
+    if let Some(display) = &status_display {
+        display.clear();
+    }
+    session.end();
+    if let Some((_, thread)) = status_server {
+        exit.store(true, std::sync::atomic::Ordering::SeqCst);
+        let _ = thread.join();
+    }
+    if let Some((_, thread)) = frame_pipe {
+        exit.store(true, std::sync::atomic::Ordering::SeqCst);
+        let _ = thread.join();
+    }
+    // don't lose the output a harness is waiting for
+    let _ = std::io::stdout().flush();
+    // the save files must reach the storage before the power is cut
+    debug!("Syncing filesystems...");
+    unsafe { libc::sync() };
+
+    Ok(())
+}
+
+/// The state to run next: [`AppState::Exit`] once a shutdown signal was received, so the
+/// saves are flushed and the GPIO released on the way out
+fn next_state(state: AppState, exit: &AtomicBool) -> AppState {
+    if exit.load(std::sync::atomic::Ordering::SeqCst) {
+        AppState::Exit
+    } else {
+        state
+    }
+}
+
+/// Platform of the game run in the given state, if any
+fn state_platform(config: &AppConfig, state: &AppState) -> Option<Platform> {
+    match state {
+        AppState::Emulator { rom_file, .. } | AppState::Playback { rom_file, .. } => {
+            rom_platform(config, rom_file)
+        }
+        _ => None,
+    }
+}
+
+/// [`Platform`] of the given ROM, by its extension
+fn rom_platform(config: &AppConfig, rom_file: &Path) -> Option<Platform> {
+    rom_file
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .and_then(|ext| config.rom_platform(ext))
+}
+
+/// Whether the colors of the game in `rom_file` are corrected to look like on a CGB screen
+fn cgb_color_correction(config: &AppConfig, rom_file: &Path, options: &EmulatorOptions) -> bool {
+    config
+        .display(rom_platform(config, rom_file), options.cgb_color_correct)
+        .cgb_color_correct
+}
+
+/// Title shown on the status display for the given state, if it has one
+fn status_title(state: &AppState) -> Option<String> {
+    match state {
+        AppState::Emulator { rom_file, .. } => Some(
+            rom_file
+                .file_stem()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+        ),
+        AppState::Menu { .. } => Some("Menu".to_string()),
+        AppState::Attract { .. } => Some("Demo".to_string()),
+        AppState::Playback { .. } => Some("Playback".to_string()),
+        _ => None,
+    }
+}
+
+fn run_calibration(
+    config: Rc<AppConfig>,
+    framebuffer: Rc<Framebuffer>,
+    exit: Arc<AtomicBool>,
+) -> anyhow::Result<AppState> {
+    info!(
+        "Calibrating framebuffer {width}x{height} with stride {stride} pixels; press any key to exit",
+        width = framebuffer.width(),
+        height = framebuffer.height(),
+        stride = framebuffer.stride_pixels(),
+    );
+    framebuffer.draw_test_pattern();
+    framebuffer.check_stride();
+
+    wait_any_key(&config, &exit)?;
+    info!("Calibration confirmed");
+
+    Ok(AppState::Exit)
+}
+
+fn run_test_card(
+    config: Rc<AppConfig>,
+    framebuffer: Rc<Framebuffer>,
+    exit: Arc<AtomicBool>,
+) -> anyhow::Result<AppState> {
+    info!("Showing test card; press any key to exit");
+    test_card::draw(&framebuffer);
+
+    wait_any_key(&config, &exit)?;
+
+    Ok(AppState::Exit)
+}
+
+/// Report the latency from the GPIO edges to the key events until exit is requested
+fn run_latency_test(
+    config: Rc<AppConfig>,
+    framebuffer: Rc<Framebuffer>,
+    exit: Arc<AtomicBool>,
+) -> anyhow::Result<AppState> {
+    if config.no_gpio {
+        anyhow::bail!("The input latency can be measured only on GPIO input");
+    }
+    info!("Measuring the input latency; press the keys, then Ctrl-C to exit");
+    let stats = INPUT_LATENCY.get_or_init(Default::default).clone();
+    let draw_report = |report: &str| {
+        framebuffer.zero();
+        framebuffer.draw_text("Input latency", GLYPH_SIZE, GLYPH_SIZE, false, 0xffff);
+        framebuffer.draw_text(report, GLYPH_SIZE, GLYPH_SIZE * 3, false, 0xffff);
+    };
+    draw_report("Press the keys");
+
+    let (keyboard_event_sender, keyboard_event_receiver) = mpsc::channel();
+    let input_listener_exit = Arc::new(AtomicBool::new(false));
+    let input_listener_thread = run_input_listener(
+        &config,
+        input_listener_exit.clone(),
+        keyboard_event_sender,
+        false,
+    )?;
+
+    while !exit.load(std::sync::atomic::Ordering::SeqCst) {
+        match keyboard_event_receiver.recv_timeout(std::time::Duration::from_millis(50)) {
+            Ok((event, key)) => {
+                let report = stats.lock().map(|s| s.to_string()).unwrap_or_default();
+                info!("Key {key:?} {event:?}; input latency: {report}");
+                draw_report(&report);
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) => continue,
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+        }
+    }
+
+    input_listener_exit.store(true, std::sync::atomic::Ordering::SeqCst);
+    let _ = input_listener_thread.join();
+    if let Ok(stats) = stats.lock() {
+        info!("Input latency: {stats}");
+    }
+
+    Ok(AppState::Exit)
+}
+
+/// Block until any key is pressed or exit is requested
+fn wait_any_key(config: &AppConfig, exit: &AtomicBool) -> anyhow::Result<()> {
+    // run input listener
+    let (keyboard_event_sender, keyboard_event_receiver) = mpsc::channel();
+
+    let input_listener_exit = Arc::new(AtomicBool::new(false));
+    let input_listener_thread = run_input_listener(
+        config,
+        input_listener_exit.clone(),
+        keyboard_event_sender,
+        false,
+    )?;
+
+    while !exit.load(std::sync::atomic::Ordering::SeqCst) {
+        match keyboard_event_receiver.recv_timeout(std::time::Duration::from_millis(50)) {
+            Ok((KeyEvent::Down, key)) => {
+                debug!("Key {key:?} pressed");
+                break;
+            }
+            Ok(_) | Err(mpsc::RecvTimeoutError::Timeout) => continue,
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+        }
+    }
+
+    // stop input listener
+    input_listener_exit.store(true, std::sync::atomic::Ordering::SeqCst);
+    let _ = input_listener_thread.join();
 
     Ok(())
 }
@@ -106,16 +674,23 @@ fn run_menu(
     config: Rc<AppConfig>,
     framebuffer: Rc<Framebuffer>,
     exit: Arc<AtomicBool>,
+    notice: Option<&str>,
 ) -> anyhow::Result<AppState> {
     // run input listener
     let (keyboard_event_sender, keyboard_event_receiver) = mpsc::channel();
 
     let input_listener_exit = Arc::new(AtomicBool::new(false));
-    let input_listener_thread =
-        run_input_listener(&config, input_listener_exit.clone(), keyboard_event_sender);
+    let input_listener_thread = run_input_listener(
+        &config,
+        input_listener_exit.clone(),
+        keyboard_event_sender,
+        true,
+    )?;
 
     // run menu
-    let res = menu::AppMenu::new(config, framebuffer, exit, keyboard_event_receiver)?.run();
+    let res = menu::AppMenu::new(config, framebuffer, exit, keyboard_event_receiver)?
+        .with_notice(notice)
+        .run();
     // stop input listener
     input_listener_exit.store(true, std::sync::atomic::Ordering::SeqCst);
     let _ = input_listener_thread.join();
@@ -125,57 +700,784 @@ fn run_menu(
     res
 }
 
+/// Play the attract mode demo, returning to the menu when it ends or on any input
+fn run_attract(
+    config: Rc<AppConfig>,
+    framebuffer: Rc<Framebuffer>,
+    exit: Arc<AtomicBool>,
+    options: &EmulatorOptions,
+) -> anyhow::Result<AppState> {
+    let Some(attract) = &config.attract else {
+        return Ok(AppState::Menu { config });
+    };
+    let rom_file = config.roms_directory().join(&attract.game);
+    let replay = match Replay::load(&config.roms_directory().join(&attract.replay)) {
+        Ok(replay) => replay,
+        Err(err) => {
+            error!("Failed to load attract mode replay: {err}");
+            return Ok(AppState::Menu { config });
+        }
+    };
+    info!("Starting attract mode demo of {}", rom_file.display());
+
+    match run_emulator(
+        &rom_file,
+        Launch::new(LaunchMode::Color),
+        config.clone(),
+        framebuffer,
+        exit,
+        options,
+        Some(Demo { replay, ram: None }),
+    ) {
+        Ok(state) => Ok(state),
+        Err(err) => {
+            error!("Attract mode demo failed: {err:#}");
+            Ok(AppState::Menu { config })
+        }
+    }
+}
+
+/// Run a game until the user quits it.
+///
+/// If `demo` is set, the game is driven by its replay, stopped by any real input.
 fn run_emulator(
     rom_file: &Path,
+    launch: Launch,
     config: Rc<AppConfig>,
     framebuffer: Rc<Framebuffer>,
     exit: Arc<AtomicBool>,
+    options: &EmulatorOptions,
+    demo: Option<Demo>,
 ) -> anyhow::Result<AppState> {
     info!("Starting emulator with ROM: {}", rom_file.display());
     // zero framebuffer
     framebuffer.zero();
     debug!("Framebuffer zeroed.");
 
-    let cpu = construct_cpu(rom_file, false, false, None);
+    // the state slots are always available from the in-game menu
+    if let Err(message) = memory_guard::check(
+        rom_file,
+        options.video_queue,
+        true,
+        config.min_free_memory(),
+    ) {
+        error!("Refusing to start {}: {message}", rom_file.display());
+        framebuffer.draw_text(&message, GLYPH_SIZE, GLYPH_SIZE, false, 0xffff);
+        framebuffer.draw_text("Press any key", GLYPH_SIZE, GLYPH_SIZE * 3, false, 0xffff);
+        wait_any_key(&config, &exit)?;
+        framebuffer.zero();
+        return Ok(AppState::Menu { config });
+    }
+
+    let Launch {
+        mut mode,
+        save_slot,
+        resume,
+    } = launch;
+    // demos play on a scratch copy, leaving the save of the player untouched
+    let (replay, save) = match demo {
+        Some(Demo { replay, ram }) => (Some(replay), BatterySave::Scratch(ram)),
+        None => (None, BatterySave::Slot(save_slot)),
+    };
+    let mut cpu = construct_cpu(
+        rom_file,
+        mode == LaunchMode::Classic,
+        &save,
+        false,
+        None,
+        &config,
+    );
+    if cpu.is_none() && mode == LaunchMode::Classic {
+        warn!("Could not start the game in classic mode; falling back to color mode");
+        mode = LaunchMode::Color;
+        cpu = construct_cpu(rom_file, false, &save, false, None, &config);
+    }
 
     let Some(mut cpu) = cpu else {
         return Err(anyhow::anyhow!("Could not construct CPU"));
     };
     debug!("CPU constructed");
+    let resume_path = save_state::resume_path(rom_file);
+    let resumed =
+        resume && resume_path.is_file() && save_state::load(&mut cpu, &resume_path, false).is_ok();
+    cpu.set_cgb_color_correction(cgb_color_correction(&config, rom_file, options));
+    // demos don't touch the options the user plays the game with
+    let mut game_cache = (config.remember_launch_options && replay.is_none())
+        .then(|| GameCache::load(config.roms_directory()));
+    let remembered = game_cache
+        .as_ref()
+        .and_then(|cache| cache.launch_options(rom_file))
+        .cloned();
+    let remembered_palette = remembered
+        .as_ref()
+        .and_then(|options| options.palette.clone());
+    let mut picture = config
+        .settings_directory()
+        .map(PictureSettings::load)
+        .unwrap_or_default();
+    picture.clamp(&config.picture);
+    if let Some(scaling) = picture.scaling {
+        framebuffer.set_scaling(scaling);
+    }
+    framebuffer.set_scanlines(picture.scanlines);
+    // the scaling of a remembered game is restored once it stops
+    let mut scaling = framebuffer.scaling();
+    if let Some(remembered) = remembered.as_ref().and_then(|options| options.scaling) {
+        framebuffer.set_scaling(remembered);
+    }
+    let mut palette = cpu.is_classic_game().then(|| {
+        PaletteCycle::new(
+            remembered_palette
+                .as_deref()
+                .or(config.dmg_palette.as_deref()),
+        )
+    });
+    framebuffer.set_palette(palette.as_ref().map(PaletteCycle::current));
+    if let Some(seed) = options.seed {
+        info!("Seeding RAM with {seed}");
+        cpu.seed(seed);
+    }
+    if let Some(printer) = config.printer {
+        info!("Attaching printer: {printer:?}");
+        cpu.attach_printer_with(printer);
+    }
 
-    let cpal_audio_stream;
-
-    let player = CpalPlayer::get();
-    debug!("Audio player initialized: {}", player.is_some());
-    match player {
-        Some((v, s)) => {
-            cpu.enable_audio(Box::new(v) as Box<dyn rboy::AudioPlayer>, false);
-            debug!("Audio enabled on CPU");
-            cpal_audio_stream = Some(s);
-        }
-        None => {
-            anyhow::bail!("Could not initialize audio device");
+    let mut cpal_audio_stream = None;
+
+    let volume = config
+        .settings_directory()
+        .map(Volume::load)
+        .unwrap_or_default();
+    if config.audio.enabled {
+        let player = CpalPlayer::get(
+            options.audio_device.as_deref(),
+            options.underrun_fade_ms,
+            volume.clone(),
+            &config.audio,
+        );
+        debug!("Audio player initialized: {}", player.is_some());
+        match player {
+            Some((v, s)) => {
+                cpu.enable_audio(Box::new(v) as Box<dyn rboy::AudioPlayer>, false);
+                debug!("Audio enabled on CPU");
+                cpal_audio_stream = Some(s);
+            }
+            None => {
+                anyhow::bail!("Could not initialize audio device");
+            }
         }
+    } else {
+        info!("Audio disabled");
     }
     let (gb_event_sender, gb_event_receiver) = mpsc::channel();
-    let (video_sender, video_receiver) = mpsc::sync_channel(1);
+    let (video_sender, video_receiver) = video_channel(options.video_queue);
+    let video_sender = FrameSender::new(video_sender);
+    let dropped_frames = video_sender.dropped();
+    let display_poll = std::time::Duration::from_millis(options.display_poll_ms);
 
     debug!("Starting CPU thread");
-    let cpu_thread = thread::spawn(move || run_cpu(cpu, video_sender, gb_event_receiver));
+    let mut attract = replay.as_ref().and_then(|_| {
+        config
+            .attract
+            .as_ref()
+            .map(|attract| AttractTimer::demo(attract.idle_timeout(), std::time::Instant::now()))
+    });
+    let haptics = ActiveProfile::load(config.roms_directory(), &config).haptics(&config);
+    if config.rumble.is_none() && !haptics.is_empty() {
+        warn!("Haptic feedback disabled: no motor configured in [rumble]");
+    }
+    let rumble = config
+        .rumble
+        .as_ref()
+        .filter(|_| !config.no_gpio && attract.is_none())
+        .and_then(|rumble| match GpioMotor::try_new(rumble) {
+            Ok(motor) => {
+                Some(Rumble::new(Box::new(motor), rumble.min_toggle()).with_haptics(haptics))
+            }
+            Err(err) => {
+                warn!("Rumble disabled: {err}");
+                None
+            }
+        });
+    let recorder = options
+        .record_session
+        .as_ref()
+        .filter(|_| replay.is_none())
+        .map(|_| InputRecorder::default());
+    if let Some(recorder) = recorder.as_ref().filter(|_| resumed) {
+        recorder.unreproducible_from(0, "resume state loaded");
+    }
+    // demos don't leave their state behind
+    let keep_resume_state = config.boot_last_game && replay.is_none();
+    // the save is written back when the game stops: keep the one the run starts with
+    let initial_ram = recorder
+        .as_ref()
+        .and_then(|_| std::fs::read(rboy::battery_save_slot_path(rom_file, save_slot)).ok());
+    let cpu_cores = config.affinity.cpu.clone();
+    let cpu_recorder = recorder.clone();
+    let frameskip = AutoFrameSkip::new(options.max_frameskip);
+    let cpu_thread = thread::spawn(move || {
+        affinity::pin_current_thread("cpu", &cpu_cores);
+        run_cpu(
+            cpu,
+            video_sender,
+            gb_event_receiver,
+            replay,
+            rumble,
+            cpu_recorder,
+            frameskip,
+        )
+    });
     debug!("CPU thread started");
 
+    // the demo always runs at normal speed
+    let analog_speed_exit = Arc::new(AtomicBool::new(false));
+    let analog_speed_thread = config
+        .analog_speed
+        .clone()
+        .filter(|_| attract.is_none())
+        .map(|speed| analog_speed::run(speed, analog_speed_exit.clone(), gb_event_sender.clone()));
+
     // run input listener
-    let (keyboard_event_sender, keyboard_event_receiver) = mpsc::channel();
-    let input_listener_thread = run_input_listener(&config, exit.clone(), keyboard_event_sender);
+    let (keyboard_event_sender, mut keyboard_event_receiver) = mpsc::channel();
+    let mut input_listener_exit = Arc::new(AtomicBool::new(false));
+    let mut input_listener_thread = run_input_listener(
+        &config,
+        input_listener_exit.clone(),
+        keyboard_event_sender,
+        false,
+    )?;
     debug!("Input listener started");
 
+    // commands sent from the menu don't apply to this game
+    stdin_input::discard_pending();
+    let mut hotkeys = Hotkeys::new(&config.hotkeys);
+    let mut last_frame: Option<Vec<u8>> = None;
+    let mut frame_clock = FrameClock::default();
+    let mut overlay: Option<Overlay> = None;
+    let mut slot_picker: Option<SlotPicker> = None;
+    let mut settings_page: Option<SettingsPage> = None;
+    let mut cheats_page: Option<CheatsPage> = None;
+    let mut cheats = match config.cheats {
+        true => Cheats::load(rom_file)
+            .unwrap_or_else(|err| {
+                error!("{err}");
+                None
+            })
+            .unwrap_or_default(),
+        false => Cheats::default(),
+    };
+    let mut osd = Osd::default();
+    // key which dismissed the OSD warning, swallowed until released
+    let mut dismiss_key = None;
+    framebuffer.set_color_lut(picture.lut());
+    framebuffer.set_scale_filter(config.scale_filter.for_context(ScaleContext::Game));
+    let mut restart = false;
+    let mut last_geometry_check = std::time::Instant::now();
+    let mut last_dropped_frames_check = std::time::Instant::now();
+    let mut last_dropped_frames = 0;
+    let mut battery_monitor = config
+        .battery
+        .as_ref()
+        .map(|battery| BatteryMonitor::new(battery.low_percent, battery.hysteresis));
+    let mut last_battery_check: Option<std::time::Instant> = None;
+    let mut debug_overlay = DebugOverlay::new(options.show_fps, std::time::Instant::now());
+    // the demo is meant to be watched
+    let mut idle_dim = config
+        .idle_dim
+        .as_ref()
+        .filter(|_| attract.is_none())
+        .map(|idle| {
+            IdleDimmer::new(
+                idle.dim_after(),
+                idle.blank_after(),
+                std::time::Instant::now(),
+            )
+        });
+    let mut speed = match attract {
+        Some(_) => SpeedPercent::default(),
+        None => config.speed(),
+    };
+    let max_speed = config.game(rom_file).max_speed;
+    if let Some(max_speed) = max_speed {
+        info!("Speed capped at {max_speed}x");
+        let _ = gb_event_sender.send(GBEvent::MaxSpeed(Some(max_speed)));
+    }
+    if speed != SpeedPercent::default() {
+        info!("Speed: {}%", speed.percent());
+        let _ = gb_event_sender.send(GBEvent::Speed(speed.multiplier()));
+    }
+    let mut boot_turbo = config
+        .boot_turbo
+        .as_ref()
+        .filter(|_| attract.is_none())
+        .and_then(|turbo| {
+            let duration = turbo.duration(rom_file)?;
+            info!("Boot turbo at {}x for {duration:?}", turbo.speed);
+            Some(BootTurbo::new(
+                turbo.speed,
+                duration,
+                std::time::Instant::now(),
+            ))
+        });
+
     loop {
         if exit.load(std::sync::atomic::Ordering::SeqCst) {
             info!("Exit requested, stopping emulator...");
             break;
         }
 
+        if let Some(change) = boot_turbo
+            .as_mut()
+            .and_then(|turbo| turbo.poll(std::time::Instant::now()))
+        {
+            let multiplier = match change {
+                TurboChange::Engage(multiplier) => multiplier,
+                // back to the speed selected
+                TurboChange::Disengage => speed.multiplier(),
+            };
+            debug!("Boot turbo: speed multiplier {multiplier}");
+            let _ = gb_event_sender.send(GBEvent::Speed(multiplier));
+        }
+
+        if let (Some(stage), Some(idle)) = (
+            idle_dim
+                .as_mut()
+                .and_then(|dimmer| dimmer.poll(std::time::Instant::now())),
+            &config.idle_dim,
+        ) {
+            let brightness = match stage {
+                IdleStage::Active => picture.brightness,
+                IdleStage::Dim => picture.brightness * idle.level,
+                IdleStage::Blank => 0.0,
+            };
+            info!("Display {stage:?}");
+            framebuffer.set_color_lut(
+                PictureSettings {
+                    brightness,
+                    ..picture
+                }
+                .lut(),
+            );
+        }
+
+        if last_dropped_frames_check.elapsed() >= DROPPED_FRAMES_CHECK_INTERVAL {
+            last_dropped_frames_check = std::time::Instant::now();
+            let dropped = dropped_frames.load(std::sync::atomic::Ordering::Relaxed);
+            if dropped > last_dropped_frames {
+                warn!(
+                    "Dropped {} frames in the last {}s: the display can't keep up",
+                    dropped - last_dropped_frames,
+                    DROPPED_FRAMES_CHECK_INTERVAL.as_secs()
+                );
+                last_dropped_frames = dropped;
+            }
+        }
+
+        if let (Some(monitor), Some(battery)) = (battery_monitor.as_mut(), &config.battery)
+            && last_battery_check.is_none_or(|last| last.elapsed() >= BATTERY_CHECK_INTERVAL)
+        {
+            last_battery_check = Some(std::time::Instant::now());
+            match battery::read_capacity(&battery.capacity) {
+                Ok(percent) if monitor.update(percent) => {
+                    warn!("Battery low: {percent}%");
+                    osd.show_sticky(format!("Battery low: {percent}%"));
+                    if battery.quick_save && attract.is_none() {
+                        quick_state(
+                            SlotMode::Save,
+                            rom_file,
+                            config.backup_corrupt_states,
+                            &gb_event_sender,
+                            |_| {},
+                        );
+                    }
+                }
+                Ok(percent) => trace!("Battery: {percent}%"),
+                Err(err) => warn!("{err}"),
+            }
+        }
+
+        if last_geometry_check.elapsed() >= GEOMETRY_CHECK_INTERVAL {
+            last_geometry_check = std::time::Instant::now();
+            if framebuffer.refresh_geometry() {
+                framebuffer.zero();
+                match (&overlay, &slot_picker, &settings_page, &cheats_page) {
+                    (_, _, _, Some(page)) => {
+                        page.draw(&framebuffer, last_frame.as_deref(), &cheats)
+                    }
+                    (_, _, Some(page), None) => {
+                        page.draw(&framebuffer, last_frame.as_deref(), &volume, &picture)
+                    }
+                    (_, Some(picker), None, None) => {
+                        picker.draw(&framebuffer, last_frame.as_deref())
+                    }
+                    (Some(menu), None, None, None) => {
+                        menu.draw(&framebuffer, last_frame.as_deref())
+                    }
+                    (None, _, None, None) => {
+                        if let Some(frame) = &last_frame {
+                            framebuffer.write(frame);
+                        }
+                    }
+                }
+            }
+        }
+
+        if let Some(menu) = overlay.as_mut() {
+            let Ok((event, key)) = keyboard_event_receiver.try_recv() else {
+                thread::sleep(std::time::Duration::from_millis(10));
+                continue;
+            };
+            if idle_dim
+                .as_mut()
+                .and_then(|dimmer| dimmer.input(std::time::Instant::now()))
+                .is_some()
+            {
+                framebuffer.set_color_lut(picture.lut());
+            }
+            // keep track of the held keys, but don't trigger hotkeys while paused
+            let _ = hotkeys.handle_event(event, key);
+            if event == KeyEvent::Up {
+                // release keys which were held when the menu was opened
+                let _ = gb_event_sender.send(GBEvent::KeyUp(key));
+                continue;
+            }
+
+            if let Some(page) = cheats_page.as_mut() {
+                match page.handle_key(key, &mut cheats) {
+                    Some(CheatsPick::Toggled) => {
+                        let _ = gb_event_sender.send(GBEvent::Cheats(cheats.applied()));
+                    }
+                    Some(CheatsPick::Back) => {
+                        cheats_page = None;
+                        menu.draw(&framebuffer, last_frame.as_deref());
+                        continue;
+                    }
+                    None => {}
+                }
+                page.draw(&framebuffer, last_frame.as_deref(), &cheats);
+                continue;
+            }
+
+            if let Some(page) = settings_page.as_mut() {
+                let mut game_scaling = framebuffer.scaling();
+                match page.handle_key(
+                    key,
+                    &volume,
+                    &mut picture,
+                    &mut game_scaling,
+                    &config.picture,
+                ) {
+                    Some(SettingsPick::Changed(Setting::Volume)) => {
+                        if let Some(dir) = config.settings_directory() {
+                            volume.save(dir);
+                        }
+                        info!("Volume: {}%", volume.percent());
+                    }
+                    Some(SettingsPick::Changed(Setting::Scaling)) => {
+                        // the scaling chosen here is the one of all the games
+                        scaling = game_scaling;
+                        picture.scaling = Some(scaling);
+                        framebuffer.set_scaling(scaling);
+                        if let Some(dir) = config.settings_directory() {
+                            picture.save(dir);
+                        }
+                        info!("Scaling: {}", scaling.name());
+                        // the previous frame may overflow the new one
+                        framebuffer.zero();
+                    }
+                    Some(SettingsPick::Changed(Setting::Scanlines)) => {
+                        framebuffer.set_scanlines(picture.scanlines);
+                        if let Some(dir) = config.settings_directory() {
+                            picture.save(dir);
+                        }
+                        info!("Scanlines: {}", picture.scanlines);
+                    }
+                    Some(SettingsPick::Changed(Setting::Brightness | Setting::Gamma)) => {
+                        framebuffer.set_color_lut(picture.lut());
+                        if let Some(dir) = config.settings_directory() {
+                            picture.save(dir);
+                        }
+                        info!("Picture settings: {}", picture.label());
+                    }
+                    Some(SettingsPick::Back) => {
+                        settings_page = None;
+                        menu.draw(&framebuffer, last_frame.as_deref());
+                        continue;
+                    }
+                    None => {}
+                }
+                page.draw(&framebuffer, last_frame.as_deref(), &volume, &picture);
+                continue;
+            }
+
+            if let Some(picker) = slot_picker.as_mut() {
+                match picker.handle_key(key) {
+                    Some(SlotPick::Slot(index))
+                        if picker.mode() == SlotMode::Load
+                            && !save_state::slot_path(rom_file, index).is_file() =>
+                    {
+                        debug!("Slot {index} is empty");
+                    }
+                    Some(SlotPick::Slot(index)) => {
+                        let path = save_state::slot_path(rom_file, index);
+                        let message = match picker.mode() {
+                            SlotMode::Save => match request_save_state(&gb_event_sender, path) {
+                                Ok(()) => format!("Saved to slot {index}"),
+                                Err(err) => format!("Slot {index}: {err}"),
+                            },
+                            SlotMode::Load => match request_load_state(
+                                &gb_event_sender,
+                                path,
+                                config.backup_corrupt_states,
+                            ) {
+                                Ok(()) => format!("Loaded slot {index}"),
+                                Err(err) => format!("Slot {index}: {}", err.message()),
+                            },
+                        };
+                        info!("{message}");
+                        slot_picker = None;
+                        overlay = None;
+                        framebuffer
+                            .set_scale_filter(config.scale_filter.for_context(ScaleContext::Game));
+                        if let Some(frame) = &last_frame {
+                            framebuffer.write(frame);
+                        }
+                        osd.show(message);
+                        let _ = gb_event_sender.send(GBEvent::Resume);
+                    }
+                    Some(SlotPick::Back) => {
+                        slot_picker = None;
+                        menu.draw(&framebuffer, last_frame.as_deref());
+                    }
+                    None => picker.draw(&framebuffer, last_frame.as_deref()),
+                }
+                continue;
+            }
+
+            match menu.handle_key(key) {
+                Some(OverlayAction::Resume) => {
+                    debug!("Closing in-game menu");
+                    overlay = None;
+                    framebuffer
+                        .set_scale_filter(config.scale_filter.for_context(ScaleContext::Game));
+                    if let Some(frame) = &last_frame {
+                        framebuffer.write(frame);
+                    }
+                    let _ = gb_event_sender.send(GBEvent::Resume);
+                }
+                Some(action @ (OverlayAction::SaveState | OverlayAction::LoadState)) => {
+                    let mode = match action {
+                        OverlayAction::SaveState => SlotMode::Save,
+                        _ => SlotMode::Load,
+                    };
+                    let picker = SlotPicker::new(mode, &save_state::list_slots(rom_file));
+                    picker.draw(&framebuffer, last_frame.as_deref());
+                    slot_picker = Some(picker);
+                }
+                Some(OverlayAction::Cheats) => {
+                    let page = CheatsPage::default();
+                    page.draw(&framebuffer, last_frame.as_deref(), &cheats);
+                    cheats_page = Some(page);
+                }
+                Some(OverlayAction::Settings) => {
+                    let page = SettingsPage::default();
+                    page.draw(&framebuffer, last_frame.as_deref(), &volume, &picture);
+                    settings_page = Some(page);
+                }
+                Some(OverlayAction::Restart) => {
+                    info!("Restarting game");
+                    restart = true;
+                    break;
+                }
+                Some(OverlayAction::QuitToMenu) => {
+                    info!("Quitting to menu");
+                    break;
+                }
+                None => menu.draw(&framebuffer, last_frame.as_deref()),
+            }
+            continue;
+        }
+
+        for command in stdin_input::take_pending() {
+            match command {
+                stdin_input::Command::Screenshot => {
+                    if let Some(frame) = &last_frame
+                        && let Err(err) = screenshot::print_screenshot(frame)
+                    {
+                        error!("Failed to print screenshot: {err}");
+                    }
+                }
+                stdin_input::Command::QuickSave => {
+                    quick_state(
+                        SlotMode::Save,
+                        rom_file,
+                        config.backup_corrupt_states,
+                        &gb_event_sender,
+                        |message| osd.show(message),
+                    );
+                }
+                stdin_input::Command::QuickLoad => {
+                    quick_state(
+                        SlotMode::Load,
+                        rom_file,
+                        config.backup_corrupt_states,
+                        &gb_event_sender,
+                        |message| osd.show(message),
+                    );
+                }
+                stdin_input::Command::Dump | stdin_input::Command::Help => {}
+            }
+        }
+
         if let Ok((event, key)) = keyboard_event_receiver.try_recv() {
+            if let Some(attract) = attract.as_mut() {
+                // the demo ignores real input, other than to stop it
+                if event == KeyEvent::Down
+                    && attract.input(std::time::Instant::now()) == Some(Trigger::StopDemo)
+                {
+                    info!("Input received; stopping attract mode");
+                    break;
+                }
+                continue;
+            }
+            if idle_dim
+                .as_mut()
+                .and_then(|dimmer| dimmer.input(std::time::Instant::now()))
+                .is_some()
+            {
+                info!("Input received; restoring the display");
+                framebuffer.set_color_lut(picture.lut());
+            }
+            // the key dismissing a warning doesn't reach the game, neither pressed nor released
+            if event == KeyEvent::Down && osd.dismiss() {
+                debug!("OSD warning dismissed");
+                dismiss_key = Some(key);
+                continue;
+            }
+            if event == KeyEvent::Up && dismiss_key == Some(key) {
+                dismiss_key = None;
+                continue;
+            }
+            match hotkeys.handle_event(event, key) {
+                Some(hotkey::Action::Menu) => {
+                    debug!("Opening in-game menu");
+                    let _ = gb_event_sender.send(GBEvent::Pause);
+                    let menu = Overlay::paused(!cheats.cheats().is_empty());
+                    framebuffer
+                        .set_scale_filter(config.scale_filter.for_context(ScaleContext::Menu));
+                    menu.draw(&framebuffer, last_frame.as_deref());
+                    overlay = Some(menu);
+                    continue;
+                }
+                Some(hotkey::Action::NextProfile) => {
+                    let mut profile = ActiveProfile::load(config.roms_directory(), &config);
+                    profile.next(&config);
+                    profile.save(config.roms_directory());
+                    info!("Switched to key profile {}", profile.label());
+                    osd.show(format!("Keys: {}", profile.label()));
+                    let _ = gb_event_sender.send(GBEvent::Haptics(profile.haptics(&config)));
+
+                    // the keys held won't be released by the new listener
+                    for key in hotkeys.release_all() {
+                        let _ = gb_event_sender.send(GBEvent::KeyUp(key));
+                    }
+                    // restart the input listener with the new key mapping
+                    input_listener_exit.store(true, std::sync::atomic::Ordering::SeqCst);
+                    let _ = input_listener_thread.join();
+                    let (keyboard_event_sender, receiver) = mpsc::channel();
+                    keyboard_event_receiver = receiver;
+                    input_listener_exit = Arc::new(AtomicBool::new(false));
+                    input_listener_thread = run_input_listener(
+                        &config,
+                        input_listener_exit.clone(),
+                        keyboard_event_sender,
+                        false,
+                    )?;
+                    continue;
+                }
+                Some(hotkey::Action::Screenshot) => {
+                    info!("Screenshot requested");
+                    if let Some(frame) = &last_frame
+                        && let Err(err) = screenshot::print_screenshot(frame)
+                    {
+                        error!("Failed to print screenshot: {err}");
+                    }
+                }
+                Some(hotkey::Action::QuickSave) => {
+                    quick_state(
+                        SlotMode::Save,
+                        rom_file,
+                        config.backup_corrupt_states,
+                        &gb_event_sender,
+                        |message| osd.show(message),
+                    );
+                }
+                Some(hotkey::Action::QuickLoad) => {
+                    quick_state(
+                        SlotMode::Load,
+                        rom_file,
+                        config.backup_corrupt_states,
+                        &gb_event_sender,
+                        |message| osd.show(message),
+                    );
+                }
+                Some(hotkey::Action::DebugOverlay) => {
+                    if !debug_overlay.toggle()
+                        && let Some(frame) = &last_frame
+                    {
+                        // clear the overlay
+                        framebuffer.write(frame);
+                    }
+                    info!("Debug overlay visible: {}", debug_overlay.is_visible());
+                }
+                Some(hotkey::Action::InvertColors) => {
+                    framebuffer.set_invert_colors(!framebuffer.invert_colors());
+                    info!("Inverted colors: {}", framebuffer.invert_colors());
+                    if let Some(frame) = &last_frame {
+                        framebuffer.write(frame);
+                    }
+                }
+                Some(hotkey::Action::NextPalette) => match palette.as_mut() {
+                    Some(palette) => {
+                        let next = palette.next();
+                        framebuffer.set_palette(Some(next));
+                        info!("Palette: {}", next.name);
+                        osd.show(format!("Palette: {}", next.name));
+                        if let Some(frame) = &last_frame {
+                            framebuffer.write(frame);
+                        }
+                    }
+                    None => osd.show("Color game: no palette"),
+                },
+                Some(hotkey::Action::NextSpeed) => {
+                    speed = speed.next();
+                    info!("Speed: {}%", speed.percent());
+                    osd.show(speed.label(max_speed));
+                    let _ = gb_event_sender.send(GBEvent::Speed(speed.multiplier()));
+                }
+                Some(hotkey::Action::NextScaling) => {
+                    let next = framebuffer.scaling().next();
+                    framebuffer.set_scaling(next);
+                    info!("Scaling: {}", next.name());
+                    osd.show(format!("Scaling: {}", next.name()));
+                    // the previous frame may overflow the new one
+                    framebuffer.zero();
+                    if let Some(frame) = &last_frame {
+                        framebuffer.write(frame);
+                    }
+                }
+                Some(hotkey::Action::Picture(adjust)) => {
+                    framebuffer.set_color_lut(picture.adjust(adjust, &config.picture));
+                    if let Some(dir) = config.settings_directory() {
+                        picture.save(dir);
+                    }
+                    info!("Picture settings: {}", picture.label());
+                    osd.show(picture.label());
+                }
+                None => {}
+            }
+
             match event {
                 KeyEvent::Down => {
                     debug!("Key Down: {:?}", key);
@@ -188,48 +1490,201 @@ fn run_emulator(
             }
         }
 
-        match video_receiver.try_recv() {
-            Ok(data) => {
-                trace!("Received video frame, updating framebuffer");
+        match next_frame(&video_receiver, display_poll) {
+            Ok(Some(frame)) => {
+                let timing = frame_clock.observe(&frame, std::time::Instant::now());
+                trace!(
+                    "Received video frame {}, updating framebuffer (latency {:?}, interval {:?})",
+                    frame.index, timing.latency, timing.interval
+                );
+                if timing.missed > 0 {
+                    debug!(
+                        "Frame {}: {} frames skipped or dropped",
+                        frame.index, timing.missed
+                    );
+                }
+                let data = frame.data;
                 framebuffer.write(&data);
+                if let Some(pipe) = &options.frame_pipe {
+                    pipe.send(&data);
+                }
+                osd.draw(&framebuffer);
+                debug_overlay.frame(
+                    dropped_frames.load(std::sync::atomic::Ordering::Relaxed),
+                    std::time::Instant::now(),
+                );
+                debug_overlay.draw(&framebuffer);
+                if let Some(Ok(mut status)) = options.status.as_ref().map(|s| s.lock()) {
+                    status.fps = debug_overlay.fps();
+                }
+                last_frame = Some(data);
             }
-            Err(TryRecvError::Empty) => {
-                thread::sleep(std::time::Duration::from_millis(10));
-            }
-            Err(TryRecvError::Disconnected) => break, // Remote end has hung-up
+            Ok(None) => {}
+            Err(_) => break, // Remote end has hung-up
+        }
+    }
+
+    info!(
+        "Dropped {} frames",
+        dropped_frames.load(std::sync::atomic::Ordering::Relaxed)
+    );
+    drop(cpal_audio_stream);
+    if let Some(pipe) = &options.frame_pipe {
+        debug!("Frame pipe: {} frames dropped so far", pipe.dropped());
+    }
+    analog_speed_exit.store(true, std::sync::atomic::Ordering::SeqCst);
+    if let Some(thread) = analog_speed_thread {
+        let _ = thread.join();
+    }
+    // Stop CPU thread by disconnecting
+    drop(video_receiver);
+    drop(gb_event_sender);
+    match supervisor::join_cpu(cpu_thread) {
+        Ok(cpu) if keep_resume_state => {
+            let _ = save_state(&cpu, &resume_path);
+        }
+        Ok(_) => {}
+        Err(crash) => {
+            error!("CPU thread crashed: {}", crash.message);
+            framebuffer.set_scale_filter(config.scale_filter.for_context(ScaleContext::Menu));
+            let action = choose_overlay_action(
+                Overlay::crashed(),
+                &framebuffer,
+                last_frame.as_deref(),
+                &keyboard_event_receiver,
+                &exit,
+            );
+            restart = action == Some(OverlayAction::Restart);
         }
     }
 
     debug!("Stopping input listener...");
+    input_listener_exit.store(true, std::sync::atomic::Ordering::SeqCst);
     let _ = input_listener_thread.join();
     debug!("Input listener stopped.");
 
-    drop(cpal_audio_stream);
-    drop(video_receiver); // Stop CPU thread by disconnecting
-    let _ = cpu_thread.join();
+    if let (Some(recorder), Some(path)) = (recorder, &options.record_session)
+        && let Err(err) = save_session_bundle(
+            rom_file,
+            options,
+            mode,
+            initial_ram.as_deref(),
+            &recorder,
+            path,
+        )
+    {
+        error!("Failed to save the session bundle: {err}");
+    }
+
+    if let Some(cache) = game_cache.as_mut() {
+        let options = LaunchOptions {
+            mode,
+            palette: palette.as_ref().map(|p| p.current().name.to_string()),
+            // the games follow the global scaling unless it's changed while playing them
+            scaling: Some(framebuffer.scaling()).filter(|current| *current != scaling),
+        };
+        cache.record_launch_options(rom_file, options);
+        if let Err(err) = cache.save() {
+            warn!("Failed to save the launch options: {err}");
+        }
+        framebuffer.set_scaling(scaling);
+    }
 
     // zero framebuffer
     framebuffer.zero();
+    framebuffer.set_palette(None);
+    framebuffer.set_scanlines(false);
     debug!("Framebuffer zeroed.");
 
     if exit.load(std::sync::atomic::Ordering::SeqCst) {
         Ok(AppState::Exit)
+    } else if restart {
+        Ok(AppState::Emulator {
+            config,
+            rom_file: rom_file.to_path_buf(),
+            mode,
+            save_slot,
+            resume: false,
+        })
     } else {
         Ok(AppState::Menu { config })
     }
 }
 
+/// Show an overlay until an action is chosen.
+///
+/// Returns `None` if exit was requested.
+fn choose_overlay_action(
+    mut overlay: Overlay,
+    framebuffer: &Framebuffer,
+    last_frame: Option<&[u8]>,
+    receiver: &Receiver<rboy::input::Event>,
+    exit: &AtomicBool,
+) -> Option<OverlayAction> {
+    overlay.draw(framebuffer, last_frame);
+
+    while !exit.load(std::sync::atomic::Ordering::SeqCst) {
+        match receiver.recv_timeout(std::time::Duration::from_millis(50)) {
+            Ok((KeyEvent::Down, key)) => match overlay.handle_key(key) {
+                Some(action) => return Some(action),
+                None => overlay.draw(framebuffer, last_frame),
+            },
+            Ok(_) | Err(mpsc::RecvTimeoutError::Timeout) => continue,
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+        }
+    }
+
+    None
+}
+
+/// Game driven by recorded inputs, on a scratch copy of the save
+#[derive(Debug, Clone)]
+struct Demo {
+    replay: Replay,
+    /// Battery-backed RAM the game starts with
+    ram: Option<Vec<u8>>,
+}
+
+/// Battery-backed RAM a game runs with
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum BatterySave {
+    /// Save file of the slot, see [`rboy::battery_save_slot_path`], written back when the game stops
+    Slot(usize),
+    /// Copy of the save data, if any, discarded when the game stops
+    Scratch(Option<Vec<u8>>),
+}
+
 fn construct_cpu(
     rom_file: &Path,
     classic_mode: bool,
+    save: &BatterySave,
     skip_checksum: bool,
     reload_mode: Option<String>,
+    config: &AppConfig,
 ) -> Option<Box<Device>> {
-    let opt_c = match classic_mode {
-        true => Device::new(rom_file, skip_checksum, reload_mode),
-        false => Device::new_cgb(rom_file, skip_checksum, reload_mode),
+    let opt_c = match save {
+        BatterySave::Slot(save_slot) if classic_mode => {
+            Device::new(rom_file, *save_slot, skip_checksum, reload_mode)
+        }
+        BatterySave::Slot(save_slot) => {
+            Device::new_cgb(rom_file, *save_slot, skip_checksum, reload_mode)
+        }
+        BatterySave::Scratch(ram) => std::fs::read(rom_file)
+            .map_err(|_| "Could not read ROM")
+            .and_then(|data| match classic_mode {
+                true => Device::new_from_buffer(data, skip_checksum, reload_mode),
+                false => Device::new_cgb_from_buffer(data, skip_checksum, reload_mode),
+            })
+            .and_then(|mut device| match ram {
+                Some(ram) => device.load_ram(ram).map(|()| device),
+                None => Ok(device),
+            }),
+    };
+    let boot_rom = match classic_mode {
+        true => &config.dmg_boot_rom,
+        false => &config.cgb_boot_rom,
     };
-    let c = match opt_c {
+    let mut c = match opt_c {
         Ok(cpu) => cpu,
         Err(message) => {
             warn!("Failed to setup cpu: {message}");
@@ -237,41 +1692,325 @@ fn construct_cpu(
         }
     };
 
+    // fall back to the fast boot if the boot ROM can't be used
+    if let Some(path) = boot_rom {
+        match std::fs::read(path) {
+            Ok(boot_rom) => match c.load_boot_rom(boot_rom) {
+                Ok(()) => info!("Running boot ROM {}", path.display()),
+                Err(message) => warn!("Invalid boot ROM {}: {message}", path.display()),
+            },
+            Err(err) => warn!("Failed to read boot ROM {}: {err}", path.display()),
+        }
+    }
+
     Some(Box::new(c))
 }
 
-fn run_cpu(mut cpu: Box<Device>, sender: SyncSender<Vec<u8>>, receiver: Receiver<GBEvent>) {
+/// Wait up to `timeout` for the next frame, waking up as soon as it arrives
+fn next_frame(
+    receiver: &Receiver<Frame>,
+    timeout: std::time::Duration,
+) -> Result<Option<Frame>, RecvTimeoutError> {
+    match receiver.recv_timeout(timeout) {
+        Ok(data) => Ok(Some(data)),
+        Err(RecvTimeoutError::Timeout) => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+/// Create the channel carrying the frames from the CPU to the display, buffering up to `depth` frames.
+///
+/// The CPU never blocks on it: frames are dropped while the queue is full.
+fn video_channel(depth: usize) -> (SyncSender<Frame>, Receiver<Frame>) {
+    mpsc::sync_channel(depth.max(1))
+}
+
+/// Save the inputs of a run of `rom_file` on `mode`, started with the battery-backed `ram`,
+/// with the configuration to a session bundle
+fn save_session_bundle(
+    rom_file: &Path,
+    options: &EmulatorOptions,
+    mode: LaunchMode,
+    ram: Option<&[u8]>,
+    recorder: &InputRecorder,
+    path: &Path,
+) -> anyhow::Result<()> {
+    let config = std::fs::read_to_string(&options.config_file).map_err(|e| {
+        anyhow::anyhow!(
+            "Failed to read config file {:?}: {}",
+            options.config_file,
+            e
+        )
+    })?;
+    SessionBundle::new(rom_file, config, options.seed, mode, ram, recorder)?.save(path)
+}
+
+/// Run the CPU until the frontend disconnects, then give back the device.
+///
+/// If `replay` is set, its events are applied at their frame and the CPU stops after the last one.
+/// If `recorder` is set, the key events received are recorded with their frame, and the recording
+/// is flagged once the state is loaded or the cheats change, as the inputs can't reproduce them;
+/// the speed isn't recorded since the inputs are applied by frame.
+fn run_cpu(
+    mut cpu: Box<Device>,
+    sender: FrameSender,
+    receiver: Receiver<GBEvent>,
+    mut replay: Option<Replay>,
+    mut rumble: Option<Rumble>,
+    recorder: Option<InputRecorder>,
+    mut frameskip: AutoFrameSkip,
+) -> Box<Device> {
+    let mut cheats: Vec<(u16, u8)> = vec![];
+    let record = |frame: u64, event: rboy::input::Event| {
+        if let Some(recorder) = &recorder {
+            recorder.record(frame, event);
+        }
+    };
+    let unreproducible = |frame: u64, event: &str| {
+        if let Some(recorder) = &recorder {
+            recorder.unreproducible_from(frame, event);
+        }
+    };
     let periodic = timer_periodic(16);
 
     let waitticks = (4194304f64 / 1000.0 * 16.0).round() as u32;
-    let mut ticks = 0;
+    let batch_time = std::time::Duration::from_millis(16);
+    let mut budget = CycleBudget::new(waitticks, batch_time);
 
+    let mut show_frame = true;
+    let mut double_speed = false;
     'outer: loop {
-        while ticks < waitticks {
-            ticks += cpu.do_cycle();
+        // the device reports ticks of the single-speed clock: the budget holds in both modes
+        if cpu.is_double_speed() != double_speed {
+            double_speed = !double_speed;
+            debug!(
+                "Frame {}: CGB double speed {double_speed}",
+                cpu.frame_count()
+            );
+        }
+        let batch_start = std::time::Instant::now();
+        let outcome = budget.run_batch(|| {
+            let ticks = cpu.do_cycle();
             if cpu.check_and_reset_gpu_updated() {
-                let data = cpu.get_gpu_data().to_vec();
-                if let Err(TrySendError::Disconnected(..)) = sender.try_send(data) {
-                    break 'outer;
+                let shown = show_frame;
+                // the next frame is only drawn if it's shown
+                show_frame = frameskip.show_frame();
+                cpu.set_skip_render(!show_frame);
+                if shown {
+                    let frame = Frame::new(cpu.get_gpu_data().to_vec(), cpu.frame_count());
+                    // frames are dropped while the display can't keep up
+                    sender.send(frame).ok()?;
                 }
             }
+            Some(ticks)
+        });
+        frameskip.update(batch_start.elapsed(), batch_time);
+        match outcome {
+            BatchOutcome::Completed => {}
+            BatchOutcome::Deferred => {
+                trace!(
+                    "CPU batch exceeded its time budget; deferring remaining ticks ({}/{} run)",
+                    budget.ticks(),
+                    budget.target_ticks()
+                );
+            }
+            BatchOutcome::Aborted => break 'outer,
         }
 
-        ticks -= waitticks;
+        if let Some(rumble) = rumble.as_mut() {
+            rumble.update(cpu.rumble(), std::time::Instant::now());
+        }
+        for (address, value) in &cheats {
+            cpu.write_byte(*address, *value);
+        }
 
         'recv: loop {
             match receiver.try_recv() {
-                Ok(event) => match event {
-                    GBEvent::KeyUp(key) => cpu.keyup(key),
-                    GBEvent::KeyDown(key) => cpu.keydown(key),
-                },
+                Ok(GBEvent::Pause) => {
+                    debug!("Frame {}: CPU paused", cpu.frame_count());
+                    if let Some(rumble) = rumble.as_mut() {
+                        rumble.stop();
+                    }
+                    // block until resumed, still applying key events
+                    loop {
+                        match receiver.recv() {
+                            Ok(GBEvent::Resume) => break,
+                            Ok(GBEvent::KeyUp(key)) => {
+                                record(cpu.frame_count(), (KeyEvent::Up, key));
+                                cpu.keyup(key)
+                            }
+                            Ok(GBEvent::KeyDown(key)) => {
+                                record(cpu.frame_count(), (KeyEvent::Down, key));
+                                cpu.keydown(key)
+                            }
+                            Ok(GBEvent::SaveState { path, outcome }) => {
+                                let _ = outcome.send(save_state(&cpu, &path));
+                            }
+                            Ok(GBEvent::LoadState {
+                                path,
+                                backup,
+                                outcome,
+                            }) => {
+                                unreproducible(cpu.frame_count(), "state loaded");
+                                let _ = outcome.send(save_state::load(&mut cpu, &path, backup));
+                            }
+                            Ok(GBEvent::Speed(speed)) => budget.set_speed(speed),
+                            Ok(GBEvent::MaxSpeed(speed)) => budget.set_max_speed(speed),
+                            Ok(GBEvent::Cheats(applied)) => {
+                                if applied != cheats {
+                                    unreproducible(cpu.frame_count(), "cheats changed");
+                                }
+                                cheats = applied
+                            }
+                            Ok(GBEvent::Haptics(haptics)) => {
+                                if let Some(rumble) = rumble.as_mut() {
+                                    rumble.set_haptics(haptics);
+                                }
+                            }
+                            Ok(GBEvent::Pause) => continue,
+                            Err(_) => break 'outer,
+                        }
+                    }
+                    debug!("Frame {}: CPU resumed", cpu.frame_count());
+                }
+                Ok(GBEvent::Resume) => {}
+                Ok(GBEvent::SaveState { path, outcome }) => {
+                    let _ = outcome.send(save_state(&cpu, &path));
+                }
+                Ok(GBEvent::LoadState {
+                    path,
+                    backup,
+                    outcome,
+                }) => {
+                    unreproducible(cpu.frame_count(), "state loaded");
+                    let _ = outcome.send(save_state::load(&mut cpu, &path, backup));
+                }
+                Ok(GBEvent::Speed(speed)) => budget.set_speed(speed),
+                Ok(GBEvent::MaxSpeed(speed)) => budget.set_max_speed(speed),
+                Ok(GBEvent::Cheats(applied)) => {
+                    if applied != cheats {
+                        unreproducible(cpu.frame_count(), "cheats changed");
+                    }
+                    cheats = applied
+                }
+                Ok(GBEvent::Haptics(haptics)) => {
+                    if let Some(rumble) = rumble.as_mut() {
+                        rumble.set_haptics(haptics);
+                    }
+                }
+                Ok(GBEvent::KeyUp(key)) => {
+                    debug!("Frame {}: key {key:?} up", cpu.frame_count());
+                    record(cpu.frame_count(), (KeyEvent::Up, key));
+                    cpu.keyup(key);
+                }
+                Ok(GBEvent::KeyDown(key)) => {
+                    debug!("Frame {}: key {key:?} down", cpu.frame_count());
+                    record(cpu.frame_count(), (KeyEvent::Down, key));
+                    cpu.keydown(key);
+                    if let Some(rumble) = rumble.as_mut() {
+                        rumble.key_down(key, std::time::Instant::now());
+                    }
+                }
                 Err(TryRecvError::Empty) => break 'recv,
                 Err(TryRecvError::Disconnected) => break 'outer,
             }
         }
 
+        if DUMP_DEVICE_STATE.swap(false, std::sync::atomic::Ordering::SeqCst) {
+            let mut stdout = std::io::stdout().lock();
+            let _ = write!(stdout, "{}", cpu.dump_state());
+            let _ = stdout.flush();
+        }
+
+        if let Some(replay) = replay.as_mut() {
+            while let Some((event, key)) = replay.next_due(cpu.frame_count()) {
+                trace!(
+                    "Frame {}: replaying key {key:?} {event:?}",
+                    cpu.frame_count()
+                );
+                match event {
+                    KeyEvent::Down => cpu.keydown(key),
+                    KeyEvent::Up => cpu.keyup(key),
+                }
+            }
+            if replay.is_finished() {
+                debug!("Frame {}: replay finished", cpu.frame_count());
+                break 'outer;
+            }
+        }
+
         let _ = periodic.recv();
     }
+
+    cpu
+}
+
+/// Save the state to or load it from the quick slot of `rom_file`, reporting the outcome to `notify`.
+///
+/// A quick save which can't be loaded is moved aside if `backup_corrupt` is set.
+fn quick_state(
+    mode: SlotMode,
+    rom_file: &Path,
+    backup_corrupt: bool,
+    gb_event_sender: &Sender<GBEvent>,
+    mut notify: impl FnMut(&str),
+) {
+    let path = save_state::quick_slot_path(rom_file);
+    match mode {
+        SlotMode::Save => {
+            info!("Quick save");
+            match request_save_state(gb_event_sender, path) {
+                Ok(()) => notify("Saved"),
+                Err(err) => notify(err),
+            }
+        }
+        SlotMode::Load if !path.is_file() => {
+            debug!("No quick save for {}", rom_file.display());
+            notify("No quick save");
+        }
+        SlotMode::Load => {
+            info!("Quick load");
+            match request_load_state(gb_event_sender, path, backup_corrupt) {
+                Ok(()) => notify("Loaded"),
+                Err(err) => notify(err.message()),
+            }
+        }
+    }
+}
+
+/// Have the CPU thread save its state to `path`, waiting for the outcome
+fn request_save_state(gb_event_sender: &Sender<GBEvent>, path: PathBuf) -> rboy::StrResult<()> {
+    let (outcome, receiver) = mpsc::channel();
+    let _ = gb_event_sender.send(GBEvent::SaveState { path, outcome });
+    // no outcome if the CPU thread is gone
+    receiver.recv().unwrap_or(Err("Could not write state"))
+}
+
+/// Have the CPU thread load the state saved at `path`, waiting for the outcome
+fn request_load_state(
+    gb_event_sender: &Sender<GBEvent>,
+    path: PathBuf,
+    backup: bool,
+) -> Result<(), StateError> {
+    let (outcome, receiver) = mpsc::channel();
+    let _ = gb_event_sender.send(GBEvent::LoadState {
+        path,
+        backup,
+        outcome,
+    });
+    // no outcome if the CPU thread is gone
+    receiver.recv().unwrap_or(Err(StateError::Unreadable))
+}
+
+/// Save the state of the device to `path`, logging failures
+fn save_state(cpu: &Device, path: &Path) -> rboy::StrResult<()> {
+    let result = cpu.save_state_to(path);
+    match result {
+        Ok(()) => info!("Saved state to {}", path.display()),
+        Err(err) => error!("Failed to save state to {}: {err}", path.display()),
+    }
+
+    result
 }
 
 fn timer_periodic(ms: u64) -> Receiver<()> {
@@ -290,11 +2029,20 @@ fn timer_periodic(ms: u64) -> Receiver<()> {
 struct CpalPlayer {
     buffer: Arc<Mutex<Vec<(f32, f32)>>>,
     sample_rate: u32,
+    volume: Volume,
+    /// Gain applied on top of the volume
+    gain: f32,
 }
 
 impl CpalPlayer {
-    fn get() -> Option<(CpalPlayer, cpal::Stream)> {
-        let device = cpal::default_host().default_output_device()?;
+    fn get(
+        device_name: Option<&str>,
+        underrun_fade_ms: u64,
+        volume: Volume,
+        audio: &AudioConfig,
+    ) -> Option<(CpalPlayer, cpal::Stream)> {
+        let device = audio_device::output_device(device_name)?;
+        info!("Using audio device: {}", device.name().unwrap_or_default());
 
         // We want a config with:
         // channels = 2
@@ -334,13 +2082,21 @@ impl CpalPlayer {
         let player = CpalPlayer {
             buffer: shared_buffer,
             sample_rate: config.sample_rate.0,
+            volume,
+            gain: audio.gain,
         };
+        let limiter = audio.limiter_threshold.map(SoftLimiter::new);
+        let fade_frames = (config.sample_rate.0 as u64 * underrun_fade_ms / 1000) as usize;
+        let mut fade = UnderrunFade::new(fade_frames);
+        let prefill_frames =
+            (config.sample_rate.0 as u64 * audio.prefill_ms.unwrap_or(0) / 1000) as usize;
+        let mut prefill = Prefill::new(prefill_frames);
 
         let stream = match sample_format {
             cpal::SampleFormat::I8 => device.build_output_stream(
                 &config,
                 move |data: &mut [i8], _callback_info: &cpal::OutputCallbackInfo| {
-                    cpal_thread(data, &stream_buffer)
+                    cpal_thread(data, &stream_buffer, &mut fade, &mut prefill, limiter)
                 },
                 err_fn,
                 None,
@@ -348,7 +2104,7 @@ impl CpalPlayer {
             cpal::SampleFormat::I16 => device.build_output_stream(
                 &config,
                 move |data: &mut [i16], _callback_info: &cpal::OutputCallbackInfo| {
-                    cpal_thread(data, &stream_buffer)
+                    cpal_thread(data, &stream_buffer, &mut fade, &mut prefill, limiter)
                 },
                 err_fn,
                 None,
@@ -356,7 +2112,7 @@ impl CpalPlayer {
             cpal::SampleFormat::I32 => device.build_output_stream(
                 &config,
                 move |data: &mut [i32], _callback_info: &cpal::OutputCallbackInfo| {
-                    cpal_thread(data, &stream_buffer)
+                    cpal_thread(data, &stream_buffer, &mut fade, &mut prefill, limiter)
                 },
                 err_fn,
                 None,
@@ -364,7 +2120,7 @@ impl CpalPlayer {
             cpal::SampleFormat::I64 => device.build_output_stream(
                 &config,
                 move |data: &mut [i64], _callback_info: &cpal::OutputCallbackInfo| {
-                    cpal_thread(data, &stream_buffer)
+                    cpal_thread(data, &stream_buffer, &mut fade, &mut prefill, limiter)
                 },
                 err_fn,
                 None,
@@ -372,7 +2128,7 @@ impl CpalPlayer {
             cpal::SampleFormat::U8 => device.build_output_stream(
                 &config,
                 move |data: &mut [u8], _callback_info: &cpal::OutputCallbackInfo| {
-                    cpal_thread(data, &stream_buffer)
+                    cpal_thread(data, &stream_buffer, &mut fade, &mut prefill, limiter)
                 },
                 err_fn,
                 None,
@@ -380,7 +2136,7 @@ impl CpalPlayer {
             cpal::SampleFormat::U16 => device.build_output_stream(
                 &config,
                 move |data: &mut [u16], _callback_info: &cpal::OutputCallbackInfo| {
-                    cpal_thread(data, &stream_buffer)
+                    cpal_thread(data, &stream_buffer, &mut fade, &mut prefill, limiter)
                 },
                 err_fn,
                 None,
@@ -388,7 +2144,7 @@ impl CpalPlayer {
             cpal::SampleFormat::U32 => device.build_output_stream(
                 &config,
                 move |data: &mut [u32], _callback_info: &cpal::OutputCallbackInfo| {
-                    cpal_thread(data, &stream_buffer)
+                    cpal_thread(data, &stream_buffer, &mut fade, &mut prefill, limiter)
                 },
                 err_fn,
                 None,
@@ -396,7 +2152,7 @@ impl CpalPlayer {
             cpal::SampleFormat::U64 => device.build_output_stream(
                 &config,
                 move |data: &mut [u64], _callback_info: &cpal::OutputCallbackInfo| {
-                    cpal_thread(data, &stream_buffer)
+                    cpal_thread(data, &stream_buffer, &mut fade, &mut prefill, limiter)
                 },
                 err_fn,
                 None,
@@ -404,7 +2160,7 @@ impl CpalPlayer {
             cpal::SampleFormat::F32 => device.build_output_stream(
                 &config,
                 move |data: &mut [f32], _callback_info: &cpal::OutputCallbackInfo| {
-                    cpal_thread(data, &stream_buffer)
+                    cpal_thread(data, &stream_buffer, &mut fade, &mut prefill, limiter)
                 },
                 err_fn,
                 None,
@@ -412,7 +2168,7 @@ impl CpalPlayer {
             cpal::SampleFormat::F64 => device.build_output_stream(
                 &config,
                 move |data: &mut [f64], _callback_info: &cpal::OutputCallbackInfo| {
-                    cpal_thread(data, &stream_buffer)
+                    cpal_thread(data, &stream_buffer, &mut fade, &mut prefill, limiter)
                 },
                 err_fn,
                 None,
@@ -430,13 +2186,21 @@ impl CpalPlayer {
 fn cpal_thread<T: Sample + FromSample<f32>>(
     outbuffer: &mut [T],
     audio_buffer: &Arc<Mutex<Vec<(f32, f32)>>>,
+    fade: &mut UnderrunFade,
+    prefill: &mut Prefill,
+    limiter: Option<SoftLimiter>,
 ) {
     let mut inbuffer = audio_buffer.lock().unwrap();
-    let outlen = std::cmp::min(outbuffer.len() / 2, inbuffer.len());
-    for (i, (in_l, in_r)) in inbuffer.drain(..outlen).enumerate() {
-        outbuffer[i * 2] = T::from_sample(in_l);
-        outbuffer[i * 2 + 1] = T::from_sample(in_r);
+    if !prefill.ready(inbuffer.len()) {
+        outbuffer.fill(T::from_sample(0.0f32));
+        return;
     }
+    if let Some(limiter) = limiter {
+        // only the samples played now, the rest is limited by the next callback
+        let available = std::cmp::min(outbuffer.len() / 2, inbuffer.len());
+        limiter.apply(&mut inbuffer[..available]);
+    }
+    fade.fill(outbuffer, &mut inbuffer);
 }
 
 impl rboy::AudioPlayer for CpalPlayer {
@@ -447,6 +2211,7 @@ impl rboy::AudioPlayer for CpalPlayer {
         );
 
         let mut buffer = self.buffer.lock().unwrap();
+        let gain = self.volume.gain() * self.gain;
 
         for (l, r) in buf_left.iter().zip(buf_right) {
             if buffer.len() > self.sample_rate as usize {
@@ -454,7 +2219,7 @@ impl rboy::AudioPlayer for CpalPlayer {
                 // This speeds up the resync after the turning on and off the speed limiter
                 return;
             }
-            buffer.push((*l, *r));
+            buffer.push((*l * gain, *r * gain));
         }
     }
 
@@ -467,63 +2232,162 @@ impl rboy::AudioPlayer for CpalPlayer {
     }
 }
 
+/// Run the input listener.
+///
+/// In the `menu`, Up and Down use the menu auto-repeat, if configured.
 fn run_input_listener(
     config: &AppConfig,
     exit: Arc<AtomicBool>,
     event_sender: Sender<rboy::input::Event>,
-) -> JoinHandle<()> {
+    menu: bool,
+) -> anyhow::Result<JoinHandle<()>> {
+    run_input_listener_from(config, exit, event_sender, menu, stdin_input::Stdin)
+}
+
+/// Run the input listener; without GPIO, the input commands are read from `stdin`
+fn run_input_listener_from(
+    config: &AppConfig,
+    exit: Arc<AtomicBool>,
+    event_sender: Sender<rboy::input::Event>,
+    menu: bool,
+    stdin: impl std::io::Read + Send + 'static,
+) -> anyhow::Result<JoinHandle<()>> {
+    if config.no_gpio {
+        debug!("GPIO disabled; reading input commands from stdin");
+        return Ok(stdin_input::run(stdin, exit, event_sender));
+    }
+
     let poll_interval = config.poll_interval();
+    let cores = config.affinity.input.clone();
     let power_switches = config
         .power_switches
         .iter()
-        .map(|ps| PowerSwitch {
-            gpio: gpio(ps.gpio, ps.active_low.unwrap_or(config.default_active_low)),
+        .map(|ps| {
+            Ok(PowerSwitch {
+                gpio: gpio(
+                    ps.gpio,
+                    ps.active_low.unwrap_or(config.default_active_low),
+                    &config.gpio_init,
+                )?,
+                debounce: ps.debounce().unwrap_or(config.default_debounce()),
+            })
         })
-        .collect();
-    let keys = config
-        .keys
+        .collect::<anyhow::Result<_>>()?;
+    let profile = ActiveProfile::load(config.roms_directory(), config);
+    debug!("Using key profile {}", profile.label());
+    let keys = profile
+        .keys(config)
         .iter()
-        .map(|kc| KeyConfig {
-            gpio: gpio(kc.gpio, kc.active_low.unwrap_or(config.default_active_low)),
-            keycode: kc.keycode.keycode(),
-            debounce: kc.debounce().unwrap_or(config.default_debounce()),
-            repeat: if kc.repeat {
-                Some(rboy::input::RepeatConfig {
-                    delay: kc
-                        .repeat_delay()
-                        .expect("Repeat delay must be set if repeat is true"),
-                    rate: kc
-                        .repeat_rate()
-                        .expect("Repeat rate must be set if repeat is true"),
-                })
-            } else {
-                None
-            },
+        .map(|kc| {
+            let keycode = config.input_rotation.apply(kc.keycode.keycode());
+            Ok(KeyConfig {
+                gpio: gpio(
+                    kc.gpio,
+                    kc.active_low.unwrap_or(config.default_active_low),
+                    &config.gpio_init,
+                )?,
+                keycode,
+                debounce: kc.debounce().unwrap_or(config.default_debounce()),
+                initial_state: kc.initial_state,
+                filter_samples: kc.filter_samples,
+                mode: kc.mode,
+                repeat: key_repeat(kc, keycode, menu.then_some(&config.menu)),
+            })
         })
-        .collect();
+        .collect::<anyhow::Result<_>>()?;
 
     let config = InputListenerConfig {
         exit,
         power_switches,
         keys,
         poll_interval,
+        event_log_size: INPUT_EVENT_LOG_SIZE,
+        dump_event_log: DUMP_INPUT_EVENT_LOG.get_or_init(Default::default).clone(),
+        latency: INPUT_LATENCY.get().cloned(),
+        max_key_events_per_sec: config.max_key_events_per_sec,
     };
-    thread::spawn(move || InputListener::new(config, event_sender).run())
+    Ok(thread::spawn(move || {
+        affinity::pin_current_thread("input", &cores);
+        InputListener::new(config, event_sender).run()
+    }))
 }
 
-fn gpio(pin: u8, active_low: bool) -> RaspberryGpio {
-    RaspberryGpio::try_new(pin, active_low).expect("Could not connect to GPIO")
+/// Get the auto-repeat of a key emitting `keycode`, once rotated;
+/// in the menu, navigation keys use the menu repeat if configured
+fn key_repeat(
+    key: &app_config::KeyConfig,
+    keycode: rboy::KeypadKey,
+    menu: Option<&app_config::MenuConfig>,
+) -> Option<rboy::input::RepeatConfig> {
+    let navigation = matches!(keycode, rboy::KeypadKey::Up | rboy::KeypadKey::Down);
+    if navigation && let Some((delay, rate)) = menu.and_then(|menu| menu.repeat()) {
+        return Some(rboy::input::RepeatConfig { delay, rate });
+    }
+
+    // the config validation rejects repeating keys without timing
+    key.repeat_timing()
+        .map(|(delay, rate)| rboy::input::RepeatConfig { delay, rate })
+}
+
+extern "C" fn on_sigusr1(_: libc::c_int) {
+    if let Some(dump) = DUMP_INPUT_EVENT_LOG.get() {
+        dump.store(true, std::sync::atomic::Ordering::SeqCst);
+    }
+}
+
+fn gpio(pin: u8, active_low: bool, init: &GpioInitConfig) -> anyhow::Result<RaspberryGpio> {
+    retry::retry(init.retries, init.backoff(), || {
+        RaspberryGpio::try_new(pin, active_low)
+    })
+    .map_err(|e| anyhow::anyhow!("Could not connect to GPIO {pin}: {e:#}"))
 }
 
 fn log_config(config: &AppConfig) {
     info!("Configuration:");
-    info!("  Rom Path: {}", config.roms_directory.display());
+    for dir in &config.roms_directories {
+        info!("  Rom Path: {}", dir.display());
+    }
     info!(
         "  Default debounce: {}",
         config.default_debounce().as_millis()
     );
     info!("  Default active_low: {}", config.default_active_low);
     info!("  Poll interval: {}", config.poll_interval().as_millis());
+    if let Some(max) = config.max_key_events_per_sec {
+        info!("  Max key events per second: {max}");
+    }
+    info!("  No GPIO: {}", config.no_gpio);
+    info!("  Boot last game: {}", config.boot_last_game);
+    info!("  Input rotation: {:?}", config.input_rotation);
+    if let Some(speed) = &config.analog_speed {
+        info!(
+            "  Analog speed: {} ({}..{} -> {}x..{}x)",
+            speed.adc.display(),
+            speed.raw_min,
+            speed.raw_max,
+            speed.min_speed,
+            speed.max_speed
+        );
+    }
+    info!(
+        "  GPIO init: {} retries, backoff {} ms",
+        config.gpio_init.retries,
+        config.gpio_init.backoff().as_millis()
+    );
+    if let Some(attract) = &config.attract {
+        info!(
+            "  Attract mode: {} with replay {} after {}s idle",
+            attract.game.display(),
+            attract.replay.display(),
+            attract.idle_timeout().as_secs()
+        );
+    }
+    if let Some(key) = &config.force_classic_key {
+        info!("  Force classic key: {key}");
+    }
+    if let Some(key) = &config.force_color_key {
+        info!("  Force color key: {key}");
+    }
     info!("  Keys:");
     for key in &config.keys {
         info!("    GPIO: {}", key.gpio);
@@ -539,6 +2403,40 @@ fn log_config(config: &AppConfig) {
         if let Some(rate) = key.repeat_rate() {
             info!("    Repeat Rate (ms): {}", rate.as_millis());
         }
+        if let Some(initial_state) = key.initial_state {
+            info!("    Initial State: {initial_state:?}");
+        }
+        if let Some(samples) = key.filter_samples {
+            info!("    Filter Samples: {samples}");
+        }
+        info!("    Mode: {:?}", key.mode);
+    }
+    for profile in &config.profiles {
+        info!(
+            "  Key profile {}: {} keys",
+            profile.name,
+            profile.keys.len()
+        );
+    }
+    info!("  Hotkeys:");
+    for (name, keys) in [
+        ("Brightness Up", &config.hotkeys.brightness_up),
+        ("Brightness Down", &config.hotkeys.brightness_down),
+        ("Gamma Up", &config.hotkeys.gamma_up),
+        ("Gamma Down", &config.hotkeys.gamma_down),
+    ] {
+        if let Some(keys) = keys {
+            info!("    {name}: {keys:?}");
+        }
+    }
+    if let Some(keys) = &config.hotkeys.menu {
+        info!("    Menu: {keys:?}");
+    }
+    if let Some(keys) = &config.hotkeys.next_profile {
+        info!("    Next Profile: {keys:?}");
+    }
+    if let Some(keys) = &config.hotkeys.screenshot {
+        info!("    Screenshot: {keys:?}");
     }
     info!("  Power Switches:");
     for ps in &config.power_switches {
@@ -550,6 +2448,57 @@ fn log_config(config: &AppConfig) {
     }
 }
 
+/// Get the state the application starts in.
+///
+/// A session bundle to play back or a mode given on the command line comes first; otherwise
+/// the ROM given on the command line runs, and without one the menu is shown.
+fn initial_state(
+    args: &args::Args,
+    config: &Rc<AppConfig>,
+    playback: Option<AppState>,
+    boot: Boot,
+) -> AppState {
+    match (playback, &args.rom_path) {
+        (Some(playback), _) => playback,
+        (None, _) if args.calibrate => AppState::Calibrate {
+            config: config.clone(),
+        },
+        (None, _) if args.test_card => AppState::TestCard {
+            config: config.clone(),
+        },
+        (None, _) if args.measure_latency => AppState::LatencyTest {
+            config: config.clone(),
+        },
+        (None, Some(rom_path)) => AppState::Emulator {
+            config: config.clone(),
+            rom_file: rom_path.clone(),
+            mode: LaunchMode::Color,
+            save_slot: 0,
+            resume: false,
+        },
+        (None, None) => match boot {
+            Boot::LastGame(rom_file) => {
+                info!("Booting last game: {}", rom_file.display());
+                let mode = config
+                    .remember_launch_options
+                    .then(|| GameCache::load(config.roms_directory()))
+                    .and_then(|cache| cache.launch_options(&rom_file).map(|options| options.mode))
+                    .unwrap_or(LaunchMode::Color);
+                AppState::Emulator {
+                    config: config.clone(),
+                    rom_file,
+                    mode,
+                    save_slot: 0,
+                    resume: true,
+                }
+            }
+            Boot::Menu { .. } => AppState::Menu {
+                config: config.clone(),
+            },
+        },
+    }
+}
+
 /// Initialize application logging with the specified log level
 fn init_app_log(level: args::LogLevel) -> anyhow::Result<()> {
     env_logger::Builder::new()
@@ -557,3 +2506,361 @@ fn init_app_log(level: args::LogLevel) -> anyhow::Result<()> {
         .try_init()
         .map_err(|e| anyhow::anyhow!("Failed to initialize logger: {}", e))
 }
+
+#[cfg(test)]
+mod tests {
+
+    use std::sync::mpsc::TrySendError;
+    use std::time::Duration;
+
+    use super::*;
+
+    fn test_config() -> Rc<AppConfig> {
+        Rc::new(
+            toml::from_str(
+                "roms_directory = \"/roms\"\ndefault_debounce_ms = 50\ndefault_active_low = true\npoll_interval_ms = 10",
+            )
+            .unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_should_start_in_menu_without_rom() {
+        let config = test_config();
+        let args = |argv: &[&str]| -> args::Args {
+            argh::FromArgs::from_args(&["rboy-legogb"], argv).unwrap()
+        };
+
+        assert!(matches!(
+            initial_state(&args(&[]), &config, None, Boot::Menu { crashed: false }),
+            AppState::Menu { .. }
+        ));
+        assert!(matches!(
+            initial_state(&args(&["/roms/tetris.gb"]), &config, None, Boot::Menu { crashed: false }),
+            AppState::Emulator { rom_file, resume: false, .. } if rom_file == Path::new("/roms/tetris.gb")
+        ));
+    }
+
+    #[test]
+    fn test_should_resume_last_game_at_boot() {
+        let config = test_config();
+        let args: args::Args = argh::FromArgs::from_args(&["rboy-legogb"], &[]).unwrap();
+
+        assert!(matches!(
+            initial_state(&args, &config, None, Boot::LastGame(PathBuf::from("/roms/tetris.gb"))),
+            AppState::Emulator { rom_file, resume: true, .. } if rom_file == Path::new("/roms/tetris.gb")
+        ));
+    }
+
+    #[test]
+    fn test_should_correct_cgb_colors_by_default() {
+        let config = test_config();
+        let options = |argv: &[&str]| {
+            EmulatorOptions::from_args(&argh::FromArgs::from_args(&["rboy-legogb"], argv).unwrap())
+        };
+        let rom_file = Path::new("/roms/zelda.gbc");
+
+        assert!(cgb_color_correction(&config, rom_file, &options(&[])));
+        assert!(!cgb_color_correction(
+            &config,
+            rom_file,
+            &options(&["--no-cgb-color-correct"])
+        ));
+    }
+
+    #[test]
+    fn test_should_exit_on_shutdown_signal() {
+        let config = test_config();
+        let exit = AtomicBool::new(false);
+        let menu = || AppState::Menu {
+            config: config.clone(),
+        };
+
+        assert!(matches!(next_state(menu(), &exit), AppState::Menu { .. }));
+        // the signal handler sets the flag
+        exit.store(true, std::sync::atomic::Ordering::SeqCst);
+        assert!(matches!(next_state(menu(), &exit), AppState::Exit));
+    }
+
+    #[test]
+    fn test_should_use_menu_repeat_for_navigation_in_menu() {
+        let key = |keycode: &str| -> app_config::KeyConfig {
+            toml::from_str(&format!(
+                "gpio = 1\nkeycode = \"{keycode}\"\nrepeat = true\nrepeat_delay_ms = 500\nrepeat_rate_ms = 100"
+            ))
+            .unwrap()
+        };
+        let menu: app_config::MenuConfig =
+            toml::from_str("repeat_delay_ms = 200\nrepeat_rate_ms = 40").unwrap();
+        let timing = |key: app_config::KeyConfig,
+                      rotation: rotation::Rotation,
+                      menu: Option<&app_config::MenuConfig>| {
+            key_repeat(&key, rotation.apply(key.keycode.keycode()), menu)
+                .map(|repeat| (repeat.delay.as_millis(), repeat.rate.as_millis()))
+        };
+        let r0 = rotation::Rotation::R0;
+
+        assert_eq!(timing(key("UP"), r0, Some(&menu)), Some((200, 40)));
+        assert_eq!(timing(key("DOWN"), r0, Some(&menu)), Some((200, 40)));
+        // other keys and gameplay use the key configuration
+        assert_eq!(timing(key("A"), r0, Some(&menu)), Some((500, 100)));
+        assert_eq!(timing(key("UP"), r0, None), Some((500, 100)));
+        // without a menu repeat, the key configuration is used in the menu too
+        assert_eq!(
+            timing(key("UP"), r0, Some(&Default::default())),
+            Some((500, 100))
+        );
+        // the keys navigating once rotated
+        let r90 = rotation::Rotation::R90;
+        assert_eq!(timing(key("LEFT"), r90, Some(&menu)), Some((200, 40)));
+        assert_eq!(timing(key("UP"), r90, Some(&menu)), Some((500, 100)));
+    }
+
+    #[test]
+    fn test_should_run_input_without_gpio() {
+        // GPIO isn't available here: the pins configured would fail to initialize
+        let config: AppConfig = toml::from_str(
+            "roms_directory = \"/roms\"\ndefault_debounce_ms = 50\ndefault_active_low = true\npoll_interval_ms = 10\nno_gpio = true\nkeymap = { 5 = \"UP\", 17 = \"A\" }\n[[powerswitch]]\ngpio = 3",
+        )
+        .unwrap();
+        let exit = Arc::new(AtomicBool::new(false));
+        let (sender, receiver) = mpsc::channel();
+        let stdin = std::io::Cursor::new("down A\n");
+
+        let listener = run_input_listener_from(&config, exit, sender, false, stdin).unwrap();
+        listener.join().unwrap();
+        assert_eq!(
+            receiver.try_iter().collect::<Vec<_>>(),
+            vec![(KeyEvent::Down, rboy::KeypadKey::A)]
+        );
+    }
+
+    #[test]
+    fn test_should_output_silence_until_prefilled() {
+        let buffer = Arc::new(Mutex::new(vec![(0.5, -0.5); 2]));
+        let mut fade = UnderrunFade::new(0);
+        let mut prefill = Prefill::new(4);
+        let mut output = [1.0f32; 4];
+
+        cpal_thread(&mut output, &buffer, &mut fade, &mut prefill, None);
+        assert_eq!(output, [0.0; 4]);
+        // the queued samples are kept for the start
+        assert_eq!(buffer.lock().unwrap().len(), 2);
+
+        buffer.lock().unwrap().extend([(0.25, -0.25); 2]);
+        cpal_thread(&mut output, &buffer, &mut fade, &mut prefill, None);
+        assert_eq!(output, [0.5, -0.5, 0.5, -0.5]);
+
+        // once started, it plays what's queued even below the threshold
+        cpal_thread(&mut output, &buffer, &mut fade, &mut prefill, None);
+        assert_eq!(output, [0.25, -0.25, 0.25, -0.25]);
+    }
+
+    #[test]
+    fn test_should_buffer_frames_up_to_queue_depth() {
+        let (sender, receiver) = video_channel(2);
+        assert!(sender.try_send(Frame::new(vec![1], 1)).is_ok());
+        assert!(sender.try_send(Frame::new(vec![2], 2)).is_ok());
+        assert!(matches!(
+            sender.try_send(Frame::new(vec![3], 3)),
+            Err(TrySendError::Full(_))
+        ));
+        assert_eq!(receiver.recv().unwrap().data, vec![1]);
+
+        // depth 0 would be a rendezvous channel, where every frame is dropped
+        let (sender, _receiver) = video_channel(0);
+        assert!(sender.try_send(Frame::new(vec![1], 1)).is_ok());
+    }
+
+    #[test]
+    fn test_should_wait_for_next_frame_until_disconnected() {
+        let (sender, receiver) = video_channel(1);
+        assert_eq!(
+            next_frame(&receiver, Duration::from_millis(10)).unwrap(),
+            None
+        );
+        sender.send(Frame::new(vec![1], 1)).unwrap();
+        assert_eq!(
+            next_frame(&receiver, Duration::from_millis(10))
+                .unwrap()
+                .map(|frame| frame.data),
+            Some(vec![1])
+        );
+
+        // a long poll interval doesn't delay the exit when the CPU thread hangs up
+        let cpu_thread = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            drop(sender);
+        });
+        let start = std::time::Instant::now();
+        assert!(next_frame(&receiver, Duration::from_secs(10)).is_err());
+        assert!(start.elapsed() < Duration::from_secs(5));
+        cpu_thread.join().unwrap();
+    }
+
+    #[test]
+    fn test_should_not_write_back_scratch_save() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let rom_file = dir.path().join("demo.gb");
+        let mut rom = vec![0; 0x8000];
+        rom[0x147] = 0x03; // MBC1 + RAM + battery
+        rom[0x149] = 0x02; // 8 KiB
+        std::fs::write(&rom_file, rom).unwrap();
+        let config = test_config();
+
+        let cpu = construct_cpu(
+            &rom_file,
+            false,
+            &BatterySave::Scratch(None),
+            true,
+            None,
+            &config,
+        );
+        drop(cpu.unwrap());
+        assert!(!rboy::battery_save_path(&rom_file).exists());
+
+        let cpu = construct_cpu(&rom_file, false, &BatterySave::Slot(0), true, None, &config);
+        drop(cpu.unwrap());
+        assert!(rboy::battery_save_path(&rom_file).exists());
+    }
+
+    #[test]
+    fn test_should_keep_device_across_pause() {
+        let mut rom = vec![0; 0x8000];
+        // entry point: jr -2
+        rom[0x100..0x102].copy_from_slice(&[0x18, 0xFE]);
+        let device = Box::new(Device::new_from_buffer(rom, true, None).unwrap());
+
+        let (gb_event_sender, gb_event_receiver) = mpsc::channel();
+        let (video_sender, video_receiver) = video_channel(1);
+        let cpu_thread = thread::spawn(move || {
+            run_cpu(
+                device,
+                FrameSender::new(video_sender),
+                gb_event_receiver,
+                None,
+                None,
+                None,
+                AutoFrameSkip::new(0),
+            )
+        });
+
+        assert!(video_receiver.recv_timeout(Duration::from_secs(5)).is_ok());
+        gb_event_sender.send(GBEvent::Pause).unwrap();
+        // drain the frames produced before the pause was handled
+        let mut pending = 0;
+        while video_receiver
+            .recv_timeout(Duration::from_millis(100))
+            .is_ok()
+        {
+            pending += 1;
+            assert!(pending <= 2, "CPU is still running while paused");
+        }
+
+        gb_event_sender.send(GBEvent::Resume).unwrap();
+        assert!(video_receiver.recv_timeout(Duration::from_secs(5)).is_ok());
+
+        drop(gb_event_sender);
+        drop(video_receiver);
+        let device = cpu_thread.join().unwrap();
+        assert!(device.frame_count() >= 2);
+    }
+
+    #[test]
+    fn test_should_quick_save_and_load() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let rom_file = dir.path().join("tetris.gb");
+        let mut rom = vec![0; 0x8000];
+        // entry point: jr -2
+        rom[0x100..0x102].copy_from_slice(&[0x18, 0xFE]);
+        let mut device = Device::new_from_buffer(rom, true, None).unwrap();
+        let (gb_event_sender, mut gb_event_receiver) = mpsc::channel();
+        let mut messages = vec![];
+
+        quick_state(
+            SlotMode::Load,
+            &rom_file,
+            false,
+            &gb_event_sender,
+            |message| messages.push(message.to_string()),
+        );
+        assert!(gb_event_receiver.try_recv().is_err());
+
+        // the CPU thread saves the state and reports the outcome
+        std::thread::scope(|scope| {
+            let (device, gb_event_receiver) = (&mut device, &mut gb_event_receiver);
+            scope.spawn(move || {
+                let Ok(GBEvent::SaveState { path, outcome }) = gb_event_receiver.recv() else {
+                    panic!("expected a save state event");
+                };
+                outcome.send(save_state(device, &path)).unwrap();
+            });
+            quick_state(
+                SlotMode::Save,
+                &rom_file,
+                false,
+                &gb_event_sender,
+                |message| messages.push(message.to_string()),
+            );
+        });
+        assert!(save_state::quick_slot_path(&rom_file).is_file());
+
+        while device.frame_count() == 0 {
+            device.do_cycle();
+        }
+        // the CPU thread loads the state and reports the outcome
+        std::thread::scope(|scope| {
+            let (device, gb_event_receiver) = (&mut device, &mut gb_event_receiver);
+            scope.spawn(move || {
+                let Ok(GBEvent::LoadState {
+                    path,
+                    backup,
+                    outcome,
+                }) = gb_event_receiver.recv()
+                else {
+                    panic!("expected a load state event");
+                };
+                outcome
+                    .send(save_state::load(device, &path, backup))
+                    .unwrap();
+            });
+            quick_state(
+                SlotMode::Load,
+                &rom_file,
+                false,
+                &gb_event_sender,
+                |message| messages.push(message.to_string()),
+            );
+        });
+        assert_eq!(device.frame_count(), 0);
+
+        // a failed save is reported
+        let missing = dir.path().join("missing").join("tetris.gb");
+        std::thread::scope(|scope| {
+            let (device, gb_event_receiver) = (&mut device, &mut gb_event_receiver);
+            scope.spawn(move || {
+                let Ok(GBEvent::SaveState { path, outcome }) = gb_event_receiver.recv() else {
+                    panic!("expected a save state event");
+                };
+                outcome.send(save_state(device, &path)).unwrap();
+            });
+            quick_state(
+                SlotMode::Save,
+                &missing,
+                false,
+                &gb_event_sender,
+                |message| messages.push(message.to_string()),
+            );
+        });
+
+        assert_eq!(
+            messages,
+            vec![
+                "No quick save",
+                "Saved",
+                "Loaded",
+                "Could not create state file"
+            ]
+        );
+    }
+}
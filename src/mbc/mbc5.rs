@@ -14,12 +14,18 @@ pub struct MBC5 {
     has_battery: bool,
     rombanks: usize,
     rambanks: usize,
+    /// The cartridge has a rumble motor, driven by bit 3 of the RAM bank register
+    #[serde(default)]
+    has_rumble: bool,
+    #[serde(default)]
+    rumble: bool,
 }
 
 impl MBC5 {
     pub fn new(data: Vec<u8>) -> StrResult<MBC5> {
         let subtype = data[0x147];
         let has_battery = matches!(subtype, 0x1B | 0x1E);
+        let has_rumble = matches!(subtype, 0x1C..=0x1E);
         let rambanks = match subtype {
             0x1A | 0x1B | 0x1D | 0x1E => ram_banks(data[0x149]),
             _ => 0,
@@ -37,6 +43,8 @@ impl MBC5 {
             has_battery,
             rombanks,
             rambanks,
+            has_rumble,
+            rumble: false,
         };
 
         Ok(res)
@@ -69,6 +77,10 @@ impl Mbc for MBC5 {
                 self.rombank =
                     ((self.rombank & 0x0FF) | (((v & 0x1) as usize) << 8)) % self.rombanks
             }
+            0x4000..=0x5FFF if self.has_rumble => {
+                self.rumble = v & 0x08 != 0;
+                self.rambank = ((v & 0x07) as usize) % self.rambanks.max(1);
+            }
             0x4000..=0x5FFF => self.rambank = ((v & 0x0F) as usize) % self.rambanks,
             0x6000..=0x7FFF => { /* ? */ }
             _ => panic!("Could not write to {:04X} (MBC5)", a),
@@ -86,6 +98,10 @@ impl Mbc for MBC5 {
         self.has_battery
     }
 
+    fn rumble(&self) -> bool {
+        self.rumble
+    }
+
     fn loadram(&mut self, ramdata: &[u8]) -> StrResult<()> {
         if ramdata.len() != self.ram.len() {
             return Err("Loaded RAM has incorrect length");
@@ -12,6 +12,9 @@ mod mbc2;
 mod mbc3;
 mod mbc5;
 
+/// Largest ROM size supported by the standard MBCs (MBC5 with 512 banks)
+pub const MAX_ROM_SIZE: usize = 8 * 1024 * 1024;
+
 #[typetag::serde(tag = "type")]
 pub trait Mbc: Send {
     fn readrom(&self, a: u16) -> u8;
@@ -21,6 +24,10 @@ pub trait Mbc: Send {
     fn check_and_reset_ram_updated(&mut self) -> bool;
 
     fn is_battery_backed(&self) -> bool;
+    /// Whether the rumble motor of the cartridge is on
+    fn rumble(&self) -> bool {
+        false
+    }
     fn loadram(&mut self, ramdata: &[u8]) -> StrResult<()>;
     fn dumpram(&self) -> Vec<u8>;
 
@@ -50,6 +57,9 @@ pub fn get_mbc(data: Vec<u8>, skip_checksum: bool) -> StrResult<Box<dyn Mbc + 's
     if data.len() < 0x150 {
         return Err("Rom size to small");
     }
+    if data.len() > MAX_ROM_SIZE {
+        return Err("Rom size too large");
+    }
     if !skip_checksum {
         check_checksum(&data)?;
     }
@@ -70,14 +80,23 @@ pub struct FileBackedMBC {
 }
 
 impl FileBackedMBC {
-    pub fn new(rompath: path::PathBuf, skip_checksum: bool) -> StrResult<FileBackedMBC> {
-        let mut data = vec![];
-        File::open(&rompath)
-            .and_then(|mut f| f.read_to_end(&mut data))
+    pub fn new(
+        rompath: path::PathBuf,
+        save_slot: usize,
+        skip_checksum: bool,
+    ) -> StrResult<FileBackedMBC> {
+        let mut file = File::open(&rompath).map_err(|_| "Could not read ROM")?;
+        // don't allocate a huge buffer for a corrupt or wrong file
+        let size = file.metadata().map_err(|_| "Could not read ROM")?.len();
+        if size > MAX_ROM_SIZE as u64 {
+            return Err("Rom size too large");
+        }
+        let mut data = Vec::with_capacity(size as usize);
+        file.read_to_end(&mut data)
             .map_err(|_| "Could not read ROM")?;
         let mut mbc = get_mbc(data, skip_checksum)?;
 
-        let rampath = rompath.with_extension("gbsave");
+        let rampath = battery_save_slot_path(&rompath, save_slot);
 
         if mbc.is_battery_backed() {
             match fs::File::open(&rampath) {
@@ -122,6 +141,10 @@ impl Mbc for FileBackedMBC {
         self.mbc.is_battery_backed()
     }
 
+    fn rumble(&self) -> bool {
+        self.mbc.rumble()
+    }
+
     fn loadram(&mut self, ramdata: &[u8]) -> StrResult<()> {
         self.mbc.loadram(ramdata)
     }
@@ -143,11 +166,43 @@ impl Drop for FileBackedMBC {
                 Ok(f) => f,
                 Err(..) => return,
             };
-            let _ = file.write_all(&self.mbc.dumpram());
+            let _ = write_save(&mut file, &self.mbc.dumpram());
         }
     }
 }
 
+/// File which can be synced to the storage
+trait SyncFile: Write {
+    fn sync_all(&mut self) -> io::Result<()>;
+}
+
+impl SyncFile for File {
+    fn sync_all(&mut self) -> io::Result<()> {
+        File::sync_all(self)
+    }
+}
+
+/// Write the save data and wait for it to reach the storage, so that it survives a power cut
+fn write_save(file: &mut impl SyncFile, data: &[u8]) -> io::Result<()> {
+    file.write_all(data)?;
+    file.flush()?;
+    file.sync_all()
+}
+
+/// Path of the battery-backed RAM save file for the given ROM
+pub fn battery_save_path(rompath: &path::Path) -> path::PathBuf {
+    rompath.with_extension("gbsave")
+}
+
+/// Path of the save file in the given slot for the given ROM, for players keeping several
+/// playthroughs; slot 0 is [`battery_save_path`], the others are `<rom>.<slot>.gbsave`
+pub fn battery_save_slot_path(rompath: &path::Path, slot: usize) -> path::PathBuf {
+    match slot {
+        0 => battery_save_path(rompath),
+        slot => rompath.with_extension(format!("{slot}.gbsave")),
+    }
+}
+
 fn ram_banks(v: u8) -> usize {
     match v {
         1 =>
@@ -195,4 +250,58 @@ mod test {
         data[0x14D] = (-(0x14D_i32 - 0x134_i32) * 2) as u8;
         super::check_checksum(&data).unwrap();
     }
+
+    #[test]
+    fn save_is_synced() {
+        use std::io::Write;
+
+        struct Synced {
+            file: std::fs::File,
+            synced: bool,
+        }
+
+        impl Write for Synced {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.synced = false;
+                self.file.write(buf)
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                self.file.flush()
+            }
+        }
+
+        impl super::SyncFile for Synced {
+            fn sync_all(&mut self) -> std::io::Result<()> {
+                self.file.sync_all()?;
+                self.synced = true;
+                Ok(())
+            }
+        }
+
+        let temp = tempfile::NamedTempFile::new().unwrap();
+        let mut file = Synced {
+            file: temp.reopen().unwrap(),
+            synced: false,
+        };
+        super::write_save(&mut file, &[1, 2, 3]).unwrap();
+        assert!(file.synced);
+        assert_eq!(std::fs::read(temp.path()).unwrap(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn rom_size_limit() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        file.as_file()
+            .set_len(super::MAX_ROM_SIZE as u64 + 1)
+            .unwrap();
+        assert_eq!(
+            super::FileBackedMBC::new(file.path().to_path_buf(), 0, true).err(),
+            Some("Rom size too large")
+        );
+        assert!(super::get_mbc(vec![0; super::MAX_ROM_SIZE + 1], true).is_err());
+
+        file.as_file().set_len(0x8000).unwrap();
+        assert!(super::FileBackedMBC::new(file.path().to_path_buf(), 0, true).is_ok());
+    }
 }
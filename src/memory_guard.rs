@@ -0,0 +1,81 @@
+use std::path::Path;
+
+/// Largest cartridge RAM (MBC5, 16 banks of 8 KiB)
+const CARTRIDGE_RAM: u64 = 128 * 1024;
+/// Device state besides the ROM and the cartridge RAM: work and video RAM, CPU, audio buffers
+const DEVICE_STATE: u64 = 256 * 1024;
+/// Size of a frame sent from the CPU to the display
+const FRAME: u64 = (rboy::SCREEN_W * rboy::SCREEN_H * 3) as u64;
+/// System file reporting the available memory
+const MEMINFO: &str = "/proc/meminfo";
+
+/// Estimate the memory in bytes needed to run a ROM of `rom_size` bytes.
+///
+/// It counts the device, the frames queued to the display (plus the one being drawn and the last one kept
+/// for the overlays) and, if `save_states` are enabled, the buffer of a save state being written.
+pub fn estimate(rom_size: u64, video_queue: usize, save_states: bool) -> u64 {
+    let device = rom_size + CARTRIDGE_RAM + DEVICE_STATE;
+    let frames = FRAME * (video_queue.max(1) as u64 + 2);
+    let save_state = if save_states {
+        CARTRIDGE_RAM + DEVICE_STATE
+    } else {
+        0
+    };
+
+    device + frames + save_state
+}
+
+/// Check that there is enough memory available to run `rom`, keeping `margin` bytes free.
+///
+/// Returns the message to show if there isn't; the check is skipped if the available memory is unknown.
+pub fn check(rom: &Path, video_queue: usize, save_states: bool, margin: u64) -> Result<(), String> {
+    let Some(available) = available_memory() else {
+        debug!("Available memory unknown; skipping the memory check");
+        return Ok(());
+    };
+    let rom_size = std::fs::metadata(rom).map(|m| m.len()).unwrap_or_default();
+    let needed = estimate(rom_size, video_queue, save_states);
+    debug!("Memory needed: {needed} bytes (+{margin} margin), available: {available} bytes");
+    if needed + margin > available {
+        return Err(format!(
+            "Not enough memory: {} KiB needed, {} KiB free",
+            (needed + margin) / 1024,
+            available / 1024
+        ));
+    }
+
+    Ok(())
+}
+
+/// Memory available for new processes, in bytes
+fn available_memory() -> Option<u64> {
+    let meminfo = std::fs::read_to_string(MEMINFO).ok()?;
+    meminfo.lines().find_map(|line| {
+        let kib = line
+            .strip_prefix("MemAvailable:")?
+            .trim()
+            .strip_suffix("kB")?;
+        kib.trim().parse::<u64>().ok().map(|kib| kib * 1024)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_should_estimate_memory_needed() {
+        let frame = 160 * 144 * 3;
+        assert_eq!(
+            estimate(1024 * 1024, 1, false),
+            1024 * 1024 + CARTRIDGE_RAM + DEVICE_STATE + frame * 3
+        );
+        // deeper queues and save states need more
+        assert_eq!(
+            estimate(1024 * 1024, 4, true) - estimate(1024 * 1024, 1, false),
+            frame * 3 + CARTRIDGE_RAM + DEVICE_STATE
+        );
+        assert_eq!(estimate(0, 0, false), estimate(0, 1, false));
+    }
+}
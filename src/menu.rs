@@ -1,3 +1,11 @@
+mod background;
+mod duplicates;
+mod frame_limiter;
+mod game_cache;
+mod input_test;
+mod save_picker;
+mod splash_wait;
+
 use std::fs::File;
 use std::io::Write;
 use std::path::{Path, PathBuf};
@@ -7,20 +15,35 @@ use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::{Receiver, TryRecvError};
 use std::time::{Duration, Instant};
 
-use font8x8::{BASIC_FONTS, UnicodeFonts};
 use rboy::KeypadKey;
-use rboy::framebuffer::Framebuffer;
+use rboy::framebuffer::{Framebuffer, GLYPH_SIZE, ScaleContext};
 use rboy::input::KeyEvent;
+use rboy::input::power::{PowerAction, PowerRunner, SystemPowerRunner};
+use serde::{Deserialize, Serialize};
 
 use crate::AppState;
-use crate::app_config::AppConfig;
+use crate::app_config::{AppConfig, Keycode};
+use crate::attract::{AttractTimer, Trigger};
+use crate::saves::{self, SaveInfo};
+use crate::splash::Image;
+use crate::stdin_input;
+
+use self::background::Background;
+pub use self::background::MenuBackground;
+pub use self::duplicates::DuplicateRoms;
+use self::frame_limiter::FrameLimiter;
+pub use self::game_cache::{GameCache, LaunchOptions};
+use self::input_test::{GRID_H, GRID_W, InputTest, LAYOUT};
+use self::save_picker::SavePicker;
+pub use self::splash_wait::SplashWait;
 
 const LINE_H: usize = 16;
 const PADDING_Y: usize = 16;
 const PADDING_X: usize = 16;
-const SPACE_SIZE: usize = 8;
-const SUBTITLE: &str = "Press start to play a game";
+const TITLE_SCALE: f32 = 1.5;
+const SUBTITLE: &str = "A/Start: play, Select: save data";
 const NO_GAMES: &str = "You have no games in your ROMs directory";
+const DUPLICATE_MARK: &str = " (dup)";
 
 const GAMEBOY_SPLASH_COLOR_RED: u8 = 0xc4;
 const GAMEBOY_SPLASH_COLOR_GREEN: u8 = 0xcf;
@@ -28,6 +51,9 @@ const GAMEBOY_SPLASH_COLOR_BLUE: u8 = 0xa1;
 const SPLASH_TEXT: &str = "Nintendo";
 const COLOR_BLACK: u16 = 0x0000;
 const COLOR_WHITE: u16 = 0xffff;
+const COLOR_PRESSED: u16 = 0x07e0;
+const COLOR_TESTED: u16 = 0x8410;
+const COLOR_UNTESTED: u16 = 0x4208;
 
 /// Gameboy boot sound bytes
 const GB_BOOT_SOUND: &[u8] = include_bytes!("../assets/gb_boot.wav");
@@ -39,14 +65,164 @@ pub struct AppMenu {
     event_receiver: Receiver<rboy::input::Event>,
     exit: Arc<AtomicBool>,
     games: Vec<GameEntry>,
+    /// Entries listed after the games
+    system_entries: Vec<SystemEntry>,
+    background: Background,
+    /// Message shown once after the splash screen
+    notice: Option<String>,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum Platform {
+/// Platform a ROM is made for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+pub enum Platform {
+    #[serde(rename = "gb")]
     GameBoy,
+    #[serde(rename = "gbc")]
     GameBoyColor,
 }
 
+/// Hardware the emulator runs a game on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LaunchMode {
+    /// Original Game Boy (DMG)
+    Classic,
+    /// Game Boy Color; classic games run in compatibility mode
+    Color,
+}
+
+/// How a game is launched
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Launch {
+    pub mode: LaunchMode,
+    /// Battery save file the game uses, see [`rboy::battery_save_slot_path`]
+    pub save_slot: usize,
+    /// Continue from the resume state the game was left in
+    pub resume: bool,
+}
+
+impl Launch {
+    /// Launch in the given mode with the default save file
+    pub fn new(mode: LaunchMode) -> Self {
+        Self {
+            mode,
+            save_slot: 0,
+            resume: false,
+        }
+    }
+}
+
+/// Actions available on the save data page
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SaveAction {
+    Backup,
+    Delete,
+    Back,
+}
+
+impl SaveAction {
+    const ALL: [SaveAction; 3] = [SaveAction::Backup, SaveAction::Delete, SaveAction::Back];
+
+    fn label(&self) -> &'static str {
+        match self {
+            SaveAction::Backup => "Back up",
+            SaveAction::Delete => "Delete",
+            SaveAction::Back => "Back",
+        }
+    }
+}
+
+/// Entries of the menu which act on the system, listed after the games
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SystemEntry {
+    /// Show which buttons are pressed, to check the wiring and the mapping
+    TestInputs,
+    Shutdown,
+    Reboot,
+}
+
+impl SystemEntry {
+    const POWER: [SystemEntry; 2] = [SystemEntry::Shutdown, SystemEntry::Reboot];
+
+    fn label(&self) -> &'static str {
+        match self {
+            SystemEntry::TestInputs => "Test Inputs",
+            SystemEntry::Shutdown => "Shut down",
+            SystemEntry::Reboot => "Reboot",
+        }
+    }
+
+    fn action(&self) -> Option<PowerAction> {
+        match self {
+            SystemEntry::TestInputs => None,
+            SystemEntry::Shutdown => Some(PowerAction::Shutdown),
+            SystemEntry::Reboot => Some(PowerAction::Reboot),
+        }
+    }
+}
+
+/// Run the power action of a system entry once confirmed.
+///
+/// Returns `None` if the user cancelled it or the entry has no power action.
+fn run_system_entry(
+    entry: SystemEntry,
+    confirm: impl FnOnce(&str) -> bool,
+    runner: &mut dyn PowerRunner,
+) -> Option<std::io::Result<()>> {
+    let action = entry.action()?;
+    confirm(&format!("{}?", entry.label())).then(|| runner.run(action))
+}
+
+/// Action of a key press in the game list
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MenuInput {
+    /// Launch the selected game
+    Launch,
+    Previous,
+    Next,
+    /// Manage the save data of the selected game
+    ManageSave,
+    /// Go back to the top of the list
+    Back,
+}
+
+/// Get the name and the [`Platform`] of a ROM from its file name, if its extension is allowed
+fn classify_rom(path: &Path, config: &AppConfig) -> Option<(String, Platform)> {
+    let Some(extension) = path.extension() else {
+        warn!("File without extension: {:?}", path);
+        return None;
+    };
+    // name without the extension
+    let Some(name) = path.file_stem().and_then(|n| n.to_str()) else {
+        warn!("Invalid file name: {:?}", path);
+        return None;
+    };
+    let Some(platform) = config.rom_platform(&extension.to_string_lossy()) else {
+        debug!("Unsupported file extension: {:?}", path);
+        return None;
+    };
+
+    Some((name.to_string(), platform))
+}
+
+/// Get the [`MenuInput`] of a key event; only presses trigger an action.
+///
+/// The `modifiers` are held while launching a game, e.g. `force_classic_key`, and trigger none.
+fn menu_input(event: KeyEvent, key: KeypadKey, modifiers: &[KeypadKey]) -> Option<MenuInput> {
+    if event != KeyEvent::Down || modifiers.contains(&key) {
+        return None;
+    }
+
+    match key {
+        KeypadKey::Start | KeypadKey::A => Some(MenuInput::Launch),
+        KeypadKey::Up => Some(MenuInput::Previous),
+        KeypadKey::Down => Some(MenuInput::Next),
+        KeypadKey::Select => Some(MenuInput::ManageSave),
+        KeypadKey::B => Some(MenuInput::Back),
+        _ => None,
+    }
+}
+
 #[derive(Debug, Clone)]
 struct GameEntry {
     name: String,
@@ -61,71 +237,73 @@ impl AppMenu {
         exit: Arc<AtomicBool>,
         event_receiver: Receiver<rboy::input::Event>,
     ) -> anyhow::Result<Self> {
-        // scan directory
-        let mut games = vec![];
-        if let Ok(entries) = std::fs::read_dir(&config.roms_directory) {
-            for entry in entries.flatten() {
-                let path = entry.path();
-                if !path.is_file() {
-                    debug!("Skipping non-file entry: {:?}", path);
-                    continue;
-                }
-                let Some(extension) = path.extension() else {
-                    warn!("File without extension: {:?}", path);
-                    continue;
-                };
-                // get name
-                let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
-                    warn!("Invalid file name: {:?}", path);
-                    continue;
-                };
-                let extension = extension.to_string_lossy().to_lowercase();
-                let platform = match extension.as_str() {
-                    "gb" => Platform::GameBoy,
-                    "gbc" => Platform::GameBoyColor,
-                    _ => {
-                        debug!("Unsupported file extension: {:?}", path);
-                        continue;
-                    }
-                };
-                // remove extension from name
-                let name = name
-                    .trim_end_matches(".gb")
-                    .trim_end_matches(".gbc")
-                    .to_string();
-                info!(
-                    "Found game: {name} for {platform:?} at {path}",
-                    path = path.display()
-                );
-                games.push(GameEntry {
-                    name: name.to_string(),
-                    path,
-                    platform,
-                });
-            }
+        let games = handle_duplicates(
+            scan_roms(&config),
+            config.duplicate_roms,
+            config.roms_directory(),
+        );
+        let background = Background::load(&config.menu.background);
+        let mut system_entries = vec![];
+        if config.menu.test_inputs_entry {
+            system_entries.push(SystemEntry::TestInputs);
+        }
+        if config.menu.power_entries {
+            system_entries.extend(SystemEntry::POWER);
         }
 
         Ok(Self {
+            background,
+            system_entries,
             config,
             event_receiver,
             exit,
             framebuffer,
             games,
+            notice: None,
         })
     }
 
+    /// Show a message once after the splash screen
+    pub fn with_notice(mut self, notice: Option<&str>) -> Self {
+        self.notice = notice.map(str::to_string);
+        self
+    }
+
     pub fn run(self) -> anyhow::Result<AppState> {
+        self.framebuffer
+            .set_scale_filter(self.config.scale_filter.for_context(ScaleContext::Menu));
         self.splash();
+        if let Some(notice) = &self.notice {
+            self.message(notice);
+        }
 
         let mut redraw = true;
+        let mut limiter = FrameLimiter::new(self.config.menu.frame_interval());
         let mut selected = 0;
+        let mut held: Vec<KeypadKey> = vec![];
+        let mut last_geometry_check = Instant::now();
+        let mut attract = self
+            .config
+            .attract
+            .as_ref()
+            .map(|attract| AttractTimer::new(attract.idle_timeout(), Instant::now()));
+        let modifiers: Vec<KeypadKey> = [
+            self.config.force_classic_key,
+            self.config.force_color_key,
+            self.config.save_picker_key,
+        ]
+        .into_iter()
+        .flatten()
+        .map(|key| key.keycode())
+        .collect();
 
         loop {
             if self.exit.load(Ordering::Relaxed) {
                 return Ok(AppState::Exit);
             }
+            stdin_input::discard_pending();
 
-            if redraw {
+            if redraw && limiter.ready(Instant::now()) {
                 self.redraw(selected);
                 redraw = false;
             }
@@ -134,7 +312,19 @@ impl AppMenu {
             let (event, key) = match self.event_receiver.try_recv() {
                 Ok(event) => event,
                 Err(TryRecvError::Empty) => {
-                    std::thread::sleep(Duration::from_millis(50));
+                    if last_geometry_check.elapsed() >= crate::GEOMETRY_CHECK_INTERVAL {
+                        last_geometry_check = Instant::now();
+                        redraw |= self.framebuffer.refresh_geometry();
+                    }
+                    if let Some(Trigger::StartDemo) =
+                        attract.as_mut().and_then(|a| a.poll(Instant::now()))
+                    {
+                        info!("Menu idle; starting attract mode");
+                        return Ok(AppState::Attract {
+                            config: self.config,
+                        });
+                    }
+                    std::thread::sleep(limiter.interval());
                     continue;
                 }
                 Err(TryRecvError::Disconnected) => {
@@ -143,29 +333,374 @@ impl AppMenu {
                     return Ok(AppState::Exit);
                 }
             };
+            if let Some(attract) = attract.as_mut() {
+                attract.input(Instant::now());
+            }
 
-            match (event, key) {
-                (KeyEvent::Down, KeypadKey::Start) => {
-                    let Some(path) = self.games.get(selected).map(|g| g.path.clone()) else {
+            match event {
+                KeyEvent::Down if !held.contains(&key) => held.push(key),
+                KeyEvent::Down => {}
+                KeyEvent::Up => held.retain(|k| *k != key),
+            }
+
+            match menu_input(event, key, &modifiers) {
+                Some(MenuInput::Launch) => {
+                    if let Some(SystemEntry::TestInputs) = self.system_entry(selected) {
+                        held.clear();
+                        self.test_inputs();
+                        redraw = true;
+                        continue;
+                    }
+                    if let Some(entry) = self.system_entry(selected) {
+                        match run_system_entry(entry, |q| self.confirm(q), &mut SystemPowerRunner) {
+                            Some(Ok(())) => return Ok(AppState::Exit),
+                            Some(Err(err)) => {
+                                error!("Failed to run {:?}: {err}", entry.action());
+                                self.message(&format!("{} failed", entry.label()));
+                            }
+                            None => {}
+                        }
+                        redraw = true;
+                        continue;
+                    }
+                    let Some(game) = self.games.get(selected) else {
                         error!("No such game at {selected}");
                         continue;
                     };
+                    let is_held =
+                        |key: Option<Keycode>| key.is_some_and(|k| held.contains(&k.keycode()));
+                    let forced = if is_held(self.config.force_classic_key) {
+                        Some(LaunchMode::Classic)
+                    } else if is_held(self.config.force_color_key) {
+                        Some(LaunchMode::Color)
+                    } else {
+                        None
+                    };
+                    let remembered = self
+                        .config
+                        .remember_launch_options
+                        .then(|| GameCache::load(self.config.roms_directory()))
+                        .and_then(|cache| {
+                            cache.launch_options(&game.path).map(|options| options.mode)
+                        });
+                    let mode = launch_mode(game.platform, forced, remembered);
+                    let pick_save = self
+                        .config
+                        .save_picker_key
+                        .is_some_and(|k| held.contains(&k.keycode()));
+                    let save_slot = if pick_save {
+                        held.clear();
+                        match self.pick_save(game) {
+                            Some(slot) => slot,
+                            None => {
+                                redraw = true;
+                                continue;
+                            }
+                        }
+                    } else {
+                        0
+                    };
+                    info!(
+                        "Launching {} in {mode:?} mode with save {save_slot}",
+                        game.name
+                    );
                     return Ok(AppState::Emulator {
-                        rom_file: path,
+                        rom_file: game.path.clone(),
+                        mode,
+                        save_slot,
+                        resume: false,
                         config: self.config,
                     });
                 }
-                (KeyEvent::Down, KeypadKey::Up) => {
+                Some(MenuInput::Previous) => {
                     selected = selected.saturating_sub(1);
                     redraw = true;
                 }
-                (KeyEvent::Down, KeypadKey::Down) => {
-                    if selected + 1 < self.games.len() {
+                Some(MenuInput::Next) => {
+                    if selected + 1 < self.games.len() + self.system_entries.len() {
                         selected = selected.saturating_add(1);
                         redraw = true;
                     }
                 }
-                _ => continue,
+                Some(MenuInput::Back) => {
+                    if selected != 0 {
+                        selected = 0;
+                        redraw = true;
+                    }
+                }
+                Some(MenuInput::ManageSave) => {
+                    if let Some(game) = self.games.get(selected) {
+                        self.manage_save(game);
+                        redraw = true;
+                        if let Some(attract) = attract.as_mut() {
+                            attract.input(Instant::now());
+                        }
+                    }
+                }
+                None => continue,
+            }
+        }
+    }
+
+    /// System entry at the given row of the list, if it's not a game
+    fn system_entry(&self, row: usize) -> Option<SystemEntry> {
+        let index = row.checked_sub(self.games.len())?;
+        self.system_entries.get(index).copied()
+    }
+
+    /// Let the user pick the save file to launch a game with.
+    ///
+    /// Returns `None` if cancelled.
+    fn pick_save(&self, game: &GameEntry) -> Option<usize> {
+        let mut picker = SavePicker::new(saves::save_slots(&game.path));
+
+        loop {
+            self.framebuffer.zero();
+            let mut y = PADDING_Y;
+            self.draw_text(&game.name, PADDING_X, &mut y, false, COLOR_WHITE);
+            self.draw_text("Save file:", PADDING_X, &mut y, false, COLOR_WHITE);
+            for (i, label) in picker.labels().iter().enumerate() {
+                let is_selected = i == picker.selected();
+                let line = format!("{} {label}", if is_selected { ">" } else { " " });
+                self.draw_text(&line, PADDING_X, &mut y, is_selected, COLOR_WHITE);
+            }
+
+            match self.wait_key_down()? {
+                KeypadKey::Up => picker.previous(),
+                KeypadKey::Down => picker.next(),
+                KeypadKey::Start | KeypadKey::A => return Some(picker.slot()),
+                KeypadKey::B => return None,
+                _ => {}
+            }
+        }
+    }
+
+    /// Show the save data of a game, letting the user back it up or delete it.
+    ///
+    /// A game with several save files lets the user pick the one first.
+    fn manage_save(&self, game: &GameEntry) {
+        let slot = match saves::save_slots(&game.path).as_slice() {
+            [] => 0,
+            [slot] => *slot,
+            _ => match self.pick_save(game) {
+                Some(slot) => slot,
+                None => return,
+            },
+        };
+        let mut selected = 0;
+
+        loop {
+            let Some(info) = SaveInfo::for_rom(&game.path, slot) else {
+                self.message(&format!("{} has no save data", game.name));
+                return;
+            };
+            self.draw_save_page(game, slot, &info, selected);
+
+            let Some(key) = self.wait_key_down() else {
+                return;
+            };
+            match key {
+                KeypadKey::Up => selected = selected.saturating_sub(1),
+                KeypadKey::Down => {
+                    selected = usize::min(selected + 1, SaveAction::ALL.len() - 1);
+                }
+                KeypadKey::Start | KeypadKey::A => match SaveAction::ALL[selected] {
+                    SaveAction::Backup => match saves::backup_save(&game.path, slot) {
+                        Ok(_) => self.message("Save data backed up"),
+                        Err(err) => {
+                            error!("Failed to back up save of {}: {err}", game.name);
+                            self.message("Failed to back up save data");
+                        }
+                    },
+                    SaveAction::Delete => {
+                        if !self.confirm(&format!("Delete save data of {}?", game.name)) {
+                            continue;
+                        }
+                        match saves::delete_save(&game.path, slot) {
+                            Ok(()) => self.message("Save data deleted"),
+                            Err(err) => {
+                                error!("Failed to delete save of {}: {err}", game.name);
+                                self.message("Failed to delete save data");
+                            }
+                        }
+                    }
+                    SaveAction::Back => return,
+                },
+                KeypadKey::B => return,
+                _ => {}
+            }
+        }
+    }
+
+    fn draw_save_page(&self, game: &GameEntry, slot: usize, info: &SaveInfo, selected: usize) {
+        self.framebuffer.zero();
+
+        let mut y = PADDING_Y;
+        self.draw_text(&game.name, PADDING_X, &mut y, false, COLOR_WHITE);
+        self.draw_text(
+            &format!("Save {}", slot + 1),
+            PADDING_X,
+            &mut y,
+            false,
+            COLOR_WHITE,
+        );
+        self.draw_text(
+            &format!("Size: {} bytes", info.size),
+            PADDING_X,
+            &mut y,
+            false,
+            COLOR_WHITE,
+        );
+        self.draw_text(
+            &format!("Modified: {}", info.modified_str()),
+            PADDING_X,
+            &mut y,
+            false,
+            COLOR_WHITE,
+        );
+        y += LINE_H;
+
+        for (i, action) in SaveAction::ALL.iter().enumerate() {
+            let is_selected = i == selected;
+            let line = format!("{} {}", if is_selected { ">" } else { " " }, action.label());
+            self.draw_text(&line, PADDING_X, &mut y, is_selected, COLOR_WHITE);
+        }
+    }
+
+    /// Ask the user to confirm with A (or Start); B cancels
+    fn confirm(&self, question: &str) -> bool {
+        self.framebuffer.zero();
+
+        let mut y = PADDING_Y;
+        self.draw_text(question, PADDING_X, &mut y, false, COLOR_WHITE);
+        self.draw_text(
+            "A: confirm, B: cancel",
+            PADDING_X,
+            &mut y,
+            false,
+            COLOR_WHITE,
+        );
+
+        loop {
+            match self.wait_key_down() {
+                Some(KeypadKey::A | KeypadKey::Start) => return true,
+                Some(KeypadKey::B) | None => return false,
+                Some(_) => continue,
+            }
+        }
+    }
+
+    /// Light up the buttons of a Game Boy layout as they're pressed, until Start and Select
+    /// are held together
+    fn test_inputs(&self) {
+        info!("Testing the inputs");
+        let mut test = InputTest::default();
+        let mut last: Option<String> = None;
+
+        loop {
+            self.draw_input_test(&test, last.as_deref());
+
+            let (event, key) = loop {
+                if self.exit.load(Ordering::Relaxed) {
+                    return;
+                }
+                match self.event_receiver.try_recv() {
+                    Ok(event) => break event,
+                    Err(TryRecvError::Empty) => std::thread::sleep(Duration::from_millis(10)),
+                    Err(TryRecvError::Disconnected) => {
+                        self.exit.store(true, Ordering::Relaxed);
+                        error!("Main thread disconnected");
+                        return;
+                    }
+                }
+            };
+            debug!("Input test: {key:?} {event:?}");
+            if event == KeyEvent::Down {
+                let gpio = self
+                    .config
+                    .keys
+                    .iter()
+                    .find(|k| k.keycode.keycode() == key)
+                    .map(|k| format!(" (GPIO {})", k.gpio))
+                    .unwrap_or_default();
+                last = Some(format!("{key:?}{gpio}"));
+            }
+            if test.handle(event, key) {
+                info!("Input test done");
+                return;
+            }
+        }
+    }
+
+    fn draw_input_test(&self, test: &InputTest, last: Option<&str>) {
+        self.framebuffer.zero();
+        let mut y = PADDING_Y;
+        self.draw_text("Test Inputs", PADDING_X, &mut y, false, COLOR_WHITE);
+        self.draw_text(
+            "Hold Start+Select to exit",
+            PADDING_X,
+            &mut y,
+            false,
+            COLOR_WHITE,
+        );
+        if let Some(last) = last {
+            self.draw_text(last, PADDING_X, &mut y, false, COLOR_WHITE);
+        }
+
+        let cell = usize::min(
+            self.framebuffer.width() / GRID_W,
+            self.framebuffer.height() / GRID_H,
+        );
+        let x0 = (self.framebuffer.width() - cell * GRID_W) / 2;
+        let y0 = (self.framebuffer.height() - cell * GRID_H) / 2;
+        for button in LAYOUT {
+            let color = if test.is_pressed(button.key) {
+                COLOR_PRESSED
+            } else if test.is_tested(button.key) {
+                COLOR_TESTED
+            } else {
+                COLOR_UNTESTED
+            };
+            let (x, y) = (x0 + button.x * cell, y0 + button.y * cell);
+            let (w, h) = (button.w * cell, button.h * cell);
+            self.framebuffer.fill_rect(x, y, w, h, color);
+            let (text_x, text_y) = centered_text_origin(w, h, button.label);
+            self.framebuffer
+                .draw_text(button.label, x + text_x, y + text_y, false, COLOR_WHITE);
+        }
+    }
+
+    /// Show a message until any key is pressed
+    fn message(&self, text: &str) {
+        self.framebuffer.zero();
+
+        let mut y = PADDING_Y;
+        self.draw_text(text, PADDING_X, &mut y, false, COLOR_WHITE);
+        self.draw_text("Press any key", PADDING_X, &mut y, false, COLOR_WHITE);
+
+        let _ = self.wait_key_down();
+    }
+
+    /// Wait for the next key down event.
+    ///
+    /// Returns `None` if exit was requested.
+    fn wait_key_down(&self) -> Option<KeypadKey> {
+        loop {
+            if self.exit.load(Ordering::Relaxed) {
+                return None;
+            }
+
+            match self.event_receiver.try_recv() {
+                Ok((KeyEvent::Down, key)) => return Some(key),
+                Ok(_) => continue,
+                Err(TryRecvError::Empty) => {
+                    std::thread::sleep(Duration::from_millis(50));
+                }
+                Err(TryRecvError::Disconnected) => {
+                    self.exit.store(true, Ordering::Relaxed);
+                    error!("Main thread disconnected");
+                    return None;
+                }
             }
         }
     }
@@ -173,31 +708,47 @@ impl AppMenu {
     /// show splash and play bling
     fn splash(&self) {
         info!("Showing splash screen");
-        self.framebuffer.fill(
-            GAMEBOY_SPLASH_COLOR_RED,
-            GAMEBOY_SPLASH_COLOR_GREEN,
-            GAMEBOY_SPLASH_COLOR_BLUE,
-        );
+        let image = self
+            .config
+            .splash_image
+            .as_deref()
+            .and_then(|path| match Image::load(path) {
+                Ok(image) => Some(image),
+                Err(err) => {
+                    error!("Failed to load splash image {}: {err}", path.display());
+                    None
+                }
+            });
+
+        if let Some(image) = image {
+            image.draw(&self.framebuffer);
+        } else {
+            self.framebuffer.fill(
+                GAMEBOY_SPLASH_COLOR_RED,
+                GAMEBOY_SPLASH_COLOR_GREEN,
+                GAMEBOY_SPLASH_COLOR_BLUE,
+            );
 
-        // write at middle
-        let mut y = (self.framebuffer.height() / 2) - 4;
-        let x = (self.framebuffer.width() / 2).saturating_sub(SPLASH_TEXT.len());
+            // write at middle
+            let (x, mut y) = centered_text_origin(
+                self.framebuffer.width(),
+                self.framebuffer.height(),
+                SPLASH_TEXT,
+            );
 
-        self.draw_text(SPLASH_TEXT, x, &mut y, false, COLOR_BLACK);
+            self.draw_text(SPLASH_TEXT, x, &mut y, false, COLOR_BLACK);
+        }
         std::thread::sleep(Duration::from_secs(1));
 
         if let Err(err) = self.play_boot_sound() {
             error!("Failed to play boot sound: {err}");
         }
 
-        // wait for 10 seconds
-        let start = Instant::now();
-        while start.elapsed() < Duration::from_secs(4) {
-            if self.exit.load(Ordering::Relaxed) {
-                break;
-            }
-            std::thread::sleep(Duration::from_millis(50));
+        let wait = self.config.splash_wait();
+        if let SplashWait::Hold { .. } = wait {
+            info!("Holding the splash screen until a key is pressed");
         }
+        wait.wait(&self.event_receiver, &self.exit);
     }
 
     /// Play boot sound
@@ -231,78 +782,367 @@ impl AppMenu {
 
     fn redraw(&self, selected: usize) {
         debug!("Redraw menu");
-        // zero
-        self.framebuffer.zero();
+        self.background.draw(&self.framebuffer);
 
-        let max_visible = (self.framebuffer.height() / 16).saturating_sub(2); // title + subtitle (2)
+        let rows = self.games.len() + self.system_entries.len();
+        let max_visible = (self.framebuffer.height() / LINE_H).saturating_sub(2); // title + subtitle (2)
         let skip = usize::clamp(
             selected.saturating_sub(max_visible / 2),
             0,
-            usize::max(0, self.games.len().saturating_sub(max_visible)),
+            usize::max(0, rows.saturating_sub(max_visible)),
         );
         debug!("Skipping {skip} (max visible: {max_visible}) games");
 
         let mut y = PADDING_Y;
 
         // write title first
-        self.draw_text(
+        self.framebuffer.draw_text_scaled(
             &format!(
                 "{crate_name} {crate_version}",
                 crate_name = env!("CARGO_PKG_NAME"),
                 crate_version = env!("CARGO_PKG_VERSION")
             ),
             PADDING_X,
-            &mut y,
-            false,
+            y,
+            TITLE_SCALE,
             COLOR_WHITE,
         );
+        y += LINE_H;
         self.draw_text(SUBTITLE, PADDING_X, &mut y, false, COLOR_WHITE);
 
         // write message if there are no games
         if self.games.is_empty() {
             self.draw_text(NO_GAMES, PADDING_X, &mut y, false, COLOR_WHITE);
-            return;
         }
 
-        for (i, game) in self.games.iter().skip(skip).take(max_visible).enumerate() {
-            let x = PADDING_X; // padding
-            let is_selected = skip + i == selected;
-            let line = format!(
-                "{} {} - {}",
-                if is_selected { ">" } else { " " },
+        let games = self.games.iter().map(|game| {
+            format!(
+                "{} - {}",
                 game.name,
                 match game.platform {
                     Platform::GameBoy => "GameBoy",
                     Platform::GameBoyColor => "GameBoyColor",
                 }
-            );
+            )
+        });
+        let entries = self.system_entries.iter().map(|e| e.label().to_string());
+        for (i, text) in games
+            .chain(entries)
+            .enumerate()
+            .skip(skip)
+            .take(max_visible)
+        {
+            let x = PADDING_X; // padding
+            let is_selected = i == selected;
+            let line = format!("{} {text}", if is_selected { ">" } else { " " });
             self.draw_text(&line, x, &mut y, is_selected, COLOR_WHITE);
         }
     }
 
-    /// Draw text
-    fn draw_text(&self, text: &str, mut x: usize, y: &mut usize, invert: bool, color: u16) {
-        debug!("Drawing text '{text}' at ({x}, {y}); invert: {invert}");
-        for glyph in text.chars() {
-            self.draw_char(x, *y, glyph, invert, color);
-            x += SPACE_SIZE;
+    /// Draw text and move `y` to the next line
+    fn draw_text(&self, text: &str, x: usize, y: &mut usize, invert: bool, color: u16) {
+        self.framebuffer.draw_text(text, x, *y, invert, color);
+        *y += LINE_H;
+    }
+}
+
+/// Get the position of a single line of text to center it on a screen of the given size in pixels
+fn centered_text_origin(width: usize, height: usize, text: &str) -> (usize, usize) {
+    let x = (width / 2).saturating_sub(text.len() * GLYPH_SIZE / 2);
+    let y = (height / 2).saturating_sub(GLYPH_SIZE / 2);
+
+    (x, y)
+}
+
+/// List the games of all the ROMs directories, in the configured order.
+///
+/// Directories which can't be read are skipped, and a directory listed twice is scanned once.
+fn scan_roms(config: &AppConfig) -> Vec<GameEntry> {
+    let mut games = vec![];
+    let mut scanned: Vec<PathBuf> = vec![];
+    for dir in &config.roms_directories {
+        let canonical = dir.canonicalize().unwrap_or_else(|_| dir.clone());
+        if scanned.contains(&canonical) {
+            debug!("Skipping {}: already scanned", dir.display());
+            continue;
         }
+        scanned.push(canonical);
 
-        *y += LINE_H;
+        let entries = match std::fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(err) => {
+                warn!("Skipping ROMs directory {}: {err}", dir.display());
+                continue;
+            }
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if !path.is_file() {
+                debug!("Skipping non-file entry: {:?}", path);
+                continue;
+            }
+            let Some((name, platform)) = classify_rom(&path, config) else {
+                continue;
+            };
+            info!(
+                "Found game: {name} for {platform:?} at {path}",
+                path = path.display()
+            );
+            games.push(GameEntry {
+                name: name.to_string(),
+                path,
+                platform,
+            });
+        }
     }
 
-    /// draw a character in the framebuffer
-    fn draw_char(&self, x: usize, y: usize, c: char, invert: bool, color: u16) {
-        let glyph = BASIC_FONTS.get(c).unwrap_or([0u8; 8]);
-        debug!("Glyph for {c} ({x}, {y}): {glyph:?}");
+    games
+}
 
-        for (row, bits) in glyph.iter().enumerate() {
-            for col in 0..8 {
-                let mask = bits & (1 << col);
-                if (!invert && mask != 0) || (invert && mask == 0) {
-                    self.framebuffer.put_pixel(x + col, y + row, color);
-                }
+/// Collapse or mark the games with the same ROM content, keeping the first one
+fn handle_duplicates(
+    mut games: Vec<GameEntry>,
+    mode: DuplicateRoms,
+    roms_directory: &Path,
+) -> Vec<GameEntry> {
+    if mode == DuplicateRoms::Show {
+        return games;
+    }
+
+    let mut cache = GameCache::load(roms_directory);
+    let paths: Vec<&Path> = games.iter().map(|g| g.path.as_path()).collect();
+    let duplicates = duplicates::find_duplicates(&paths, &mut cache);
+    if let Err(err) = cache.save() {
+        warn!("Failed to save the game cache: {err}");
+    }
+
+    for (i, original) in duplicates.iter().enumerate() {
+        if let Some(original) = *original {
+            info!(
+                "{} is a duplicate of {}",
+                games[i].path.display(),
+                games[original].path.display()
+            );
+            games[i].name.push_str(DUPLICATE_MARK);
+        }
+    }
+    if mode == DuplicateRoms::Collapse {
+        let mut duplicates = duplicates.iter();
+        games.retain(|_| duplicates.next().is_some_and(Option::is_none));
+    }
+
+    games
+}
+
+/// Decide the hardware to run a game on.
+///
+/// Games run on the Game Boy Color, which is compatible with classic games,
+/// unless a mode is `forced` by the user.
+///
+/// Otherwise a game keeps the mode it was last played with, if remembered.
+fn launch_mode(
+    platform: Platform,
+    forced: Option<LaunchMode>,
+    remembered: Option<LaunchMode>,
+) -> LaunchMode {
+    match (platform, forced.or(remembered)) {
+        (_, Some(mode)) => mode,
+        (Platform::GameBoy | Platform::GameBoyColor, None) => LaunchMode::Color,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_should_center_text_in_pixels() {
+        // "Nintendo" is 64px wide
+        assert_eq!(centered_text_origin(320, 240, SPLASH_TEXT), (128, 116));
+        assert_eq!(centered_text_origin(32, 4, SPLASH_TEXT), (0, 0));
+    }
+
+    #[test]
+    fn test_should_collapse_duplicate_roms() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let games: Vec<GameEntry> = [
+            ("tetris", [1, 2]),
+            ("tetris (1)", [1, 2]),
+            ("zelda", [3, 4]),
+        ]
+        .into_iter()
+        .map(|(name, rom)| {
+            let path = dir.path().join(format!("{name}.gb"));
+            std::fs::write(&path, rom).unwrap();
+            GameEntry {
+                name: name.to_string(),
+                path,
+                platform: Platform::GameBoy,
+            }
+        })
+        .collect();
+        let names = |games: &[GameEntry]| games.iter().map(|g| g.name.clone()).collect::<Vec<_>>();
+
+        let marked = handle_duplicates(games.clone(), DuplicateRoms::Mark, dir.path());
+        assert_eq!(names(&marked), ["tetris", "tetris (1) (dup)", "zelda"]);
+        // the hashes are cached
+        assert!(dir.path().join(".rboy-game-cache").exists());
+
+        let collapsed = handle_duplicates(games.clone(), DuplicateRoms::Collapse, dir.path());
+        assert_eq!(names(&collapsed), ["tetris", "zelda"]);
+
+        let shown = handle_duplicates(games, DuplicateRoms::Show, dir.path());
+        assert_eq!(shown.len(), 3);
+    }
+
+    #[test]
+    fn test_should_merge_roms_directories() {
+        let internal = tempfile::TempDir::new().unwrap();
+        let usb = tempfile::TempDir::new().unwrap();
+        std::fs::write(internal.path().join("tetris.gb"), [1]).unwrap();
+        std::fs::write(usb.path().join("zelda.gbc"), [2]).unwrap();
+        let config: AppConfig = toml::from_str(&format!(
+            "roms_directory = [{:?}, \"/missing\", {:?}, {:?}]\ndefault_debounce_ms = 50\ndefault_active_low = true\npoll_interval_ms = 10",
+            internal.path(),
+            usb.path(),
+            internal.path()
+        ))
+        .unwrap();
+
+        let mut names: Vec<String> = scan_roms(&config).into_iter().map(|g| g.name).collect();
+        names.sort();
+        assert_eq!(names, ["tetris", "zelda"]);
+        assert_eq!(config.roms_directory(), internal.path());
+    }
+
+    #[test]
+    fn test_should_classify_roms_by_extension() {
+        let config: AppConfig = toml::from_str(
+            r#"
+roms_directory = "./roms"
+default_debounce_ms = 50
+default_active_low = true
+poll_interval_ms = 10
+key = []
+
+[rom_extensions]
+GBZ = "gbc"
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            classify_rom(Path::new("/roms/zelda.gbz"), &config),
+            Some(("zelda".to_string(), Platform::GameBoyColor))
+        );
+        // the default extensions are kept, whatever the case
+        assert_eq!(
+            classify_rom(Path::new("/roms/tetris.GB"), &config),
+            Some(("tetris".to_string(), Platform::GameBoy))
+        );
+        assert_eq!(classify_rom(Path::new("/roms/notes.txt"), &config), None);
+        assert_eq!(classify_rom(Path::new("/roms/README"), &config), None);
+    }
+
+    #[test]
+    fn test_should_limit_menu_redraws() {
+        let start = Instant::now();
+        let mut limiter = FrameLimiter::new(Duration::from_millis(100));
+        let redraws = (0..100)
+            .filter(|ms| limiter.ready(start + Duration::from_millis(ms * 10)))
+            .count();
+        // one redraw every 100ms over a second
+        assert_eq!(redraws, 10);
+        assert!(!limiter.ready(start + Duration::from_millis(950)));
+        assert!(limiter.ready(start + Duration::from_millis(1000)));
+    }
+
+    #[test]
+    fn test_should_interpolate_gradient() {
+        let rows = background::gradient([0, 100, 200], [100, 200, 0], 5);
+        assert_eq!(rows.len(), 5);
+        assert_eq!(rows[0], [0, 100, 200]);
+        assert_eq!(rows[2], [50, 150, 100]);
+        assert_eq!(rows[4], [100, 200, 0]);
+    }
+
+    #[test]
+    fn test_should_launch_with_start_or_a() {
+        for key in [KeypadKey::Start, KeypadKey::A] {
+            assert_eq!(
+                menu_input(KeyEvent::Down, key, &[]),
+                Some(MenuInput::Launch),
+                "{key:?}"
+            );
+            assert_eq!(menu_input(KeyEvent::Up, key, &[]), None, "{key:?}");
+        }
+        assert_eq!(
+            menu_input(KeyEvent::Down, KeypadKey::B, &[]),
+            Some(MenuInput::Back)
+        );
+        assert_eq!(menu_input(KeyEvent::Down, KeypadKey::Left, &[]), None);
+        // held to launch a game, e.g. `force_classic_key = "B"`
+        let modifiers = [KeypadKey::B, KeypadKey::Select];
+        assert_eq!(menu_input(KeyEvent::Down, KeypadKey::B, &modifiers), None);
+        assert_eq!(
+            menu_input(KeyEvent::Down, KeypadKey::Select, &modifiers),
+            None
+        );
+        assert_eq!(
+            menu_input(KeyEvent::Down, KeypadKey::Start, &modifiers),
+            Some(MenuInput::Launch)
+        );
+    }
+
+    #[test]
+    fn test_should_force_classic_launch_mode() {
+        use LaunchMode::{Classic, Color};
+
+        assert_eq!(launch_mode(Platform::GameBoy, None, None), Color);
+        assert_eq!(launch_mode(Platform::GameBoyColor, None, None), Color);
+        assert_eq!(launch_mode(Platform::GameBoy, Some(Classic), None), Classic);
+        assert_eq!(
+            launch_mode(Platform::GameBoyColor, Some(Classic), None),
+            Classic
+        );
+        // a remembered game keeps its mode, unless another one is forced
+        assert_eq!(launch_mode(Platform::GameBoy, None, Some(Classic)), Classic);
+        assert_eq!(
+            launch_mode(Platform::GameBoy, Some(Color), Some(Classic)),
+            Color
+        );
+        assert_eq!(
+            launch_mode(Platform::GameBoy, Some(Classic), Some(Color)),
+            Classic
+        );
+    }
+
+    #[test]
+    fn test_should_run_power_action_once_confirmed() {
+        #[derive(Default)]
+        struct MockRunner(Vec<PowerAction>);
+
+        impl PowerRunner for MockRunner {
+            fn run(&mut self, action: PowerAction) -> std::io::Result<()> {
+                self.0.push(action);
+                Ok(())
             }
         }
+
+        let mut runner = MockRunner::default();
+        assert!(run_system_entry(SystemEntry::Shutdown, |_| false, &mut runner).is_none());
+        assert!(runner.0.is_empty());
+
+        let result = run_system_entry(
+            SystemEntry::Shutdown,
+            |question| {
+                assert_eq!(question, "Shut down?");
+                true
+            },
+            &mut runner,
+        );
+        assert!(matches!(result, Some(Ok(()))));
+        assert_eq!(runner.0, vec![PowerAction::Shutdown]);
     }
 }
@@ -0,0 +1,87 @@
+use std::path::PathBuf;
+
+use rboy::framebuffer::Framebuffer;
+use serde::Deserialize;
+
+use crate::splash::Image;
+
+/// RGB color
+pub type Rgb = [u8; 3];
+
+/// Background of the menu, drawn before the text
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum MenuBackground {
+    /// Solid color
+    Solid { color: Rgb },
+    /// Vertical gradient from `top` to `bottom`
+    Gradient { top: Rgb, bottom: Rgb },
+    /// PNG or BMP image, scaled to fit the screen
+    Image { path: PathBuf },
+}
+
+impl Default for MenuBackground {
+    fn default() -> Self {
+        Self::Solid { color: [0, 0, 0] }
+    }
+}
+
+/// [`MenuBackground`] ready to be drawn
+#[derive(Debug)]
+pub enum Background {
+    Solid(Rgb),
+    Gradient { top: Rgb, bottom: Rgb },
+    Image(Image),
+}
+
+impl Background {
+    /// Prepare the background, loading the image if any; falls back to black if it can't be loaded
+    pub fn load(config: &MenuBackground) -> Self {
+        match config {
+            MenuBackground::Solid { color } => Self::Solid(*color),
+            MenuBackground::Gradient { top, bottom } => Self::Gradient {
+                top: *top,
+                bottom: *bottom,
+            },
+            MenuBackground::Image { path } => match Image::load(path) {
+                Ok(image) => Self::Image(image),
+                Err(err) => {
+                    error!("Failed to load menu background {}: {err}", path.display());
+                    Self::Solid([0, 0, 0])
+                }
+            },
+        }
+    }
+
+    /// Draw the background over the whole framebuffer
+    pub fn draw(&self, framebuffer: &Framebuffer) {
+        match self {
+            Self::Solid([0, 0, 0]) => framebuffer.zero(),
+            Self::Solid([r, g, b]) => framebuffer.fill(*r, *g, *b),
+            Self::Gradient { top, bottom } => {
+                for (y, [r, g, b]) in gradient(*top, *bottom, framebuffer.height())
+                    .into_iter()
+                    .enumerate()
+                {
+                    for x in 0..framebuffer.width() {
+                        framebuffer.put_pixel_rgb(x, y, r, g, b);
+                    }
+                }
+            }
+            Self::Image(image) => image.draw(framebuffer),
+        }
+    }
+}
+
+/// Color of each of the `rows` of a vertical gradient from `top` to `bottom`
+pub fn gradient(top: Rgb, bottom: Rgb, rows: usize) -> Vec<Rgb> {
+    let last = rows.saturating_sub(1).max(1) as f32;
+    (0..rows)
+        .map(|row| {
+            let t = row as f32 / last;
+            std::array::from_fn(|c| {
+                (top[c] as f32 + (bottom[c] as f32 - top[c] as f32) * t).round() as u8
+            })
+        })
+        .collect()
+}
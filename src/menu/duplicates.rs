@@ -0,0 +1,87 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use super::GameCache;
+
+/// How the menu shows ROMs with the same content
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DuplicateRoms {
+    /// Show all the ROMs; contents are not hashed
+    #[default]
+    Show,
+    /// Keep only the first ROM with a given content
+    Collapse,
+    /// Show all the ROMs, marking the duplicates
+    Mark,
+}
+
+/// For each ROM, find the index of the first previous ROM with the same content, if any.
+///
+/// The contents are compared by CRC32 and size; ROMs which can't be read are never duplicates.
+pub fn find_duplicates(roms: &[&Path], cache: &mut GameCache) -> Vec<Option<usize>> {
+    let mut first: HashMap<(u32, u64), usize> = HashMap::new();
+    roms.iter()
+        .enumerate()
+        .map(|(i, rom)| match cache.fingerprint(rom) {
+            Ok(fingerprint) => first.get(&fingerprint).copied().or_else(|| {
+                first.insert(fingerprint, i);
+                None
+            }),
+            Err(err) => {
+                warn!("Failed to hash {}: {err}", rom.display());
+                None
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::time::UNIX_EPOCH;
+
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_should_not_match_roms_of_different_size() {
+        let dir = TempDir::new().unwrap();
+        let tetris = dir.path().join("tetris.gb");
+        let zelda = dir.path().join("zelda.gb");
+        std::fs::write(&tetris, [1]).unwrap();
+        std::fs::write(&zelda, [1, 2]).unwrap();
+        // cached with the same CRC32, as a collision would be
+        let mtime = |rom: &Path| {
+            std::fs::metadata(rom)
+                .unwrap()
+                .modified()
+                .unwrap()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs()
+        };
+        std::fs::write(
+            dir.path().join(".rboy-game-cache"),
+            format!(
+                "[{:?}.fingerprint]\ncrc = 0xdeadbeef\nsize = 1\nmtime = {}\n\
+                 [{:?}.fingerprint]\ncrc = 0xdeadbeef\nsize = 2\nmtime = {}\n",
+                tetris.canonicalize().unwrap(),
+                mtime(&tetris),
+                zelda.canonicalize().unwrap(),
+                mtime(&zelda)
+            ),
+        )
+        .unwrap();
+
+        let mut cache = GameCache::load(dir.path());
+        assert_eq!(cache.fingerprint(&zelda).unwrap(), (0xdeadbeef, 2));
+        assert_eq!(
+            find_duplicates(&[&tetris, &zelda], &mut cache),
+            [None, None]
+        );
+    }
+}
@@ -0,0 +1,35 @@
+use std::time::{Duration, Instant};
+
+/// Caps the redraws of the menu to a fixed rate, to save power
+#[derive(Debug)]
+pub struct FrameLimiter {
+    interval: Duration,
+    last: Option<Instant>,
+}
+
+impl FrameLimiter {
+    /// Create a [`FrameLimiter`] drawing at most one frame per `interval`
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            last: None,
+        }
+    }
+
+    /// Minimum time between two frames
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Whether a frame can be drawn at `now`; if so, it's counted as drawn
+    pub fn ready(&mut self, now: Instant) -> bool {
+        if self
+            .last
+            .is_some_and(|last| now.saturating_duration_since(last) < self.interval)
+        {
+            return false;
+        }
+        self.last = Some(now);
+        true
+    }
+}
@@ -0,0 +1,183 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use rboy::framebuffer::ScalingMode;
+use serde::{Deserialize, Serialize};
+
+use super::LaunchMode;
+
+/// Name of the game cache file in the ROMs directory
+const CACHE_FILE: &str = ".rboy-game-cache";
+
+/// Options a game was last played with
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LaunchOptions {
+    pub mode: LaunchMode,
+    /// Colorization palette of a classic game
+    pub palette: Option<String>,
+    /// Scaling of the game, if it differs from the global one
+    pub scaling: Option<ScalingMode>,
+}
+
+/// Metadata of the games, keyed by the canonical path of the ROM: the CRC32 of their content
+/// and the options they were last played with.
+///
+/// CRC32s are reused as long as the size and modification time of the file don't change.
+/// It's stored as TOML in the ROMs directory, one table per ROM.
+#[derive(Debug)]
+pub struct GameCache {
+    path: PathBuf,
+    entries: HashMap<PathBuf, GameMetadata>,
+    dirty: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+struct GameMetadata {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    fingerprint: Option<Fingerprint>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    launch: Option<LaunchOptions>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+struct Fingerprint {
+    crc: u32,
+    size: u64,
+    mtime: u64,
+}
+
+impl GameCache {
+    /// Load the cache from the ROMs directory; a missing or invalid cache is empty
+    pub fn load(dir: &Path) -> Self {
+        let path = dir.join(CACHE_FILE);
+        let entries = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|cache| toml::from_str(&cache).ok())
+            .unwrap_or_default();
+
+        Self {
+            path,
+            entries,
+            dirty: false,
+        }
+    }
+
+    /// Get the CRC32 and the size of a ROM, hashing it if not cached
+    pub fn fingerprint(&mut self, rom: &Path) -> std::io::Result<(u32, u64)> {
+        let metadata = std::fs::metadata(rom)?;
+        let size = metadata.len();
+        let mtime = metadata
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        let key = key(rom);
+
+        if let Some(entry) = self.entries.get(&key).and_then(|game| game.fingerprint)
+            && entry.size == size
+            && entry.mtime == mtime
+        {
+            return Ok((entry.crc, size));
+        }
+
+        debug!("Hashing {}", rom.display());
+        let crc = crc32fast::hash(&std::fs::read(rom)?);
+        self.entries.entry(key).or_default().fingerprint = Some(Fingerprint { crc, size, mtime });
+        self.dirty = true;
+
+        Ok((crc, size))
+    }
+
+    /// Options the given ROM was last played with, if any
+    pub fn launch_options(&self, rom: &Path) -> Option<&LaunchOptions> {
+        self.entries
+            .get(&key(rom))
+            .and_then(|game| game.launch.as_ref())
+    }
+
+    /// Remember the options the given ROM is played with
+    pub fn record_launch_options(&mut self, rom: &Path, options: LaunchOptions) {
+        let game = self.entries.entry(key(rom)).or_default();
+        if game.launch.as_ref() != Some(&options) {
+            game.launch = Some(options);
+            self.dirty = true;
+        }
+    }
+
+    /// Write the cache back to the ROMs directory, if changed
+    pub fn save(&self) -> std::io::Result<()> {
+        if !self.dirty {
+            return Ok(());
+        }
+
+        let cache = toml::to_string(&self.entries).map_err(std::io::Error::other)?;
+        std::fs::write(&self.path, cache)
+    }
+}
+
+/// Key of a ROM in the cache: its canonical path, or the path itself if it can't be resolved
+fn key(rom: &Path) -> PathBuf {
+    rom.canonicalize().unwrap_or_else(|_| rom.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_should_remember_launch_options() {
+        let dir = TempDir::new().unwrap();
+        let rom = dir.path().join("tetris dx.gb");
+        std::fs::write(&rom, [1, 2, 3]).unwrap();
+        let mut cache = GameCache::load(dir.path());
+        assert_eq!(cache.launch_options(&rom), None);
+
+        let options = LaunchOptions {
+            mode: LaunchMode::Classic,
+            palette: Some("Pocket".to_string()),
+            scaling: Some(ScalingMode::Integer),
+        };
+        let fingerprint = cache.fingerprint(&rom).unwrap();
+        cache.record_launch_options(&rom, options.clone());
+        cache.save().unwrap();
+
+        let mut cache = GameCache::load(dir.path());
+        assert_eq!(cache.launch_options(&rom), Some(&options));
+        assert_eq!(cache.launch_options(&dir.path().join("zelda.gb")), None);
+        // kept along with the other metadata
+        assert_eq!(cache.fingerprint(&rom).unwrap(), fingerprint);
+        assert!(!cache.dirty);
+    }
+
+    #[test]
+    fn test_should_key_games_by_path() {
+        let dir = TempDir::new().unwrap();
+        let usb = TempDir::new().unwrap();
+        let rom = dir.path().join("tetris.gb");
+        let other = usb.path().join("tetris.gb");
+        std::fs::write(&rom, [1]).unwrap();
+        std::fs::write(&other, [1, 2]).unwrap();
+
+        let mut cache = GameCache::load(dir.path());
+        let options = LaunchOptions {
+            mode: LaunchMode::Classic,
+            palette: None,
+            scaling: None,
+        };
+        cache.record_launch_options(&rom, options.clone());
+        assert_eq!(cache.fingerprint(&rom).unwrap().1, 1);
+        assert_eq!(cache.fingerprint(&other).unwrap().1, 2);
+        cache.save().unwrap();
+
+        let mut cache = GameCache::load(dir.path());
+        assert_eq!(cache.launch_options(&rom), Some(&options));
+        assert_eq!(cache.launch_options(&other), None);
+        assert!(cache.fingerprint(&other).is_ok());
+        assert!(!cache.dirty);
+    }
+}
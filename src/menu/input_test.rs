@@ -0,0 +1,109 @@
+use rboy::KeypadKey;
+use rboy::input::KeyEvent;
+
+/// Button of the Game Boy layout drawn by the input test
+#[derive(Debug, Clone, Copy)]
+pub struct Button {
+    pub key: KeypadKey,
+    pub label: &'static str,
+    /// Position and size, in cells of a [`GRID_W`] x [`GRID_H`] grid
+    pub x: usize,
+    pub y: usize,
+    pub w: usize,
+    pub h: usize,
+}
+
+/// Size of the grid of the layout, in cells
+pub const GRID_W: usize = 16;
+pub const GRID_H: usize = 12;
+
+const fn button(key: KeypadKey, label: &'static str, x: usize, y: usize, w: usize) -> Button {
+    Button {
+        key,
+        label,
+        x,
+        y,
+        w,
+        h: 1,
+    }
+}
+
+/// The buttons of a Game Boy: the D-pad on the left, B and A on the right, Select and Start below
+pub const LAYOUT: [Button; 8] = [
+    button(KeypadKey::Up, "^", 3, 4, 1),
+    button(KeypadKey::Left, "<", 2, 5, 1),
+    button(KeypadKey::Right, ">", 4, 5, 1),
+    button(KeypadKey::Down, "v", 3, 6, 1),
+    button(KeypadKey::B, "B", 11, 6, 1),
+    button(KeypadKey::A, "A", 13, 5, 1),
+    button(KeypadKey::Select, "SEL", 5, 9, 2),
+    button(KeypadKey::Start, "STA", 9, 9, 2),
+];
+
+/// State of the buttons during the input test.
+///
+/// The test ends when Start and Select are held together, so both can still be tested alone.
+#[derive(Debug, Default)]
+pub struct InputTest {
+    pressed: Vec<KeypadKey>,
+    /// Buttons pressed at least once
+    tested: Vec<KeypadKey>,
+}
+
+impl InputTest {
+    /// Handle a key event; returns whether the test is over
+    pub fn handle(&mut self, event: KeyEvent, key: KeypadKey) -> bool {
+        match event {
+            KeyEvent::Down => {
+                if !self.pressed.contains(&key) {
+                    self.pressed.push(key);
+                }
+                if !self.tested.contains(&key) {
+                    self.tested.push(key);
+                }
+            }
+            KeyEvent::Up => self.pressed.retain(|k| *k != key),
+        }
+
+        self.is_pressed(KeypadKey::Start) && self.is_pressed(KeypadKey::Select)
+    }
+
+    pub fn is_pressed(&self, key: KeypadKey) -> bool {
+        self.pressed.contains(&key)
+    }
+
+    /// Whether the button has been pressed since the test started
+    pub fn is_tested(&self, key: KeypadKey) -> bool {
+        self.tested.contains(&key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_should_light_up_pressed_buttons() {
+        let mut test = InputTest::default();
+        assert!(!test.is_pressed(KeypadKey::A));
+
+        assert!(!test.handle(KeyEvent::Down, KeypadKey::A));
+        assert!(test.is_pressed(KeypadKey::A));
+        assert!(!test.is_pressed(KeypadKey::B));
+        assert!(!test.handle(KeyEvent::Up, KeypadKey::A));
+        assert!(!test.is_pressed(KeypadKey::A));
+        assert!(test.is_tested(KeypadKey::A));
+
+        // Start and Select alone don't end the test
+        assert!(!test.handle(KeyEvent::Down, KeypadKey::Start));
+        assert!(!test.handle(KeyEvent::Up, KeypadKey::Start));
+        assert!(!test.handle(KeyEvent::Down, KeypadKey::Select));
+        assert!(test.handle(KeyEvent::Down, KeypadKey::Start));
+
+        // every key has a button
+        for key in [KeypadKey::Up, KeypadKey::B, KeypadKey::Select] {
+            assert!(LAYOUT.iter().any(|button| button.key == key));
+        }
+    }
+}
@@ -0,0 +1,68 @@
+/// Choice of the battery save file a game is launched with
+#[derive(Debug, Clone)]
+pub struct SavePicker {
+    /// Slots of the existing save files
+    slots: Vec<usize>,
+    selected: usize,
+}
+
+impl SavePicker {
+    /// Create a [`SavePicker`] over the existing save `slots`, in order; a new save is offered after them
+    pub fn new(slots: Vec<usize>) -> Self {
+        Self { slots, selected: 0 }
+    }
+
+    /// Labels of the choices
+    pub fn labels(&self) -> Vec<String> {
+        self.slots
+            .iter()
+            .map(|slot| format!("Save {}", slot + 1))
+            .chain(std::iter::once("New save".to_string()))
+            .collect()
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    pub fn previous(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    pub fn next(&mut self) {
+        self.selected = usize::min(self.selected + 1, self.slots.len());
+    }
+
+    /// Slot of the selected save; the new save takes the first free slot
+    pub fn slot(&self) -> usize {
+        match self.slots.get(self.selected) {
+            Some(slot) => *slot,
+            None => (0..).find(|slot| !self.slots.contains(slot)).unwrap_or(0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_should_pick_save_slot() {
+        let mut picker = SavePicker::new(vec![0, 2]);
+        assert_eq!(picker.labels(), vec!["Save 1", "Save 3", "New save"]);
+        assert_eq!(picker.slot(), 0);
+
+        picker.next();
+        assert_eq!(picker.slot(), 2);
+        picker.next();
+        picker.next();
+        assert_eq!(picker.selected(), 2);
+        // the new save fills the gap
+        assert_eq!(picker.slot(), 1);
+
+        picker.previous();
+        assert_eq!(picker.slot(), 2);
+        assert_eq!(SavePicker::new(vec![]).slot(), 0);
+    }
+}
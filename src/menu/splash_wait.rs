@@ -0,0 +1,84 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::time::{Duration, Instant};
+
+use rboy::input::{Event, KeyEvent};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// How long the splash screen stays
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SplashWait {
+    /// A fixed time; the keys pressed meanwhile are left to the menu
+    Timer(Duration),
+    /// Until a key is pressed, or the timeout expires, if any
+    Hold { timeout: Option<Duration> },
+}
+
+impl SplashWait {
+    /// Wait for the end of the splash screen, or for exit to be requested.
+    ///
+    /// Returns whether the splash screen was left with a key press, which is consumed.
+    pub fn wait(self, events: &Receiver<Event>, exit: &AtomicBool) -> bool {
+        let start = Instant::now();
+        loop {
+            if exit.load(Ordering::Relaxed) {
+                return false;
+            }
+            match self {
+                SplashWait::Timer(duration) if start.elapsed() >= duration => return false,
+                SplashWait::Timer(_) => {}
+                SplashWait::Hold { timeout } => {
+                    if timeout.is_some_and(|timeout| start.elapsed() >= timeout) {
+                        info!("Splash screen timed out");
+                        return false;
+                    }
+                    match events.try_recv() {
+                        Ok((KeyEvent::Down, _)) => return true,
+                        Ok(_) => continue,
+                        Err(TryRecvError::Empty) => {}
+                        Err(TryRecvError::Disconnected) => return false,
+                    }
+                }
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::sync::mpsc;
+
+    use rboy::KeypadKey;
+
+    use super::*;
+
+    #[test]
+    fn test_should_hold_splash_until_key_down() {
+        let (sender, receiver) = mpsc::channel();
+        let exit = AtomicBool::new(false);
+        let timeout = Duration::from_millis(200);
+        let hold = SplashWait::Hold {
+            timeout: Some(timeout),
+        };
+
+        // a release doesn't leave the splash screen
+        sender.send((KeyEvent::Up, KeypadKey::A)).unwrap();
+        let start = Instant::now();
+        assert!(!hold.wait(&receiver, &exit));
+        assert!(start.elapsed() >= timeout);
+
+        sender.send((KeyEvent::Down, KeypadKey::Start)).unwrap();
+        let start = Instant::now();
+        assert!(hold.wait(&receiver, &exit));
+        assert!(start.elapsed() < timeout);
+        assert!(receiver.try_recv().is_err());
+
+        // the timer leaves the keys to the menu
+        sender.send((KeyEvent::Down, KeypadKey::A)).unwrap();
+        assert!(!SplashWait::Timer(Duration::ZERO).wait(&receiver, &exit));
+        assert!(receiver.try_recv().is_ok());
+    }
+}
@@ -10,6 +10,10 @@ use crate::{StrResult, mbc};
 
 const WRAM_SIZE: usize = 0x8000;
 const ZRAM_SIZE: usize = 0x7F;
+/// Size of the DMG boot ROM, mapped at 0x0000-0x00FF
+pub const DMG_BOOT_ROM_SIZE: usize = 0x100;
+/// Size of the CGB boot ROM, mapped at 0x0000-0x00FF and 0x0200-0x08FF
+pub const CGB_BOOT_ROM_SIZE: usize = 0x900;
 
 #[derive(PartialEq, Serialize, Deserialize)]
 enum DMAType {
@@ -43,6 +47,9 @@ pub struct Mmu {
     gbspeed: GbSpeed,
     speed_switch_req: bool,
     undocumented_cgb_regs: [u8; 3], // 0xFF72, 0xFF73, 0xFF75
+    /// Boot ROM mapped over the cartridge until the boot sequence writes to 0xFF50
+    #[serde(default)]
+    boot_rom: Option<Vec<u8>>,
 }
 
 fn fill_random(slice: &mut [u8], start: u32) {
@@ -88,6 +95,7 @@ impl Mmu {
             hdma_status: DMAType::NoDMA,
             hdma_len: 0xFF,
             undocumented_cgb_regs: [0; 3],
+            boot_rom: None,
         };
         fill_random(&mut res.wram, 42);
         if res.rb(0x0143) == 0xC0 {
@@ -126,6 +134,7 @@ impl Mmu {
             hdma_status: DMAType::NoDMA,
             hdma_len: 0xFF,
             undocumented_cgb_regs: [0; 3],
+            boot_rom: None,
         };
         fill_random(&mut res.wram, 42);
         res.determine_mode();
@@ -167,6 +176,21 @@ impl Mmu {
         self.wb(0xFF4B, 0);
     }
 
+    /// Map a boot ROM over the cartridge; the size must be checked by the caller
+    pub fn map_boot_rom(&mut self, boot_rom: Vec<u8>) {
+        self.boot_rom = Some(boot_rom);
+    }
+
+    /// Byte of the boot ROM at `address`, if it's mapped there
+    fn boot_rom_byte(&self, address: u16) -> Option<u8> {
+        let boot_rom = self.boot_rom.as_ref()?;
+        match address {
+            // the cartridge header stays visible in the CGB boot ROM
+            0x0000..=0x00FF | 0x0200..=0x08FF => boot_rom.get(address as usize).copied(),
+            _ => None,
+        }
+    }
+
     fn determine_mode(&mut self) {
         let mode = match self.rb(0x0143) & 0x80 {
             0x80 => GbMode::Color,
@@ -203,6 +227,9 @@ impl Mmu {
     }
 
     pub fn rb(&mut self, address: u16) -> u8 {
+        if let Some(value) = self.boot_rom_byte(address) {
+            return value;
+        }
         match address {
             0x0000..=0x7FFF => self.mbc.readrom(address),
             0x8000..=0x9FFF => self.gpu.rb(address),
@@ -262,6 +289,7 @@ impl Mmu {
             0xFF04..=0xFF07 => self.timer.wb(address, value),
             0xFF10..=0xFF3F => self.sound.as_mut().map_or((), |s| s.wb(address, value)),
             0xFF46 => self.oamdma(value),
+            0xFF50 if value != 0 => self.boot_rom = None,
             0xFF4D | 0xFF4F | 0xFF51..=0xFF55 | 0xFF6C | 0xFF70 | 0xFF76..=0xFF77
                 if self.gbmode != GbMode::Color => {}
             0xFF72..=0xFF73 | 0xFF75..=0xFF77 if self.gbmode == GbMode::Classic => {}
@@ -293,6 +321,15 @@ impl Mmu {
         self.wb(address + 1, (value >> 8) as u8);
     }
 
+    /// Fill the work RAM with pseudo-random values generated from `seed`
+    pub fn seed_ram(&mut self, seed: u32) {
+        fill_random(&mut self.wram, seed);
+    }
+
+    pub fn is_double_speed(&self) -> bool {
+        self.gbspeed == GbSpeed::Double
+    }
+
     pub fn switch_speed(&mut self) {
         if self.speed_switch_req {
             if self.gbspeed == GbSpeed::Double {
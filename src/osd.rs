@@ -0,0 +1,93 @@
+use std::time::{Duration, Instant};
+
+use rboy::framebuffer::{Framebuffer, GLYPH_SIZE};
+
+const OSD_DURATION: Duration = Duration::from_millis(1500);
+const OSD_X: usize = 4;
+const OSD_Y: usize = 4;
+const OSD_PADDING: usize = 2;
+const COLOR_BACKGROUND: u16 = 0x0000;
+const COLOR_TEXT: u16 = 0xffff;
+
+/// On-screen display of a short message over the game frames
+#[derive(Debug, Default)]
+pub struct Osd {
+    message: Option<(String, Instant)>,
+    /// The message stays until dismissed
+    sticky: bool,
+}
+
+impl Osd {
+    /// Show a message for a short time; a sticky message isn't replaced until dismissed
+    pub fn show(&mut self, text: impl Into<String>) {
+        let text = text.into();
+        if self.sticky {
+            debug!("OSD message \"{text}\" hidden by a sticky message");
+            return;
+        }
+        self.message = Some((text, Instant::now()));
+    }
+
+    /// Show a message until [`Osd::dismiss`] is called
+    pub fn show_sticky(&mut self, text: impl Into<String>) {
+        self.message = Some((text.into(), Instant::now()));
+        self.sticky = true;
+    }
+
+    /// Hide the message shown with [`Osd::show_sticky`]; returns whether there was one
+    pub fn dismiss(&mut self) -> bool {
+        if !self.sticky {
+            return false;
+        }
+        self.sticky = false;
+        self.message = None;
+        true
+    }
+
+    /// Text currently shown, if any
+    pub fn text(&self) -> Option<&str> {
+        self.message
+            .as_ref()
+            .filter(|(_, shown_at)| self.sticky || shown_at.elapsed() < OSD_DURATION)
+            .map(|(text, _)| text.as_str())
+    }
+
+    /// Draw the message, if any, over the last frame written
+    pub fn draw(&mut self, framebuffer: &Framebuffer) {
+        let Some(text) = self.text() else {
+            self.message = None;
+            return;
+        };
+
+        framebuffer.fill_overlay_rect(
+            OSD_X - OSD_PADDING,
+            OSD_Y - OSD_PADDING,
+            text.len() * GLYPH_SIZE + OSD_PADDING * 2,
+            GLYPH_SIZE + OSD_PADDING * 2,
+            COLOR_BACKGROUND,
+        );
+        framebuffer.draw_text(text, OSD_X, OSD_Y, false, COLOR_TEXT);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_should_keep_sticky_message_until_dismissed() {
+        let mut osd = Osd::default();
+        assert!(!osd.dismiss());
+
+        osd.show_sticky("Battery low: 9%");
+        osd.show("Saved");
+        assert_eq!(osd.text(), Some("Battery low: 9%"));
+
+        assert!(osd.dismiss());
+        assert_eq!(osd.text(), None);
+        osd.show("Saved");
+        assert_eq!(osd.text(), Some("Saved"));
+        assert!(!osd.dismiss());
+    }
+}
@@ -0,0 +1,523 @@
+use rboy::KeypadKey;
+use rboy::framebuffer::{Framebuffer, GLYPH_SIZE, ScalingMode, scaled_glyph_size};
+
+use crate::app_config::PictureConfig;
+use crate::cheats::Cheats;
+use crate::picture::{PictureAdjust, PictureSettings};
+use crate::save_state::{self, Slot};
+use crate::saves;
+use crate::volume::Volume;
+
+const PAUSED_ACTIONS: &[OverlayAction] = &[
+    OverlayAction::Resume,
+    OverlayAction::SaveState,
+    OverlayAction::LoadState,
+    OverlayAction::Settings,
+    OverlayAction::QuitToMenu,
+];
+const PAUSED_ACTIONS_WITH_CHEATS: &[OverlayAction] = &[
+    OverlayAction::Resume,
+    OverlayAction::SaveState,
+    OverlayAction::LoadState,
+    OverlayAction::Cheats,
+    OverlayAction::Settings,
+    OverlayAction::QuitToMenu,
+];
+const SETTINGS: &[Setting] = &[
+    Setting::Volume,
+    Setting::Brightness,
+    Setting::Gamma,
+    Setting::Scaling,
+    Setting::Scanlines,
+];
+const CRASHED_ACTIONS: &[OverlayAction] = &[OverlayAction::Restart, OverlayAction::QuitToMenu];
+const PADDING: usize = 8;
+const LINE_H: usize = 16;
+const TITLE_SCALE: f32 = 1.5;
+const COLOR_BACKGROUND: u16 = 0x0000;
+const COLOR_BORDER: u16 = 0xffff;
+const COLOR_TEXT: u16 = 0xffff;
+
+/// Action selected from an in-game overlay
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverlayAction {
+    /// Close the menu and resume the game
+    Resume,
+    /// Pick a slot to save the state of the game to
+    SaveState,
+    /// Pick a slot to load the state of the game from
+    LoadState,
+    /// Turn the cheats of the game on and off
+    Cheats,
+    /// Adjust the volume and the picture
+    Settings,
+    /// Start the game again from power on
+    Restart,
+    /// Stop the game and go back to the game list
+    QuitToMenu,
+}
+
+impl OverlayAction {
+    fn label(&self) -> &'static str {
+        match self {
+            OverlayAction::Resume => "Resume",
+            OverlayAction::SaveState => "Save state",
+            OverlayAction::LoadState => "Load state",
+            OverlayAction::Cheats => "Cheats",
+            OverlayAction::Settings => "Settings",
+            OverlayAction::Restart => "Restart",
+            OverlayAction::QuitToMenu => "Quit to menu",
+        }
+    }
+}
+
+/// Menu drawn over the last frame while the emulator is not running
+#[derive(Debug)]
+pub struct Overlay {
+    title: &'static str,
+    actions: &'static [OverlayAction],
+    /// Action triggered by B, if any
+    cancel: Option<OverlayAction>,
+    selected: usize,
+}
+
+impl Overlay {
+    /// In-game menu shown while the game is paused; the cheats are listed if the game has any
+    pub fn paused(cheats: bool) -> Self {
+        Self {
+            title: "Paused",
+            actions: if cheats {
+                PAUSED_ACTIONS_WITH_CHEATS
+            } else {
+                PAUSED_ACTIONS
+            },
+            cancel: Some(OverlayAction::Resume),
+            selected: 0,
+        }
+    }
+
+    /// Menu shown after the emulation crashed
+    pub fn crashed() -> Self {
+        Self {
+            title: "The game crashed",
+            actions: CRASHED_ACTIONS,
+            cancel: None,
+            selected: 0,
+        }
+    }
+
+    /// Handle a key press and return the [`OverlayAction`] chosen, if any
+    pub fn handle_key(&mut self, key: KeypadKey) -> Option<OverlayAction> {
+        match key {
+            KeypadKey::Up => {
+                self.selected = self.selected.saturating_sub(1);
+                None
+            }
+            KeypadKey::Down => {
+                self.selected = usize::min(self.selected + 1, self.actions.len() - 1);
+                None
+            }
+            KeypadKey::A | KeypadKey::Start => Some(self.actions[self.selected]),
+            KeypadKey::B => self.cancel,
+            _ => None,
+        }
+    }
+
+    /// Draw the menu over the last frame
+    pub fn draw(&self, framebuffer: &Framebuffer, last_frame: Option<&[u8]>) {
+        let labels: Vec<&str> = self.actions.iter().map(|action| action.label()).collect();
+        draw_box(framebuffer, last_frame, self.title, &labels, self.selected);
+    }
+}
+
+/// Whether the [`SlotPicker`] saves to or loads from the chosen slot
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SlotMode {
+    Save,
+    Load,
+}
+
+/// Choice made in the [`SlotPicker`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SlotPick {
+    Slot(usize),
+    Back,
+}
+
+/// Overlay listing the save state slots of a game with their timestamps
+#[derive(Debug)]
+pub struct SlotPicker {
+    mode: SlotMode,
+    labels: Vec<String>,
+    selected: usize,
+}
+
+impl SlotPicker {
+    /// Create a [`SlotPicker`] from the existing slots of a game
+    pub fn new(mode: SlotMode, slots: &[Slot]) -> Self {
+        let labels = (0..save_state::SLOTS)
+            .map(
+                |index| match slots.iter().find(|slot| slot.index == index) {
+                    Some(slot) => format!("{index}: {}", saves::format_modified(slot.modified)),
+                    None => format!("{index}: (empty)"),
+                },
+            )
+            .collect();
+
+        Self {
+            mode,
+            labels,
+            selected: 0,
+        }
+    }
+
+    pub fn mode(&self) -> SlotMode {
+        self.mode
+    }
+
+    /// Handle a key press and return the [`SlotPick`], if any
+    pub fn handle_key(&mut self, key: KeypadKey) -> Option<SlotPick> {
+        match key {
+            KeypadKey::Up => {
+                self.selected = self.selected.saturating_sub(1);
+                None
+            }
+            KeypadKey::Down => {
+                self.selected = usize::min(self.selected + 1, self.labels.len() - 1);
+                None
+            }
+            KeypadKey::A | KeypadKey::Start => Some(SlotPick::Slot(self.selected)),
+            KeypadKey::B => Some(SlotPick::Back),
+            _ => None,
+        }
+    }
+
+    /// Draw the slots over the last frame
+    pub fn draw(&self, framebuffer: &Framebuffer, last_frame: Option<&[u8]>) {
+        let title = match self.mode {
+            SlotMode::Save => "Save to slot",
+            SlotMode::Load => "Load from slot",
+        };
+        let labels: Vec<&str> = self.labels.iter().map(String::as_str).collect();
+        draw_box(framebuffer, last_frame, title, &labels, self.selected);
+    }
+}
+
+/// Option of the [`SettingsPage`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Setting {
+    Volume,
+    Brightness,
+    Gamma,
+    Scaling,
+    Scanlines,
+}
+
+/// Choice made in the [`SettingsPage`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SettingsPick {
+    /// The setting has been changed and must be applied and saved
+    Changed(Setting),
+    Back,
+}
+
+/// Overlay adjusting the settings: Up and Down select an option, Left and Right change it
+#[derive(Debug, Default)]
+pub struct SettingsPage {
+    selected: usize,
+}
+
+impl SettingsPage {
+    /// Handle a key press, changing the selected setting within its bounds, and return the [`SettingsPick`], if any
+    pub fn handle_key(
+        &mut self,
+        key: KeypadKey,
+        volume: &Volume,
+        picture: &mut PictureSettings,
+        scaling: &mut ScalingMode,
+        config: &PictureConfig,
+    ) -> Option<SettingsPick> {
+        let setting = SETTINGS[self.selected];
+        let adjust = |up: bool| match (setting, up) {
+            (Setting::Brightness, true) => Some(PictureAdjust::BrightnessUp),
+            (Setting::Brightness, false) => Some(PictureAdjust::BrightnessDown),
+            (Setting::Gamma, true) => Some(PictureAdjust::GammaUp),
+            (Setting::Gamma, false) => Some(PictureAdjust::GammaDown),
+            (Setting::Volume | Setting::Scaling | Setting::Scanlines, _) => None,
+        };
+
+        match key {
+            KeypadKey::Up => {
+                self.selected = self.selected.saturating_sub(1);
+                None
+            }
+            KeypadKey::Down => {
+                self.selected = usize::min(self.selected + 1, SETTINGS.len() - 1);
+                None
+            }
+            KeypadKey::Left | KeypadKey::Right => {
+                let up = key == KeypadKey::Right;
+                match (adjust(up), setting) {
+                    (Some(adjust), _) => {
+                        picture.adjust(adjust, config);
+                    }
+                    (None, Setting::Scaling) if up => *scaling = scaling.next(),
+                    (None, Setting::Scaling) => *scaling = scaling.previous(),
+                    (None, Setting::Scanlines) => picture.scanlines = !picture.scanlines,
+                    (None, _) if up => volume.up(),
+                    (None, _) => volume.down(),
+                }
+                Some(SettingsPick::Changed(setting))
+            }
+            KeypadKey::B => Some(SettingsPick::Back),
+            _ => None,
+        }
+    }
+
+    /// Draw the settings with their values over the last frame
+    pub fn draw(
+        &self,
+        framebuffer: &Framebuffer,
+        last_frame: Option<&[u8]>,
+        volume: &Volume,
+        picture: &PictureSettings,
+    ) {
+        let labels: Vec<String> = SETTINGS
+            .iter()
+            .map(|setting| match setting {
+                Setting::Volume => format!("Volume     < {:>3}% >", volume.percent()),
+                Setting::Brightness => format!("Brightness <  {:.1} >", picture.brightness),
+                Setting::Gamma => format!("Gamma      <  {:.1} >", picture.gamma),
+                Setting::Scaling => format!("Scaling    < {} >", framebuffer.scaling().name()),
+                Setting::Scanlines => match picture.scanlines {
+                    true => "Scanlines  < on >".to_string(),
+                    false => "Scanlines  < off >".to_string(),
+                },
+            })
+            .collect();
+        let labels: Vec<&str> = labels.iter().map(String::as_str).collect();
+        draw_box(framebuffer, last_frame, "Settings", &labels, self.selected);
+    }
+}
+
+/// Choice made in the [`CheatsPage`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheatsPick {
+    /// A cheat has been turned on or off and the applied cheats must be updated
+    Toggled,
+    Back,
+}
+
+/// Overlay listing the cheats of the game: A turns the selected one on or off
+#[derive(Debug, Default)]
+pub struct CheatsPage {
+    selected: usize,
+}
+
+impl CheatsPage {
+    /// Handle a key press and return the [`CheatsPick`], if any
+    pub fn handle_key(&mut self, key: KeypadKey, cheats: &mut Cheats) -> Option<CheatsPick> {
+        match key {
+            KeypadKey::Up => {
+                self.selected = self.selected.saturating_sub(1);
+                None
+            }
+            KeypadKey::Down => {
+                self.selected =
+                    usize::min(self.selected + 1, cheats.cheats().len().saturating_sub(1));
+                None
+            }
+            KeypadKey::A | KeypadKey::Start => {
+                cheats.toggle(self.selected);
+                Some(CheatsPick::Toggled)
+            }
+            KeypadKey::B => Some(CheatsPick::Back),
+            _ => None,
+        }
+    }
+
+    /// Draw the cheats with their state over the last frame
+    pub fn draw(&self, framebuffer: &Framebuffer, last_frame: Option<&[u8]>, cheats: &Cheats) {
+        let labels: Vec<String> = cheats
+            .cheats()
+            .iter()
+            .map(|cheat| format!("[{}] {}", if cheat.enabled { "x" } else { " " }, cheat.name))
+            .collect();
+        let labels: Vec<&str> = labels.iter().map(String::as_str).collect();
+        draw_box(framebuffer, last_frame, "Cheats", &labels, self.selected);
+    }
+}
+
+/// Draw a box with a title and a list of entries over the last frame
+fn draw_box(
+    framebuffer: &Framebuffer,
+    last_frame: Option<&[u8]>,
+    title: &str,
+    labels: &[&str],
+    selected: usize,
+) {
+    match last_frame {
+        Some(frame) => framebuffer.write(frame),
+        None => framebuffer.zero(),
+    }
+
+    let (x, y, width, height) = geometry(framebuffer, title, labels);
+    framebuffer.fill_rect(x, y, width, 1, COLOR_BORDER);
+    framebuffer.fill_rect(x, y + height - 1, width, 1, COLOR_BORDER);
+    framebuffer.fill_rect(x, y, 1, height, COLOR_BORDER);
+    framebuffer.fill_rect(x + width - 1, y, 1, height, COLOR_BORDER);
+    framebuffer.fill_overlay_rect(x + 1, y + 1, width - 2, height - 2, COLOR_BACKGROUND);
+
+    let text_x = x + PADDING;
+    let mut text_y = y + PADDING;
+    framebuffer.draw_text_scaled(title, text_x, text_y, TITLE_SCALE, COLOR_TEXT);
+    for (i, label) in labels.iter().enumerate() {
+        text_y += LINE_H;
+        let is_selected = i == selected;
+        let line = format!("{} {label}", if is_selected { ">" } else { " " });
+        framebuffer.draw_text(&line, text_x, text_y, is_selected, COLOR_TEXT);
+    }
+}
+
+/// Get the box of the menu (x, y, width, height), centered on the screen
+fn geometry(
+    framebuffer: &Framebuffer,
+    title: &str,
+    labels: &[&str],
+) -> (usize, usize, usize, usize) {
+    let title_size = scaled_glyph_size(TITLE_SCALE);
+    let text_width = labels
+        .iter()
+        .map(|label| (label.len() + 2) * GLYPH_SIZE)
+        .chain(std::iter::once(title.len() * title_size))
+        .max()
+        .unwrap_or_default();
+    let width = text_width + PADDING * 2;
+    let height = labels.len() * LINE_H + title_size + PADDING * 2;
+    let x = (framebuffer.width() / 2).saturating_sub(width / 2);
+    let y = (framebuffer.height() / 2).saturating_sub(height / 2);
+
+    (x, y, width, height)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_should_select_overlay_action() {
+        let mut overlay = Overlay::paused(false);
+        assert_eq!(
+            overlay.handle_key(KeypadKey::A),
+            Some(OverlayAction::Resume)
+        );
+
+        assert_eq!(overlay.handle_key(KeypadKey::Down), None);
+        assert_eq!(
+            overlay.handle_key(KeypadKey::A),
+            Some(OverlayAction::SaveState)
+        );
+        for _ in 0..4 {
+            assert_eq!(overlay.handle_key(KeypadKey::Down), None);
+        }
+        assert_eq!(
+            overlay.handle_key(KeypadKey::Start),
+            Some(OverlayAction::QuitToMenu)
+        );
+        assert_eq!(
+            overlay.handle_key(KeypadKey::B),
+            Some(OverlayAction::Resume)
+        );
+
+        assert_eq!(overlay.handle_key(KeypadKey::Up), None);
+        assert_eq!(
+            overlay.handle_key(KeypadKey::A),
+            Some(OverlayAction::Settings)
+        );
+        assert_eq!(overlay.handle_key(KeypadKey::Up), None);
+        assert_eq!(
+            overlay.handle_key(KeypadKey::A),
+            Some(OverlayAction::LoadState)
+        );
+    }
+
+    #[test]
+    fn test_should_adjust_settings_within_bounds() {
+        let config = PictureConfig {
+            brightness_min: 0.5,
+            brightness_max: 1.2,
+            gamma_min: 0.5,
+            gamma_max: 2.0,
+            step: 0.1,
+        };
+        let volume = Volume::default();
+        let mut picture = PictureSettings::default();
+        let mut scaling = ScalingMode::FitHeight;
+        let mut page = SettingsPage::default();
+        let mut press = |key| page.handle_key(key, &volume, &mut picture, &mut scaling, &config);
+
+        assert_eq!(
+            press(KeypadKey::Right),
+            Some(SettingsPick::Changed(Setting::Volume))
+        );
+        assert_eq!(
+            press(KeypadKey::Left),
+            Some(SettingsPick::Changed(Setting::Volume))
+        );
+        assert_eq!(press(KeypadKey::Down), None);
+        for _ in 0..5 {
+            assert_eq!(
+                press(KeypadKey::Right),
+                Some(SettingsPick::Changed(Setting::Brightness))
+            );
+        }
+        assert_eq!(press(KeypadKey::Down), None);
+        assert_eq!(
+            press(KeypadKey::Left),
+            Some(SettingsPick::Changed(Setting::Gamma))
+        );
+        assert_eq!(press(KeypadKey::Down), None);
+        assert_eq!(
+            press(KeypadKey::Left),
+            Some(SettingsPick::Changed(Setting::Scaling))
+        );
+        assert_eq!(
+            press(KeypadKey::Left),
+            Some(SettingsPick::Changed(Setting::Scaling))
+        );
+        assert_eq!(press(KeypadKey::Down), None);
+        assert_eq!(
+            press(KeypadKey::Right),
+            Some(SettingsPick::Changed(Setting::Scanlines))
+        );
+        // the last option stays selected
+        assert_eq!(press(KeypadKey::Down), None);
+        assert_eq!(
+            press(KeypadKey::Left),
+            Some(SettingsPick::Changed(Setting::Scanlines))
+        );
+        assert_eq!(
+            press(KeypadKey::Right),
+            Some(SettingsPick::Changed(Setting::Scanlines))
+        );
+        assert_eq!(press(KeypadKey::B), Some(SettingsPick::Back));
+
+        assert_eq!(volume.percent(), 90);
+        assert_eq!(picture.brightness, 1.2);
+        assert!((picture.gamma - 0.9).abs() < 1e-6);
+        // wrapped backwards
+        assert_eq!(scaling, ScalingMode::FitWidth);
+        assert!(picture.scanlines);
+    }
+
+    #[test]
+    fn test_should_not_cancel_crash_overlay() {
+        let mut overlay = Overlay::crashed();
+        assert_eq!(overlay.handle_key(KeypadKey::B), None);
+        assert_eq!(
+            overlay.handle_key(KeypadKey::A),
+            Some(OverlayAction::Restart)
+        );
+    }
+}
@@ -0,0 +1,433 @@
+use std::time::{Duration, Instant};
+
+/// Outcome of a batch of CPU cycles
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BatchOutcome {
+    /// All the ticks of the batch have been run
+    Completed,
+    /// The batch exceeded its time budget; remaining ticks are carried over to the next batch
+    Deferred,
+    /// The batch has been aborted by the step function
+    Aborted,
+}
+
+/// Emulation speeds which can be selected, in percent of the normal speed
+pub const SPEED_PERCENTS: [u32; 5] = [25, 50, 100, 200, 400];
+
+/// Emulation speed selected among [`SPEED_PERCENTS`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpeedPercent(usize);
+
+impl Default for SpeedPercent {
+    fn default() -> Self {
+        Self::new(100).expect("normal speed")
+    }
+}
+
+impl SpeedPercent {
+    /// Select `percent`, if it's one of [`SPEED_PERCENTS`]
+    pub fn new(percent: u32) -> Option<Self> {
+        SPEED_PERCENTS.iter().position(|p| *p == percent).map(Self)
+    }
+
+    pub fn percent(&self) -> u32 {
+        SPEED_PERCENTS[self.0]
+    }
+
+    /// Speed multiplier, see [`CycleBudget::set_speed`]
+    pub fn multiplier(&self) -> f32 {
+        self.percent() as f32 / 100.0
+    }
+
+    /// Label of the speed for the OSD, noting the cap of the game if it's slower,
+    /// see [`CycleBudget::set_max_speed`]
+    pub fn label(&self, max_speed: Option<f32>) -> String {
+        match max_speed.filter(|max_speed| *max_speed < self.multiplier()) {
+            Some(max_speed) => format!(
+                "Speed: {}% (max {}%)",
+                self.percent(),
+                (max_speed * 100.0).round()
+            ),
+            None => format!("Speed: {}%", self.percent()),
+        }
+    }
+
+    /// The next faster speed, wrapping to the slowest one
+    pub fn next(&self) -> Self {
+        Self((self.0 + 1) % SPEED_PERCENTS.len())
+    }
+}
+
+/// Fast-forward engaged for a while after a game starts, to skip long intros
+#[derive(Debug)]
+pub struct BootTurbo {
+    speed: f32,
+    until: Instant,
+    state: BootTurboState,
+}
+
+/// Speed change requested by the [`BootTurbo`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TurboChange {
+    /// Run at the given speed multiplier
+    Engage(f32),
+    /// Go back to the speed selected before the fast-forward
+    Disengage,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BootTurboState {
+    Pending,
+    Engaged,
+    Done,
+}
+
+impl BootTurbo {
+    /// Create a [`BootTurbo`] running at `speed` for `duration` since `now`
+    pub fn new(speed: f32, duration: Duration, now: Instant) -> Self {
+        Self {
+            speed,
+            until: now + duration,
+            state: BootTurboState::Pending,
+        }
+    }
+
+    /// Get the speed change at `now`, if any
+    pub fn poll(&mut self, now: Instant) -> Option<TurboChange> {
+        match self.state {
+            BootTurboState::Pending => {
+                self.state = BootTurboState::Engaged;
+                Some(TurboChange::Engage(self.speed))
+            }
+            BootTurboState::Engaged if now >= self.until => {
+                self.state = BootTurboState::Done;
+                Some(TurboChange::Disengage)
+            }
+            BootTurboState::Engaged | BootTurboState::Done => None,
+        }
+    }
+}
+
+/// Late batches in a row before one more frame is skipped
+const LATE_BATCHES: u32 = 3;
+/// Batches on time in a row before one frame less is skipped
+const ON_TIME_BATCHES: u32 = 60;
+
+/// Adaptive frame skip: skips more frames while the emulation falls behind the wall clock,
+/// and fewer once it catches up, to keep the timing while showing as many frames as possible.
+///
+/// The skipped frames aren't drawn, which is what reduces the load.
+#[derive(Debug)]
+pub struct AutoFrameSkip {
+    /// Most frames skipped between two shown frames; 0 never skips
+    max_skip: u32,
+    /// Frames currently skipped between two shown frames
+    skip: u32,
+    /// Frames left to skip before the next one is shown
+    countdown: u32,
+    /// Late batches in a row
+    late: u32,
+    /// Batches on time in a row
+    on_time: u32,
+}
+
+impl AutoFrameSkip {
+    /// Create an [`AutoFrameSkip`] skipping up to `max_skip` frames in a row
+    pub fn new(max_skip: u32) -> Self {
+        Self {
+            max_skip,
+            skip: 0,
+            countdown: 0,
+            late: 0,
+            on_time: 0,
+        }
+    }
+
+    /// Frames currently skipped between two shown frames
+    pub fn skip(&self) -> u32 {
+        self.skip
+    }
+
+    /// Update with the wall-clock time a batch took and the time it should have taken.
+    ///
+    /// A batch is on time only with some slack, so the skip doesn't flap at the limit.
+    pub fn update(&mut self, elapsed: Duration, budget: Duration) {
+        if elapsed > budget {
+            self.on_time = 0;
+            self.late += 1;
+            if self.late >= LATE_BATCHES && self.skip < self.max_skip {
+                self.skip += 1;
+                self.late = 0;
+                debug!("Falling behind; skipping {} frames", self.skip());
+            }
+        } else if elapsed < budget * 3 / 4 {
+            self.late = 0;
+            self.on_time += 1;
+            if self.on_time >= ON_TIME_BATCHES && self.skip > 0 {
+                self.skip -= 1;
+                self.on_time = 0;
+                debug!("Caught up; skipping {} frames", self.skip());
+            }
+        }
+    }
+
+    /// Whether the next frame must be shown; a skipped frame needn't be drawn
+    pub fn show_frame(&mut self) -> bool {
+        if self.countdown > 0 {
+            self.countdown -= 1;
+            false
+        } else {
+            self.countdown = self.skip;
+            true
+        }
+    }
+}
+
+/// Whether `speed` is a speed multiplier the CPU can run at, i.e. a finite positive number
+pub fn is_valid_speed(speed: f32) -> bool {
+    speed.is_finite() && speed > 0.0
+}
+
+/// Tracks the amount of ticks to run for each batch of the CPU loop,
+/// enforcing a wall-clock budget so a slow batch can't starve the rest of the loop.
+#[derive(Debug)]
+pub struct CycleBudget {
+    /// Ticks to run per batch at normal speed
+    base_ticks: u32,
+    /// Ticks to run per batch at the current speed
+    target_ticks: u32,
+    /// Multiplier of the emulation speed
+    speed: f32,
+    /// Highest multiplier of the emulation speed; the speed set is clamped to it
+    max_speed: Option<f32>,
+    /// Maximum wall-clock time a batch can take
+    time_budget: Duration,
+    /// Ticks run so far in the current batch (possibly carried over from a deferred batch)
+    ticks: u32,
+}
+
+impl CycleBudget {
+    /// Create a new [`CycleBudget`] running `target_ticks` per batch within `time_budget`
+    pub fn new(target_ticks: u32, time_budget: Duration) -> Self {
+        Self {
+            base_ticks: target_ticks,
+            target_ticks,
+            speed: 1.0,
+            max_speed: None,
+            time_budget,
+            ticks: 0,
+        }
+    }
+
+    /// Ticks to run in the next batch
+    pub fn target_ticks(&self) -> u32 {
+        self.target_ticks
+    }
+
+    /// Set the emulation speed multiplier, e.g. `2.0` to run twice as fast.
+    ///
+    /// A multiplier which isn't a positive number would stop the game, so it's ignored.
+    pub fn set_speed(&mut self, speed: f32) {
+        if !is_valid_speed(speed) {
+            warn!("Ignoring invalid speed multiplier {speed}");
+            return;
+        }
+        self.speed = speed;
+        self.update_target_ticks();
+    }
+
+    /// Cap the emulation speed multiplier, e.g. for a game glitching when fast-forwarded,
+    /// or remove the cap with `None`; an invalid cap is ignored, as in [`Self::set_speed`]
+    pub fn set_max_speed(&mut self, max_speed: Option<f32>) {
+        if let Some(speed) = max_speed.filter(|speed| !is_valid_speed(*speed)) {
+            warn!("Ignoring invalid speed cap {speed}");
+            return;
+        }
+        self.max_speed = max_speed;
+        self.update_target_ticks();
+    }
+
+    /// Multiplier of the emulation speed, clamped to the cap
+    pub fn speed(&self) -> f32 {
+        match self.max_speed {
+            Some(max_speed) => self.speed.min(max_speed),
+            None => self.speed,
+        }
+    }
+
+    fn update_target_ticks(&mut self) {
+        self.target_ticks = (self.base_ticks as f32 * self.speed()).round() as u32;
+    }
+
+    /// Ticks run so far in the current batch
+    pub fn ticks(&self) -> u32 {
+        self.ticks
+    }
+
+    /// Run a batch calling `step` until the target ticks are reached or the time budget is exceeded.
+    ///
+    /// `step` must return the ticks elapsed, or `None` to abort the batch.
+    pub fn run_batch<F>(&mut self, mut step: F) -> BatchOutcome
+    where
+        F: FnMut() -> Option<u32>,
+    {
+        let started_at = Instant::now();
+
+        while self.ticks < self.target_ticks {
+            let Some(ticks) = step() else {
+                return BatchOutcome::Aborted;
+            };
+            self.ticks += ticks;
+
+            if self.ticks < self.target_ticks && started_at.elapsed() >= self.time_budget {
+                return BatchOutcome::Deferred;
+            }
+        }
+
+        self.ticks -= self.target_ticks;
+        BatchOutcome::Completed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_should_complete_batch_within_budget() {
+        let mut budget = CycleBudget::new(100, Duration::from_secs(60));
+
+        assert_eq!(budget.run_batch(|| Some(30)), BatchOutcome::Completed);
+        // 120 - 100
+        assert_eq!(budget.ticks(), 20);
+    }
+
+    #[test]
+    fn test_should_defer_overlong_batch() {
+        let mut budget = CycleBudget::new(100, Duration::ZERO);
+
+        assert_eq!(budget.run_batch(|| Some(30)), BatchOutcome::Deferred);
+        assert_eq!(budget.ticks(), 30);
+        // the remaining ticks are carried over to the next batch
+        assert_eq!(budget.run_batch(|| Some(30)), BatchOutcome::Deferred);
+        assert_eq!(budget.ticks(), 60);
+        assert_eq!(budget.run_batch(|| Some(30)), BatchOutcome::Deferred);
+        assert_eq!(budget.run_batch(|| Some(30)), BatchOutcome::Completed);
+        assert_eq!(budget.ticks(), 20);
+    }
+
+    #[test]
+    fn test_should_adapt_frame_skip_to_lateness() {
+        let budget = Duration::from_millis(16);
+        let mut frameskip = AutoFrameSkip::new(2);
+        assert!(frameskip.show_frame());
+        assert!(frameskip.show_frame());
+
+        for _ in 0..LATE_BATCHES * 5 {
+            frameskip.update(Duration::from_millis(30), budget);
+        }
+        // capped
+        assert_eq!(frameskip.skip(), 2);
+        let shown: Vec<bool> = (0..6).map(|_| frameskip.show_frame()).collect();
+        assert_eq!(shown, [true, false, false, true, false, false]);
+
+        // barely on time doesn't count as catching up
+        for _ in 0..ON_TIME_BATCHES {
+            frameskip.update(Duration::from_millis(15), budget);
+        }
+        assert_eq!(frameskip.skip(), 2);
+        for _ in 0..ON_TIME_BATCHES {
+            frameskip.update(Duration::from_millis(5), budget);
+        }
+        assert_eq!(frameskip.skip(), 1);
+
+        // disabled
+        let mut frameskip = AutoFrameSkip::new(0);
+        frameskip.update(Duration::from_secs(1), budget);
+        frameskip.update(Duration::from_secs(1), budget);
+        frameskip.update(Duration::from_secs(1), budget);
+        assert_eq!(frameskip.skip(), 0);
+    }
+
+    #[test]
+    fn test_should_disengage_boot_turbo() {
+        let start = Instant::now();
+        let mut turbo = BootTurbo::new(4.0, Duration::from_secs(10), start);
+
+        assert_eq!(turbo.poll(start), Some(TurboChange::Engage(4.0)));
+        assert_eq!(turbo.poll(start + Duration::from_secs(9)), None);
+        assert_eq!(
+            turbo.poll(start + Duration::from_secs(10)),
+            Some(TurboChange::Disengage)
+        );
+        assert_eq!(turbo.poll(start + Duration::from_secs(60)), None);
+    }
+
+    #[test]
+    fn test_should_clamp_speed_to_cap() {
+        let mut budget = CycleBudget::new(100, Duration::from_secs(60));
+        budget.set_speed(4.0);
+        assert_eq!(budget.target_ticks(), 400);
+
+        budget.set_max_speed(Some(2.0));
+        assert_eq!(budget.speed(), 2.0);
+        assert_eq!(budget.target_ticks(), 200);
+        // slower speeds aren't affected
+        budget.set_speed(0.5);
+        assert_eq!(budget.target_ticks(), 50);
+        budget.set_speed(8.0);
+        assert_eq!(budget.target_ticks(), 200);
+
+        budget.set_max_speed(None);
+        assert_eq!(budget.target_ticks(), 800);
+    }
+
+    #[test]
+    fn test_should_ignore_invalid_speed() {
+        let mut budget = CycleBudget::new(100, Duration::from_secs(60));
+        budget.set_speed(2.0);
+        for speed in [0.0, -1.0, f32::NAN, f32::INFINITY] {
+            budget.set_speed(speed);
+            budget.set_max_speed(Some(speed));
+            assert_eq!(budget.speed(), 2.0);
+            assert_eq!(budget.target_ticks(), 200);
+        }
+    }
+
+    #[test]
+    fn test_should_scale_budget_to_speed_percent() {
+        // the CPU loop runs the ticks of 16 ms every 16 ms
+        let clock = 4194304.0;
+        let batch_time = Duration::from_millis(16);
+        let mut budget = CycleBudget::new(
+            (clock * batch_time.as_secs_f64()).round() as u32,
+            batch_time,
+        );
+
+        let mut speed = SpeedPercent::new(25).unwrap();
+        for percent in SPEED_PERCENTS {
+            assert_eq!(speed.percent(), percent);
+            budget.set_speed(speed.multiplier());
+            let ticks_per_sec = budget.target_ticks() as f64 / batch_time.as_secs_f64();
+            let expected = clock * percent as f64 / 100.0;
+            assert!((ticks_per_sec - expected).abs() / expected < 1e-4);
+            speed = speed.next();
+        }
+        assert_eq!(speed.percent(), 25);
+        assert_eq!(SpeedPercent::default().percent(), 100);
+        // the OSD notes the cap of the game
+        let fast = SpeedPercent::new(200).unwrap();
+        assert_eq!(fast.label(None), "Speed: 200%");
+        assert_eq!(fast.label(Some(1.5)), "Speed: 200% (max 150%)");
+        assert_eq!(fast.label(Some(2.0)), "Speed: 200%");
+        assert!(SpeedPercent::new(150).is_none());
+    }
+
+    #[test]
+    fn test_should_abort_batch() {
+        let mut budget = CycleBudget::new(100, Duration::from_secs(60));
+
+        assert_eq!(budget.run_batch(|| None), BatchOutcome::Aborted);
+    }
+}
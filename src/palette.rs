@@ -0,0 +1,56 @@
+use rboy::framebuffer::{DMG_PALETTES, DmgPalette};
+
+/// Cycles through the colorization palettes of the classic games
+#[derive(Debug, Clone, Default)]
+pub struct PaletteCycle {
+    index: usize,
+}
+
+impl PaletteCycle {
+    /// Create a [`PaletteCycle`] starting at the palette with the given name, case insensitive,
+    /// or at the first one
+    pub fn new(name: Option<&str>) -> Self {
+        let Some(name) = name else {
+            return Self::default();
+        };
+        match DMG_PALETTES
+            .iter()
+            .position(|palette| palette.name.eq_ignore_ascii_case(name))
+        {
+            Some(index) => Self { index },
+            None => {
+                warn!("Unknown palette: {name}");
+                Self::default()
+            }
+        }
+    }
+
+    pub fn current(&self) -> DmgPalette {
+        DMG_PALETTES[self.index]
+    }
+
+    /// Switch to the next palette, wrapping around, and return it
+    pub fn next(&mut self) -> DmgPalette {
+        self.index = (self.index + 1) % DMG_PALETTES.len();
+        self.current()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_should_cycle_palettes() {
+        let mut cycle = PaletteCycle::new(Some("pocket"));
+        assert_eq!(cycle.current().name, "Pocket");
+        assert_eq!(cycle.next().name, "Brown");
+
+        let mut cycle = PaletteCycle::new(Some("Inverted"));
+        assert_eq!(cycle.current(), DMG_PALETTES[DMG_PALETTES.len() - 1]);
+        assert_eq!(cycle.next().name, "Gray");
+
+        assert_eq!(PaletteCycle::new(Some("sepia")).current().name, "Gray");
+    }
+}
@@ -0,0 +1,170 @@
+use std::path::Path;
+
+use rboy::framebuffer::{ColorLut, ScalingMode};
+use serde::{Deserialize, Serialize};
+
+use crate::app_config::PictureConfig;
+
+/// File storing the picture settings chosen by the user
+const PICTURE_FILE: &str = ".rboy-picture";
+
+/// Step of a picture setting
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PictureAdjust {
+    BrightnessUp,
+    BrightnessDown,
+    GammaUp,
+    GammaDown,
+}
+
+/// Brightness, gamma, scaling and scanlines of the game picture
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PictureSettings {
+    pub brightness: f32,
+    pub gamma: f32,
+    /// Scaling of the games, unless one is remembered for the game; the command line one if unset
+    pub scaling: Option<ScalingMode>,
+    pub scanlines: bool,
+}
+
+impl Default for PictureSettings {
+    fn default() -> Self {
+        Self {
+            brightness: 1.0,
+            gamma: 1.0,
+            scaling: None,
+            scanlines: false,
+        }
+    }
+}
+
+impl PictureSettings {
+    /// Load the settings saved in `dir`, or the defaults
+    pub fn load(dir: &Path) -> Self {
+        let path = dir.join(PICTURE_FILE);
+        if !path.exists() {
+            return Self::default();
+        }
+        Self::load_from_file(&path).unwrap_or_else(|err| {
+            warn!("{err}");
+            Self::default()
+        })
+    }
+
+    /// Save the settings in `dir`
+    pub fn save(&self, dir: &Path) {
+        if let Err(err) = self.save_to_file(&dir.join(PICTURE_FILE)) {
+            warn!("{err}");
+        }
+    }
+
+    /// Load the settings from a TOML file
+    pub fn load_from_file(path: &Path) -> anyhow::Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Failed to read picture settings {:?}: {}", path, e))?;
+        toml::from_str(&content)
+            .map_err(|e| anyhow::anyhow!("Invalid picture settings {:?}: {}", path, e))
+    }
+
+    /// Save the settings to a TOML file
+    pub fn save_to_file(&self, path: &Path) -> anyhow::Result<()> {
+        let content = toml::to_string(self)
+            .map_err(|e| anyhow::anyhow!("Failed to serialize picture settings: {e}"))?;
+        std::fs::write(path, content)
+            .map_err(|e| anyhow::anyhow!("Failed to save picture settings to {:?}: {}", path, e))
+    }
+
+    /// Step a setting, clamped to the configured bounds, and return the new [`ColorLut`]
+    pub fn adjust(&mut self, adjust: PictureAdjust, config: &PictureConfig) -> ColorLut {
+        match adjust {
+            PictureAdjust::BrightnessUp => self.brightness += config.step,
+            PictureAdjust::BrightnessDown => self.brightness -= config.step,
+            PictureAdjust::GammaUp => self.gamma += config.step,
+            PictureAdjust::GammaDown => self.gamma -= config.step,
+        }
+        self.clamp(config);
+
+        self.lut()
+    }
+
+    /// Clamp the settings to the configured bounds
+    pub fn clamp(&mut self, config: &PictureConfig) {
+        self.brightness = self
+            .brightness
+            .clamp(config.brightness_min, config.brightness_max);
+        self.gamma = self.gamma.clamp(config.gamma_min, config.gamma_max);
+    }
+
+    pub fn lut(&self) -> ColorLut {
+        ColorLut::new(self.brightness, self.gamma)
+    }
+
+    /// Text shown on the OSD
+    pub fn label(&self) -> String {
+        format!("Brightness {:.1} Gamma {:.1}", self.brightness, self.gamma)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_should_step_brightness_and_clamp() {
+        let config = PictureConfig {
+            brightness_min: 0.5,
+            brightness_max: 1.2,
+            gamma_min: 0.5,
+            gamma_max: 2.0,
+            step: 0.1,
+        };
+        let mut settings = PictureSettings::default();
+
+        let lut = settings.adjust(PictureAdjust::BrightnessUp, &config);
+        assert!((settings.brightness - 1.1).abs() < 1e-6);
+        assert_eq!(lut, ColorLut::new(settings.brightness, 1.0));
+
+        settings.adjust(PictureAdjust::BrightnessUp, &config);
+        let lut = settings.adjust(PictureAdjust::BrightnessUp, &config);
+        assert_eq!(settings.brightness, 1.2);
+        assert_eq!(lut.brightness(), 1.2);
+
+        for _ in 0..20 {
+            settings.adjust(PictureAdjust::BrightnessDown, &config);
+        }
+        assert_eq!(settings.brightness, 0.5);
+    }
+
+    #[test]
+    fn test_should_persist_picture_settings() {
+        let dir = TempDir::new().unwrap();
+        assert_eq!(
+            PictureSettings::load(dir.path()),
+            PictureSettings::default()
+        );
+
+        let settings = PictureSettings {
+            brightness: 0.8,
+            gamma: 1.4,
+            scaling: Some(ScalingMode::FitWidth),
+            scanlines: true,
+        };
+        settings.save(dir.path());
+        assert_eq!(PictureSettings::load(dir.path()), settings);
+
+        // the settings missing from the file keep their default
+        let path = dir.path().join("picture.toml");
+        std::fs::write(&path, "gamma = 1.2\n").unwrap();
+        let loaded = PictureSettings::load_from_file(&path).unwrap();
+        assert_eq!(loaded.gamma, 1.2);
+        assert_eq!(loaded.brightness, 1.0);
+        assert_eq!(loaded.scaling, None);
+        assert!(!loaded.scanlines);
+        loaded.save_to_file(&path).unwrap();
+        assert_eq!(PictureSettings::load_from_file(&path).unwrap(), loaded);
+    }
+}
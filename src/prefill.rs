@@ -0,0 +1,27 @@
+/// Holds the audio playback at startup until enough samples are queued, avoiding the crackle
+/// of a buffer which starts empty.
+#[derive(Debug)]
+pub struct Prefill {
+    /// Frames to queue before starting
+    frames: usize,
+    started: bool,
+}
+
+impl Prefill {
+    /// Create a [`Prefill`] starting once `frames` frames are queued; 0 starts immediately
+    pub fn new(frames: usize) -> Self {
+        Self {
+            frames,
+            started: frames == 0,
+        }
+    }
+
+    /// Whether the playback can run with `queued` frames; once started, it keeps running
+    pub fn ready(&mut self, queued: usize) -> bool {
+        if !self.started && queued >= self.frames {
+            debug!("Audio prefilled with {queued} frames; starting playback");
+            self.started = true;
+        }
+        self.started
+    }
+}
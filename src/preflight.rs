@@ -0,0 +1,167 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::app_config::AppConfig;
+
+/// Check the configuration, the ROMs directory and the framebuffer without starting the app.
+///
+/// Returns the problems found; if empty, the app is ready to run.
+pub fn run(config_path: &Path, framebuffer_path: &Path) -> Vec<String> {
+    let mut problems = vec![];
+
+    match AppConfig::load_from_file(config_path) {
+        Ok(config) => {
+            info!("Config {} loaded", config_path.display());
+            problems.extend(check_pinout(&config));
+            // the other directories may be on removable media
+            for (i, dir) in config.roms_directories.iter().enumerate() {
+                match count_roms(dir, &config) {
+                    Err(err) if i == 0 => problems.push(err),
+                    Err(err) => warn!("{err}"),
+                    Ok(_) => {}
+                }
+            }
+        }
+        Err(err) => problems.push(err.to_string()),
+    }
+
+    // open only: mapping or writing would draw on the screen
+    match std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(framebuffer_path)
+    {
+        Ok(_) => info!("Framebuffer {} can be opened", framebuffer_path.display()),
+        Err(err) => problems.push(format!(
+            "Cannot open framebuffer {}: {err}",
+            framebuffer_path.display()
+        )),
+    }
+
+    problems
+}
+
+/// Check that no GPIO is used twice by the keys and the power switches of each key mapping
+fn check_pinout(config: &AppConfig) -> Vec<String> {
+    let mappings = std::iter::once(("default", config.keys.as_slice())).chain(
+        config
+            .profiles
+            .iter()
+            .map(|p| (p.name.as_str(), p.keys.as_slice())),
+    );
+
+    let mut problems = vec![];
+    for (name, keys) in mappings {
+        let mut used: HashMap<u8, String> = HashMap::new();
+        let pins = keys
+            .iter()
+            .map(|key| (key.gpio, format!("key {}", key.keycode)))
+            .chain(
+                config
+                    .power_switches
+                    .iter()
+                    .map(|ps| (ps.gpio, "power switch".to_string())),
+            );
+        for (gpio, user) in pins {
+            if let Some(other) = used.get(&gpio) {
+                problems.push(format!(
+                    "GPIO {gpio} is used by both {other} and {user} in the {name} key mapping"
+                ));
+            } else {
+                used.insert(gpio, user);
+            }
+        }
+    }
+
+    problems
+}
+
+/// Count the games in the ROMs directory, with the extensions listed in the menu
+fn count_roms(dir: &Path, config: &AppConfig) -> Result<usize, String> {
+    let entries = std::fs::read_dir(dir)
+        .map_err(|err| format!("Cannot read ROMs directory {}: {err}", dir.display()))?;
+    let count = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|ext| config.rom_platform(&ext.to_string_lossy()).is_some())
+        })
+        .count();
+    info!("Found {count} games in {}", dir.display());
+
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_should_report_duplicate_gpio() {
+        let dir = TempDir::new().unwrap();
+        let framebuffer = dir.path().join("fb");
+        std::fs::write(&framebuffer, []).unwrap();
+        std::fs::write(dir.path().join("tetris.gb"), []).unwrap();
+
+        let config = dir.path().join("config.toml");
+        let write_config = |second_gpio: u8| {
+            std::fs::write(
+                &config,
+                format!(
+                    r#"
+roms_directory = "{roms}"
+default_debounce_ms = 20
+default_active_low = true
+poll_interval_ms = 5
+
+[[key]]
+gpio = 17
+keycode = "A"
+repeat = false
+
+[[key]]
+gpio = {second_gpio}
+keycode = "B"
+repeat = false
+"#,
+                    roms = dir.path().display()
+                ),
+            )
+            .unwrap();
+        };
+
+        write_config(18);
+        assert!(run(&config, &framebuffer).is_empty());
+
+        write_config(17);
+        let problems = run(&config, &framebuffer);
+        assert_eq!(
+            problems,
+            vec!["GPIO 17 is used by both key A and key B in the default key mapping".to_string()]
+        );
+
+        // missing framebuffer
+        let problems = run(&config, &dir.path().join("missing"));
+        assert_eq!(problems.len(), 2);
+    }
+
+    #[test]
+    fn test_should_count_roms_with_configured_extensions() {
+        let dir = TempDir::new().unwrap();
+        for file in ["tetris.gb", "zelda.GBC", "wario.gbz", "notes.txt"] {
+            std::fs::write(dir.path().join(file), []).unwrap();
+        }
+        let config: AppConfig = toml::from_str(&format!(
+            "roms_directory = {:?}\ndefault_debounce_ms = 20\ndefault_active_low = true\npoll_interval_ms = 5\nrom_extensions = {{ gbz = \"gbc\" }}",
+            dir.path()
+        ))
+        .unwrap();
+
+        assert_eq!(count_roms(dir.path(), &config), Ok(3));
+    }
+}
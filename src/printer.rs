@@ -2,6 +2,35 @@ use serde::{Deserialize, Serialize};
 
 use crate::serial::SerialCallback;
 
+/// Dithering applied to the printed images
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Dithering {
+    /// Keep the 4 shades of the printer data
+    #[default]
+    None,
+    /// Reduce to black and white with Floyd-Steinberg error diffusion
+    FloydSteinberg,
+}
+
+/// Options used to assemble the printed images
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PrintOptions {
+    /// Integer scale factor of the output image
+    pub scale: usize,
+    pub dithering: Dithering,
+}
+
+impl Default for PrintOptions {
+    fn default() -> Self {
+        Self {
+            scale: 1,
+            dithering: Dithering::None,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct GbPrinter {
     status: u8,
@@ -15,6 +44,8 @@ pub struct GbPrinter {
     datasize: usize,
     result: u8,
     printcount: u8,
+    #[serde(default)]
+    options: PrintOptions,
 }
 
 impl SerialCallback for GbPrinter {
@@ -24,7 +55,7 @@ impl SerialCallback for GbPrinter {
 }
 
 impl GbPrinter {
-    pub fn new() -> GbPrinter {
+    pub fn with_options(options: PrintOptions) -> GbPrinter {
         GbPrinter {
             status: 0,
             state: 0,
@@ -35,6 +66,7 @@ impl GbPrinter {
             datasize: 0,
             result: 0,
             printcount: 0,
+            options,
         }
     }
 
@@ -84,8 +116,6 @@ impl GbPrinter {
             .truncate(true)
             .open(&filename)?;
 
-        writeln!(f, "P5 160 {} 3", image_height)?;
-
         let palbyte = self.packet[8];
         let palette = [
             3 - (palbyte & 3),
@@ -94,6 +124,7 @@ impl GbPrinter {
             3 - ((palbyte >> 6) & 3),
         ];
 
+        let mut shades = Vec::with_capacity(160 * image_height);
         for y in 0..image_height {
             for x in 0..160 {
                 let tilenumber = ((y >> 3) * 20) + (x >> 3);
@@ -103,10 +134,17 @@ impl GbPrinter {
                 let colourindex = ((self.data[tileoffset] >> bx) & 1)
                     | (((self.data[tileoffset + 1] >> bx) << 1) & 2);
 
-                f.write_all(&[palette[colourindex as usize]])?;
+                shades.push(palette[colourindex as usize]);
             }
         }
 
+        if self.options.dithering == Dithering::FloydSteinberg {
+            shades = dither_floyd_steinberg(&shades, 160, image_height);
+        }
+        let scale = self.options.scale.max(1);
+        writeln!(f, "P5 {} {} 3", 160 * scale, image_height * scale)?;
+        f.write_all(&scale_image(&shades, 160, image_height, scale))?;
+
         Ok(filename)
     }
 
@@ -218,3 +256,95 @@ impl GbPrinter {
         self.result
     }
 }
+
+/// Reduce an image of shades 0 (black) to 3 (white) to black and white with Floyd-Steinberg dithering
+fn dither_floyd_steinberg(shades: &[u8], width: usize, height: usize) -> Vec<u8> {
+    let mut levels: Vec<f32> = shades.iter().map(|s| *s as f32).collect();
+    let mut out = vec![0; shades.len()];
+
+    for y in 0..height {
+        for x in 0..width {
+            let i = y * width + x;
+            let value = if levels[i] >= 1.5 { 3 } else { 0 };
+            let error = levels[i] - value as f32;
+            out[i] = value;
+
+            if x + 1 < width {
+                levels[i + 1] += error * 7.0 / 16.0;
+            }
+            if y + 1 < height {
+                if x > 0 {
+                    levels[i + width - 1] += error * 3.0 / 16.0;
+                }
+                levels[i + width] += error * 5.0 / 16.0;
+                if x + 1 < width {
+                    levels[i + width + 1] += error * 1.0 / 16.0;
+                }
+            }
+        }
+    }
+
+    out
+}
+
+/// Scale an image by an integer factor, repeating the pixels
+fn scale_image(pixels: &[u8], width: usize, height: usize, scale: usize) -> Vec<u8> {
+    let mut out = Vec::with_capacity(pixels.len() * scale * scale);
+    for y in 0..height {
+        let row = &pixels[y * width..(y + 1) * width];
+        for _ in 0..scale {
+            for pixel in row {
+                out.extend(std::iter::repeat_n(*pixel, scale));
+            }
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_should_dither_gradient_to_two_tones() {
+        // 4 vertical bands, from black to white
+        let (width, height) = (160, 32);
+        let shades: Vec<u8> = (0..height)
+            .flat_map(|_| (0..width).map(|x| (x / 40) as u8))
+            .collect();
+
+        let dithered = dither_floyd_steinberg(&shades, width, height);
+        assert!(dithered.iter().all(|p| *p == 0 || *p == 3));
+
+        let white_ratio = |band: usize| {
+            let white = (0..height)
+                .flat_map(|y| (band * 40..(band + 1) * 40).map(move |x| y * width + x))
+                .filter(|i| dithered[*i] == 3)
+                .count();
+            white as f32 / (40 * height) as f32
+        };
+        assert_eq!(white_ratio(0), 0.0);
+        assert!(
+            (white_ratio(1) - 1.0 / 3.0).abs() < 0.05,
+            "{}",
+            white_ratio(1)
+        );
+        assert!(
+            (white_ratio(2) - 2.0 / 3.0).abs() < 0.05,
+            "{}",
+            white_ratio(2)
+        );
+        assert_eq!(white_ratio(3), 1.0);
+    }
+
+    #[test]
+    fn test_should_scale_image() {
+        assert_eq!(scale_image(&[1, 2, 3, 0], 2, 2, 1), vec![1, 2, 3, 0]);
+        assert_eq!(
+            scale_image(&[1, 2, 3, 0], 2, 2, 2),
+            vec![1, 1, 2, 2, 1, 1, 2, 2, 3, 3, 0, 0, 3, 3, 0, 0]
+        );
+    }
+}
@@ -0,0 +1,185 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::Duration;
+
+use rboy::KeypadKey;
+
+use crate::app_config::{AppConfig, KeyConfig};
+
+/// File storing the name of the active key profile
+const PROFILE_FILE: &str = ".rboy-profile";
+
+/// The key profile in use; `None` is the default `[[key]]` mapping
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ActiveProfile {
+    pub name: Option<String>,
+}
+
+impl ActiveProfile {
+    /// Load the profile saved in `dir`, falling back to the default one if it's not configured anymore
+    pub fn load(dir: &Path, config: &AppConfig) -> Self {
+        let name = std::fs::read_to_string(dir.join(PROFILE_FILE))
+            .ok()
+            .map(|name| name.trim().to_string())
+            .filter(|name| !name.is_empty());
+        match name {
+            Some(name) if config.profiles.iter().any(|p| p.name == name) => {
+                Self { name: Some(name) }
+            }
+            Some(name) => {
+                warn!("Unknown key profile {name}; using the default keys");
+                Self::default()
+            }
+            None => Self::default(),
+        }
+    }
+
+    /// Save the profile in `dir`
+    pub fn save(&self, dir: &Path) {
+        let path = dir.join(PROFILE_FILE);
+        if let Err(err) = std::fs::write(&path, self.name.as_deref().unwrap_or_default()) {
+            warn!("Failed to save key profile to {}: {err}", path.display());
+        }
+    }
+
+    /// Switch to the next profile, going back to the default keys after the last one
+    pub fn next(&mut self, config: &AppConfig) {
+        let position = self
+            .name
+            .as_ref()
+            .and_then(|name| config.profiles.iter().position(|p| &p.name == name));
+        let next = match position {
+            Some(i) => i + 1,
+            None => 0,
+        };
+        self.name = config.profiles.get(next).map(|p| p.name.clone());
+    }
+
+    /// Key mapping of the profile
+    pub fn keys<'a>(&self, config: &'a AppConfig) -> &'a [KeyConfig] {
+        self.name
+            .as_ref()
+            .and_then(|name| config.profiles.iter().find(|p| &p.name == name))
+            .map(|p| p.keys.as_slice())
+            .unwrap_or(&config.keys)
+    }
+
+    /// Length of the haptic pulse of each key of the profile with haptic feedback, by the key
+    /// sent to the game
+    pub fn haptics(&self, config: &AppConfig) -> HashMap<KeypadKey, Duration> {
+        self.keys(config)
+            .iter()
+            .filter_map(|key| {
+                let keycode = config.input_rotation.apply(key.keycode.keycode());
+                Some((keycode, key.haptic()?))
+            })
+            .collect()
+    }
+
+    /// Name shown to the user
+    pub fn label(&self) -> &str {
+        self.name.as_deref().unwrap_or("default")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_should_switch_key_mapping_with_profile() {
+        let config: AppConfig = toml::from_str(
+            r#"
+roms_directory = "./roms"
+default_debounce_ms = 20
+default_active_low = true
+poll_interval_ms = 5
+
+[[key]]
+gpio = 17
+keycode = "A"
+repeat = false
+
+[[profile]]
+name = "lefty"
+
+[[profile.key]]
+gpio = 17
+keycode = "B"
+repeat = false
+
+[[profile.key]]
+gpio = 22
+keycode = "A"
+repeat = false
+"#,
+        )
+        .unwrap();
+        let bindings = |profile: &ActiveProfile| -> Vec<(u8, KeypadKey)> {
+            profile
+                .keys(&config)
+                .iter()
+                .map(|k| (k.gpio, k.keycode.keycode()))
+                .collect()
+        };
+
+        let mut profile = ActiveProfile::default();
+        assert_eq!(bindings(&profile), vec![(17, KeypadKey::A)]);
+
+        profile.next(&config);
+        assert_eq!(profile.label(), "lefty");
+        assert_eq!(
+            bindings(&profile),
+            vec![(17, KeypadKey::B), (22, KeypadKey::A)]
+        );
+
+        profile.next(&config);
+        assert_eq!(profile, ActiveProfile::default());
+        assert_eq!(bindings(&profile), vec![(17, KeypadKey::A)]);
+    }
+
+    #[test]
+    fn test_should_pulse_keys_of_active_profile_only() {
+        let config: AppConfig = toml::from_str(
+            r#"
+roms_directory = "./roms"
+default_debounce_ms = 20
+default_active_low = true
+poll_interval_ms = 5
+
+[[key]]
+gpio = 17
+keycode = "A"
+repeat = false
+haptic_ms = 20
+
+[[profile]]
+name = "lefty"
+
+[[profile.key]]
+gpio = 17
+keycode = "B"
+repeat = false
+haptic_ms = 30
+
+[[profile.key]]
+gpio = 22
+keycode = "A"
+repeat = false
+"#,
+        )
+        .unwrap();
+
+        let mut profile = ActiveProfile::default();
+        assert_eq!(
+            profile.haptics(&config),
+            HashMap::from([(KeypadKey::A, Duration::from_millis(20))])
+        );
+        profile.next(&config);
+        assert_eq!(
+            profile.haptics(&config),
+            HashMap::from([(KeypadKey::B, Duration::from_millis(30))])
+        );
+    }
+}
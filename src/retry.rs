@@ -0,0 +1,57 @@
+use std::time::Duration;
+
+/// Call `f` until it succeeds, up to `retries` more times after the first failure.
+///
+/// The delay between attempts starts at `backoff` and doubles after each failure.
+/// Returns the last error if all the attempts fail.
+pub fn retry<T>(
+    retries: u32,
+    backoff: Duration,
+    mut f: impl FnMut() -> anyhow::Result<T>,
+) -> anyhow::Result<T> {
+    let mut delay = backoff;
+    let mut attempt = 0;
+    loop {
+        match f() {
+            Ok(value) => return Ok(value),
+            Err(err) if attempt < retries => {
+                attempt += 1;
+                warn!("Attempt {attempt} failed: {err}; retrying in {delay:?}");
+                std::thread::sleep(delay);
+                delay = delay.saturating_mul(2);
+            }
+            Err(err) => {
+                return Err(err.context(format!("giving up after {} attempts", attempt + 1)));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_should_retry_until_success() {
+        let mut calls = 0;
+        let value = retry(3, Duration::ZERO, || {
+            calls += 1;
+            if calls < 2 {
+                anyhow::bail!("GPIO busy")
+            }
+            Ok(calls)
+        })
+        .unwrap();
+        assert_eq!(value, 2);
+
+        let mut calls = 0;
+        let err = retry(2, Duration::ZERO, || -> anyhow::Result<()> {
+            calls += 1;
+            anyhow::bail!("GPIO busy")
+        })
+        .unwrap_err();
+        assert_eq!(calls, 3);
+        assert_eq!(err.to_string(), "giving up after 3 attempts");
+    }
+}
@@ -0,0 +1,70 @@
+use rboy::KeypadKey;
+use serde::Deserialize;
+
+/// Clockwise rotation of the display, in degrees.
+///
+/// Directional keys are remapped so that they match the directions on the rotated screen.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "u16")]
+pub enum Rotation {
+    #[default]
+    R0,
+    R90,
+    R180,
+    R270,
+}
+
+impl TryFrom<u16> for Rotation {
+    type Error = String;
+
+    fn try_from(degrees: u16) -> Result<Self, Self::Error> {
+        match degrees {
+            0 => Ok(Self::R0),
+            90 => Ok(Self::R90),
+            180 => Ok(Self::R180),
+            270 => Ok(Self::R270),
+            _ => Err(format!(
+                "invalid rotation {degrees}; expected 0, 90, 180 or 270"
+            )),
+        }
+    }
+}
+
+impl Rotation {
+    /// Remap a key pressed on the rotated device to the key it means on screen
+    pub fn apply(self, key: KeypadKey) -> KeypadKey {
+        let turns = match self {
+            Self::R0 => 0,
+            Self::R90 => 1,
+            Self::R180 => 2,
+            Self::R270 => 3,
+        };
+        (0..turns).fold(key, |key, _| match key {
+            KeypadKey::Up => KeypadKey::Right,
+            KeypadKey::Right => KeypadKey::Down,
+            KeypadKey::Down => KeypadKey::Left,
+            KeypadKey::Left => KeypadKey::Up,
+            other => other,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_should_remap_directions_for_rotation() {
+        let rotation = Rotation::try_from(90).unwrap();
+        assert_eq!(rotation, Rotation::R90);
+        assert_eq!(rotation.apply(KeypadKey::Up), KeypadKey::Right);
+        assert_eq!(rotation.apply(KeypadKey::Left), KeypadKey::Up);
+        assert_eq!(rotation.apply(KeypadKey::A), KeypadKey::A);
+
+        assert_eq!(Rotation::R180.apply(KeypadKey::Up), KeypadKey::Down);
+        assert_eq!(Rotation::R270.apply(KeypadKey::Up), KeypadKey::Left);
+        assert_eq!(Rotation::R0.apply(KeypadKey::Up), KeypadKey::Up);
+        assert!(Rotation::try_from(45).is_err());
+    }
+}
@@ -0,0 +1,246 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use rboy::KeypadKey;
+use rboy::input::gpio::{GpioOut, GpioValue, RaspberryGpio};
+
+use crate::app_config::RumbleConfig;
+
+/// Frequency of the software PWM driving the motor
+const PWM_FREQUENCY: f64 = 100.0;
+
+/// Output driving the vibration motor
+pub trait Motor: Send {
+    /// Turn the motor on or off
+    fn set(&mut self, on: bool) -> anyhow::Result<()>;
+}
+
+/// Vibration motor on a GPIO output
+pub struct GpioMotor {
+    line: Box<dyn GpioOut + Send>,
+    duty_cycle: Option<f64>,
+}
+
+impl GpioMotor {
+    /// Open the GPIO of the motor, which starts off
+    pub fn try_new(config: &RumbleConfig) -> anyhow::Result<Self> {
+        let line = RaspberryGpio::try_new_output(config.gpio, config.active_low)?;
+
+        Ok(Self::new(Box::new(line), config.duty_cycle))
+    }
+
+    /// Drive the motor through `line`, with a PWM at `duty_cycle` if set
+    fn new(line: Box<dyn GpioOut + Send>, duty_cycle: Option<f64>) -> Self {
+        Self {
+            line,
+            duty_cycle: duty_cycle.map(|duty| duty.clamp(0.0, 1.0)),
+        }
+    }
+}
+
+impl Motor for GpioMotor {
+    fn set(&mut self, on: bool) -> anyhow::Result<()> {
+        match (on, self.duty_cycle) {
+            (true, Some(duty)) => self.line.pwm(PWM_FREQUENCY, duty),
+            (true, None) => self.line.write(GpioValue::Enabled),
+            (false, _) => self.line.write(GpioValue::Disabled),
+        }
+    }
+}
+
+/// Drives the [`Motor`] from the rumble state of the cartridge, and pulses it on the presses of
+/// the keys with haptic feedback.
+///
+/// Changes closer than `min_toggle` to the previous one are delayed, so the motor doesn't chatter.
+pub struct Rumble {
+    motor: Box<dyn Motor>,
+    min_toggle: Duration,
+    on: bool,
+    last_toggle: Option<Instant>,
+    /// Length of the pulse of the keys with haptic feedback
+    haptics: HashMap<KeypadKey, Duration>,
+    /// End of the haptic pulse running, if any
+    pulse_until: Option<Instant>,
+}
+
+impl Rumble {
+    pub fn new(motor: Box<dyn Motor>, min_toggle: Duration) -> Self {
+        Self {
+            motor,
+            min_toggle,
+            on: false,
+            last_toggle: None,
+            haptics: HashMap::new(),
+            pulse_until: None,
+        }
+    }
+
+    /// Pulse the motor for the given length when the keys are pressed
+    pub fn with_haptics(mut self, haptics: HashMap<KeypadKey, Duration>) -> Self {
+        self.haptics = haptics;
+        self
+    }
+
+    /// Replace the keys with haptic feedback, e.g. when the key profile changes
+    pub fn set_haptics(&mut self, haptics: HashMap<KeypadKey, Duration>) {
+        self.haptics = haptics;
+    }
+
+    /// Handle a key pressed at `now`, starting its haptic pulse if any; it ends on a later [`Rumble::update`]
+    pub fn key_down(&mut self, key: KeypadKey, now: Instant) {
+        let Some(pulse) = self.haptics.get(&key) else {
+            return;
+        };
+        self.pulse_until = Some(now + *pulse);
+        if !self.on {
+            self.on = true;
+            self.last_toggle = Some(now);
+            if let Err(err) = self.motor.set(true) {
+                warn!("Failed to pulse the rumble motor: {err}");
+            }
+        }
+    }
+
+    /// Update the motor with the rumble state of the cartridge at `now`
+    pub fn update(&mut self, rumble: bool, now: Instant) {
+        if self.pulse_until.is_some_and(|until| now >= until) {
+            self.pulse_until = None;
+        }
+        let rumble = rumble || self.pulse_until.is_some();
+        if rumble == self.on
+            || self
+                .last_toggle
+                .is_some_and(|last| now.saturating_duration_since(last) < self.min_toggle)
+        {
+            return;
+        }
+
+        self.on = rumble;
+        self.last_toggle = Some(now);
+        if let Err(err) = self.motor.set(rumble) {
+            warn!("Failed to drive the rumble motor: {err}");
+        }
+    }
+
+    /// Turn the motor off right away, e.g. when the game is paused
+    pub fn stop(&mut self) {
+        self.pulse_until = None;
+        if self.on {
+            self.on = false;
+            self.last_toggle = None;
+            if let Err(err) = self.motor.set(false) {
+                warn!("Failed to stop the rumble motor: {err}");
+            }
+        }
+    }
+}
+
+impl Drop for Rumble {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    struct MockMotor {
+        states: Arc<Mutex<Vec<bool>>>,
+    }
+
+    impl Motor for MockMotor {
+        fn set(&mut self, on: bool) -> anyhow::Result<()> {
+            self.states.lock().unwrap().push(on);
+            Ok(())
+        }
+    }
+
+    /// Output line recording what it's driven with
+    struct MockLine {
+        writes: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl GpioOut for MockLine {
+        fn write(&mut self, value: GpioValue) -> anyhow::Result<()> {
+            self.writes.lock().unwrap().push(format!("{value:?}"));
+            Ok(())
+        }
+
+        fn pwm(&mut self, frequency: f64, duty_cycle: f64) -> anyhow::Result<()> {
+            self.writes
+                .lock()
+                .unwrap()
+                .push(format!("pwm {frequency} {duty_cycle}"));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_should_drive_motor_line() {
+        let writes = Arc::new(Mutex::new(vec![]));
+        let line = MockLine {
+            writes: writes.clone(),
+        };
+        let mut motor = GpioMotor::new(Box::new(line), None);
+        motor.set(true).unwrap();
+        motor.set(false).unwrap();
+        assert_eq!(*writes.lock().unwrap(), vec!["Enabled", "Disabled"]);
+
+        let writes = Arc::new(Mutex::new(vec![]));
+        let line = MockLine {
+            writes: writes.clone(),
+        };
+        let mut motor = GpioMotor::new(Box::new(line), Some(1.5));
+        motor.set(true).unwrap();
+        motor.set(false).unwrap();
+        assert_eq!(*writes.lock().unwrap(), vec!["pwm 100 1", "Disabled"]);
+    }
+
+    #[test]
+    fn test_should_drive_motor_from_rumble_state() {
+        let states = Arc::new(Mutex::new(vec![]));
+        let motor = MockMotor {
+            states: states.clone(),
+        };
+        let mut rumble = Rumble::new(Box::new(motor), Duration::from_millis(30));
+        let start = Instant::now();
+
+        rumble.update(false, start);
+        assert!(states.lock().unwrap().is_empty());
+        rumble.update(true, start);
+        assert_eq!(*states.lock().unwrap(), vec![true]);
+        // too soon after the previous change
+        rumble.update(false, start + Duration::from_millis(10));
+        assert_eq!(*states.lock().unwrap(), vec![true]);
+        rumble.update(false, start + Duration::from_millis(30));
+        assert_eq!(*states.lock().unwrap(), vec![true, false]);
+
+        rumble.update(true, start + Duration::from_millis(60));
+        drop(rumble);
+        assert_eq!(*states.lock().unwrap(), vec![true, false, true, false]);
+    }
+
+    #[test]
+    fn test_should_pulse_motor_on_haptic_key_press() {
+        let states = Arc::new(Mutex::new(vec![]));
+        let motor = MockMotor {
+            states: states.clone(),
+        };
+        let mut rumble = Rumble::new(Box::new(motor), Duration::from_millis(10))
+            .with_haptics(HashMap::from([(KeypadKey::A, Duration::from_millis(40))]));
+        let start = Instant::now();
+
+        rumble.key_down(KeypadKey::B, start);
+        assert!(states.lock().unwrap().is_empty());
+        rumble.key_down(KeypadKey::A, start);
+        assert_eq!(*states.lock().unwrap(), vec![true]);
+        rumble.update(false, start + Duration::from_millis(39));
+        assert_eq!(*states.lock().unwrap(), vec![true]);
+        rumble.update(false, start + Duration::from_millis(40));
+        assert_eq!(*states.lock().unwrap(), vec![true, false]);
+    }
+}
@@ -0,0 +1,138 @@
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use rboy::device::{Device, StateError};
+
+/// Number of save state slots for each game
+pub const SLOTS: usize = 10;
+
+/// A save state slot of a game
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Slot {
+    pub index: usize,
+    pub modified: Option<SystemTime>,
+}
+
+/// Path of the save state in the given slot of a ROM, e.g. `tetris.state3`
+pub fn slot_path(rom: &Path, index: usize) -> PathBuf {
+    rom.with_extension(format!("state{index}"))
+}
+
+/// Path of the quick save state of a ROM, e.g. `tetris.statequick`
+pub fn quick_slot_path(rom: &Path) -> PathBuf {
+    rom.with_extension("statequick")
+}
+
+/// Path of the state a game is left in when it stops, to resume it at the next boot,
+/// e.g. `tetris.stateresume`
+pub fn resume_path(rom: &Path) -> PathBuf {
+    rom.with_extension("stateresume")
+}
+
+/// List the slots of the given ROM which contain a save state
+pub fn list_slots(rom: &Path) -> Vec<Slot> {
+    (0..SLOTS)
+        .filter_map(|index| {
+            let metadata = std::fs::metadata(slot_path(rom, index)).ok()?;
+            metadata.is_file().then(|| Slot {
+                index,
+                modified: metadata.modified().ok(),
+            })
+        })
+        .collect()
+}
+
+/// Path a corrupt save state is moved to, e.g. `tetris.state3.bad`
+pub fn bad_path(state: &Path) -> PathBuf {
+    let mut path = state.as_os_str().to_owned();
+    path.push(".bad");
+    PathBuf::from(path)
+}
+
+/// Load the save state at `path` into the device, logging why it can't be loaded.
+///
+/// If `backup` is set, a corrupt or incompatible state is moved to its [`bad_path`], so it
+/// doesn't show up as a saved slot anymore but can still be recovered.
+pub fn load(cpu: &mut Device, path: &Path, backup: bool) -> Result<(), StateError> {
+    let Err(err) = cpu.load_state_from(path) else {
+        info!("Loaded state from {}", path.display());
+        return Ok(());
+    };
+    error!("Save state {} can't be loaded: {err}", path.display());
+    if backup && err != StateError::Unreadable {
+        let bad = bad_path(path);
+        match std::fs::rename(path, &bad) {
+            Ok(()) => info!("Moved {} to {}", path.display(), bad.display()),
+            Err(rename_err) => error!("Failed to move {}: {rename_err}", path.display()),
+        }
+    }
+
+    Err(err)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_should_list_existing_slots() {
+        let dir = TempDir::new().unwrap();
+        let rom = dir.path().join("tetris.gb");
+        assert!(list_slots(&rom).is_empty());
+
+        std::fs::write(slot_path(&rom, 3), [0]).unwrap();
+        std::fs::write(slot_path(&rom, 0), [0]).unwrap();
+        // other games' states are ignored
+        std::fs::write(slot_path(&dir.path().join("zelda.gb"), 1), [0]).unwrap();
+
+        let slots = list_slots(&rom);
+        assert_eq!(
+            slots.iter().map(|slot| slot.index).collect::<Vec<_>>(),
+            vec![0, 3]
+        );
+        let modified = std::fs::metadata(dir.path().join("tetris.state3"))
+            .unwrap()
+            .modified()
+            .unwrap();
+        assert_eq!(slots[1].modified, Some(modified));
+    }
+
+    #[test]
+    fn test_should_report_corrupt_state() {
+        let dir = TempDir::new().unwrap();
+        let rom = dir.path().join("tetris.gb");
+        let mut rom_data = vec![0; 0x8000];
+        // entry point: jr -2
+        rom_data[0x100..0x102].copy_from_slice(&[0x18, 0xFE]);
+        let mut device = Device::new_from_buffer(rom_data, true, None).unwrap();
+
+        let path = slot_path(&rom, 0);
+        device.save_state_to(&path).unwrap();
+        assert_eq!(load(&mut device, &path, true), Ok(()));
+
+        // truncated
+        let state = std::fs::read(&path).unwrap();
+        std::fs::write(&path, &state[..state.len() / 2]).unwrap();
+        assert_eq!(load(&mut device, &path, false), Err(StateError::Corrupt));
+        assert!(path.is_file());
+
+        // saved by another version
+        let mut other_version = state.clone();
+        other_version[8] = 0xff;
+        std::fs::write(&path, other_version).unwrap();
+        assert_eq!(
+            load(&mut device, &path, false),
+            Err(StateError::UnsupportedVersion(255))
+        );
+
+        // garbage is moved aside
+        std::fs::write(&path, b"not a state").unwrap();
+        assert_eq!(load(&mut device, &path, true), Err(StateError::Corrupt));
+        assert!(!path.exists());
+        assert_eq!(std::fs::read(bad_path(&path)).unwrap(), b"not a state");
+        assert!(list_slots(&rom).is_empty());
+    }
+}
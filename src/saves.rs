@@ -0,0 +1,187 @@
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Information about the battery save of a game
+#[derive(Debug, Clone)]
+pub struct SaveInfo {
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+}
+
+impl SaveInfo {
+    /// Get the [`SaveInfo`] for the save file in `slot` of the given ROM, if any
+    pub fn for_rom(rom: &Path, slot: usize) -> Option<Self> {
+        let path = rboy::battery_save_slot_path(rom, slot);
+        let metadata = std::fs::metadata(&path).ok()?;
+        if !metadata.is_file() {
+            return None;
+        }
+
+        Some(Self {
+            size: metadata.len(),
+            modified: metadata.modified().ok(),
+        })
+    }
+
+    /// Last modification date as `YYYY-MM-DD HH:MM` (UTC)
+    pub fn modified_str(&self) -> String {
+        format_modified(self.modified)
+    }
+}
+
+/// Format a modification time as `YYYY-MM-DD HH:MM` (UTC), or `unknown`
+pub fn format_modified(modified: Option<SystemTime>) -> String {
+    modified
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| format_unix_time(d.as_secs()))
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// Path of the backup copy of a save file
+pub fn backup_path(save: &Path) -> PathBuf {
+    let mut path = save.as_os_str().to_owned();
+    path.push(".bak");
+    PathBuf::from(path)
+}
+
+/// Copy the save file in `slot` of the given ROM to its backup path.
+///
+/// Returns the path of the backup.
+pub fn backup_save(rom: &Path, slot: usize) -> std::io::Result<PathBuf> {
+    let save = rboy::battery_save_slot_path(rom, slot);
+    let backup = backup_path(&save);
+    std::fs::copy(&save, &backup)?;
+    info!("Backed up {} to {}", save.display(), backup.display());
+
+    Ok(backup)
+}
+
+/// Delete the save file in `slot` of the given ROM
+pub fn delete_save(rom: &Path, slot: usize) -> std::io::Result<()> {
+    let save = rboy::battery_save_slot_path(rom, slot);
+    std::fs::remove_file(&save)?;
+    info!("Deleted {}", save.display());
+
+    Ok(())
+}
+
+/// Slots of the save files of the given ROM, in order; see [`rboy::battery_save_slot_path`]
+pub fn save_slots(rom: &Path) -> Vec<usize> {
+    let (Some(dir), Some(stem)) = (rom.parent(), rom.file_stem()) else {
+        return vec![];
+    };
+    let stem = stem.to_string_lossy();
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return vec![];
+    };
+
+    let mut slots: Vec<usize> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let slot = name.strip_prefix(&*stem)?.strip_suffix(".gbsave")?;
+            match slot {
+                "" => Some(0),
+                slot => slot
+                    .strip_prefix('.')?
+                    .parse()
+                    .ok()
+                    .filter(|slot| *slot > 0),
+            }
+        })
+        .collect();
+    slots.sort_unstable();
+
+    slots
+}
+
+/// Format a unix timestamp as `YYYY-MM-DD HH:MM` (UTC)
+fn format_unix_time(secs: u64) -> String {
+    let days = (secs / 86400) as i64;
+    let minutes = (secs % 86400) / 60;
+
+    // civil from days; see <http://howardhinnant.github.io/date_algorithms.html>
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{year:04}-{month:02}-{day:02} {hour:02}:{minute:02}",
+        hour = minutes / 60,
+        minute = minutes % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_should_backup_and_delete_save() {
+        let dir = TempDir::new().unwrap();
+        let rom = dir.path().join("tetris.gb");
+        let save = rboy::battery_save_path(&rom);
+        std::fs::write(&save, [1, 2, 3, 4]).unwrap();
+        std::fs::write(rboy::battery_save_slot_path(&rom, 1), [1, 2]).unwrap();
+
+        let info = SaveInfo::for_rom(&rom, 0).unwrap();
+        assert_eq!(info.size, 4);
+        assert_eq!(SaveInfo::for_rom(&rom, 1).unwrap().size, 2);
+        assert!(SaveInfo::for_rom(&rom, 2).is_none());
+
+        let backup = backup_save(&rom, 0).unwrap();
+        assert_eq!(backup, dir.path().join("tetris.gbsave.bak"));
+        assert_eq!(std::fs::read(&backup).unwrap(), vec![1, 2, 3, 4]);
+        let backup = backup_save(&rom, 1).unwrap();
+        assert_eq!(backup, dir.path().join("tetris.1.gbsave.bak"));
+        assert_eq!(std::fs::read(&backup).unwrap(), vec![1, 2]);
+
+        delete_save(&rom, 1).unwrap();
+        assert!(save.exists());
+        assert!(backup.exists());
+        assert!(SaveInfo::for_rom(&rom, 1).is_none());
+        delete_save(&rom, 0).unwrap();
+        assert!(!save.exists());
+        assert!(SaveInfo::for_rom(&rom, 0).is_none());
+    }
+
+    #[test]
+    fn test_should_format_unix_time() {
+        assert_eq!(format_unix_time(0), "1970-01-01 00:00");
+        assert_eq!(
+            format_unix_time(951_782_400 + 3600 + 120),
+            "2000-02-29 01:02"
+        );
+        assert_eq!(format_unix_time(1_790_000_000), "2026-09-21 14:13");
+    }
+
+    #[test]
+    fn test_should_enumerate_save_slots() {
+        let dir = TempDir::new().unwrap();
+        let rom = dir.path().join("tetris.gb");
+        assert!(save_slots(&rom).is_empty());
+
+        for slot in [2, 0, 1] {
+            std::fs::write(rboy::battery_save_slot_path(&rom, slot), [0]).unwrap();
+        }
+        // saves of other games or with an invalid slot are ignored
+        std::fs::write(dir.path().join("tetris dx.gbsave"), [0]).unwrap();
+        std::fs::write(dir.path().join("tetris.x.gbsave"), [0]).unwrap();
+        std::fs::write(dir.path().join("tetris.0.gbsave"), [0]).unwrap();
+
+        assert_eq!(
+            rboy::battery_save_slot_path(&rom, 1),
+            dir.path().join("tetris.1.gbsave")
+        );
+        assert_eq!(save_slots(&rom), vec![0, 1, 2]);
+    }
+}
@@ -0,0 +1,43 @@
+use std::fmt::Write as _;
+use std::io::Write as _;
+
+/// Print a screenshot of the GPU data to stdout, so a connected host can capture it.
+///
+/// The screenshot is framed between a `SCREENSHOT <width> <height>` header and an `END` line;
+/// each line in between is a row of hex-encoded RGB888 pixels.
+pub fn print_screenshot(data: &[u8]) -> std::io::Result<()> {
+    let mut stdout = std::io::stdout().lock();
+    writeln!(stdout, "SCREENSHOT {} {}", rboy::SCREEN_W, rboy::SCREEN_H)?;
+    write!(stdout, "{}", encode_hex(data, rboy::SCREEN_W * 3))?;
+    writeln!(stdout, "END")?;
+    stdout.flush()
+}
+
+/// Encode data as lowercase hex, breaking a line every `row_len` bytes
+pub fn encode_hex(data: &[u8], row_len: usize) -> String {
+    let mut out = String::with_capacity(data.len() * 2 + data.len() / row_len.max(1));
+    for row in data.chunks(row_len.max(1)) {
+        for byte in row {
+            let _ = write!(out, "{byte:02x}");
+        }
+        out.push('\n');
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_should_encode_hex() {
+        assert_eq!(
+            encode_hex(&[0x00, 0xff, 0x10, 0xab, 0x07, 0x7f], 3),
+            "00ff10\nab077f\n"
+        );
+        assert_eq!(encode_hex(&[0x01, 0x02, 0x03], 2), "0102\n03\n");
+        assert_eq!(encode_hex(&[], 2), "");
+    }
+}
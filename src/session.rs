@@ -0,0 +1,125 @@
+use std::path::{Path, PathBuf};
+
+/// Marker file which exists while the app is running; if found at startup, the last session crashed
+const RUNNING_MARKER: &str = ".rboy-running";
+/// File containing the path of the last game played
+const LAST_GAME: &str = ".rboy-last-game";
+
+/// What to show at startup
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Boot {
+    /// Start the last game played
+    LastGame(PathBuf),
+    /// Show the menu; `crashed` is set if the previous session didn't shut down cleanly
+    Menu { crashed: bool },
+}
+
+/// Tracks the state of the app sessions through files in a directory
+#[derive(Debug)]
+pub struct Session {
+    dir: PathBuf,
+}
+
+impl Session {
+    /// Create a new [`Session`] storing its files in `dir`
+    pub fn new(dir: &Path) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+        }
+    }
+
+    /// Start the session.
+    ///
+    /// Returns whether the previous session crashed.
+    pub fn start(&self) -> bool {
+        let marker = self.dir.join(RUNNING_MARKER);
+        let crashed = marker.exists();
+        if let Err(err) = std::fs::write(&marker, std::process::id().to_string()) {
+            warn!("Failed to write {}: {err}", marker.display());
+        }
+
+        crashed
+    }
+
+    /// End the session cleanly
+    pub fn end(&self) {
+        let marker = self.dir.join(RUNNING_MARKER);
+        if let Err(err) = std::fs::remove_file(&marker) {
+            warn!("Failed to remove {}: {err}", marker.display());
+        }
+    }
+
+    /// Path of the last game played, if it still exists
+    pub fn last_game(&self) -> Option<PathBuf> {
+        let path = std::fs::read_to_string(self.dir.join(LAST_GAME)).ok()?;
+        let path = PathBuf::from(path.trim());
+
+        path.is_file().then_some(path)
+    }
+
+    /// Remember the game being played
+    pub fn set_last_game(&self, rom: &Path) {
+        let file = self.dir.join(LAST_GAME);
+        if let Err(err) = std::fs::write(&file, rom.to_string_lossy().as_bytes()) {
+            warn!("Failed to write {}: {err}", file.display());
+        }
+    }
+}
+
+/// Decide what to show at startup.
+///
+/// The last game is started only if enabled and the previous session ended cleanly.
+pub fn boot_decision(boot_last_game: bool, crashed: bool, last_game: Option<PathBuf>) -> Boot {
+    match last_game {
+        Some(rom) if boot_last_game && !crashed => Boot::LastGame(rom),
+        _ => Boot::Menu { crashed },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_should_decide_boot() {
+        let rom = PathBuf::from("/roms/tetris.gb");
+
+        assert_eq!(
+            boot_decision(true, false, Some(rom.clone())),
+            Boot::LastGame(rom.clone())
+        );
+        assert_eq!(
+            boot_decision(true, true, Some(rom.clone())),
+            Boot::Menu { crashed: true }
+        );
+        assert_eq!(
+            boot_decision(false, false, Some(rom)),
+            Boot::Menu { crashed: false }
+        );
+        assert_eq!(
+            boot_decision(true, false, None),
+            Boot::Menu { crashed: false }
+        );
+    }
+
+    #[test]
+    fn test_should_detect_unclean_shutdown() {
+        let dir = TempDir::new().unwrap();
+        let rom = dir.path().join("tetris.gb");
+        std::fs::write(&rom, [0]).unwrap();
+
+        let session = Session::new(dir.path());
+        assert!(!session.start());
+        session.set_last_game(&rom);
+        session.end();
+
+        // clean shutdown
+        assert!(!session.start());
+        assert_eq!(session.last_game(), Some(rom));
+        // crash: the marker isn't removed
+        assert!(session.start());
+    }
+}
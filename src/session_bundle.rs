@@ -0,0 +1,292 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use rboy::input::{Event, KeyEvent};
+use serde::{Deserialize, Serialize};
+
+use crate::app_config::AppConfig;
+use crate::attract::Replay;
+use crate::menu::LaunchMode;
+
+/// Version of the bundle format
+const BUNDLE_VERSION: u32 = 2;
+
+/// Everything needed to reproduce a run for a bug report: the ROM hash, the configuration,
+/// the hardware, the save the game started with and the inputs.
+///
+/// It's stored as TOML; the inputs use the replay format, `<frame> <down|up> <KEY>`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionBundle {
+    pub version: u32,
+    /// File name of the ROM, for reference
+    pub rom_name: String,
+    /// CRC32 of the ROM
+    pub rom_crc: u32,
+    /// Seed of the power-on RAM contents
+    pub seed: Option<u32>,
+    /// Hardware the game ran on
+    pub mode: LaunchMode,
+    /// Battery-backed RAM the game started with, as hex
+    pub ram: Option<String>,
+    /// Content of the configuration file
+    pub config: String,
+    /// CRC32 of the configuration
+    pub config_crc: u32,
+    /// Key events applied to the CPU, in order
+    pub inputs: Vec<String>,
+    /// First event of the run which the inputs can't reproduce, e.g. a state loaded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unreproducible: Option<String>,
+}
+
+impl SessionBundle {
+    /// Create a [`SessionBundle`] for a run of `rom` on `mode`, started with the battery-backed `ram`
+    pub fn new(
+        rom: &Path,
+        config: String,
+        seed: Option<u32>,
+        mode: LaunchMode,
+        ram: Option<&[u8]>,
+        recorder: &InputRecorder,
+    ) -> anyhow::Result<Self> {
+        let data = std::fs::read(rom)
+            .map_err(|e| anyhow::anyhow!("Failed to read ROM {:?}: {}", rom, e))?;
+
+        Ok(Self {
+            version: BUNDLE_VERSION,
+            rom_name: rom
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            rom_crc: crc32fast::hash(&data),
+            seed,
+            mode,
+            ram: ram.map(|ram| ram.iter().map(|byte| format!("{byte:02x}")).collect()),
+            config_crc: crc32fast::hash(config.as_bytes()),
+            config,
+            inputs: recorder.inputs(),
+            unreproducible: recorder.unreproducible(),
+        })
+    }
+
+    /// Load a [`SessionBundle`] from a file
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Failed to read session bundle {:?}: {}", path, e))?;
+        let bundle: Self = toml::from_str(&content)
+            .map_err(|e| anyhow::anyhow!("Invalid session bundle {:?}: {}", path, e))?;
+        if bundle.version != BUNDLE_VERSION {
+            anyhow::bail!(
+                "Unsupported session bundle version {} (expected {BUNDLE_VERSION})",
+                bundle.version
+            );
+        }
+        if crc32fast::hash(bundle.config.as_bytes()) != bundle.config_crc {
+            anyhow::bail!("The configuration of the session bundle {path:?} has been altered");
+        }
+
+        Ok(bundle)
+    }
+
+    /// Save the [`SessionBundle`] to a file
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let content = toml::to_string(self)
+            .map_err(|e| anyhow::anyhow!("Failed to serialize session bundle: {e}"))?;
+        std::fs::write(path, content)
+            .map_err(|e| anyhow::anyhow!("Failed to write session bundle {:?}: {}", path, e))?;
+        if let Some(reason) = &self.unreproducible {
+            warn!("The session bundle can't be played back: {reason}");
+        }
+        info!(
+            "Session bundle with {} inputs saved to {}",
+            self.inputs.len(),
+            path.display()
+        );
+
+        Ok(())
+    }
+
+    /// Parse the configuration of the run
+    pub fn app_config(&self) -> anyhow::Result<AppConfig> {
+        let config: AppConfig = toml::from_str(&self.config)
+            .map_err(|e| anyhow::anyhow!("Invalid configuration in session bundle: {e}"))?;
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    /// Get the inputs as a [`Replay`]; fails if they don't reproduce the run
+    pub fn replay(&self) -> anyhow::Result<Replay> {
+        if let Some(reason) = &self.unreproducible {
+            anyhow::bail!("The session bundle can't be played back: {reason}");
+        }
+        Replay::parse(&self.inputs.join("\n"))
+            .map_err(|e| anyhow::anyhow!("Invalid inputs in session bundle: {e}"))
+    }
+
+    /// Get the battery-backed RAM the game started with
+    pub fn ram(&self) -> anyhow::Result<Option<Vec<u8>>> {
+        let Some(hex) = &self.ram else {
+            return Ok(None);
+        };
+        if hex.len() % 2 != 0 {
+            anyhow::bail!("Invalid save data in session bundle: odd number of digits");
+        }
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| {
+                hex.get(i..i + 2)
+                    .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+                    .ok_or_else(|| anyhow::anyhow!("Invalid save data in session bundle at {i}"))
+            })
+            .collect::<anyhow::Result<Vec<u8>>>()
+            .map(Some)
+    }
+
+    /// Find the ROM of the run among `candidates` and the files in `dir`, by its CRC32
+    pub fn find_rom(&self, candidates: &[PathBuf], dir: &Path) -> anyhow::Result<PathBuf> {
+        let entries = std::fs::read_dir(dir)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|entry| entry.path());
+        candidates
+            .iter()
+            .cloned()
+            .chain(entries)
+            .filter(|path| path.is_file())
+            .find(|path| {
+                std::fs::read(path).is_ok_and(|data| crc32fast::hash(&data) == self.rom_crc)
+            })
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "ROM {} (CRC32 {:08x}) not found in {}",
+                    self.rom_name,
+                    self.rom_crc,
+                    dir.display()
+                )
+            })
+    }
+}
+
+/// Records the key events applied to the CPU, shared between the CPU thread and the frontend
+#[derive(Debug, Clone, Default)]
+pub struct InputRecorder {
+    inputs: Arc<Mutex<Vec<String>>>,
+    unreproducible: Arc<Mutex<Option<String>>>,
+}
+
+impl InputRecorder {
+    /// Record a key event applied at `frame`
+    pub fn record(&self, frame: u64, (event, key): Event) {
+        let event = match event {
+            KeyEvent::Down => "down",
+            KeyEvent::Up => "up",
+        };
+        let line = format!(
+            "{frame} {event} {}",
+            format!("{key:?}").to_ascii_uppercase()
+        );
+        if let Ok(mut inputs) = self.inputs.lock() {
+            inputs.push(line);
+        }
+    }
+
+    /// Flag the recording as not reproducible by its inputs from `frame`, where the `event`
+    /// (e.g. "state loaded") happened; the first one is kept
+    pub fn unreproducible_from(&self, frame: u64, event: &str) {
+        if let Ok(mut unreproducible) = self.unreproducible.lock() {
+            unreproducible.get_or_insert_with(|| format!("{event} at frame {frame}"));
+        }
+    }
+
+    /// First event the recorded inputs can't reproduce, if any
+    pub fn unreproducible(&self) -> Option<String> {
+        self.unreproducible
+            .lock()
+            .ok()
+            .and_then(|unreproducible| unreproducible.clone())
+    }
+
+    /// Inputs recorded so far
+    pub fn inputs(&self) -> Vec<String> {
+        self.inputs
+            .lock()
+            .map(|inputs| inputs.clone())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use rboy::KeypadKey;
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_should_round_trip_session_bundle() {
+        let dir = TempDir::new().unwrap();
+        let rom = dir.path().join("tetris.gb");
+        std::fs::write(&rom, [1, 2, 3, 4]).unwrap();
+
+        let recorder = InputRecorder::default();
+        recorder.record(10, (KeyEvent::Down, KeypadKey::Start));
+        recorder.record(12, (KeyEvent::Up, KeypadKey::Start));
+        let config = "roms_directory = \"./roms\"\n".to_string();
+        let bundle = SessionBundle::new(
+            &rom,
+            config,
+            Some(42),
+            LaunchMode::Classic,
+            Some(&[0x00, 0xab, 0x10]),
+            &recorder,
+        )
+        .unwrap();
+        assert_eq!(bundle.inputs, ["10 down START", "12 up START"]);
+        assert_eq!(bundle.ram.as_deref(), Some("00ab10"));
+
+        let path = dir.path().join("session.toml");
+        bundle.save(&path).unwrap();
+        let loaded = SessionBundle::load(&path).unwrap();
+        assert_eq!(loaded, bundle);
+        assert_eq!(loaded.rom_crc, crc32fast::hash(&[1, 2, 3, 4]));
+        assert_eq!(loaded.config_crc, crc32fast::hash(bundle.config.as_bytes()));
+        assert_eq!(loaded.find_rom(&[], dir.path()).unwrap(), rom);
+        assert_eq!(loaded.mode, LaunchMode::Classic);
+        assert_eq!(loaded.ram().unwrap(), Some(vec![0x00, 0xab, 0x10]));
+
+        let mut replay = loaded.replay().unwrap();
+        assert_eq!(
+            replay.next_due(10),
+            Some((KeyEvent::Down, KeypadKey::Start))
+        );
+    }
+
+    #[test]
+    fn test_should_not_replay_session_with_loaded_state() {
+        let dir = TempDir::new().unwrap();
+        let rom = dir.path().join("tetris.gb");
+        std::fs::write(&rom, [1, 2, 3, 4]).unwrap();
+
+        let recorder = InputRecorder::default();
+        recorder.record(10, (KeyEvent::Down, KeypadKey::Start));
+        recorder.unreproducible_from(20, "state loaded");
+        recorder.unreproducible_from(30, "cheats changed");
+        recorder.record(40, (KeyEvent::Up, KeypadKey::Start));
+        let config = "roms_directory = \"./roms\"\n".to_string();
+        let bundle =
+            SessionBundle::new(&rom, config, None, LaunchMode::Classic, None, &recorder).unwrap();
+        assert_eq!(bundle.inputs, ["10 down START", "40 up START"]);
+
+        let path = dir.path().join("session.toml");
+        bundle.save(&path).unwrap();
+        let loaded = SessionBundle::load(&path).unwrap();
+        assert_eq!(
+            loaded.unreproducible.as_deref(),
+            Some("state loaded at frame 20")
+        );
+        assert!(loaded.replay().is_err());
+    }
+}
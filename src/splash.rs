@@ -0,0 +1,205 @@
+use std::path::Path;
+
+use rboy::framebuffer::Framebuffer;
+
+/// Decoded RGB888 image
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    /// Rows of pixels, top to bottom
+    pub pixels: Vec<(u8, u8, u8)>,
+}
+
+impl Image {
+    /// Load a PNG or an uncompressed BMP image
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let data = std::fs::read(path)?;
+        if data.starts_with(b"\x89PNG") {
+            decode_png(&data)
+        } else if data.starts_with(b"BM") {
+            decode_bmp(&data)
+        } else {
+            anyhow::bail!("Unsupported image format")
+        }
+    }
+
+    fn pixel(&self, x: usize, y: usize) -> (u8, u8, u8) {
+        self.pixels[y * self.width + x]
+    }
+
+    /// Draw the image scaled to fit the framebuffer, centered on a black background
+    pub fn draw(&self, framebuffer: &Framebuffer) {
+        framebuffer.zero();
+        if self.width == 0 || self.height == 0 {
+            return;
+        }
+
+        // fit, keeping the aspect ratio
+        let scale = f32::min(
+            framebuffer.width() as f32 / self.width as f32,
+            framebuffer.height() as f32 / self.height as f32,
+        );
+        let scaled_w = (self.width as f32 * scale).round() as usize;
+        let scaled_h = (self.height as f32 * scale).round() as usize;
+        let x_offset = (framebuffer.width() - scaled_w) / 2;
+        let y_offset = (framebuffer.height() - scaled_h) / 2;
+
+        for dy in 0..scaled_h {
+            let sy = usize::min((dy as f32 / scale) as usize, self.height - 1);
+            for dx in 0..scaled_w {
+                let sx = usize::min((dx as f32 / scale) as usize, self.width - 1);
+                let (r, g, b) = self.pixel(sx, sy);
+                framebuffer.put_pixel_rgb(x_offset + dx, y_offset + dy, r, g, b);
+            }
+        }
+    }
+}
+
+/// Decode a PNG image
+fn decode_png(data: &[u8]) -> anyhow::Result<Image> {
+    let mut decoder = png::Decoder::new(data);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info()?;
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf)?;
+
+    let channels = info.color_type.samples();
+    let pixels = buf[..info.buffer_size()]
+        .chunks_exact(channels)
+        .map(|px| match channels {
+            1 | 2 => (px[0], px[0], px[0]),
+            _ => (px[0], px[1], px[2]),
+        })
+        .collect();
+
+    Ok(Image {
+        width: info.width as usize,
+        height: info.height as usize,
+        pixels,
+    })
+}
+
+/// Decode an uncompressed 24 or 32 bits BMP image
+fn decode_bmp(data: &[u8]) -> anyhow::Result<Image> {
+    let u16_at = |offset: usize| -> anyhow::Result<u16> {
+        data.get(offset..offset + 2)
+            .map(|b| u16::from_le_bytes([b[0], b[1]]))
+            .ok_or_else(|| anyhow::anyhow!("Truncated BMP header"))
+    };
+    let u32_at = |offset: usize| -> anyhow::Result<u32> {
+        data.get(offset..offset + 4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .ok_or_else(|| anyhow::anyhow!("Truncated BMP header"))
+    };
+
+    let pixels_offset = u32_at(10)? as usize;
+    let width = u32_at(18)? as i32;
+    let height = u32_at(22)? as i32;
+    let bits = u16_at(28)?;
+    let compression = u32_at(30)?;
+    // 3 is BI_BITFIELDS, used by 32 bits images with the default BGRA layout
+    if !matches!((bits, compression), (24, 0) | (32, 0) | (32, 3)) {
+        anyhow::bail!("Unsupported BMP: {bits} bits, compression {compression}");
+    }
+
+    let bytes_per_pixel = bits as usize / 8;
+    let (width, top_down) = (width.unsigned_abs() as usize, height < 0);
+    let height = height.unsigned_abs() as usize;
+    // don't allocate for dimensions the file can't hold; the last row may be unpadded
+    let row_len = width
+        .checked_mul(bytes_per_pixel)
+        .ok_or_else(|| anyhow::anyhow!("Truncated BMP data"))?;
+    let row_size = row_len.div_ceil(4) * 4;
+    let size = row_size
+        .checked_mul(height.saturating_sub(1))
+        .and_then(|size| size.checked_add(row_len))
+        .and_then(|size| size.checked_add(pixels_offset));
+    if size.is_none_or(|size| size > data.len()) {
+        anyhow::bail!("Truncated BMP data");
+    }
+
+    let mut pixels = Vec::with_capacity(width * height);
+    for y in 0..height {
+        let row = if top_down { y } else { height - 1 - y };
+        let start = pixels_offset + row * row_size;
+        let row = data
+            .get(start..start + row_len)
+            .ok_or_else(|| anyhow::anyhow!("Truncated BMP data"))?;
+        pixels.extend(
+            row.chunks_exact(bytes_per_pixel)
+                .map(|px| (px[2], px[1], px[0])),
+        );
+    }
+
+    Ok(Image {
+        width,
+        height,
+        pixels,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+
+    use rboy::framebuffer::PixelFormat;
+
+    use super::*;
+    use crate::test_util::mock_framebuffer;
+
+    #[test]
+    fn test_should_draw_image_scaled_to_fit() {
+        // 3x3 image, with a distinct center
+        let mut image = vec![(0x10, 0x20, 0x30); 9];
+        image[4] = (0xc4, 0x5a, 0x07);
+        let bmp = encode_bmp(3, 3, &image);
+        let decoded = decode_bmp(&bmp).unwrap();
+        assert_eq!(decoded.pixels, image);
+
+        let (file, framebuffer) = mock_framebuffer(320, 240, PixelFormat::Rgb888);
+        decoded.draw(&framebuffer);
+
+        let data = std::fs::read(file.path()).unwrap();
+        let pixel = |x: usize, y: usize| {
+            let offset = (y * 320 + x) * 3;
+            (data[offset], data[offset + 1], data[offset + 2])
+        };
+        assert_eq!(pixel(160, 120), (0xc4, 0x5a, 0x07));
+        // scaled to 240x240, centered
+        assert_eq!(pixel(40, 0), (0x10, 0x20, 0x30));
+        assert_eq!(pixel(279, 239), (0x10, 0x20, 0x30));
+        assert_eq!(pixel(39, 120), (0, 0, 0));
+        assert_eq!(pixel(280, 120), (0, 0, 0));
+    }
+
+    #[test]
+    fn test_should_reject_dimensions_beyond_data() {
+        let mut bmp = encode_bmp(1, 1, &[(0, 0, 0)]);
+        bmp[18..22].copy_from_slice(&i32::MAX.to_le_bytes());
+        bmp[22..26].copy_from_slice(&i32::MIN.to_le_bytes());
+        assert!(decode_bmp(&bmp).is_err());
+    }
+
+    /// Encode a bottom-up 24 bits BMP
+    fn encode_bmp(width: usize, height: usize, pixels: &[(u8, u8, u8)]) -> Vec<u8> {
+        let row_size = (width * 3).div_ceil(4) * 4;
+        let mut bmp = vec![0; 54];
+        bmp[0..2].copy_from_slice(b"BM");
+        bmp[10..14].copy_from_slice(&54u32.to_le_bytes());
+        bmp[14..18].copy_from_slice(&40u32.to_le_bytes());
+        bmp[18..22].copy_from_slice(&(width as u32).to_le_bytes());
+        bmp[22..26].copy_from_slice(&(height as u32).to_le_bytes());
+        bmp[26..28].copy_from_slice(&1u16.to_le_bytes());
+        bmp[28..30].copy_from_slice(&24u16.to_le_bytes());
+        for y in (0..height).rev() {
+            let mut row: Vec<u8> = pixels[y * width..(y + 1) * width]
+                .iter()
+                .flat_map(|(r, g, b)| [*b, *g, *r])
+                .collect();
+            row.resize(row_size, 0);
+            bmp.extend(row);
+        }
+
+        bmp
+    }
+}
@@ -0,0 +1,90 @@
+use std::path::PathBuf;
+
+use rboy::framebuffer::{Framebuffer, FramebufferConfig, GLYPH_SIZE};
+
+use crate::app_config::StatusDisplayConfig;
+use crate::battery;
+
+const PADDING: usize = 2;
+const COLOR_TEXT: u16 = 0xffff;
+
+/// Small secondary display showing the status (current game and battery level),
+/// updated independently of the game framebuffer.
+pub struct StatusDisplay {
+    framebuffer: Framebuffer,
+    /// Charge file of the fuel gauge, if any
+    battery: Option<PathBuf>,
+}
+
+impl StatusDisplay {
+    /// Open the status display
+    pub fn open(config: &StatusDisplayConfig, battery: Option<PathBuf>) -> anyhow::Result<Self> {
+        let framebuffer = Framebuffer::new(FramebufferConfig {
+            path: config.path.clone(),
+            width: config.width,
+            height: config.height,
+            pixel_format: config.pixel_format,
+            stride_pixels: config.width,
+        })?;
+        framebuffer.zero();
+
+        Ok(Self {
+            framebuffer,
+            battery,
+        })
+    }
+
+    /// Show the status with the given title
+    pub fn update(&self, title: &str) {
+        let battery = self
+            .battery
+            .as_deref()
+            .and_then(|path| match battery::read_capacity(path) {
+                Ok(percent) => Some(percent),
+                Err(err) => {
+                    warn!("{err}");
+                    None
+                }
+            });
+        let max_chars = self.framebuffer.width().saturating_sub(PADDING * 2) / GLYPH_SIZE;
+        let text = status_text(title, battery, max_chars);
+        debug!("Status display: {text:?}");
+
+        self.framebuffer.zero();
+        for (i, line) in text.lines().enumerate() {
+            self.framebuffer
+                .draw_text(line, PADDING, PADDING + i * GLYPH_SIZE, false, COLOR_TEXT);
+        }
+    }
+
+    /// Clear the display
+    pub fn clear(&self) {
+        self.framebuffer.zero();
+    }
+}
+
+/// Format the status: the title, cut to `max_chars`, and the battery level on the next line
+fn status_text(title: &str, battery: Option<u8>, max_chars: usize) -> String {
+    let mut text: String = title.chars().take(max_chars).collect();
+    if let Some(percent) = battery {
+        text.push_str(&format!("\nBAT {percent}%"));
+    }
+
+    text
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_should_format_status_text() {
+        assert_eq!(status_text("Tetris", Some(80), 16), "Tetris\nBAT 80%");
+        assert_eq!(
+            status_text("The Legend of Zelda", Some(5), 10),
+            "The Legend\nBAT 5%"
+        );
+        assert_eq!(status_text("Menu", None, 16), "Menu");
+    }
+}
@@ -0,0 +1,198 @@
+use std::io::{ErrorKind, Write};
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::UnixListener;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::battery;
+
+/// Interval between two checks for new clients and for the exit
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Status of the app, updated by the main thread
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Status {
+    /// Name of the game being played, if any
+    pub game: Option<String>,
+    /// Frame rate of the game being played
+    pub fps: Option<f32>,
+}
+
+/// Reports the status of the app as JSON to each client connecting to a Unix socket,
+/// e.g. for an external supervisor.
+#[derive(Debug, Clone)]
+pub struct StatusServer {
+    status: Arc<Mutex<Status>>,
+    /// Charge file of the fuel gauge, if any
+    battery: Option<PathBuf>,
+    started: Instant,
+}
+
+impl StatusServer {
+    pub fn new(battery: Option<PathBuf>, started: Instant) -> Self {
+        Self {
+            status: Arc::default(),
+            battery,
+            started,
+        }
+    }
+
+    /// Status shared with the main thread
+    pub fn status(&self) -> Arc<Mutex<Status>> {
+        self.status.clone()
+    }
+
+    /// Report the status at `now` as a JSON object
+    pub fn report(&self, now: Instant) -> String {
+        let status = self.status.lock().map(|s| s.clone()).unwrap_or_default();
+        let battery = self
+            .battery
+            .as_deref()
+            .and_then(|path| battery::read_capacity(path).ok());
+
+        format!(
+            "{{\"game\":{},\"fps\":{},\"battery\":{},\"uptime_secs\":{}}}",
+            status
+                .game
+                .as_deref()
+                .map(json_string)
+                .unwrap_or_else(|| "null".to_string()),
+            status
+                .fps
+                .map(|fps| format!("{fps:.1}"))
+                .unwrap_or_else(|| "null".to_string()),
+            battery
+                .map(|percent| percent.to_string())
+                .unwrap_or_else(|| "null".to_string()),
+            now.saturating_duration_since(self.started).as_secs()
+        )
+    }
+
+    /// Listen on the socket at `path` until `exit` is set; a stale socket file is replaced, while
+    /// any other file is left alone
+    pub fn spawn(self, path: &Path, exit: Arc<AtomicBool>) -> anyhow::Result<JoinHandle<()>> {
+        match std::fs::symlink_metadata(path) {
+            Ok(metadata) if metadata.file_type().is_socket() => std::fs::remove_file(path)
+                .map_err(|e| anyhow::anyhow!("Failed to remove stale socket {:?}: {}", path, e))?,
+            Ok(_) => anyhow::bail!("{:?} exists and is not a socket", path),
+            Err(_) => {}
+        }
+        let listener = UnixListener::bind(path)
+            .map_err(|e| anyhow::anyhow!("Failed to bind status socket {:?}: {}", path, e))?;
+        listener.set_nonblocking(true)?;
+        info!("Status socket listening on {}", path.display());
+
+        let path = path.to_path_buf();
+        Ok(std::thread::spawn(move || {
+            while !exit.load(Ordering::Relaxed) {
+                match listener.accept() {
+                    Ok((mut stream, _)) => {
+                        let report = self.report(Instant::now());
+                        if let Err(err) = writeln!(stream, "{report}") {
+                            debug!("Failed to send the status: {err}");
+                        }
+                    }
+                    Err(err) if err.kind() == ErrorKind::WouldBlock => {
+                        std::thread::sleep(POLL_INTERVAL)
+                    }
+                    Err(err) => {
+                        warn!("Status socket error: {err}");
+                        std::thread::sleep(POLL_INTERVAL);
+                    }
+                }
+            }
+            let _ = std::fs::remove_file(&path);
+        }))
+    }
+}
+
+/// Quote a string as JSON
+fn json_string(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::io::Read;
+    use std::os::unix::net::UnixStream;
+
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_should_report_status_as_json() {
+        let dir = TempDir::new().unwrap();
+        let capacity = dir.path().join("capacity");
+        std::fs::write(&capacity, "80\n").unwrap();
+        let started = Instant::now() - Duration::from_secs(90);
+        let server = StatusServer::new(Some(capacity), started);
+        assert_eq!(
+            server.report(started),
+            r#"{"game":null,"fps":null,"battery":80,"uptime_secs":0}"#
+        );
+
+        *server.status().lock().unwrap() = Status {
+            game: Some("Pokemon \"Red\"".to_string()),
+            fps: Some(59.73),
+        };
+        let exit = Arc::new(AtomicBool::new(false));
+        let socket = dir.path().join("status.sock");
+        let thread = server.spawn(&socket, exit.clone()).unwrap();
+
+        let mut report = String::new();
+        UnixStream::connect(&socket)
+            .unwrap()
+            .read_to_string(&mut report)
+            .unwrap();
+        let (prefix, uptime) = report.trim_end().rsplit_once(":").unwrap();
+        assert_eq!(
+            prefix,
+            r#"{"game":"Pokemon \"Red\"","fps":59.7,"battery":80,"uptime_secs""#
+        );
+        let uptime: u64 = uptime.strip_suffix('}').unwrap().parse().unwrap();
+        assert!(uptime >= 90);
+
+        exit.store(true, Ordering::Relaxed);
+        thread.join().unwrap();
+        assert!(!socket.exists());
+    }
+
+    #[test]
+    fn test_should_replace_stale_socket_only() {
+        let dir = TempDir::new().unwrap();
+        let exit = Arc::new(AtomicBool::new(true));
+        let socket = dir.path().join("status.sock");
+        let stale = UnixListener::bind(&socket).unwrap();
+        drop(stale);
+        let thread = StatusServer::new(None, Instant::now())
+            .spawn(&socket, exit.clone())
+            .unwrap();
+        thread.join().unwrap();
+
+        let file = dir.path().join("notes.txt");
+        std::fs::write(&file, "keep me").unwrap();
+        assert!(
+            StatusServer::new(None, Instant::now())
+                .spawn(&file, exit)
+                .is_err()
+        );
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "keep me");
+    }
+}
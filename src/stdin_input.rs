@@ -0,0 +1,274 @@
+use std::io::{ErrorKind, Read};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+use rboy::input::{Event, KeyEvent};
+
+use crate::app_config::Keycode;
+
+/// Time to wait for stdin to be readable before checking the exit flag again
+const POLL_TIMEOUT_MS: libc::c_int = 50;
+
+/// Command controlling the emulator, besides the key commands
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    Dump,
+    Screenshot,
+    QuickSave,
+    QuickLoad,
+    Help,
+}
+
+/// Dispatch table of the commands: name, [`Command`] and description listed by `?`
+const COMMANDS: &[(&str, Command, &str)] = &[
+    (
+        "dump",
+        Command::Dump,
+        "print the state of the running device",
+    ),
+    (
+        "screenshot",
+        Command::Screenshot,
+        "print a screenshot of the current frame",
+    ),
+    (
+        "save",
+        Command::QuickSave,
+        "save the state to the quick slot",
+    ),
+    (
+        "load",
+        Command::QuickLoad,
+        "load the state from the quick slot",
+    ),
+    ("?", Command::Help, "list the commands"),
+];
+
+/// Commands waiting to be handled by the emulator loop
+static PENDING: Mutex<Vec<Command>> = Mutex::new(Vec::new());
+
+/// Standard input, which fails with [`ErrorKind::WouldBlock`] when nothing is written for
+/// [`POLL_TIMEOUT_MS`], so the reader can check the exit flag
+pub struct Stdin;
+
+impl Read for Stdin {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let mut fds = libc::pollfd {
+            fd: libc::STDIN_FILENO,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        if unsafe { libc::poll(&mut fds, 1, POLL_TIMEOUT_MS) } <= 0 {
+            return Err(ErrorKind::WouldBlock.into());
+        }
+        let read = unsafe {
+            libc::read(
+                libc::STDIN_FILENO,
+                buf.as_mut_ptr() as *mut libc::c_void,
+                buf.len(),
+            )
+        };
+        if read < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+
+        Ok(read as usize)
+    }
+}
+
+/// Run an input source reading key commands from `input` (e.g. [`Stdin`]), used when no GPIO is wired.
+///
+/// Each line is a command: `down <KEY>`, `up <KEY>` or `press <KEY>` (down then up),
+/// or one of the emulator commands of [`COMMANDS`], listed by `?`.
+pub fn run(
+    mut input: impl Read + Send + 'static,
+    exit: Arc<AtomicBool>,
+    event_sender: Sender<Event>,
+) -> JoinHandle<()> {
+    std::thread::spawn(move || {
+        let mut buffer = Vec::new();
+
+        while !exit.load(Ordering::SeqCst) {
+            let mut chunk = [0u8; 256];
+            let read = match input.read(&mut chunk) {
+                Ok(0) => {
+                    debug!("Input commands closed");
+                    break;
+                }
+                Ok(read) => read,
+                Err(err)
+                    if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::Interrupted) =>
+                {
+                    continue;
+                }
+                Err(err) => {
+                    warn!("Failed to read input commands: {err}");
+                    break;
+                }
+            };
+            buffer.extend_from_slice(&chunk[..read]);
+
+            while let Some(pos) = buffer.iter().position(|b| *b == b'\n') {
+                let line: Vec<u8> = buffer.drain(..=pos).collect();
+                let line = String::from_utf8_lossy(&line);
+                if let Some(command) = lookup(&line) {
+                    dispatch(command);
+                    continue;
+                }
+                match parse_command(&line) {
+                    Ok(events) => {
+                        for event in events {
+                            if event_sender.send(event).is_err() {
+                                return;
+                            }
+                        }
+                    }
+                    Err(err) => warn!("Invalid input command '{}': {err}", line.trim()),
+                }
+            }
+        }
+    })
+}
+
+/// Look up an emulator command in the dispatch table
+pub fn lookup(line: &str) -> Option<Command> {
+    let line = line.trim();
+    COMMANDS
+        .iter()
+        .find(|(name, _, _)| name.eq_ignore_ascii_case(line))
+        .map(|(_, command, _)| *command)
+}
+
+/// Handle a command; the ones acting on the running game are queued for the emulator loop
+fn dispatch(command: Command) {
+    match command {
+        Command::Dump => crate::DUMP_DEVICE_STATE.store(true, Ordering::SeqCst),
+        Command::Help => {
+            use std::io::Write as _;
+
+            let mut stdout = std::io::stdout().lock();
+            let _ = write!(stdout, "{}", help());
+            let _ = stdout.flush();
+        }
+        Command::Screenshot | Command::QuickSave | Command::QuickLoad => {
+            PENDING.lock().unwrap().push(command)
+        }
+    }
+}
+
+/// Take the commands queued for the emulator loop
+pub fn take_pending() -> Vec<Command> {
+    std::mem::take(&mut *PENDING.lock().unwrap())
+}
+
+/// Drop the commands queued while no game is running, so they aren't handled by the next one
+pub fn discard_pending() {
+    let discarded = take_pending();
+    if !discarded.is_empty() {
+        debug!("Discarding commands with no game running: {discarded:?}");
+    }
+}
+
+/// List of the commands
+fn help() -> String {
+    let mut help = String::from("down|up|press <KEY>  hold, release or tap a key\n");
+    for (name, _, description) in COMMANDS {
+        help.push_str(&format!("{name:<20} {description}\n"));
+    }
+
+    help
+}
+
+/// Parse an input command into the events it emits
+pub fn parse_command(line: &str) -> Result<Vec<Event>, &'static str> {
+    let mut tokens = line.split_whitespace();
+    let (Some(command), Some(key), None) = (tokens.next(), tokens.next(), tokens.next()) else {
+        return Err("Expected `<down|up|press> <KEY>`");
+    };
+    let key = Keycode::from_str(key)?.keycode();
+
+    match command.to_ascii_lowercase().as_str() {
+        "down" => Ok(vec![(KeyEvent::Down, key)]),
+        "up" => Ok(vec![(KeyEvent::Up, key)]),
+        "press" => Ok(vec![(KeyEvent::Down, key), (KeyEvent::Up, key)]),
+        _ => Err("Unknown command"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use rboy::KeypadKey;
+
+    use super::*;
+
+    #[test]
+    fn test_should_parse_command() {
+        assert_eq!(
+            parse_command("down A\n").unwrap(),
+            vec![(KeyEvent::Down, KeypadKey::A)]
+        );
+        assert_eq!(
+            parse_command("up start").unwrap(),
+            vec![(KeyEvent::Up, KeypadKey::Start)]
+        );
+        assert_eq!(
+            parse_command("PRESS select").unwrap(),
+            vec![
+                (KeyEvent::Down, KeypadKey::Select),
+                (KeyEvent::Up, KeypadKey::Select)
+            ]
+        );
+        assert!(parse_command("down").is_err());
+        assert!(parse_command("down X").is_err());
+        assert!(parse_command("hold A").is_err());
+        assert!(parse_command("down A B").is_err());
+    }
+
+    #[test]
+    fn test_should_send_events_of_input_commands() {
+        let input = std::io::Cursor::new("press A\nbogus\ndown START\n");
+        let (sender, receiver) = std::sync::mpsc::channel();
+
+        run(input, Arc::new(AtomicBool::new(false)), sender)
+            .join()
+            .unwrap();
+        assert_eq!(
+            receiver.try_iter().collect::<Vec<_>>(),
+            vec![
+                (KeyEvent::Down, KeypadKey::A),
+                (KeyEvent::Up, KeypadKey::A),
+                (KeyEvent::Down, KeypadKey::Start)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_should_dispatch_commands() {
+        assert_eq!(lookup("dump\n"), Some(Command::Dump));
+        assert_eq!(lookup("Screenshot"), Some(Command::Screenshot));
+        assert_eq!(lookup(" save "), Some(Command::QuickSave));
+        assert_eq!(lookup("load"), Some(Command::QuickLoad));
+        assert_eq!(lookup("?"), Some(Command::Help));
+        assert_eq!(lookup("press A"), None);
+        assert_eq!(lookup("reboot"), None);
+
+        dispatch(Command::QuickSave);
+        dispatch(Command::Screenshot);
+        assert_eq!(
+            take_pending(),
+            vec![Command::QuickSave, Command::Screenshot]
+        );
+        assert!(take_pending().is_empty());
+        // the commands sent with no game running are dropped
+        dispatch(Command::QuickLoad);
+        discard_pending();
+        assert!(take_pending().is_empty());
+
+        let help = help();
+        assert!(COMMANDS.iter().all(|(name, _, _)| help.contains(name)));
+    }
+}
@@ -0,0 +1,46 @@
+use std::any::Any;
+use std::thread::JoinHandle;
+
+/// The CPU thread panicked
+#[derive(Debug)]
+pub struct CpuCrash {
+    /// Panic message
+    pub message: String,
+}
+
+/// Join the CPU thread, detecting whether it panicked
+pub fn join_cpu<T>(handle: JoinHandle<T>) -> Result<T, CpuCrash> {
+    handle.join().map_err(|payload| CpuCrash {
+        message: panic_message(payload.as_ref()),
+    })
+}
+
+/// Get the message of a panic payload
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_should_detect_cpu_panic() {
+        let handle = std::thread::spawn(|| -> u32 { panic!("Instruction DD is not implemented") });
+        let crash = join_cpu(handle).unwrap_err();
+        assert_eq!(crash.message, "Instruction DD is not implemented");
+
+        let handle = std::thread::spawn(|| -> u32 { panic!("opcode {:02X}", 0xdd) });
+        assert_eq!(join_cpu(handle).unwrap_err().message, "opcode DD");
+
+        let handle = std::thread::spawn(|| 42);
+        assert_eq!(join_cpu(handle).unwrap(), 42);
+    }
+}
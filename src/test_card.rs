@@ -0,0 +1,108 @@
+use rboy::framebuffer::{Framebuffer, GLYPH_SIZE};
+
+const GRID_SIZE: usize = 32;
+const GRID_COLOR: u16 = 0x8410;
+const MARKER_SIZE: usize = 8;
+const MARKER_COLOR: u16 = 0xf800;
+const TEXT_COLOR: u16 = 0xffff;
+const TEXT_BACKGROUND_COLOR: u16 = 0x0000;
+const TEXT_PADDING: usize = 4;
+
+/// Draw the test card used to verify the display configuration.
+///
+/// It's composed of color bars, a grid, markers on the four corners and the framebuffer geometry.
+pub fn draw(framebuffer: &Framebuffer) {
+    let width = framebuffer.width();
+    let height = framebuffer.height();
+
+    framebuffer.draw_test_pattern();
+
+    // grid
+    for x in (0..width).step_by(GRID_SIZE) {
+        framebuffer.fill_rect(x, 0, 1, height, GRID_COLOR);
+    }
+    for y in (0..height).step_by(GRID_SIZE) {
+        framebuffer.fill_rect(0, y, width, 1, GRID_COLOR);
+    }
+
+    // corner markers
+    let right = width.saturating_sub(MARKER_SIZE);
+    let bottom = height.saturating_sub(MARKER_SIZE);
+    for (x, y) in [(0, 0), (right, 0), (0, bottom), (right, bottom)] {
+        framebuffer.fill_rect(x, y, MARKER_SIZE, MARKER_SIZE, MARKER_COLOR);
+    }
+
+    // geometry
+    let label = geometry_label(framebuffer);
+    let (x, y) = label_origin(framebuffer, &label);
+    framebuffer.fill_rect(
+        x.saturating_sub(TEXT_PADDING),
+        y.saturating_sub(TEXT_PADDING),
+        label.len() * GLYPH_SIZE + TEXT_PADDING * 2,
+        GLYPH_SIZE + TEXT_PADDING * 2,
+        TEXT_BACKGROUND_COLOR,
+    );
+    framebuffer.draw_text(&label, x, y, false, TEXT_COLOR);
+}
+
+/// Describe the framebuffer geometry
+fn geometry_label(framebuffer: &Framebuffer) -> String {
+    format!(
+        "{width}x{height} stride {stride}px {format}",
+        width = framebuffer.width(),
+        height = framebuffer.height(),
+        stride = framebuffer.stride_pixels(),
+        format = framebuffer.pixel_format(),
+    )
+}
+
+/// Get the position of the label to center it on the screen
+fn label_origin(framebuffer: &Framebuffer, label: &str) -> (usize, usize) {
+    let x = (framebuffer.width() / 2).saturating_sub(label.len() * GLYPH_SIZE / 2);
+    let y = (framebuffer.height() / 2).saturating_sub(GLYPH_SIZE / 2);
+
+    (x, y)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use rboy::framebuffer::PixelFormat;
+
+    use super::*;
+    use crate::test_util::mock_framebuffer;
+
+    #[test]
+    fn test_should_draw_test_card() {
+        let (file, framebuffer) = mock_framebuffer(320, 240, PixelFormat::Rgb565);
+        draw(&framebuffer);
+
+        let data = std::fs::read(file.path()).unwrap();
+        let pixel = |x: usize, y: usize| {
+            let offset = (y * 320 + x) * 2;
+            u16::from_ne_bytes([data[offset], data[offset + 1]])
+        };
+
+        // corners
+        assert_eq!(pixel(0, 0), MARKER_COLOR);
+        assert_eq!(pixel(319, 0), MARKER_COLOR);
+        assert_eq!(pixel(0, 239), MARKER_COLOR);
+        assert_eq!(pixel(319, 239), MARKER_COLOR);
+
+        // geometry
+        let label = geometry_label(&framebuffer);
+        assert_eq!(label, "320x240 stride 320px rgb565");
+        let (x, y) = label_origin(&framebuffer, &label);
+        let text_pixels: Vec<u16> = (y..y + GLYPH_SIZE)
+            .flat_map(|y| (x..x + label.len() * GLYPH_SIZE).map(move |x| (x, y)))
+            .map(|(x, y)| pixel(x, y))
+            .collect();
+        assert!(text_pixels.contains(&TEXT_COLOR));
+        assert!(text_pixels.contains(&TEXT_BACKGROUND_COLOR));
+        assert!(
+            text_pixels
+                .iter()
+                .all(|p| *p == TEXT_COLOR || *p == TEXT_BACKGROUND_COLOR)
+        );
+    }
+}
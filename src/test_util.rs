@@ -0,0 +1,25 @@
+use rboy::framebuffer::{Framebuffer, FramebufferConfig, PixelFormat};
+use tempfile::NamedTempFile;
+
+/// Create a [`Framebuffer`] backed by a temporary file
+pub fn mock_framebuffer(
+    width: usize,
+    height: usize,
+    pixel_format: PixelFormat,
+) -> (NamedTempFile, Framebuffer) {
+    let file = NamedTempFile::new().unwrap();
+    file.as_file()
+        .set_len((width * height * pixel_format.bytes_per_pixel()) as u64)
+        .unwrap();
+
+    let framebuffer = Framebuffer::new(FramebufferConfig {
+        path: file.path().to_path_buf(),
+        width,
+        height,
+        pixel_format,
+        stride_pixels: width,
+    })
+    .unwrap();
+
+    (file, framebuffer)
+}
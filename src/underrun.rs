@@ -0,0 +1,78 @@
+use cpal::{FromSample, Sample};
+
+/// Fills the audio output, fading to silence when the emulator doesn't produce samples fast enough.
+///
+/// Stopping abruptly on an underrun causes a click; instead the output ramps down
+/// from the last sample played to zero over the fade length.
+#[derive(Debug)]
+pub struct UnderrunFade {
+    /// Last sample played, per channel
+    last: (f32, f32),
+    /// Gain applied to the last sample while fading
+    gain: f32,
+    /// Gain decrease for each frame of underrun
+    step: f32,
+}
+
+impl UnderrunFade {
+    /// Create an [`UnderrunFade`] fading to silence in `frames` frames; 0 stops immediately
+    pub fn new(frames: usize) -> Self {
+        Self {
+            last: (0.0, 0.0),
+            gain: 0.0,
+            step: 1.0 / frames.max(1) as f32,
+        }
+    }
+
+    /// Fill an interleaved stereo output buffer with the queued samples, fading out the remainder
+    pub fn fill<T: Sample + FromSample<f32>>(
+        &mut self,
+        output: &mut [T],
+        samples: &mut Vec<(f32, f32)>,
+    ) {
+        let available = std::cmp::min(output.len() / 2, samples.len());
+        for (frame, (left, right)) in output.chunks_exact_mut(2).zip(samples.drain(..available)) {
+            frame[0] = T::from_sample(left);
+            frame[1] = T::from_sample(right);
+            self.last = (left, right);
+            self.gain = 1.0;
+        }
+
+        let missing = output.len() / 2 - available;
+        if missing > 0 {
+            trace!("Audio underrun: {missing} frames missing");
+        }
+        for frame in output.chunks_exact_mut(2).skip(available) {
+            self.gain = (self.gain - self.step).max(0.0);
+            frame[0] = T::from_sample(self.last.0 * self.gain);
+            frame[1] = T::from_sample(self.last.1 * self.gain);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_should_fade_to_silence_on_underrun() {
+        let mut fade = UnderrunFade::new(4);
+        let mut output = [1.0f32; 16];
+        let mut samples = vec![(0.8, -0.4), (0.8, -0.4)];
+
+        fade.fill(&mut output, &mut samples);
+        assert!(samples.is_empty());
+
+        let left: Vec<f32> = output.iter().step_by(2).copied().collect();
+        let right: Vec<f32> = output.iter().skip(1).step_by(2).copied().collect();
+        assert_eq!(left, vec![0.8, 0.8, 0.6, 0.4, 0.2, 0.0, 0.0, 0.0]);
+        assert_eq!(right, vec![-0.4, -0.4, -0.3, -0.2, -0.1, 0.0, 0.0, 0.0]);
+
+        // without fade, it stops immediately
+        let mut fade = UnderrunFade::new(0);
+        let mut output = [1.0f32; 4];
+        fade.fill(&mut output, &mut vec![(0.5, 0.5)]);
+        assert_eq!(output, [0.5, 0.5, 0.0, 0.0]);
+    }
+}
@@ -0,0 +1,97 @@
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// File storing the volume chosen by the user
+const VOLUME_FILE: &str = ".rboy-volume";
+/// Step of the volume, in percent
+const STEP: u8 = 10;
+const MAX: u8 = 100;
+
+/// Audio volume in percent, shared with the audio player
+#[derive(Debug, Clone)]
+pub struct Volume {
+    percent: Arc<AtomicU8>,
+}
+
+impl Default for Volume {
+    fn default() -> Self {
+        Self {
+            percent: Arc::new(AtomicU8::new(MAX)),
+        }
+    }
+}
+
+impl Volume {
+    /// Load the volume saved in `dir`, or the full volume
+    pub fn load(dir: &Path) -> Self {
+        let Ok(content) = std::fs::read_to_string(dir.join(VOLUME_FILE)) else {
+            return Self::default();
+        };
+        match content.trim().parse::<u8>() {
+            Ok(percent) => Self {
+                percent: Arc::new(AtomicU8::new(percent.min(MAX))),
+            },
+            Err(_) => {
+                warn!("Invalid volume: {content}");
+                Self::default()
+            }
+        }
+    }
+
+    /// Save the volume in `dir`
+    pub fn save(&self, dir: &Path) {
+        let path = dir.join(VOLUME_FILE);
+        if let Err(err) = std::fs::write(&path, self.percent().to_string()) {
+            warn!("Failed to save volume to {}: {err}", path.display());
+        }
+    }
+
+    pub fn percent(&self) -> u8 {
+        self.percent.load(Ordering::Relaxed)
+    }
+
+    /// Gain applied to the samples
+    pub fn gain(&self) -> f32 {
+        self.percent() as f32 / MAX as f32
+    }
+
+    /// Raise the volume by a step, up to 100%
+    pub fn up(&self) {
+        self.percent.store(
+            self.percent().saturating_add(STEP).min(MAX),
+            Ordering::Relaxed,
+        );
+    }
+
+    /// Lower the volume by a step, down to mute
+    pub fn down(&self) {
+        self.percent
+            .store(self.percent().saturating_sub(STEP), Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_should_save_and_load_volume() {
+        let dir = TempDir::new().unwrap();
+        let volume = Volume::load(dir.path());
+        assert_eq!(volume.percent(), 100);
+
+        // the clones share the value
+        let player = volume.clone();
+        volume.down();
+        volume.down();
+        assert_eq!(player.percent(), 80);
+        assert!((player.gain() - 0.8).abs() < 1e-6);
+        volume.save(dir.path());
+
+        assert_eq!(Volume::load(dir.path()).percent(), 80);
+    }
+}
@@ -0,0 +1,264 @@
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use rboy::KeypadKey;
+use rboy::framebuffer::Framebuffer;
+use rboy::input::gpio::{Gpio, GpioValue, RaspberryGpio};
+
+use crate::app_config::{AppConfig, Keycode};
+
+/// GPIOs of the 40 pins header which can be wired to a button
+const CANDIDATE_GPIOS: std::ops::RangeInclusive<u8> = 2..=27;
+/// GPIOs of the I2C1 (2, 3) and SPI0 (7-11) buses, used by displays and add-ons
+const RESERVED_GPIOS: [u8; 7] = [2, 3, 7, 8, 9, 10, 11];
+/// How long the setup waits for a button before giving up
+const INPUT_TIMEOUT: Duration = Duration::from_secs(120);
+/// Keys mapped by the wizard, in the order they are asked
+const KEYS: [&str; 8] = ["UP", "DOWN", "LEFT", "RIGHT", "A", "B", "START", "SELECT"];
+/// ROMs directories offered by the wizard
+const ROMS_DIRECTORIES: [&str; 3] = ["/home/pi/roms", "/media/usb/roms", "/roms"];
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+const LINE_H: usize = 16;
+const PADDING: usize = 16;
+const COLOR_TEXT: u16 = 0xffff;
+
+/// Run the first run setup: map each button to the GPIO it's wired to, pick the ROMs directory
+/// and write the configuration to `path`.
+///
+/// Fails if no button is pressed within [`INPUT_TIMEOUT`] or once `exit` is set.
+pub fn run(framebuffer: &Framebuffer, path: &Path, exit: &AtomicBool) -> anyhow::Result<()> {
+    info!("No configuration at {}; running the setup", path.display());
+    let roms_directories = existing_directories(&ROMS_DIRECTORIES);
+    if roms_directories.is_empty() {
+        anyhow::bail!("None of the ROMs directories exists: {ROMS_DIRECTORIES:?}");
+    }
+    let mut pins: Vec<(u8, RaspberryGpio)> = candidate_gpios(RaspberryGpio::is_free)
+        .into_iter()
+        .filter_map(|gpio| {
+            RaspberryGpio::try_new(gpio, true)
+                .ok()
+                .map(|pin| (gpio, pin))
+        })
+        .collect();
+    if pins.is_empty() {
+        anyhow::bail!("No GPIO available to map the buttons");
+    }
+
+    let mut keys = vec![];
+    for key in KEYS {
+        draw(framebuffer, &["Setup", "", &format!("Press {key}")], None);
+        let gpio = capture_gpio(&mut pins, exit)?;
+        info!("{key} is on GPIO {gpio}");
+        pins.retain(|(pin, _)| *pin != gpio);
+        keys.push((Keycode::from_str(key).map_err(anyhow::Error::msg)?, gpio));
+    }
+
+    let roms_directory = pick_roms_directory(framebuffer, &keys, &roms_directories, exit)?;
+    let (config, _) = build_config(&keys, &roms_directory)?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| anyhow::anyhow!("Failed to create {:?}: {}", dir, e))?;
+    }
+    std::fs::write(path, config)
+        .map_err(|e| anyhow::anyhow!("Failed to write config file {:?}: {}", path, e))?;
+    info!("Configuration written to {}", path.display());
+
+    Ok(())
+}
+
+/// GPIOs which may be wired to a button: the candidates, but the reserved ones and those
+/// already in use according to `is_free`
+fn candidate_gpios(is_free: impl Fn(u8) -> bool) -> Vec<u8> {
+    CANDIDATE_GPIOS
+        .filter(|gpio| !RESERVED_GPIOS.contains(gpio) && is_free(*gpio))
+        .collect()
+}
+
+/// The `directories` which exist
+fn existing_directories(directories: &[&str]) -> Vec<PathBuf> {
+    directories
+        .iter()
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_dir())
+        .collect()
+}
+
+/// Fail if the user gave up on the setup: `exit` is set or nothing was pressed since `started`
+fn check_abort(started: Instant, exit: &AtomicBool) -> anyhow::Result<()> {
+    if exit.load(Ordering::Relaxed) {
+        anyhow::bail!("Setup aborted");
+    }
+    if started.elapsed() >= INPUT_TIMEOUT {
+        anyhow::bail!("Setup timed out waiting for a button");
+    }
+
+    Ok(())
+}
+
+/// Assemble the configuration from the GPIO of each key, returning it as TOML and parsed
+fn build_config(
+    keys: &[(Keycode, u8)],
+    roms_directory: &Path,
+) -> anyhow::Result<(String, AppConfig)> {
+    let roms_directory = toml::Value::String(roms_directory.to_string_lossy().into_owned());
+    let mut config = format!(
+        "# written by the first run setup\nroms_directory = {roms_directory}\ndefault_debounce_ms = 50\ndefault_active_low = true\npoll_interval_ms = 10\n"
+    );
+    for (keycode, gpio) in keys {
+        let _ = write!(
+            config,
+            "\n[[key]]\ngpio = {gpio}\nkeycode = \"{}\"\n",
+            keycode.to_string().to_ascii_uppercase()
+        );
+        let key = keycode.keycode();
+        if key == KeypadKey::Up || key == KeypadKey::Down {
+            config.push_str("repeat = true\nrepeat_delay_ms = 300\nrepeat_rate_ms = 80\n");
+        } else {
+            config.push_str("repeat = false\n");
+        }
+    }
+
+    let parsed: AppConfig = toml::from_str(&config)
+        .map_err(|e| anyhow::anyhow!("Invalid generated configuration: {e}"))?;
+    parsed.validate()?;
+
+    Ok((config, parsed))
+}
+
+/// Wait for a button to be pressed and released on one of the `pins`, and return its GPIO
+fn capture_gpio(pins: &mut [(u8, RaspberryGpio)], exit: &AtomicBool) -> anyhow::Result<u8> {
+    let started = Instant::now();
+    let read = |pin: &mut RaspberryGpio| pin.read().unwrap_or(GpioValue::Disabled);
+    // lines already active (e.g. held or floating) must be released first
+    let mut baseline: Vec<GpioValue> = pins.iter_mut().map(|(_, pin)| read(pin)).collect();
+
+    loop {
+        check_abort(started, exit)?;
+        for (i, (gpio, pin)) in pins.iter_mut().enumerate() {
+            let value = read(pin);
+            if value == GpioValue::Enabled && baseline[i] == GpioValue::Disabled {
+                while read(pin) == GpioValue::Enabled {
+                    check_abort(started, exit)?;
+                    std::thread::sleep(POLL_INTERVAL);
+                }
+                return Ok(*gpio);
+            }
+            baseline[i] = value;
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Let the user pick the ROMs directory with the buttons just mapped
+fn pick_roms_directory(
+    framebuffer: &Framebuffer,
+    keys: &[(Keycode, u8)],
+    directories: &[PathBuf],
+    exit: &AtomicBool,
+) -> anyhow::Result<PathBuf> {
+    let mut pins = vec![];
+    for (keycode, gpio) in keys {
+        pins.push((keycode.keycode(), RaspberryGpio::try_new(*gpio, true)?));
+    }
+    let mut selected = 0;
+    let mut held: Option<KeypadKey> = None;
+
+    loop {
+        let mut lines = vec!["Setup".into(), String::new(), "ROMs directory:".into()];
+        lines.extend(directories.iter().map(|dir| dir.display().to_string()));
+        let lines: Vec<&str> = lines.iter().map(String::as_str).collect();
+        draw(framebuffer, &lines, Some(3 + selected));
+
+        let started = Instant::now();
+        let pressed = loop {
+            check_abort(started, exit)?;
+            let active = pins.iter_mut().find_map(|(key, pin)| {
+                pin.read()
+                    .is_ok_and(|value| value == GpioValue::Enabled)
+                    .then_some(*key)
+            });
+            if active != held {
+                held = active;
+                if let Some(key) = active {
+                    break key;
+                }
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        };
+
+        match pressed {
+            KeypadKey::Up => selected = selected.saturating_sub(1),
+            KeypadKey::Down => selected = usize::min(selected + 1, directories.len() - 1),
+            KeypadKey::A | KeypadKey::Start => {
+                return Ok(directories[selected].clone());
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Draw the lines of the wizard, highlighting the `selected` one
+fn draw(framebuffer: &Framebuffer, lines: &[&str], selected: Option<usize>) {
+    framebuffer.zero();
+    for (i, line) in lines.iter().enumerate() {
+        framebuffer.draw_text(
+            line,
+            PADDING,
+            PADDING + i * LINE_H,
+            selected == Some(i),
+            COLOR_TEXT,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_should_build_config_from_captured_buttons() {
+        let keys: Vec<(Keycode, u8)> = KEYS
+            .iter()
+            .zip([5, 6, 13, 19, 26, 21, 20, 16])
+            .map(|(key, gpio)| (Keycode::from_str(key).unwrap(), gpio))
+            .collect();
+
+        let (toml, config) = build_config(&keys, Path::new("/home/pi/my \"roms\"")).unwrap();
+        assert!(toml.contains("[[key]]"));
+        assert_eq!(config.roms_directory(), Path::new("/home/pi/my \"roms\""));
+        assert_eq!(config.keys.len(), 8);
+        assert_eq!(config.keys[0].gpio, 5);
+        assert_eq!(config.keys[0].keycode.keycode(), KeypadKey::Up);
+        assert!(config.keys[0].repeat);
+        assert_eq!(config.keys[7].gpio, 16);
+        assert_eq!(config.keys[7].keycode.keycode(), KeypadKey::Select);
+        assert!(!config.keys[7].repeat);
+        assert!(config.default_active_low);
+    }
+
+    #[test]
+    fn test_should_skip_reserved_and_busy_gpios() {
+        let gpios = candidate_gpios(|gpio| gpio != 18);
+        assert!(!gpios.contains(&18));
+        assert!(RESERVED_GPIOS.iter().all(|gpio| !gpios.contains(gpio)));
+        assert_eq!(gpios.first(), Some(&4));
+        assert_eq!(gpios.last(), Some(&27));
+    }
+
+    #[test]
+    fn test_should_offer_existing_roms_directories_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let roms = dir.path().join("roms");
+        std::fs::create_dir(&roms).unwrap();
+        let missing = dir.path().join("missing");
+
+        assert_eq!(
+            existing_directories(&[missing.to_str().unwrap(), roms.to_str().unwrap()]),
+            vec![roms]
+        );
+    }
+}